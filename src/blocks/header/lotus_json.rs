@@ -12,12 +12,14 @@ use crate::{
 use cid::Cid;
 use num::BigInt;
 use once_cell::sync::OnceCell;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use super::BlockHeader;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
+#[schemars(rename = "BlockHeader")]
 pub struct BlockHeaderLotusJson {
     miner: LotusJson<Address>,
     #[serde(skip_serializing_if = "LotusJson::is_none", default)]
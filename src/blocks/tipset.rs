@@ -15,6 +15,7 @@ use fvm_ipld_blockstore::Blockstore;
 use fvm_ipld_encoding::CborStore;
 use num::BigInt;
 use once_cell::sync::OnceCell;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use tracing::info;
 
@@ -24,10 +25,14 @@ use super::{Block, BlockHeader, Error, Ticket};
 /// Equal keys will have equivalent iteration order, but note that the `CIDs`
 /// are *not* maintained in the same order as the canonical iteration order of
 /// blocks in a tipset (which is by ticket)
-#[derive(Clone, Debug, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+///
+/// Without [`crate::lotus_json`], the `CIDs` are serialized as raw bytes.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default, Serialize, Deserialize, JsonSchema)]
 #[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
 #[serde(transparent)]
+#[schemars(rename = "TipsetKeysBytes")]
 pub struct TipsetKeys {
+    #[schemars(with = "Vec<Vec<u8>>")]
     pub cids: FrozenCidVec,
 }
 
@@ -451,14 +456,26 @@ pub mod lotus_json {
 
     use crate::blocks::{BlockHeader, Tipset};
     use crate::lotus_json::*;
+    use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
     use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
     use super::TipsetKeys;
 
     pub struct TipsetLotusJson(Tipset);
 
-    #[derive(Serialize, Deserialize)]
+    impl JsonSchema for TipsetLotusJson {
+        fn schema_name() -> String {
+            TipsetLotusJsonInner::schema_name()
+        }
+
+        fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+            TipsetLotusJsonInner::json_schema(gen)
+        }
+    }
+
+    #[derive(Serialize, Deserialize, JsonSchema)]
     #[serde(rename_all = "PascalCase")]
+    #[schemars(rename = "Tipset")]
     struct TipsetLotusJsonInner {
         cids: LotusJson<TipsetKeys>,
         blocks: LotusJson<Vec<BlockHeader>>,
@@ -1,6 +1,7 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 pub mod store;
+pub mod usage;
 mod weight;
 use crate::blocks::Tipset;
 use crate::cid_collections::CidHashSet;
@@ -22,6 +23,7 @@ pub async fn export<D: Digest>(
     lookup_depth: ChainEpochDelta,
     writer: impl AsyncWrite + Unpin,
     seen: CidHashSet,
+    state_light: bool,
     skip_checksum: bool,
 ) -> anyhow::Result<Option<digest::Output<D>>, Error> {
     let db = Arc::new(db);
@@ -32,7 +34,9 @@ pub async fn export<D: Digest>(
     let mut writer = AsyncWriterWithChecksum::<D, _>::new(BufWriter::new(writer), !skip_checksum);
 
     // Stream stateroots in range stateroot_lookup_limit..=tipset.epoch(). Also
-    // stream all block headers until genesis.
+    // stream all block headers until genesis. In `state_light` mode, only the
+    // actors of the state roots older than `tipset`, and the state of the
+    // actors which changed, are streamed.
     let blocks = par_buffer(
         // Queue 1k blocks. This is enuogh to saturate the compressor and blocks
         // are small enough that keeping 1k in memory isn't a problem. Average
@@ -43,7 +47,8 @@ pub async fn export<D: Digest>(
             tipset.clone().chain(Arc::clone(&db)),
             stateroot_lookup_limit,
         )
-        .with_seen(seen),
+        .with_seen(seen)
+        .with_state_light(state_light),
     );
 
     // Encode Ipld key-value pairs in zstd frames
@@ -1,7 +1,7 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 
 use crate::blocks::{BlockHeader, Tipset, TipsetKeys, TxMeta};
 use crate::fil_cns;
@@ -28,6 +28,7 @@ use tracing::{debug, info, warn};
 
 use super::{
     index::{ChainIndex, ResolveNullTipset},
+    lazy_state::{LazyStateStore, StateFetcher},
     tipset_tracker::TipsetTracker,
     Error,
 };
@@ -71,6 +72,9 @@ pub struct ChainStore<DB> {
 
     /// validated blocks
     validated_blocks: Mutex<HashSet<Cid>>,
+
+    /// Fetches the state missing from the database when reading actors.
+    state_fetcher: OnceLock<Arc<StateFetcher>>,
 }
 
 impl<DB> BitswapStoreRead for ChainStore<DB>
@@ -128,11 +132,25 @@ where
             settings,
             genesis_block_header,
             validated_blocks,
+            state_fetcher: OnceLock::new(),
         };
 
         Ok(cs)
     }
 
+    /// Sets the [`StateFetcher`] of the [`ChainStore::state_blockstore`], once.
+    pub fn set_state_fetcher(&self, fetcher: Arc<StateFetcher>) {
+        if self.state_fetcher.set(fetcher).is_err() {
+            warn!("State fetcher already set");
+        }
+    }
+
+    /// Returns a [`Blockstore`] for reading state which fetches the blocks
+    /// missing from the database with the state fetcher, if any.
+    pub fn state_blockstore(&self) -> LazyStateStore<DB> {
+        LazyStateStore::new(Arc::clone(&self.db), self.state_fetcher.get().cloned())
+    }
+
     /// Sets heaviest tipset within `ChainStore` and store its tipset keys in
     /// the settings store under the [`crate::db::setting_keys::HEAD_KEY`] key.
     pub fn set_heaviest_tipset(&self, ts: Arc<Tipset>) -> Result<(), Error> {
@@ -0,0 +1,96 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::sync::Arc;
+
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+
+/// Fetches a block missing from the database, typically from peers, and stores
+/// it in the database. Returns whether the block was found.
+pub type StateFetcher = dyn Fn(Cid) -> bool + Send + Sync;
+
+/// A [`Blockstore`] which fetches the blocks missing from the database with a
+/// [`StateFetcher`] when they're read, e.g. the state of older tipsets left out
+/// of state-light snapshots.
+pub struct LazyStateStore<DB> {
+    db: Arc<DB>,
+    fetcher: Option<Arc<StateFetcher>>,
+}
+
+impl<DB> LazyStateStore<DB> {
+    pub fn new(db: Arc<DB>, fetcher: Option<Arc<StateFetcher>>) -> Self {
+        Self { db, fetcher }
+    }
+}
+
+impl<DB: Blockstore> Blockstore for LazyStateStore<DB> {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        if let Some(data) = self.db.get(k)? {
+            return Ok(Some(data));
+        }
+        match &self.fetcher {
+            Some(fetch) if fetch(*k) => self.db.get(k),
+            _ => Ok(None),
+        }
+    }
+
+    // Checking for a block doesn't fetch it
+    fn has(&self, k: &Cid) -> anyhow::Result<bool> {
+        self.db.has(k)
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        self.db.put_keyed(k, block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryDB;
+    use crate::utils::db::CborStoreExt as _;
+    use fvm_ipld_encoding::CborStore as _;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn fetches_missing_blocks() {
+        let peer = Arc::new(MemoryDB::default());
+        let fetched = peer.put_cbor_default(&"fetched").unwrap();
+        let db = Arc::new(MemoryDB::default());
+        let local = db.put_cbor_default(&"local").unwrap();
+        let missing = MemoryDB::default().put_cbor_default(&"missing").unwrap();
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let fetcher: Arc<StateFetcher> = Arc::new({
+            let db = db.clone();
+            let fetches = fetches.clone();
+            move |cid| {
+                fetches.fetch_add(1, Ordering::Relaxed);
+                match peer.get(&cid).unwrap() {
+                    Some(data) => {
+                        db.put_keyed(&cid, &data).unwrap();
+                        true
+                    }
+                    None => false,
+                }
+            }
+        });
+
+        let store = LazyStateStore::new(db.clone(), Some(fetcher));
+        assert_eq!(store.get_cbor::<String>(&local).unwrap().unwrap(), "local");
+        assert_eq!(fetches.load(Ordering::Relaxed), 0);
+        assert!(!store.has(&fetched).unwrap());
+        assert_eq!(fetches.load(Ordering::Relaxed), 0);
+        assert_eq!(
+            store.get_cbor::<String>(&fetched).unwrap().unwrap(),
+            "fetched"
+        );
+        // Fetched blocks are stored in the database
+        assert!(db.has(&fetched).unwrap());
+        assert!(store.get(&missing).unwrap().is_none());
+        assert_eq!(fetches.load(Ordering::Relaxed), 2);
+
+        let store = LazyStateStore::new(db, None);
+        assert!(store.get(&missing).unwrap().is_none());
+    }
+}
@@ -0,0 +1,374 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Classify chain objects to find out what takes up space in archives and
+//! databases.
+//!
+//! Most objects are classified by their shape, so a block that happens to
+//! look like a receipt is counted as one. Actor state is attributed to the
+//! code CID of its actor, using the state trees added with
+//! [`ObjectClassifier::add_state_tree`]. Only the head block of each actor
+//! state is attributed to the actor; the HAMTs and AMTs below it are counted
+//! as such.
+//!
+//! [`sample_db_usage`] classifies the data reachable from the head of a
+//! database, and [`UsageSampler`] does the same during other walks of that
+//! graph. Samples are kept in a [history](usage_history) in the settings
+//! store, which the node appends to during each garbage collection, and the
+//! last one is exported as Prometheus gauges.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::sync::Arc;
+
+use crate::blocks::{BlockHeader, Tipset};
+use crate::daemon::bundle::load_actor_bundles;
+use crate::db::setting_keys::DB_USAGE_HISTORY_KEY;
+use crate::db::{MemoryDB, SettingsStore, SettingsStoreExt as _};
+use crate::ipld::unordered_stream_graph;
+use crate::ipld::Ipld::{self, Bytes, Integer, Link, List, Null};
+use crate::networks::{ActorBundleInfo, ACTOR_BUNDLES};
+use crate::shim::clock::ChainEpoch;
+use crate::shim::machine::BuiltinActorManifest;
+use crate::shim::message::Message;
+use crate::shim::state_tree::StateTree;
+use crate::utils::db::DB_KEY_BYTES;
+use crate::utils::encoding::from_slice_with_fallback;
+use ahash::HashMap;
+use cid::Cid;
+use futures::TryStreamExt as _;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::DAG_CBOR;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+/// Number of samples kept in the usage history.
+const USAGE_HISTORY_LENGTH: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ObjectKind {
+    BlockHeader,
+    Message,
+    Receipt,
+    HamtNode,
+    AmtNode,
+    /// The head of the state of an actor with this code CID.
+    ActorState(#[serde(with = "crate::lotus_json")] Cid),
+    Other,
+}
+
+impl ObjectKind {
+    /// Value of the `kind` label of the usage metrics.
+    pub fn label(&self) -> &'static str {
+        match self {
+            ObjectKind::BlockHeader => "block_header",
+            ObjectKind::Message => "message",
+            ObjectKind::Receipt => "receipt",
+            ObjectKind::HamtNode => "hamt_node",
+            ObjectKind::AmtNode => "amt_node",
+            ObjectKind::ActorState(_) => "actor_state",
+            ObjectKind::Other => "other",
+        }
+    }
+
+    /// Like [`Display`], but with the names of the builtin actors in `names`.
+    pub fn describe(&self, names: &HashMap<Cid, &str>) -> String {
+        match self {
+            ObjectKind::ActorState(code) => match names.get(code) {
+                Some(name) => format!("actor state ({name})"),
+                None => self.to_string(),
+            },
+            kind => kind.to_string(),
+        }
+    }
+}
+
+impl Display for ObjectKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjectKind::BlockHeader => write!(f, "block headers"),
+            ObjectKind::Message => write!(f, "messages"),
+            ObjectKind::Receipt => write!(f, "receipts"),
+            ObjectKind::HamtNode => write!(f, "HAMT nodes"),
+            ObjectKind::AmtNode => write!(f, "AMT nodes"),
+            ObjectKind::ActorState(code) => write!(f, "actor state ({code})"),
+            ObjectKind::Other => write!(f, "other"),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ObjectClassifier {
+    // Actor state heads and the code CIDs of their actors
+    actor_states: HashMap<Cid, Cid>,
+}
+
+impl ObjectClassifier {
+    /// Attribute the state of the actors in the state tree at `state_root` to
+    /// their code CIDs.
+    pub fn add_state_tree<DB: Blockstore>(
+        &mut self,
+        db: &Arc<DB>,
+        state_root: &Cid,
+    ) -> anyhow::Result<()> {
+        StateTree::new_from_root(Arc::clone(db), state_root)?.for_each(|_, actor| {
+            self.actor_states.insert(actor.state, actor.code);
+            Ok(())
+        })
+    }
+
+    pub fn classify(&self, cid: &Cid, data: &[u8]) -> ObjectKind {
+        if let Some(code) = self.actor_states.get(cid) {
+            return ObjectKind::ActorState(*code);
+        }
+        if cid.codec() != DAG_CBOR {
+            return ObjectKind::Other;
+        }
+        let Ok(List(fields)) = from_slice_with_fallback::<Ipld>(data) else {
+            return ObjectKind::Other;
+        };
+        match fields.as_slice() {
+            fields
+                if fields.len() == 16 && from_slice_with_fallback::<BlockHeader>(data).is_ok() =>
+            {
+                ObjectKind::BlockHeader
+            }
+            fields if fields.len() == 10 && from_slice_with_fallback::<Message>(data).is_ok() => {
+                ObjectKind::Message
+            }
+            // Signed messages
+            [List(message), Bytes(_)] if message.len() == 10 => ObjectKind::Message,
+            [Integer(_), Bytes(_), Integer(_)]
+            | [Integer(_), Bytes(_), Integer(_), Link(_) | Null] => ObjectKind::Receipt,
+            // Bitfield and pointers
+            [Bytes(_), List(_)] => ObjectKind::HamtNode,
+            // Bitmap, links and values
+            [Bytes(_), List(_), List(_)] => ObjectKind::AmtNode,
+            // AMT roots, with or without a bit width
+            [Integer(_), Integer(_), List(_)] | [Integer(_), Integer(_), Integer(_), List(_)] => {
+                ObjectKind::AmtNode
+            }
+            _ => ObjectKind::Other,
+        }
+    }
+}
+
+/// Number and total size of objects.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Usage {
+    pub count: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct UsageStats {
+    pub by_kind: BTreeMap<ObjectKind, Usage>,
+}
+
+impl UsageStats {
+    pub fn add(&mut self, kind: ObjectKind, bytes: usize) {
+        let usage = self.by_kind.entry(kind).or_default();
+        usage.count += 1;
+        usage.bytes += bytes as u64;
+    }
+
+    pub fn total(&self) -> Usage {
+        self.by_kind
+            .values()
+            .fold(Usage::default(), |total, usage| Usage {
+                count: total.count + usage.count,
+                bytes: total.bytes + usage.bytes,
+            })
+    }
+}
+
+/// Usage of a database, sampled by walking the graph reachable from its head.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DbUsageSample {
+    /// Epoch of the head the sample was taken from.
+    pub epoch: ChainEpoch,
+    /// When the sample was taken, in seconds since the Unix epoch.
+    pub timestamp: i64,
+    /// Reachable objects by kind, including those in read-only archives.
+    pub reachable: Vec<(ObjectKind, Usage)>,
+    /// Size of the database that isn't taken by reachable objects, which is
+    /// garbage and storage overhead.
+    pub unreachable_bytes: u64,
+}
+
+impl DbUsageSample {
+    pub fn reachable_total(&self) -> Usage {
+        self.reachable
+            .iter()
+            .fold(Usage::default(), |total, (_, usage)| Usage {
+                count: total.count + usage.count,
+                bytes: total.bytes + usage.bytes,
+            })
+    }
+}
+
+/// Builds a [`DbUsageSample`] out of the blocks visited by a walk of the graph
+/// reachable from a head, such as the mark walk of garbage collection.
+pub struct UsageSampler {
+    epoch: ChainEpoch,
+    classifier: ObjectClassifier,
+    stats: Mutex<UsageStats>,
+}
+
+impl UsageSampler {
+    /// Actor state is attributed using the state tree of `head`.
+    pub fn new<DB: Blockstore>(db: &Arc<DB>, head: &Tipset) -> anyhow::Result<Self> {
+        let mut classifier = ObjectClassifier::default();
+        classifier.add_state_tree(db, head.parent_state())?;
+        Ok(Self {
+            epoch: head.epoch(),
+            classifier,
+            stats: Mutex::new(UsageStats::default()),
+        })
+    }
+
+    /// Classify a visited block. Every block must be visited once.
+    pub fn add(&self, cid: &Cid, data: &[u8]) {
+        let kind = self.classifier.classify(cid, data);
+        self.stats.lock().add(kind, data.len());
+    }
+
+    /// The sample of the visited blocks. `db_bytes` is the size of the
+    /// database, and `stored_bytes` the size of the visited blocks that are
+    /// stored in it rather than in read-only archives, including their keys.
+    pub fn finish(&self, db_bytes: u64, stored_bytes: u64) -> DbUsageSample {
+        let stats = std::mem::take(&mut *self.stats.lock());
+        DbUsageSample {
+            epoch: self.epoch,
+            timestamp: chrono::Utc::now().timestamp(),
+            reachable: stats.by_kind.into_iter().collect(),
+            unreachable_bytes: db_bytes.saturating_sub(stored_bytes),
+        }
+    }
+}
+
+/// Classify the graph reachable from `head`, including the state-trees of the
+/// last `depth` epochs. Actor state is attributed using the state tree of
+/// `head`.
+///
+/// `db_bytes` is the size of the database, and `is_stored` tells whether a
+/// block is stored in it rather than in a read-only archive. Reachable blocks
+/// that are stored are subtracted from `db_bytes` to estimate the size of
+/// unreachable data.
+pub async fn sample_db_usage<DB: Blockstore + Send + Sync + 'static>(
+    db: Arc<DB>,
+    head: Tipset,
+    depth: ChainEpoch,
+    db_bytes: u64,
+    is_stored: impl Fn(&Cid) -> anyhow::Result<bool>,
+) -> anyhow::Result<DbUsageSample> {
+    let sampler = UsageSampler::new(&db, &head)?;
+    let mut stored_bytes = 0;
+    let epoch = head.epoch();
+    let mut stream = unordered_stream_graph(db.clone(), head.chain(db), epoch - depth);
+    while let Some(block) = stream.try_next().await? {
+        sampler.add(&block.cid, &block.data);
+        if is_stored(&block.cid)? {
+            stored_bytes += (DB_KEY_BYTES + block.data.len()) as u64;
+        }
+    }
+    Ok(sampler.finish(db_bytes, stored_bytes))
+}
+
+/// The usage samples recorded in `db`, oldest first.
+pub fn usage_history(db: &dyn SettingsStore) -> anyhow::Result<Vec<DbUsageSample>> {
+    Ok(db.read_obj(DB_USAGE_HISTORY_KEY)?.unwrap_or_default())
+}
+
+/// Append `sample` to the usage history in `db`, dropping the oldest samples
+/// if the history is full.
+pub fn record_usage_sample(db: &dyn SettingsStore, sample: DbUsageSample) -> anyhow::Result<()> {
+    let mut history = usage_history(db)?;
+    history.push(sample);
+    let excess = history.len().saturating_sub(USAGE_HISTORY_LENGTH);
+    history.drain(..excess);
+    db.write_obj(DB_USAGE_HISTORY_KEY, &history)
+}
+
+/// Names of the builtin actors in all known actor bundles, by code CID.
+pub async fn builtin_actor_names() -> anyhow::Result<HashMap<Cid, &'static str>> {
+    let db = MemoryDB::default();
+    load_actor_bundles(&db).await?;
+    let mut names = HashMap::default();
+    for ActorBundleInfo { manifest, .. } in ACTOR_BUNDLES.iter() {
+        for (actor, code) in BuiltinActorManifest::load_manifest(&db, manifest)?.builtin_actors() {
+            names.insert(code, actor.name());
+        }
+    }
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::networks::calibnet;
+    use crate::utils::db::car_stream::CarStream;
+    use crate::utils::db::car_util::load_car;
+
+    #[tokio::test]
+    async fn classify_genesis() {
+        let db = Arc::new(crate::db::car::PlainCar::try_from(calibnet::DEFAULT_GENESIS).unwrap());
+        let genesis = db.heaviest_tipset().unwrap();
+        let mut classifier = ObjectClassifier::default();
+        classifier
+            .add_state_tree(&db, genesis.parent_state())
+            .unwrap();
+
+        let mut stats = UsageStats::default();
+        let mut stream = CarStream::new(calibnet::DEFAULT_GENESIS).await.unwrap();
+        while let Some(block) = stream.try_next().await.unwrap() {
+            stats.add(
+                classifier.classify(&block.cid, &block.data),
+                block.data.len(),
+            );
+        }
+        assert_eq!(stats.by_kind[&ObjectKind::BlockHeader].count, 1);
+        assert!(stats
+            .by_kind
+            .keys()
+            .any(|kind| matches!(kind, ObjectKind::ActorState(_))));
+        assert!(stats.by_kind.contains_key(&ObjectKind::HamtNode));
+        assert_eq!(
+            stats.total().bytes,
+            stats.by_kind.values().map(|usage| usage.bytes).sum::<u64>()
+        );
+    }
+
+    // The graph walk busy-polls its workers
+    #[tokio::test(flavor = "multi_thread")]
+    async fn sample_and_record() {
+        let db = Arc::new(MemoryDB::default());
+        load_car(db.as_ref(), calibnet::DEFAULT_GENESIS)
+            .await
+            .unwrap();
+        let genesis = crate::db::car::PlainCar::try_from(calibnet::DEFAULT_GENESIS)
+            .unwrap()
+            .heaviest_tipset()
+            .unwrap();
+        let sample = sample_db_usage(db.clone(), genesis, 0, 1000, |_| Ok(false))
+            .await
+            .unwrap();
+        let headers = sample
+            .reachable
+            .iter()
+            .find(|(kind, _)| *kind == ObjectKind::BlockHeader)
+            .map(|(_, usage)| usage.count);
+        assert_eq!(headers, Some(1));
+        assert_eq!(sample.unreachable_bytes, 1000);
+
+        for _ in 0..=USAGE_HISTORY_LENGTH {
+            record_usage_sample(db.as_ref(), sample.clone()).unwrap();
+        }
+        let history = usage_history(db.as_ref()).unwrap();
+        assert_eq!(history.len(), USAGE_HISTORY_LENGTH);
+        assert_eq!(history.last(), Some(&sample));
+    }
+}
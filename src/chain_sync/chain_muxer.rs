@@ -2,10 +2,11 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use std::{
+    path::PathBuf,
     pin::Pin,
     sync::Arc,
     task::{Context, Poll},
-    time::SystemTime,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use crate::blocks::{
@@ -17,7 +18,10 @@ use crate::libp2p::{
 };
 use crate::message::SignedMessage;
 use crate::message_pool::{MessagePool, Provider};
-use crate::shim::{clock::SECONDS_IN_DAY, message::Message};
+use crate::shim::{
+    clock::{ChainEpoch, SECONDS_IN_DAY},
+    message::Message,
+};
 use crate::state_manager::StateManager;
 use cid::Cid;
 use futures::{
@@ -33,15 +37,29 @@ use tracing::{debug, error, info, trace, warn};
 
 use crate::chain_sync::{
     bad_block_cache::BadBlockCache,
+    checkpoints::Checkpoints,
+    fork_tree::ForkTree,
     metrics,
     network_context::SyncNetworkContext,
     sync_state::SyncState,
+    tipset_source::TipsetSource,
     tipset_syncer::{
         TipsetProcessor, TipsetProcessorError, TipsetRangeSyncer, TipsetRangeSyncerError,
     },
     validation::{TipsetValidationError, TipsetValidator},
 };
 
+/// How often peer heads are refreshed, and lagging peers evicted
+const PEER_HEAD_REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Number of epochs a peer's head may lag behind the network, at the time the
+/// peer reported it, before the peer is evicted. An hour on mainnet.
+const MAX_PEER_HEAD_LAG: ChainEpoch = 120;
+
+/// Number of consecutive refreshes in which a peer may fail to serve the head
+/// of this node before it is evicted as lagging.
+const MAX_FAILED_HEAD_PROBES: u32 = 3;
+
 pub(in crate::chain_sync) type WorkerState = Arc<RwLock<SyncState>>;
 
 type ChainMuxerFuture<T, E> = Pin<Box<dyn Future<Output = Result<T, E>> + Send>>;
@@ -68,6 +86,10 @@ pub enum ChainMuxerError {
     Block(#[from] ForestBlockError),
     #[error("Following network unexpectedly failed: {0}")]
     NetworkFollowingFailure(String),
+    #[error("Loading checkpoints failed: {0}")]
+    Checkpoints(String),
+    #[error("Invalid tipset source: {0}")]
+    TipsetSource(String),
 }
 
 /// Structure that defines syncing configuration options
@@ -80,6 +102,15 @@ pub struct SyncConfig {
     /// head is
     #[cfg_attr(test, arbitrary(gen(|g| u32::arbitrary(g) as _)))]
     pub tipset_sample_size: usize,
+    /// File of trusted checkpoints, in the format of `build/known_blocks.yaml`,
+    /// used in addition to the built-in ones to download the headers of long
+    /// tipset ranges in concurrent segments
+    pub checkpoint_file: Option<PathBuf>,
+    /// Directory of CAR files to sync from instead of the libp2p peers
+    pub car_source_dir: Option<PathBuf>,
+    /// API info of a trusted Forest node to sync from instead of the libp2p
+    /// peers, in the `[token:]multiaddress` format
+    pub rpc_source: Option<String>,
 }
 
 impl Default for SyncConfig {
@@ -87,6 +118,9 @@ impl Default for SyncConfig {
         Self {
             req_window: 200,
             tipset_sample_size: 5,
+            checkpoint_file: None,
+            car_source_dir: None,
+            rpc_source: None,
         }
     }
 }
@@ -138,6 +172,15 @@ pub struct ChainMuxer<DB, M> {
     /// cache
     bad_blocks: Arc<BadBlockCache>,
 
+    /// Tipsets recently received from the network, on any fork
+    fork_tree: Arc<ForkTree>,
+
+    /// Trusted checkpoints of the network
+    checkpoints: Arc<Checkpoints>,
+
+    /// Source of chain data used instead of the libp2p peers, if any
+    tipset_source: Option<Arc<TipsetSource>>,
+
     /// Incoming network events to be handled by synchronizer
     net_handler: flume::Receiver<NetworkEvent>,
 
@@ -169,11 +212,40 @@ where
         genesis: Arc<Tipset>,
         tipset_sender: flume::Sender<Arc<Tipset>>,
         tipset_receiver: flume::Receiver<Arc<Tipset>>,
-        cfg: SyncConfig,
+        mut cfg: SyncConfig,
     ) -> Result<Self, ChainMuxerError> {
-        let network =
+        let mut network =
             SyncNetworkContext::new(network_send, peer_manager, state_manager.blockstore_owned());
 
+        let tipset_source = match (&cfg.car_source_dir, &cfg.rpc_source) {
+            (Some(_), Some(_)) => {
+                return Err(ChainMuxerError::TipsetSource(
+                    "a CAR directory and an RPC source can't be used together".into(),
+                ))
+            }
+            (Some(path), None) => Some(TipsetSource::car_directory(path.clone())),
+            (None, Some(api_info)) => Some(
+                TipsetSource::rpc(api_info)
+                    .map_err(|e| ChainMuxerError::TipsetSource(format!("{e:#}")))?,
+            ),
+            (None, None) => None,
+        }
+        .map(Arc::new);
+        if let Some(tipset_source) = &tipset_source {
+            network = network.with_tipset_source(tipset_source.clone());
+            // There is a single head to sample
+            cfg.tipset_sample_size = 1;
+        }
+
+        let chain = &state_manager.chain_config().network;
+        let mut checkpoints = Checkpoints::known(chain);
+        if let Some(path) = &cfg.checkpoint_file {
+            checkpoints.extend(
+                Checkpoints::from_file(path, chain)
+                    .map_err(|e| ChainMuxerError::Checkpoints(format!("{e:#}")))?,
+            );
+        }
+
         Ok(Self {
             state: ChainMuxerState::Idle,
             worker_state: Default::default(),
@@ -181,6 +253,9 @@ where
             genesis,
             state_manager,
             bad_blocks: Arc::new(BadBlockCache::default()),
+            fork_tree: Arc::new(ForkTree::default()),
+            checkpoints: Arc::new(checkpoints),
+            tipset_source,
             net_handler: network_rx,
             mpool,
             tipset_sender,
@@ -195,6 +270,17 @@ where
         self.bad_blocks.clone()
     }
 
+    /// Returns a clone of the fork tree to be used outside of chain sync.
+    pub fn fork_tree_cloned(&self) -> Arc<ForkTree> {
+        self.fork_tree.clone()
+    }
+
+    /// Returns the source of chain data used instead of the libp2p peers, whose
+    /// heads need to be announced with [`TipsetSource::announce_heads`].
+    pub fn tipset_source_cloned(&self) -> Option<Arc<TipsetSource>> {
+        self.tipset_source.clone()
+    }
+
     /// Returns a cloned `Arc` of the sync worker state.
     pub fn sync_state_cloned(&self) -> WorkerState {
         self.worker_state.clone()
@@ -203,7 +289,7 @@ where
     async fn get_full_tipset(
         network: SyncNetworkContext<DB>,
         chain_store: Arc<ChainStore<DB>>,
-        peer_id: PeerId,
+        peer_id: Option<PeerId>,
         tipset_keys: TipsetKeys,
     ) -> Result<FullTipset, ChainMuxerError> {
         // Attempt to load from the store
@@ -212,7 +298,7 @@ where
         }
         // Load from the network
         network
-            .chain_exchange_fts(Some(peer_id), &tipset_keys.clone())
+            .chain_exchange_fts(peer_id, &tipset_keys.clone())
             .await
             .map_err(ChainMuxerError::ChainExchange)
     }
@@ -247,36 +333,130 @@ where
         peer_id: PeerId,
         genesis_block_cid: Cid,
     ) {
-        // Query the heaviest TipSet from the store
-        let heaviest = chain_store.heaviest_tipset();
         if network.peer_manager().is_peer_new(&peer_id).await {
             // Since the peer is new, send them a hello request
-            let request = HelloRequest {
-                heaviest_tip_set: heaviest.cids(),
-                heaviest_tipset_height: heaviest.epoch(),
-                heaviest_tipset_weight: heaviest.weight().clone().into(),
-                genesis_cid: genesis_block_cid,
-            };
-            let (peer_id, moment_sent, response) =
-                match network.hello_request(peer_id, request).await {
-                    Ok(response) => response,
-                    Err(e) => {
-                        debug!("Hello request failed: {}", e);
-                        return;
+            Self::send_hello(network, chain_store, peer_id, genesis_block_cid).await;
+        }
+    }
+
+    /// Tells a peer about the heaviest tipset of the node.
+    async fn send_hello(
+        network: SyncNetworkContext<DB>,
+        chain_store: Arc<ChainStore<DB>>,
+        peer_id: PeerId,
+        genesis_block_cid: Cid,
+    ) {
+        // Query the heaviest TipSet from the store
+        let heaviest = chain_store.heaviest_tipset();
+        let request = HelloRequest {
+            heaviest_tip_set: heaviest.cids(),
+            heaviest_tipset_height: heaviest.epoch(),
+            heaviest_tipset_weight: heaviest.weight().clone().into(),
+            genesis_cid: genesis_block_cid,
+        };
+        let (peer_id, moment_sent, response) = match network.hello_request(peer_id, request).await {
+            Ok(response) => response,
+            Err(e) => {
+                debug!("Hello request failed: {}", e);
+                return;
+            }
+        };
+        let dur = SystemTime::now()
+            .duration_since(moment_sent)
+            .unwrap_or_default();
+
+        // Update the peer metadata based on the response
+        match response {
+            Some(_) => {
+                network.peer_manager().log_success(peer_id, dur).await;
+            }
+            None => {
+                network.peer_manager().log_failure(peer_id, dur).await;
+            }
+        }
+    }
+
+    /// Periodically refreshes the heads of the peers, and evicts the peers
+    /// whose freshly reported heads lag far behind the network.
+    ///
+    /// Peers only report their head when they connect, so they are asked for
+    /// the heaviest head known to this node over chain exchange instead. A
+    /// peer serving it is at least at its epoch. Hello requests are sent as
+    /// well, so that the peers refresh the head they know of this node.
+    pub fn peer_heads_task(&self) -> impl Future<Output = anyhow::Result<()>> {
+        let network = self.network.clone();
+        let chain_store = self.state_manager.chain_store().clone();
+        let genesis_block_cid = *self.genesis.blocks()[0].cid();
+        let genesis_timestamp = self.genesis.min_timestamp();
+        let block_delay = self.state_manager.chain_config().block_delay_secs as u64;
+        // The epoch the network is at, at a given time
+        let expected_epoch = move |at: SystemTime| {
+            let since_genesis = at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs()
+                .saturating_sub(genesis_timestamp);
+            (since_genesis / block_delay.max(1)) as ChainEpoch
+        };
+        async move {
+            let mut interval = tokio::time::interval(PEER_HEAD_REFRESH_INTERVAL);
+            loop {
+                interval.tick().await;
+                // Includes the heads reported in hello messages since the last
+                // refresh
+                let fresh_since = SystemTime::now() - PEER_HEAD_REFRESH_INTERVAL;
+                let head = match network.peer_manager().heaviest_peer_head().await {
+                    Some(peer_head)
+                        if peer_head.weight() > chain_store.heaviest_tipset().weight() =>
+                    {
+                        peer_head
                     }
+                    _ => chain_store.heaviest_tipset(),
                 };
-            let dur = SystemTime::now()
-                .duration_since(moment_sent)
-                .unwrap_or_default();
-
-            // Update the peer metadata based on the response
-            match response {
-                Some(_) => {
-                    network.peer_manager().log_success(peer_id, dur).await;
-                }
-                None => {
-                    network.peer_manager().log_failure(peer_id, dur).await;
+                let peers = network.peer_manager().full_peers().await;
+                for peer_id in peers.iter().copied() {
+                    tokio::task::spawn(Self::send_hello(
+                        network.clone(),
+                        chain_store.clone(),
+                        peer_id,
+                        genesis_block_cid,
+                    ));
                 }
+                futures::future::join_all(peers.into_iter().map(|peer_id| {
+                    let network = network.clone();
+                    let head = head.clone();
+                    async move {
+                        match network
+                            .chain_exchange_headers(Some(peer_id), head.key(), 1)
+                            .await
+                        {
+                            Ok(tipsets) if !tipsets.is_empty() => {
+                                network
+                                    .peer_manager()
+                                    .update_peer_reported_head(peer_id, head.epoch())
+                                    .await
+                            }
+                            // The peer is behind this head
+                            _ => {
+                                network
+                                    .peer_manager()
+                                    .record_failed_head_probe(peer_id)
+                                    .await
+                            }
+                        }
+                    }
+                }))
+                .await;
+                network
+                    .peer_manager()
+                    .evict_lagging_peers(
+                        MAX_PEER_HEAD_LAG,
+                        MAX_FAILED_HEAD_PROBES,
+                        fresh_since,
+                        expected_epoch,
+                    )
+                    .await;
+                network.peer_manager().head_spread().await;
             }
         }
     }
@@ -346,11 +526,12 @@ where
         network: SyncNetworkContext<DB>,
         chain_store: Arc<ChainStore<DB>>,
         bad_block_cache: Arc<BadBlockCache>,
+        fork_tree: Arc<ForkTree>,
         mem_pool: Arc<MessagePool<M>>,
         genesis: Arc<Tipset>,
         message_processing_strategy: PubsubMessageProcessingStrategy,
         block_delay: u64,
-    ) -> Result<Option<(FullTipset, PeerId)>, ChainMuxerError> {
+    ) -> Result<Option<(FullTipset, Option<PeerId>)>, ChainMuxerError> {
         let (tipset, source) = match event {
             NetworkEvent::HelloRequestInbound { source, request } => {
                 metrics::LIBP2P_MESSAGE_TOTAL
@@ -369,7 +550,7 @@ where
                 let tipset = match Self::get_full_tipset(
                     network.clone(),
                     chain_store.clone(),
-                    source,
+                    Some(source),
                     tipset_keys,
                 )
                 .await
@@ -380,7 +561,14 @@ where
                         return Err(why);
                     }
                 };
-                (tipset, source)
+                (tipset, Some(source))
+            }
+            NetworkEvent::TipsetSourceHead(tipset_keys) => {
+                // The tipset source answers chain exchange requests itself
+                let tipset =
+                    Self::get_full_tipset(network.clone(), chain_store.clone(), None, tipset_keys)
+                        .await?;
+                (tipset, None)
             }
             NetworkEvent::HelloRequestOutbound { .. } => {
                 metrics::LIBP2P_MESSAGE_TOTAL
@@ -430,7 +618,7 @@ where
                     // Assemble full tipset from block
                     let tipset =
                         Self::gossipsub_block_to_full_tipset(b, source, network.clone()).await?;
-                    (tipset, source)
+                    (tipset, Some(source))
                 }
                 PubsubMessage::Message(m) => {
                     metrics::LIBP2P_MESSAGE_TOTAL
@@ -472,11 +660,12 @@ where
             < chain_store.heaviest_tipset().epoch()
         {
             debug!(
-                "Skip processing tipset at epoch {} from {source} that is too old",
+                "Skip processing tipset at epoch {} that is too old",
                 tipset.epoch()
             );
             return Ok(None);
         }
+        fork_tree.observe(Arc::new(tipset.clone().into_tipset()));
 
         // Validate tipset
         if let Err(why) = TipsetValidator(&tipset).validate(
@@ -501,13 +690,15 @@ where
         }
 
         // Update the peer head
-        network
-            .peer_manager()
-            .update_peer_head(source, Arc::new(tipset.clone().into_tipset()))
-            .await;
-        metrics::PEER_TIPSET_EPOCH
-            .with_label_values(&[source.to_string().as_str()])
-            .set(tipset.epoch());
+        if let Some(source) = source {
+            network
+                .peer_manager()
+                .update_peer_head(source, Arc::new(tipset.clone().into_tipset()))
+                .await;
+            metrics::PEER_TIPSET_EPOCH
+                .with_label_values(&[source.to_string().as_str()])
+                .set(tipset.epoch());
+        }
 
         Ok(Some((tipset, source)))
     }
@@ -518,6 +709,7 @@ where
         let network = self.network.clone();
         let genesis = self.genesis.clone();
         let bad_block_cache = self.bad_blocks.clone();
+        let fork_tree = self.fork_tree.clone();
         let mem_pool = self.mpool.clone();
         let tipset_sample_size = self.sync_config.tipset_sample_size;
         let block_delay = self.state_manager.chain_config().block_delay_secs as u64;
@@ -538,6 +730,7 @@ where
                     network.clone(),
                     chain_store.clone(),
                     bad_block_cache.clone(),
+                    fork_tree.clone(),
                     mem_pool.clone(),
                     genesis.clone(),
                     PubsubMessageProcessingStrategy::Process,
@@ -602,6 +795,7 @@ where
         let trs_network = self.network.clone();
         let trs_tracker = self.worker_state.clone();
         let trs_genesis = self.genesis.clone();
+        let trs_checkpoints = self.checkpoints.clone();
         let tipset_range_syncer: ChainMuxerFuture<(), ChainMuxerError> = Box::pin(async move {
             let network_head_epoch = network_head.epoch();
             let tipset_range_syncer = match TipsetRangeSyncer::new(
@@ -613,6 +807,7 @@ where
                 trs_chain_store,
                 trs_bad_block_cache,
                 trs_genesis,
+                trs_checkpoints,
             ) {
                 Ok(tipset_range_syncer) => tipset_range_syncer,
                 Err(why) => {
@@ -636,6 +831,7 @@ where
         let network = self.network.clone();
         let genesis = self.genesis.clone();
         let bad_block_cache = self.bad_blocks.clone();
+        let fork_tree = self.fork_tree.clone();
         let mem_pool = self.mpool.clone();
         let block_delay = self.state_manager.chain_config().block_delay_secs as u64;
         let stream_processor: ChainMuxerFuture<(), ChainMuxerError> = Box::pin(async move {
@@ -653,6 +849,7 @@ where
                     network.clone(),
                     chain_store.clone(),
                     bad_block_cache.clone(),
+                    fork_tree.clone(),
                     mem_pool.clone(),
                     genesis.clone(),
                     PubsubMessageProcessingStrategy::DoNotProcess,
@@ -699,6 +896,7 @@ where
         let tp_tipset_receiver = self.tipset_receiver.clone();
         let tp_tracker = self.worker_state.clone();
         let tp_genesis = self.genesis.clone();
+        let tp_checkpoints = self.checkpoints.clone();
         enum UnexpectedReturnKind {
             TipsetProcessor,
         }
@@ -712,6 +910,7 @@ where
                     tp_chain_store,
                     tp_bad_block_cache,
                     tp_genesis,
+                    tp_checkpoints,
                 )
                 .await
                 .map_err(ChainMuxerError::TipsetProcessor)?;
@@ -726,6 +925,7 @@ where
         let network = self.network.clone();
         let genesis = self.genesis.clone();
         let bad_block_cache = self.bad_blocks.clone();
+        let fork_tree = self.fork_tree.clone();
         let mem_pool = self.mpool.clone();
         let tipset_sender = self.tipset_sender.clone();
         let block_delay = self.state_manager.chain_config().block_delay_secs as u64;
@@ -755,6 +955,7 @@ where
                         network.clone(),
                         chain_store.clone(),
                         bad_block_cache.clone(),
+                        fork_tree.clone(),
                         mem_pool.clone(),
                         genesis.clone(),
                         PubsubMessageProcessingStrategy::Process,
@@ -0,0 +1,98 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Trusted checkpoints are blocks known to be part of the canonical chain.
+//! They split long tipset ranges into segments whose headers can be
+//! downloaded concurrently, each segment being walked back from its own
+//! checkpoint.
+
+use std::{collections::BTreeMap, path::Path};
+
+use ahash::HashMap;
+use anyhow::Context as _;
+use cid::Cid;
+
+use crate::networks::NetworkChain;
+use crate::shim::clock::ChainEpoch;
+
+/// Maps network names to checkpoints, in the format of
+/// `build/known_blocks.yaml` and of `forest-tool archive checkpoints`.
+type CheckpointFile = HashMap<String, BTreeMap<ChainEpoch, String>>;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Checkpoints(BTreeMap<ChainEpoch, Cid>);
+
+impl Checkpoints {
+    /// Loads the checkpoints of `network` from `build/known_blocks.yaml`.
+    pub fn known(network: &NetworkChain) -> Self {
+        Self::parse(include_str!("../../build/known_blocks.yaml"), network)
+            .expect("known blocks must be valid")
+    }
+
+    /// Loads the checkpoints of `network` from a YAML file, as printed by
+    /// `forest-tool archive checkpoints`.
+    pub fn from_file(path: &Path, network: &NetworkChain) -> anyhow::Result<Self> {
+        let yaml = std::fs::read_to_string(path)
+            .with_context(|| format!("couldn't read {}", path.display()))?;
+        Self::parse(&yaml, network)
+            .with_context(|| format!("invalid checkpoints in {}", path.display()))
+    }
+
+    fn parse(yaml: &str, network: &NetworkChain) -> anyhow::Result<Self> {
+        let mut file: CheckpointFile = serde_yaml::from_str(yaml)?;
+        file.remove(&network.to_string())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(epoch, cid)| Ok((epoch, Cid::try_from(cid.as_str())?)))
+            .collect::<anyhow::Result<_>>()
+            .map(Self)
+    }
+
+    pub fn extend(&mut self, other: Self) {
+        self.0.extend(other.0)
+    }
+
+    /// Returns the checkpoints strictly between the `from` and `to` epochs, in
+    /// ascending order.
+    pub fn between(
+        &self,
+        from: ChainEpoch,
+        to: ChainEpoch,
+    ) -> impl Iterator<Item = (ChainEpoch, Cid)> + '_ {
+        self.0
+            .range(from.saturating_add(1)..to.max(from.saturating_add(1)))
+            .map(|(epoch, cid)| (*epoch, *cid))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::cid::CidCborExt as _;
+
+    #[test]
+    fn known_checkpoints() {
+        assert!(!Checkpoints::known(&NetworkChain::Mainnet).0.is_empty());
+        assert!(!Checkpoints::known(&NetworkChain::Calibnet).0.is_empty());
+        assert!(Checkpoints::known(&NetworkChain::Devnet("devnet".into()))
+            .0
+            .is_empty());
+    }
+
+    #[test]
+    fn between() {
+        let cid = Cid::from_cbor_blake2b256(&"checkpoint").unwrap();
+        let yaml =
+            format!("calibnet:\n  10: {cid}\n  20: {cid}\n  30: {cid}\nmainnet:\n  15: {cid}\n");
+        let checkpoints = Checkpoints::parse(&yaml, &NetworkChain::Calibnet).unwrap();
+        let epochs = |from, to| {
+            checkpoints
+                .between(from, to)
+                .map(|(epoch, _)| epoch)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(epochs(0, 40), vec![10, 20, 30]);
+        assert_eq!(epochs(10, 30), vec![20]);
+        assert_eq!(epochs(20, 10), Vec::<ChainEpoch>::new());
+    }
+}
@@ -0,0 +1,247 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::{num::NonZeroUsize, sync::Arc};
+
+use ahash::{HashMap, HashSet};
+use lru::LruCache;
+use nonzero_ext::nonzero;
+use parking_lot::Mutex;
+
+use crate::blocks::{Tipset, TipsetKeys};
+use crate::chain::ChainStore;
+use crate::shim::clock::ChainEpoch;
+use fvm_ipld_blockstore::Blockstore;
+
+/// Thread-safe, bounded record of the tipsets recently received from the
+/// network, whether they ended up on the canonical chain, on a competing fork,
+/// or rejected as bad.
+#[derive(Debug)]
+pub struct ForkTree {
+    tipsets: Mutex<LruCache<TipsetKeys, Arc<Tipset>>>,
+}
+
+impl Default for ForkTree {
+    fn default() -> Self {
+        Self::new(nonzero!(1usize << 10))
+    }
+}
+
+impl ForkTree {
+    pub fn new(cap: NonZeroUsize) -> Self {
+        Self {
+            tipsets: Mutex::new(LruCache::new(cap)),
+        }
+    }
+
+    /// Records a tipset received from the network. Blocks are received one by
+    /// one, so the tipset is merged with the observed tipsets of the same epoch
+    /// and parents.
+    pub fn observe(&self, tipset: Arc<Tipset>) {
+        let mut tipsets = self.tipsets.lock();
+        let siblings = tipsets
+            .iter()
+            .filter(|(_, observed)| {
+                observed.epoch() == tipset.epoch() && observed.parents() == tipset.parents()
+            })
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        if siblings.is_empty() {
+            tipsets.put(tipset.key().clone(), tipset);
+            return;
+        }
+        let mut headers = tipset.blocks().to_vec();
+        for sibling in siblings.iter().filter_map(|key| tipsets.peek(key)) {
+            for header in sibling.blocks() {
+                if !headers.iter().any(|h| h.cid() == header.cid()) {
+                    headers.push(header.clone());
+                }
+            }
+        }
+        match Tipset::new(headers) {
+            Ok(merged) => {
+                for key in &siblings {
+                    tipsets.pop(key);
+                }
+                tipsets.put(merged.key().clone(), Arc::new(merged));
+            }
+            // Keep the blocks apart if they can't form a tipset
+            Err(_) => {
+                tipsets.put(tipset.key().clone(), tipset);
+            }
+        }
+    }
+
+    /// Returns the observed tipset with the given key, if any.
+    pub fn get(&self, key: &TipsetKeys) -> Option<Arc<Tipset>> {
+        self.tipsets.lock().peek(key).cloned()
+    }
+
+    /// Returns the tips of the tree, that is the observed tipsets no other
+    /// observed tipset builds upon, highest epoch first.
+    pub fn tips(&self) -> Vec<Arc<Tipset>> {
+        let tipsets = self.tipsets.lock();
+        let parents = tipsets
+            .iter()
+            .map(|(_, tipset)| tipset.parents())
+            .collect::<HashSet<_>>();
+        let mut tips = tipsets
+            .iter()
+            .filter(|(key, _)| !parents.contains(key))
+            .map(|(_, tipset)| tipset.clone())
+            .collect::<Vec<_>>();
+        tips.sort_by_key(|tipset| std::cmp::Reverse(tipset.epoch()));
+        tips
+    }
+
+    /// Returns the latest ancestor of `tipset`, itself included, which is part
+    /// of the `canonical` chain. Ancestors are looked up among the observed
+    /// tipsets, then in the store, down to the lowest epoch of the `canonical`
+    /// chain.
+    pub fn fork_point<DB: Blockstore>(
+        &self,
+        chain_store: &ChainStore<DB>,
+        canonical: &CanonicalChain,
+        tipset: Arc<Tipset>,
+    ) -> Option<Arc<Tipset>> {
+        let mut current = tipset;
+        loop {
+            if canonical.contains(&current) {
+                return Some(current);
+            }
+            if current.epoch() <= canonical.since {
+                return None;
+            }
+            current = match self.get(current.parents()) {
+                Some(parent) => parent,
+                None => chain_store.tipset_from_keys(current.parents()).ok()?,
+            };
+        }
+    }
+}
+
+/// Keys of the tipsets of a chain by epoch, from its head down to some epoch.
+pub struct CanonicalChain {
+    keys: HashMap<ChainEpoch, TipsetKeys>,
+    since: ChainEpoch,
+}
+
+impl CanonicalChain {
+    pub fn new<DB: Blockstore>(
+        chain_store: &ChainStore<DB>,
+        head: Arc<Tipset>,
+        since: ChainEpoch,
+    ) -> Self {
+        let keys = chain_store
+            .chain_index
+            .chain(head)
+            .take_while(|tipset| tipset.epoch() >= since)
+            .map(|tipset| (tipset.epoch(), tipset.key().clone()))
+            .collect();
+        Self { keys, since }
+    }
+
+    pub fn contains(&self, tipset: &Tipset) -> bool {
+        self.keys.get(&tipset.epoch()) == Some(tipset.key())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::BlockHeader;
+    use crate::db::MemoryDB;
+    use crate::networks::ChainConfig;
+    use crate::shim::address::Address;
+
+    fn child(db: &MemoryDB, parent: &Tipset, miner: u64) -> Arc<Tipset> {
+        child_after(db, parent, miner, 1)
+    }
+
+    /// A child after `rounds` rounds, all but the last of which are null.
+    fn child_after(db: &MemoryDB, parent: &Tipset, miner: u64, rounds: i64) -> Arc<Tipset> {
+        let header = BlockHeader::builder()
+            .miner_address(Address::new_id(miner))
+            .parents(parent.key().clone())
+            .epoch(parent.epoch() + rounds)
+            .build()
+            .unwrap();
+        crate::chain::persist_objects(db, &[&header]).unwrap();
+        Arc::new(Tipset::from(header))
+    }
+
+    #[test]
+    fn merges_blocks_of_an_epoch() {
+        let db = MemoryDB::default();
+        let genesis = Tipset::from(
+            BlockHeader::builder()
+                .miner_address(Address::new_id(0))
+                .build()
+                .unwrap(),
+        );
+        let a1 = child(&db, &genesis, 1);
+        let b1 = child(&db, &genesis, 2);
+        let merged = Tipset::new(vec![
+            a1.min_ticket_block().clone(),
+            b1.min_ticket_block().clone(),
+        ])
+        .unwrap();
+        let c2 = child(&db, &merged, 1);
+
+        let fork_tree = ForkTree::default();
+        for tipset in [&a1, &b1, &a1, &c2] {
+            fork_tree.observe(tipset.clone());
+        }
+        assert_eq!(fork_tree.get(merged.key()).unwrap().blocks().len(), 2);
+        assert!(fork_tree.get(a1.key()).is_none());
+        assert_eq!(fork_tree.tips(), vec![c2]);
+    }
+
+    #[test]
+    fn tips_and_fork_points() {
+        let db = Arc::new(MemoryDB::default());
+        let genesis_header = BlockHeader::builder()
+            .miner_address(Address::new_id(0))
+            .build()
+            .unwrap();
+        crate::chain::persist_objects(&*db, &[&genesis_header]).unwrap();
+        let genesis = Tipset::from(genesis_header.clone());
+        let chain_store = ChainStore::new(
+            db.clone(),
+            db.clone(),
+            Arc::new(ChainConfig::default()),
+            genesis_header,
+        )
+        .unwrap();
+
+        let a1 = child(&db, &genesis, 1);
+        let a2 = child(&db, &a1, 1);
+        // Blocks of the same epoch and parents would be merged into one tipset
+        let b2 = child_after(&db, &a1, 2, 2);
+        let b3 = child(&db, &b2, 2);
+
+        let fork_tree = ForkTree::default();
+        for tipset in [&a1, &a2, &b2, &b3] {
+            fork_tree.observe(tipset.clone());
+        }
+        assert_eq!(fork_tree.tips(), vec![b3.clone(), a2.clone()]);
+
+        let canonical = CanonicalChain::new(&chain_store, a2.clone(), 0);
+        assert!(canonical.contains(&a1));
+        assert!(!canonical.contains(&b2));
+        assert_eq!(
+            fork_tree.fork_point(&chain_store, &canonical, b3.clone()),
+            Some(a1.clone())
+        );
+        assert_eq!(
+            fork_tree.fork_point(&chain_store, &canonical, a2.clone()),
+            Some(a2.clone())
+        );
+
+        // The fork point is not searched for below the canonical chain
+        let canonical = CanonicalChain::new(&chain_store, a1.clone(), 1);
+        assert_eq!(fork_tree.fork_point(&chain_store, &canonical, b3), Some(a1));
+        let canonical = CanonicalChain::new(&chain_store, b2.clone(), 2);
+        assert_eq!(fork_tree.fork_point(&chain_store, &canonical, a2), None);
+    }
+}
@@ -3,17 +3,23 @@
 
 mod bad_block_cache;
 mod chain_muxer;
+mod checkpoints;
 pub mod consensus;
+mod fork_tree;
 mod metrics;
 mod network_context;
 mod sync_state;
+mod tipset_source;
 mod tipset_syncer;
 mod validation;
 
 pub use self::{
     bad_block_cache::BadBlockCache,
     chain_muxer::{ChainMuxer, SyncConfig},
+    checkpoints::Checkpoints,
     consensus::{collect_errs, Consensus},
+    fork_tree::{CanonicalChain, ForkTree},
     sync_state::{SyncStage, SyncState},
+    tipset_source::TipsetSource,
     validation::TipsetValidator,
 };
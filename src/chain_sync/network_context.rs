@@ -10,6 +10,7 @@ use std::{
     time::{Duration, SystemTime},
 };
 
+use super::tipset_source::TipsetSource;
 use crate::blocks::{FullTipset, Tipset, TipsetKeys};
 use crate::libp2p::{
     chain_exchange::{
@@ -51,6 +52,9 @@ pub(in crate::chain_sync) struct SyncNetworkContext<DB> {
     /// respective peers.
     peer_manager: Arc<PeerManager>,
     db: Arc<DB>,
+
+    /// Source answering chain exchange requests instead of the peers
+    tipset_source: Option<Arc<TipsetSource>>,
 }
 
 impl<DB> Clone for SyncNetworkContext<DB> {
@@ -59,6 +63,7 @@ impl<DB> Clone for SyncNetworkContext<DB> {
             network_send: self.network_send.clone(),
             peer_manager: self.peer_manager.clone(),
             db: self.db.clone(),
+            tipset_source: self.tipset_source.clone(),
         }
     }
 }
@@ -119,9 +124,17 @@ where
             network_send,
             peer_manager,
             db,
+            tipset_source: None,
         }
     }
 
+    /// Answers chain exchange requests from `tipset_source` rather than from
+    /// the peers of the node.
+    pub fn with_tipset_source(mut self, tipset_source: Arc<TipsetSource>) -> Self {
+        self.tipset_source = Some(tipset_source);
+        self
+    }
+
     /// Returns a reference to the peer manager of the network context.
     pub fn peer_manager(&self) -> &PeerManager {
         self.peer_manager.as_ref()
@@ -233,6 +246,14 @@ where
         let global_pre_time = SystemTime::now();
         let network_failures = Arc::new(AtomicU64::new(0));
         let lookup_failures = Arc::new(AtomicU64::new(0));
+        if let Some(tipset_source) = &self.tipset_source {
+            return tipset_source
+                .chain_exchange(&request)
+                .await
+                .map_err(|e| format!("Tipset source request failed: {e:#}"))?
+                .into_result();
+        }
+
         let chain_exchange_result = match peer_id {
             // Specific peer is given to send request, send specifically to that peer.
             Some(id) => Self::chain_exchange_request(
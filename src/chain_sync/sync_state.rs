@@ -137,11 +137,13 @@ mod lotus_json {
     use chrono::{DateTime, Utc};
     use std::sync::Arc;
 
+    use schemars::JsonSchema;
     use serde::{Deserialize, Serialize};
     use serde_json::json;
 
-    #[derive(Serialize, Deserialize)]
+    #[derive(Serialize, Deserialize, JsonSchema)]
     #[serde(rename_all = "PascalCase")]
+    #[schemars(rename = "SyncState")]
     pub struct SyncStateLotusJson {
         #[serde(skip_serializing_if = "LotusJson::is_none", default)]
         base: LotusJson<Option<Tipset>>,
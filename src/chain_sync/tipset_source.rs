@@ -0,0 +1,394 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Alternative sources of chain data, for nodes which can't or shouldn't rely
+//! on libp2p peers. When a source is configured, the chain exchange requests
+//! of the syncer are answered by the source, and the head of the source is
+//! announced to the [`ChainMuxer`](super::ChainMuxer) as a [`NetworkEvent`],
+//! so that its tipsets go through the usual validation.
+
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use ahash::{HashMap, HashMapExt as _, HashSet};
+use anyhow::Context as _;
+use cid::Cid;
+use futures::future::try_join_all;
+use parking_lot::Mutex;
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::blocks::{Tipset, TipsetKeys};
+use crate::db::car::ManyCar;
+use crate::libp2p::{
+    chain_exchange::{
+        make_chain_exchange_response_from_store, ChainExchangeRequest, ChainExchangeResponse,
+        ChainExchangeResponseStatus, CompactedMessages, TipsetBundle,
+    },
+    NetworkEvent,
+};
+use crate::lotus_json::LotusJson;
+use crate::rpc_api::{chain_api::*, data_types::BlockMessages};
+use crate::rpc_client::ApiInfo;
+
+/// How often the head of a source is checked for changes
+const HEAD_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+pub enum TipsetSource {
+    /// A directory of CAR files, such as rolling `.forest.car.zst` segments
+    /// exported by another node. Files are picked up as they appear.
+    CarDirectory {
+        path: PathBuf,
+        store: ManyCar,
+        loaded: Mutex<HashSet<PathBuf>>,
+    },
+    /// A trusted Forest node, queried over JSON-RPC
+    Rpc(ApiInfo),
+}
+
+impl TipsetSource {
+    pub fn car_directory(path: PathBuf) -> Self {
+        Self::CarDirectory {
+            path,
+            store: ManyCar::default(),
+            loaded: Default::default(),
+        }
+    }
+
+    /// Creates a source from the API info of a Forest node, in the
+    /// `[token:]multiaddress` format of `FULLNODE_API_INFO`.
+    pub fn rpc(api_info: &str) -> anyhow::Result<Self> {
+        Ok(Self::Rpc(api_info.parse()?))
+    }
+
+    /// Answers a chain exchange request from the source.
+    pub async fn chain_exchange(
+        &self,
+        request: &ChainExchangeRequest,
+    ) -> anyhow::Result<ChainExchangeResponse> {
+        match self {
+            Self::CarDirectory { store, .. } => {
+                self.load_new_car_files()?;
+                Ok(make_chain_exchange_response_from_store(store, request))
+            }
+            Self::Rpc(api_info) => rpc_chain_exchange(api_info, request).await,
+        }
+    }
+
+    /// Returns the heaviest tipset of the source.
+    pub async fn head(&self) -> anyhow::Result<Tipset> {
+        match self {
+            Self::CarDirectory { store, .. } => {
+                self.load_new_car_files()?;
+                store.heaviest_tipset()
+            }
+            Self::Rpc(api_info) => {
+                let LotusJson(head): ChainHeadResult = call(api_info, CHAIN_HEAD, ()).await?;
+                Ok(head)
+            }
+        }
+    }
+
+    /// Announces the head of the source through `events` whenever it changes.
+    pub async fn announce_heads(
+        self: Arc<Self>,
+        events: flume::Sender<NetworkEvent>,
+    ) -> anyhow::Result<()> {
+        let mut announced: Option<TipsetKeys> = None;
+        let mut interval = tokio::time::interval(HEAD_POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let head = match self.head().await {
+                Ok(head) => head,
+                Err(e) => {
+                    warn!("Failed to get the head of the tipset source: {e:#}");
+                    continue;
+                }
+            };
+            if announced.as_ref() == Some(head.key()) {
+                continue;
+            }
+            debug!("Tipset source head at epoch {}", head.epoch());
+            events
+                .send_async(NetworkEvent::TipsetSourceHead(head.key().clone()))
+                .await
+                .context("network event receiver dropped")?;
+            announced = Some(head.key().clone());
+        }
+    }
+
+    /// Adds the CAR files which appeared in the directory since the last call.
+    /// Files which can't be read yet, such as segments still being written,
+    /// are retried on the next call.
+    fn load_new_car_files(&self) -> anyhow::Result<()> {
+        let Self::CarDirectory {
+            path,
+            store,
+            loaded,
+        } = self
+        else {
+            return Ok(());
+        };
+        let mut files = std::fs::read_dir(path)
+            .with_context(|| format!("couldn't read {}", path.display()))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        files.sort();
+        let mut loaded = loaded.lock();
+        for file in files {
+            let is_car = file
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map_or(false, |name| {
+                    name.ends_with(".car") || name.ends_with(".car.zst")
+                });
+            if !is_car || loaded.contains(&file) {
+                continue;
+            }
+            match store.read_only_files(std::iter::once(file.clone())) {
+                Ok(()) => {
+                    info!("Loaded {} into the tipset source", file.display());
+                    loaded.insert(file);
+                }
+                Err(e) => debug!("Couldn't load {} yet: {e}", file.display()),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Builds the response to a chain exchange request out of the tipsets and
+/// messages returned by the RPC API of a Forest node.
+async fn rpc_chain_exchange(
+    api_info: &ApiInfo,
+    request: &ChainExchangeRequest,
+) -> anyhow::Result<ChainExchangeResponse> {
+    let mut chain = Vec::with_capacity(request.request_len as usize);
+    let mut tsk = TipsetKeys::from_iter(request.start.clone());
+    while (chain.len() as u64) < request.request_len {
+        let LotusJson(tipset): ChainGetTipSetResult =
+            call(api_info, CHAIN_GET_TIPSET, (LotusJson(tsk),)).await?;
+        let messages = if request.include_messages() {
+            let block_messages: Vec<ChainGetBlockMessagesResult> = try_join_all(
+                tipset
+                    .cids()
+                    .into_iter()
+                    .map(|cid| call(api_info, CHAIN_GET_BLOCK_MESSAGES, (LotusJson(cid),))),
+            )
+            .await?;
+            Some(Arc::new(compact_messages(block_messages)))
+        } else {
+            None
+        };
+        tsk = tipset.parents().clone();
+        let epoch = tipset.epoch();
+        chain.push(TipsetBundle {
+            blocks: if request.include_blocks() {
+                tipset.blocks().to_vec()
+            } else {
+                vec![]
+            },
+            messages,
+        });
+        if epoch == 0 {
+            break;
+        }
+    }
+    Ok(ChainExchangeResponse {
+        status: if (chain.len() as u64) < request.request_len {
+            ChainExchangeResponseStatus::PartialResponse
+        } else {
+            ChainExchangeResponseStatus::Success
+        },
+        chain,
+        message: "Success".into(),
+    })
+}
+
+async fn call<P: Serialize, R: DeserializeOwned>(
+    api_info: &ApiInfo,
+    method_name: &str,
+    params: P,
+) -> anyhow::Result<R> {
+    api_info
+        .call(method_name, params, &None)
+        .await
+        .map_err(|e| match serde_json::to_string(&e) {
+            Ok(err_msg) => anyhow::Error::msg(err_msg),
+            Err(err) => err.into(),
+        })
+        .with_context(|| format!("{method_name} failed"))
+}
+
+/// Deduplicates the messages of the blocks of a tipset, in the same order as
+/// chain exchange providers.
+fn compact_messages(block_messages: Vec<BlockMessages>) -> CompactedMessages {
+    let mut bls_order = HashMap::new();
+    let mut secp_order = HashMap::new();
+    let mut compacted = CompactedMessages {
+        bls_msgs: vec![],
+        bls_msg_includes: vec![],
+        secp_msgs: vec![],
+        secp_msg_includes: vec![],
+    };
+    for BlockMessages {
+        bls_msg,
+        secp_msg,
+        cids,
+    } in block_messages
+    {
+        // Message CIDs are those of the BLS messages, then the SECP ones
+        let (bls_cids, secp_cids) = cids.split_at(bls_msg.len());
+        compacted.bls_msg_includes.push(include(
+            &mut bls_order,
+            &mut compacted.bls_msgs,
+            bls_cids.iter().copied().zip(bls_msg),
+        ));
+        compacted.secp_msg_includes.push(include(
+            &mut secp_order,
+            &mut compacted.secp_msgs,
+            secp_cids.iter().copied().zip(secp_msg),
+        ));
+    }
+    compacted
+}
+
+/// Adds the messages of a block which weren't included by the previous blocks,
+/// returning the indices of all messages of the block.
+fn include<T>(
+    order: &mut HashMap<Cid, u64>,
+    messages: &mut Vec<T>,
+    block_messages: impl Iterator<Item = (Cid, T)>,
+) -> Vec<u64> {
+    block_messages
+        .map(|(cid, message)| {
+            *order.entry(cid).or_insert_with(|| {
+                messages.push(message);
+                messages.len() as u64 - 1
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genesis::EXPORT_SR_40;
+    use crate::libp2p::chain_exchange::{HEADERS, MESSAGES};
+    use crate::shim::{address::Address, message::Message};
+    use crate::utils::db::car_stream::CarStream;
+
+    fn message(sequence: u64) -> (Cid, Message) {
+        let message = Message {
+            from: Address::new_id(0),
+            to: Address::new_id(1),
+            sequence,
+            ..Default::default()
+        };
+        (message.cid().unwrap(), message)
+    }
+
+    #[test]
+    fn compact_messages_dedups() {
+        let (cid_0, message_0) = message(0);
+        let (cid_1, message_1) = message(1);
+        let (cid_2, message_2) = message(2);
+        let block_messages = vec![
+            BlockMessages {
+                bls_msg: vec![message_0.clone(), message_1.clone()],
+                secp_msg: vec![],
+                cids: vec![cid_0, cid_1],
+            },
+            BlockMessages {
+                bls_msg: vec![message_1.clone(), message_2.clone()],
+                secp_msg: vec![],
+                cids: vec![cid_1, cid_2],
+            },
+        ];
+        let compacted = compact_messages(block_messages);
+        assert_eq!(compacted.bls_msgs, vec![message_0, message_1, message_2]);
+        assert_eq!(compacted.bls_msg_includes, vec![vec![0, 1], vec![1, 2]]);
+        assert!(compacted.secp_msgs.is_empty());
+        assert_eq!(compacted.secp_msg_includes, vec![Vec::<u64>::new(); 2]);
+    }
+
+    #[tokio::test]
+    async fn car_directory_serves_tipsets_and_messages() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = TipsetSource::car_directory(dir.path().into());
+        // Nothing to serve yet
+        assert!(source.head().await.is_err());
+
+        // Files are picked up as they appear, other files are ignored
+        std::fs::write(dir.path().join("export40.car"), EXPORT_SR_40).unwrap();
+        std::fs::write(dir.path().join("notes.txt"), b"not a car").unwrap();
+        std::fs::write(dir.path().join("partial.car"), b"still being written").unwrap();
+        let roots = CarStream::new(EXPORT_SR_40).await.unwrap().header.roots;
+        let head = source.head().await.unwrap();
+        assert_eq!(head.epoch(), 39);
+        assert_eq!(head.cids(), roots);
+
+        let response = source
+            .chain_exchange(&ChainExchangeRequest {
+                start: roots,
+                request_len: 2,
+                options: HEADERS | MESSAGES,
+            })
+            .await
+            .unwrap();
+        assert_eq!(response.status, ChainExchangeResponseStatus::Success);
+        assert_eq!(response.chain.len(), 2);
+        assert_eq!(response.chain[0].blocks, head.blocks().to_vec());
+        let messages = response.chain[0].messages.as_ref().unwrap();
+        assert_eq!(messages.secp_msgs.len(), 22);
+        assert_eq!(messages.bls_msgs.len(), 12);
+        // The tipset at epoch 38 has two blocks
+        assert_eq!(response.chain[1].blocks.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn car_directory_missing_tipset() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("export40.car"), EXPORT_SR_40).unwrap();
+        let source = TipsetSource::car_directory(dir.path().into());
+        let (missing, _) = message(0);
+
+        let response = source
+            .chain_exchange(&ChainExchangeRequest {
+                start: vec![missing],
+                request_len: 1,
+                options: HEADERS,
+            })
+            .await
+            .unwrap();
+        assert!(response.chain.is_empty());
+        assert_ne!(response.status, ChainExchangeResponseStatus::Success);
+
+        // A directory that doesn't exist is an error
+        let source = TipsetSource::car_directory(dir.path().join("missing"));
+        assert!(source.head().await.is_err());
+    }
+
+    #[test]
+    fn parse_api_info() {
+        let api_info: ApiInfo = "/ip4/127.0.0.1/tcp/2345/http".parse().unwrap();
+        assert_eq!(
+            api_info.multiaddr,
+            "/ip4/127.0.0.1/tcp/2345/http".parse().unwrap()
+        );
+        assert_eq!(api_info.token, None);
+
+        let api_info: ApiInfo = "token:/dns/forest.local/tcp/1234/http".parse().unwrap();
+        assert_eq!(
+            api_info.multiaddr,
+            "/dns/forest.local/tcp/1234/http".parse().unwrap()
+        );
+        assert_eq!(api_info.token.as_deref(), Some("token"));
+
+        assert!("not a multiaddress".parse::<ApiInfo>().is_err());
+        assert!("token:not a multiaddress".parse::<ApiInfo>().is_err());
+        assert!(matches!(
+            TipsetSource::rpc("/ip4/127.0.0.1/tcp/2345/http"),
+            Ok(TipsetSource::Rpc(_))
+        ));
+    }
+}
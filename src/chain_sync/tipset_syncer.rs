@@ -35,14 +35,17 @@ use fvm_ipld_encoding::to_vec;
 use nonempty::{nonempty, NonEmpty};
 use thiserror::Error;
 use tokio::task::JoinSet;
-use tracing::{debug, error, info, trace, warn};
+use tracing::{debug, error, info, info_span, instrument, trace, warn, Instrument as _};
 
 use crate::chain_sync::{
-    bad_block_cache::BadBlockCache, consensus::collect_errs, metrics,
+    bad_block_cache::BadBlockCache, checkpoints::Checkpoints, consensus::collect_errs, metrics,
     network_context::SyncNetworkContext, sync_state::SyncStage, validation::TipsetValidator,
 };
 
 const MAX_TIPSETS_TO_REQUEST: u64 = 100;
+/// Maximum number of segments of a tipset range to download headers for at
+/// once
+const MAX_CONCURRENT_SEGMENTS: usize = 8;
 
 #[derive(Debug, Error)]
 pub enum TipsetProcessorError {
@@ -84,6 +87,8 @@ pub enum TipsetRangeSyncerError {
     ChainForkLengthExceedsFinalityThreshold,
     #[error("Chain for block forked from local chain at genesis, refusing to sync block: {0}")]
     ForkAtGenesisBlock(String),
+    #[error("Chain does not match trusted checkpoint: {0}")]
+    CheckpointMismatch(String),
     #[error("Querying tipsets from the network failed: {0}")]
     NetworkTipsetQueryFailed(String),
     #[error("Query tipset messages from the network failed: {0}")]
@@ -240,6 +245,7 @@ pub(in crate::chain_sync) struct TipsetProcessor<DB> {
     chain_store: Arc<ChainStore<DB>>,
     bad_block_cache: Arc<BadBlockCache>,
     genesis: Arc<Tipset>,
+    checkpoints: Arc<Checkpoints>,
 }
 
 impl<DB> TipsetProcessor<DB>
@@ -255,6 +261,7 @@ where
         chain_store: Arc<ChainStore<DB>>,
         bad_block_cache: Arc<BadBlockCache>,
         genesis: Arc<Tipset>,
+        checkpoints: Arc<Checkpoints>,
     ) -> Self {
         Self {
             state: TipsetProcessorState::Idle,
@@ -265,6 +272,7 @@ where
             chain_store,
             bad_block_cache,
             genesis,
+            checkpoints,
         }
     }
 
@@ -275,6 +283,7 @@ where
         let bad_block_cache = self.bad_block_cache.clone();
         let tracker = self.tracker.clone();
         let genesis = self.genesis.clone();
+        let checkpoints = self.checkpoints.clone();
 
         // Define the low end of the range
         let current_head = chain_store.heaviest_tipset();
@@ -293,6 +302,7 @@ where
             chain_store,
             bad_block_cache,
             genesis,
+            checkpoints,
         )
         .ok()?;
         for tipset in tipset_group.tipsets() {
@@ -602,6 +612,7 @@ where
         chain_store: Arc<ChainStore<DB>>,
         bad_block_cache: Arc<BadBlockCache>,
         genesis: Arc<Tipset>,
+        checkpoints: Arc<Checkpoints>,
     ) -> Result<Self, TipsetRangeSyncerError> {
         let mut tipset_tasks = JoinSet::new();
         let tipset_range_length = proposed_head.epoch() - current_head.epoch();
@@ -623,6 +634,7 @@ where
             network.clone(),
             bad_block_cache.clone(),
             genesis.clone(),
+            checkpoints,
         ));
 
         let tipsets_included = HashSet::from_iter([proposed_head.key().clone()]);
@@ -714,6 +726,7 @@ where
 /// messages going forward on the chain and validate each extension. Finally set
 /// the proposed head as the heaviest tipset.
 #[allow(clippy::too_many_arguments)]
+#[instrument(skip_all, fields(from = current_head.epoch(), to = proposed_head.epoch()))]
 async fn sync_tipset_range<DB: Blockstore + Sync + Send + 'static>(
     proposed_head: Arc<Tipset>,
     current_head: Arc<Tipset>,
@@ -724,23 +737,26 @@ async fn sync_tipset_range<DB: Blockstore + Sync + Send + 'static>(
     network: SyncNetworkContext<DB>,
     bad_block_cache: Arc<BadBlockCache>,
     genesis: Arc<Tipset>,
+    checkpoints: Arc<Checkpoints>,
 ) -> Result<(), TipsetRangeSyncerError> {
     tracker
         .write()
         .init(current_head.clone(), proposed_head.clone());
 
-    let parent_tipsets = match sync_headers_in_reverse(
+    let segments = match sync_headers_in_segments(
         tracker.clone(),
         tipset_range_length,
         proposed_head.clone(),
         &current_head,
+        &checkpoints,
         &bad_block_cache,
         &chain_store,
         network.clone(),
     )
+    .instrument(info_span!("sync_stage", stage = %SyncStage::Headers))
     .await
     {
-        Ok(parent_tipsets) => parent_tipsets,
+        Ok(segments) => segments,
         Err(why) => {
             tracker.write().error(why.to_string());
             return Err(why);
@@ -749,7 +765,7 @@ async fn sync_tipset_range<DB: Blockstore + Sync + Send + 'static>(
 
     // Persist the blocks from the synced Tipsets into the store
     tracker.write().set_stage(SyncStage::Headers);
-    let headers: Vec<&BlockHeader> = parent_tipsets.iter().flat_map(|t| t.blocks()).collect();
+    let headers: Vec<&BlockHeader> = segments.iter().flatten().flat_map(|t| t.blocks()).collect();
     if let Err(why) = persist_objects(chain_store.blockstore(), &headers) {
         tracker.write().error(why.to_string());
         return Err(why.into());
@@ -757,16 +773,16 @@ async fn sync_tipset_range<DB: Blockstore + Sync + Send + 'static>(
 
     //  Sync and validate messages from the tipsets
     tracker.write().set_stage(SyncStage::Messages);
-    if let Err(why) = sync_messages_check_state(
+    if let Err(why) = sync_messages_in_segments(
         tracker.clone(),
         state_manager,
         network,
         chain_store.clone(),
         &bad_block_cache,
-        parent_tipsets,
+        segments,
         &genesis,
-        InvalidBlockStrategy::Strict,
     )
+    .instrument(info_span!("sync_stage", stage = %SyncStage::Messages))
     .await
     {
         error!("Sync messages check state failed for tipset range");
@@ -795,6 +811,133 @@ async fn sync_tipset_range<DB: Blockstore + Sync + Send + 'static>(
     Ok(())
 }
 
+/// Download headers between the proposed head and the current one available
+/// locally, concurrently in segments delimited by the trusted `checkpoints`
+/// within the range. Segments are joined once the lowest tipset of each one is
+/// found to contain the checkpoint block of the segment below. Returns the
+/// segments from the lowest to the highest, each from its head down. The head
+/// of each segment is the parent of the lowest tipset of the segment above.
+#[allow(clippy::too_many_arguments)]
+async fn sync_headers_in_segments<DB: Blockstore + Sync + Send + 'static>(
+    tracker: crate::chain_sync::chain_muxer::WorkerState,
+    tipset_range_length: u64,
+    proposed_head: Arc<Tipset>,
+    current_head: &Tipset,
+    checkpoints: &Checkpoints,
+    bad_block_cache: &BadBlockCache,
+    chain_store: &ChainStore<DB>,
+    network: SyncNetworkContext<DB>,
+) -> Result<Vec<Vec<Arc<Tipset>>>, TipsetRangeSyncerError> {
+    let checkpoints = checkpoints
+        .between(current_head.epoch(), proposed_head.epoch())
+        .collect::<Vec<_>>();
+    if checkpoints.is_empty() {
+        let tipsets = sync_headers_in_reverse(
+            tracker,
+            tipset_range_length,
+            proposed_head,
+            current_head,
+            bad_block_cache,
+            chain_store,
+            network,
+        )
+        .await?;
+        return Ok(vec![tipsets]);
+    }
+    info!(
+        "Downloading headers in {} segments delimited by checkpoints",
+        checkpoints.len() + 1
+    );
+
+    // The checkpoint blocks, each heading the segment below it
+    let anchors = stream::iter(checkpoints.iter().copied())
+        .map(|(epoch, cid)| {
+            let network = network.clone();
+            async move {
+                let key = TipsetKeys::from_iter([cid]);
+                let anchor = match chain_store.tipset_from_keys(&key) {
+                    Ok(anchor) => anchor,
+                    Err(_) => network
+                        .chain_exchange_headers(None, &key, 1)
+                        .await
+                        .map_err(TipsetRangeSyncerError::NetworkTipsetQueryFailed)?
+                        .into_iter()
+                        .next()
+                        .ok_or_else(|| {
+                            TipsetRangeSyncerError::NetworkTipsetQueryFailed(format!(
+                                "checkpoint block {cid} not found"
+                            ))
+                        })?,
+                };
+                if anchor.epoch() != epoch {
+                    return Err(TipsetRangeSyncerError::CheckpointMismatch(format!(
+                        "block {cid} is at epoch {}, not {epoch}",
+                        anchor.epoch()
+                    )));
+                }
+                Ok(anchor)
+            }
+        })
+        .buffered(MAX_CONCURRENT_SEGMENTS)
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    // The segments above the lowest checkpoint, from the highest one down
+    let segment_bounds = std::iter::once(proposed_head)
+        .chain(anchors.iter().skip(1).rev().cloned())
+        .zip(anchors.iter().rev().map(|anchor| anchor.epoch()))
+        .collect::<Vec<_>>();
+    let upper_segments = stream::iter(segment_bounds)
+        .map(|(top, until_epoch)| {
+            download_headers(
+                tracker.clone(),
+                top,
+                until_epoch,
+                bad_block_cache,
+                chain_store,
+                network.clone(),
+            )
+        })
+        .buffered(MAX_CONCURRENT_SEGMENTS)
+        .try_collect::<Vec<_>>();
+    // Unwrapping is safe as there is at least one checkpoint
+    let lowest_anchor = anchors.first().unwrap().clone();
+    let lowest_segment = sync_headers_in_reverse(
+        tracker.clone(),
+        (lowest_anchor.epoch() - current_head.epoch()) as u64,
+        lowest_anchor,
+        current_head,
+        bad_block_cache,
+        chain_store,
+        network.clone(),
+    );
+    let (upper_segments, lowest_segment) =
+        futures::future::try_join(upper_segments, lowest_segment).await?;
+
+    let mut segments: Vec<Vec<Arc<Tipset>>> = upper_segments;
+    segments.push(lowest_segment);
+    for i in 1..segments.len() {
+        // Replace the checkpoint block heading this segment with the full
+        // tipset found by the segment above
+        let bottom = segments[i - 1]
+            .pop()
+            .expect("upper segments end below their head");
+        let anchor = &mut segments[i][0];
+        if bottom.epoch() != anchor.epoch()
+            || !bottom.cids().contains(anchor.min_ticket_block().cid())
+        {
+            return Err(TipsetRangeSyncerError::CheckpointMismatch(format!(
+                "tipset at epoch {} doesn't contain checkpoint block {}",
+                bottom.epoch(),
+                anchor.min_ticket_block().cid()
+            )));
+        }
+        *anchor = bottom;
+    }
+    segments.reverse();
+    Ok(segments)
+}
+
 /// Download headers between the proposed head and the current one available
 /// locally. If they turn out to be on different forks, download more headers up
 /// to a certain limit to try to find a common ancestor.
@@ -807,55 +950,19 @@ async fn sync_headers_in_reverse<DB: Blockstore + Sync + Send + 'static>(
     chain_store: &ChainStore<DB>,
     network: SyncNetworkContext<DB>,
 ) -> Result<Vec<Arc<Tipset>>, TipsetRangeSyncerError> {
-    let mut parent_blocks: Vec<Cid> = vec![];
-    let mut parent_tipsets = Vec::with_capacity(tipset_range_length as usize + 1);
-    parent_tipsets.push(proposed_head.clone());
     tracker.write().set_epoch(current_head.epoch());
-
-    let total_size = proposed_head.epoch() - current_head.epoch();
-    #[allow(deprecated)] // Tracking issue: https://github.com/ChainSafe/forest/issues/3157
-    let wp = WithProgressRaw::new("Downloading headers", total_size as u64);
-
-    'sync: loop {
-        // Unwrapping is safe here because the tipset vector always
-        // has at least one element
-        let oldest_parent = parent_tipsets.last().unwrap();
-        let work_to_be_done = oldest_parent.epoch() - current_head.epoch();
-        wp.set((work_to_be_done - total_size).unsigned_abs());
-        validate_tipset_against_cache(bad_block_cache, oldest_parent.parents(), &parent_blocks)?;
-
-        // Check if we are at the end of the range
-        if oldest_parent.epoch() <= current_head.epoch() {
-            // Current tipset epoch is less than or equal to the epoch of
-            // Tipset we a synchronizing toward, stop.
-            break;
-        }
-        // Attempt to load the parent tipset from local store
-        if let Ok(tipset) = chain_store.tipset_from_keys(oldest_parent.parents()) {
-            parent_blocks.extend(tipset.cids());
-            parent_tipsets.push(tipset);
-            continue;
-        }
-
-        let epoch_diff = oldest_parent.epoch() - current_head.epoch();
-        let window = min(epoch_diff, MAX_TIPSETS_TO_REQUEST as i64);
-        let network_tipsets = network
-            .chain_exchange_headers(None, oldest_parent.parents(), window as u64)
-            .await
-            .map_err(TipsetRangeSyncerError::NetworkTipsetQueryFailed)?;
-
-        for tipset in network_tipsets {
-            // Break if have already traversed the entire tipset range
-            if tipset.epoch() < current_head.epoch() {
-                break 'sync;
-            }
-            validate_tipset_against_cache(bad_block_cache, tipset.key(), &parent_blocks)?;
-            parent_blocks.extend(tipset.cids());
-            tracker.write().set_epoch(tipset.epoch());
-            parent_tipsets.push(tipset);
-        }
-    }
-    drop(wp);
+    let mut parent_tipsets = Vec::with_capacity(tipset_range_length as usize + 1);
+    parent_tipsets.extend(
+        download_headers(
+            tracker,
+            proposed_head,
+            current_head.epoch(),
+            bad_block_cache,
+            chain_store,
+            network.clone(),
+        )
+        .await?,
+    );
 
     // Unwrapping is safe here because we assume that the tipset
     // vector was initialized with a tipset that will not be removed
@@ -913,6 +1020,66 @@ async fn sync_headers_in_reverse<DB: Blockstore + Sync + Send + 'static>(
     Ok(parent_tipsets)
 }
 
+/// Download the headers from `proposed_head` back to the first tipset at or
+/// below `until_epoch`, loading them from the store when available.
+async fn download_headers<DB: Blockstore + Sync + Send + 'static>(
+    tracker: crate::chain_sync::chain_muxer::WorkerState,
+    proposed_head: Arc<Tipset>,
+    until_epoch: ChainEpoch,
+    bad_block_cache: &BadBlockCache,
+    chain_store: &ChainStore<DB>,
+    network: SyncNetworkContext<DB>,
+) -> Result<Vec<Arc<Tipset>>, TipsetRangeSyncerError> {
+    let mut parent_blocks: Vec<Cid> = vec![];
+    let mut parent_tipsets = vec![proposed_head.clone()];
+
+    let total_size = proposed_head.epoch() - until_epoch;
+    #[allow(deprecated)] // Tracking issue: https://github.com/ChainSafe/forest/issues/3157
+    let wp = WithProgressRaw::new("Downloading headers", total_size as u64);
+
+    'sync: loop {
+        // Unwrapping is safe here because the tipset vector always
+        // has at least one element
+        let oldest_parent = parent_tipsets.last().unwrap();
+        let work_to_be_done = oldest_parent.epoch() - until_epoch;
+        wp.set((work_to_be_done - total_size).unsigned_abs());
+        validate_tipset_against_cache(bad_block_cache, oldest_parent.parents(), &parent_blocks)?;
+
+        // Check if we are at the end of the range
+        if oldest_parent.epoch() <= until_epoch {
+            // Current tipset epoch is less than or equal to the epoch of
+            // Tipset we a synchronizing toward, stop.
+            break;
+        }
+        // Attempt to load the parent tipset from local store
+        if let Ok(tipset) = chain_store.tipset_from_keys(oldest_parent.parents()) {
+            parent_blocks.extend(tipset.cids());
+            parent_tipsets.push(tipset);
+            continue;
+        }
+
+        let epoch_diff = oldest_parent.epoch() - until_epoch;
+        let window = min(epoch_diff, MAX_TIPSETS_TO_REQUEST as i64);
+        let network_tipsets = network
+            .chain_exchange_headers(None, oldest_parent.parents(), window as u64)
+            .await
+            .map_err(TipsetRangeSyncerError::NetworkTipsetQueryFailed)?;
+
+        for tipset in network_tipsets {
+            // Break if have already traversed the entire tipset range
+            if tipset.epoch() < until_epoch {
+                break 'sync;
+            }
+            validate_tipset_against_cache(bad_block_cache, tipset.key(), &parent_blocks)?;
+            parent_blocks.extend(tipset.cids());
+            tracker.write().set_epoch(tipset.epoch());
+            parent_tipsets.push(tipset);
+        }
+    }
+    drop(wp);
+    Ok(parent_tipsets)
+}
+
 #[allow(clippy::too_many_arguments)]
 async fn sync_tipset<DB: Blockstore + Sync + Send + 'static>(
     proposed_head: Arc<Tipset>,
@@ -937,6 +1104,7 @@ async fn sync_tipset<DB: Blockstore + Sync + Send + 'static>(
         vec![proposed_head.clone()],
         &genesis,
         InvalidBlockStrategy::Forgiving,
+        true,
     )
     .await
     {
@@ -998,7 +1166,7 @@ async fn fetch_batch<DB: Blockstore>(
                 // Construct full tipset from fetched messages
                 let bundle = TipsetBundle {
                     blocks: tipset.blocks().to_vec(),
-                    messages: Some(messages),
+                    messages: Some(Arc::new(messages)),
                 };
 
                 let full_tipset = FullTipset::try_from(&bundle)
@@ -1019,9 +1187,132 @@ async fn fetch_batch<DB: Blockstore>(
     }
 }
 
+/// Sync and validate the messages of `segments`, as returned by
+/// [`sync_headers_in_segments`], concurrently.
+///
+/// Validating the lowest tipset of a segment executes the head of the segment
+/// below, starting from the state root in its header. When that state is
+/// available locally, the segment is validated straight away, otherwise its
+/// messages are downloaded and persisted while the segment below computes it,
+/// and only the validation waits for the segment below. Either way, a segment is only joined to
+/// the chain, and its head set as the heaviest tipset, once the segment below
+/// has validated its own head, which checks that the state root the segment
+/// started from is the one computed by the segment below.
+#[allow(clippy::too_many_arguments)]
+async fn sync_messages_in_segments<DB: Blockstore + Send + Sync + 'static>(
+    tracker: crate::chain_sync::chain_muxer::WorkerState,
+    state_manager: Arc<StateManager<DB>>,
+    network: SyncNetworkContext<DB>,
+    chainstore: Arc<ChainStore<DB>>,
+    bad_block_cache: &BadBlockCache,
+    segments: Vec<Vec<Arc<Tipset>>>,
+    genesis: &Tipset,
+) -> Result<(), TipsetRangeSyncerError> {
+    // Set once each segment is validated and joined to the chain
+    let (joined_tx, joined_rx): (Vec<_>, Vec<_>) = segments
+        .iter()
+        .map(|_| tokio::sync::watch::channel(false))
+        .unzip();
+    let wait_joined = |mut joined: tokio::sync::watch::Receiver<bool>| async move {
+        joined
+            .wait_for(|joined| *joined)
+            .await
+            .map(|_| ())
+            .map_err(|_| {
+                TipsetRangeSyncerError::Validation("segment below failed to sync".to_string())
+            })
+    };
+
+    let segment_syncs =
+        segments
+            .into_iter()
+            .zip(joined_tx)
+            .enumerate()
+            .map(|(i, (segment, joined))| {
+                let below = i.checked_sub(1).map(|below| joined_rx[below].clone());
+                let tracker = tracker.clone();
+                let state_manager = state_manager.clone();
+                let network = network.clone();
+                let chainstore = chainstore.clone();
+                async move {
+                    let Some(below) = below else {
+                        sync_messages_check_state(
+                            tracker,
+                            state_manager,
+                            network,
+                            chainstore,
+                            bad_block_cache,
+                            segment,
+                            genesis,
+                            InvalidBlockStrategy::Strict,
+                            true,
+                        )
+                        .await?;
+                        let _ = joined.send(true);
+                        return Ok(());
+                    };
+
+                    // Unwrapping is safe as segments are never empty
+                    let lowest = segment.last().unwrap();
+                    let boundary = chainstore.tipset_from_keys(lowest.parents())?;
+                    let ahead = chainstore
+                        .blockstore()
+                        .has(boundary.parent_state())
+                        .unwrap_or(false);
+                    if !ahead {
+                        let request_window = state_manager.chain_config().request_window;
+                        fetch_messages(&segment, request_window, &network, chainstore.blockstore())
+                            .await?;
+                        debug!(
+                            "State at epoch {} not available, waiting for the segment below",
+                            boundary.epoch()
+                        );
+                        wait_joined(below.clone()).await?;
+                    }
+                    let head = segment[0].clone();
+                    sync_messages_check_state(
+                        tracker,
+                        state_manager,
+                        network,
+                        chainstore.clone(),
+                        bad_block_cache,
+                        segment,
+                        genesis,
+                        InvalidBlockStrategy::Strict,
+                        !ahead,
+                    )
+                    .await?;
+                    wait_joined(below).await?;
+                    info!("Joined segment up to epoch {}", head.epoch());
+                    chainstore.set_heaviest_tipset(head)?;
+                    let _ = joined.send(true);
+                    Ok::<_, TipsetRangeSyncerError>(())
+                }
+            });
+    futures::future::try_join_all(segment_syncs).await?;
+    Ok(())
+}
+
+/// Download the messages of `tipsets` that aren't in the `BlockStore` yet and
+/// persist them, without validating anything.
+async fn fetch_messages<DB: Blockstore>(
+    tipsets: &[Arc<Tipset>],
+    request_window: u32,
+    network: &SyncNetworkContext<DB>,
+    db: &DB,
+) -> Result<(), TipsetRangeSyncerError> {
+    stream::iter(tipsets.iter().rev().cloned())
+        .chunks(request_window as usize)
+        .map(|batch| fetch_batch(batch, network, db))
+        .buffer_unordered(64)
+        .try_for_each(|_| futures::future::ok(()))
+        .await
+}
+
 /// Going forward along the tipsets, try to load the messages in them from the
 /// `BlockStore`, or download them from the network, then validate the full
-/// tipset on each epoch.
+/// tipset on each epoch. Tipsets are set as the heaviest tipset once validated
+/// if `set_heaviest` is set.
 #[allow(clippy::too_many_arguments)]
 async fn sync_messages_check_state<DB: Blockstore + Send + Sync + 'static>(
     tracker: crate::chain_sync::chain_muxer::WorkerState,
@@ -1032,6 +1323,7 @@ async fn sync_messages_check_state<DB: Blockstore + Send + Sync + 'static>(
     tipsets: Vec<Arc<Tipset>>,
     genesis: &Tipset,
     invalid_block_strategy: InvalidBlockStrategy,
+    set_heaviest: bool,
 ) -> Result<(), TipsetRangeSyncerError> {
     let request_window = state_manager.chain_config().request_window;
     let db = chainstore.blockstore();
@@ -1059,7 +1351,9 @@ async fn sync_messages_check_state<DB: Blockstore + Send + Sync + 'static>(
                 )
                 .await?;
                 drop(timer);
-                chainstore.set_heaviest_tipset(Arc::new(full_tipset.into_tipset()))?;
+                if set_heaviest {
+                    chainstore.set_heaviest_tipset(Arc::new(full_tipset.into_tipset()))?;
+                }
                 tracker.write().set_epoch(current_epoch);
                 metrics::LAST_VALIDATED_TIPSET_EPOCH.set(current_epoch as u64);
             }
@@ -1072,6 +1366,7 @@ async fn sync_messages_check_state<DB: Blockstore + Send + Sync + 'static>(
 /// executed), adding the successful ones to the tipset tracker, and the failed
 /// ones to the bad block cache, depending on strategy. Any bad block fails
 /// validation.
+#[instrument(skip_all, fields(epoch = full_tipset.epoch()))]
 async fn validate_tipset<DB: Blockstore + Send + Sync + 'static>(
     state_manager: Arc<StateManager<DB>>,
     chainstore: &ChainStore<DB>,
@@ -1098,7 +1393,9 @@ async fn validate_tipset<DB: Blockstore + Send + Sync + 'static>(
     debug!("Tipset keys: {:?}", full_tipset_key.cids);
 
     for b in blocks {
-        let validation_fn = tokio::task::spawn(validate_block(state_manager.clone(), Arc::new(b)));
+        let validation_fn = tokio::task::spawn(
+            validate_block(state_manager.clone(), Arc::new(b)).in_current_span(),
+        );
         validations.push(validation_fn);
     }
 
@@ -1151,6 +1448,7 @@ async fn validate_tipset<DB: Blockstore + Send + Sync + 'static>(
 /// * Checking that the messages in the block correspond to the agreed upon
 ///   total ordering
 /// * That the block is a deterministic derivative of the underlying consensus
+#[instrument(skip_all, fields(cid = %block.cid()))]
 async fn validate_block<DB: Blockstore + Sync + Send + 'static>(
     state_manager: Arc<StateManager<DB>>,
     block: Arc<Block>,
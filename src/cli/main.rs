@@ -26,7 +26,10 @@ where
         .block_on(async {
             let mut config = crate::Config::default();
             config.client.rpc_token = token;
-            logger::setup_logger(&crate::cli_shared::cli::CliOpts::default());
+            logger::setup_logger(
+                &crate::cli_shared::cli::CliOpts::default(),
+                &Default::default(),
+            );
             if let Ok(name) = state_network_name((), &config.client.rpc_token).await {
                 if get_actual_chain_name(&name) != "mainnet" {
                     CurrentNetwork::set_global(Network::Testnet);
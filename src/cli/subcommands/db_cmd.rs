@@ -1,11 +1,15 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::path::PathBuf;
 use std::sync::Arc;
 
 use crate::cli_shared::cli::Config;
 use crate::rpc_api::progress_api::GetProgressType;
-use crate::rpc_client::{db_ops::db_gc, progress_ops::get_progress};
+use crate::rpc_client::{
+    db_ops::{db_backup, db_gc},
+    progress_ops::get_progress,
+};
 use crate::utils::io::ProgressBar;
 use chrono::Utc;
 use clap::Subcommand;
@@ -17,6 +21,13 @@ use crate::utils::bail_moved_cmd;
 pub enum DBCommands {
     /// Run DB garbage collection
     GC,
+    /// Back up the database of the running node to a directory on the node's
+    /// machine, which must not exist or be empty. The node keeps syncing
+    /// during the backup. Restore it with `forest-tool db restore`
+    Backup {
+        /// Directory to write the backup to
+        dir: PathBuf,
+    },
     // Those subcommands are hidden and only here to help users migrating to forest-tool
     #[command(hide = true)]
     Stats,
@@ -72,6 +83,25 @@ impl DBCommands {
 
                 Ok(())
             }
+            Self::Backup { dir } => {
+                let start = Utc::now();
+                // The backup is written by the node, which may run in another
+                // working directory
+                let dir = std::env::current_dir()?.join(dir);
+
+                println!("Backing up the database to {}", dir.display());
+                db_backup((dir.clone(),), &config.client.rpc_token)
+                    .await
+                    .map_err(handle_rpc_err)?;
+
+                println!(
+                    "Database backed up to {}. took {}s",
+                    dir.display(),
+                    (Utc::now() - start).num_seconds()
+                );
+
+                Ok(())
+            }
             Self::Clean { .. } => bail_moved_cmd("db clean", "forest-tool db destroy"),
         }
     }
@@ -81,7 +81,12 @@ impl NetCommands {
                         if addresses.is_empty() {
                             return None;
                         }
-                        Some(format!("{}, [{}]", info.id, addresses.join(", ")))
+                        Some(match info.head_epoch {
+                            Some(epoch) => {
+                                format!("{}, [{}], head {epoch}", info.id, addresses.join(", "))
+                            }
+                            None => format!("{}, [{}]", info.id, addresses.join(", ")),
+                        })
                     })
                     .collect();
                 print_stdout(output.join("\n"));
@@ -112,6 +117,7 @@ impl NetCommands {
                 let addr_info = AddrInfo {
                     id: id.clone(),
                     addrs,
+                    head_epoch: None,
                 };
 
                 net_connect((addr_info,), &config.client.rpc_token)
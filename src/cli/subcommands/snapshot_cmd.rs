@@ -3,16 +3,22 @@
 
 use super::*;
 use crate::cli::subcommands::{cli_error_and_die, handle_rpc_err};
+use crate::cli_shared::snapshot::manifest::{
+    manifest_path, SignedSnapshotManifest, SnapshotManifest,
+};
 use crate::cli_shared::snapshot::{self, TrustedVendor};
 use crate::db::car::forest::DEFAULT_FOREST_CAR_FRAME_SIZE;
 use crate::rpc_api::chain_api::ChainExportParams;
-use crate::rpc_client::{chain_ops::*, state_network_name};
+use crate::rpc_client::{chain_ops::*, state_network_name, wallet_sign};
+use crate::shim::address::{Address, StrictAddress};
 use crate::utils::bail_moved_cmd;
 use anyhow::{bail, Context as _};
+use base64::{prelude::BASE64_STANDARD, Engine};
 use chrono::Utc;
 use clap::Subcommand;
 use human_repr::HumanCount;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use tempfile::NamedTempFile;
 use tokio::io::AsyncWriteExt;
 
@@ -35,6 +41,10 @@ pub enum SnapshotCommands {
         /// How many state-roots to include. Lower limit is 900 for `calibnet` and `mainnet`.
         #[arg(short, long)]
         depth: Option<crate::chain::ChainEpochDelta>,
+        /// Sign a manifest of the snapshot with the key of this wallet address
+        /// and save it next to the snapshot.
+        #[arg(long, conflicts_with_all = ["skip_checksum", "dry_run"])]
+        sign: Option<String>,
     },
 
     // This subcommand is hidden and only here to help users migrating to forest-tool
@@ -83,7 +93,16 @@ impl SnapshotCommands {
                 dry_run,
                 tipset,
                 depth,
+                sign,
             } => {
+                let signer = sign
+                    .map(|address| {
+                        StrictAddress::from_str(&address)
+                            .map(Address::from)
+                            .with_context(|| format!("Invalid address: {address}"))
+                    })
+                    .transpose()?;
+
                 let chain_head = match chain_head(&config.client.rpc_token).await {
                     Ok(LotusJson(head)) => head,
                     Err(_) => cli_error_and_die("Could not get network head", 1),
@@ -99,7 +118,7 @@ impl SnapshotCommands {
                 let output_path = match output_path.is_dir() {
                     true => output_path.join(snapshot::filename(
                         TrustedVendor::Forest,
-                        chain_name,
+                        &chain_name,
                         Utc::now().date_naive(),
                         epoch,
                         true,
@@ -110,9 +129,10 @@ impl SnapshotCommands {
                 let output_dir = output_path.parent().context("invalid output path")?;
                 let temp_path = NamedTempFile::new_in(output_dir)?.into_temp_path();
 
+                let recent_roots = depth.unwrap_or(config.chain.recent_state_roots);
                 let params = ChainExportParams {
                     epoch,
-                    recent_roots: depth.unwrap_or(config.chain.recent_state_roots),
+                    recent_roots,
                     output_path: temp_path.to_path_buf(),
                     tipset_keys: chain_head.key().clone(),
                     skip_checksum,
@@ -162,7 +182,18 @@ impl SnapshotCommands {
                 let _ = handle.await;
 
                 if let Some(hash) = hash_result {
-                    save_checksum(&output_path, hash).await?;
+                    save_checksum(&output_path, hash.clone()).await?;
+                    if let Some(signer) = signer {
+                        let LotusJson(head) = chain_get_tipset_by_height(
+                            (epoch, chain_head.key().clone()),
+                            &config.client.rpc_token,
+                        )
+                        .await
+                        .map_err(handle_rpc_err)?;
+                        let manifest = SnapshotManifest::new(chain_name, &head, recent_roots, hash);
+                        save_manifest(&output_path, manifest, signer, &config.client.rpc_token)
+                            .await?;
+                    }
                 }
                 temp_path.persist(output_path)?;
 
@@ -180,6 +211,29 @@ impl SnapshotCommands {
     }
 }
 
+/// Signs `manifest` with the wallet key of `signer` and saves it next to the
+/// snapshot at `source`.
+async fn save_manifest(
+    source: &Path,
+    manifest: SnapshotManifest,
+    signer: Address,
+    token: &Option<String>,
+) -> anyhow::Result<()> {
+    let payload = manifest.to_payload();
+    let message = BASE64_STANDARD.encode(&payload);
+    let LotusJson(signature) = wallet_sign((signer.into(), message.into_bytes()), token)
+        .await
+        .map_err(handle_rpc_err)?;
+    let signed = SignedSnapshotManifest {
+        payload,
+        signer,
+        signature,
+    };
+    // Check the signature, e.g. in case `signer` is an ID address
+    signed.verify(&[signer])?;
+    signed.save(&manifest_path(source)).await
+}
+
 /// Prints hex-encoded representation of SHA-256 checksum and saves it to a file
 /// with the same name but with a `.sha256sum` extension.
 async fn save_checksum(source: &Path, encoded_hash: String) -> anyhow::Result<()> {
@@ -4,7 +4,7 @@
 use std::path::PathBuf;
 
 use crate::lotus_json::LotusJson;
-use crate::rpc_client::state_ops::state_fetch_root;
+use crate::rpc_client::state_ops::{state_compute_range, state_fetch_root, state_index_messages};
 use crate::shim::clock::ChainEpoch;
 use crate::shim::econ::TokenAmount;
 use cid::Cid;
@@ -33,6 +33,36 @@ pub enum StateCommands {
         #[arg(short, long)]
         save_to_file: Option<PathBuf>,
     },
+    /// Add the messages of past tipsets to the message index used by
+    /// `Filecoin.StateListMessages`. The node must run with
+    /// `enable_message_index` set.
+    IndexMessages {
+        /// Lowest epoch to index
+        #[arg(long)]
+        from: ChainEpoch,
+        /// Highest epoch to index, defaults to the current head
+        #[arg(long)]
+        to: Option<ChainEpoch>,
+    },
+    /// Compute the states of past tipsets and persist them, so that they
+    /// don't have to be computed again when queried
+    Compute {
+        /// Range of epochs to compute, as `FROM..TO` (inclusive). `TO`
+        /// defaults to the current head.
+        #[arg(long, value_parser = parse_epoch_range)]
+        range: (ChainEpoch, Option<ChainEpoch>),
+    },
+}
+
+fn parse_epoch_range(range: &str) -> anyhow::Result<(ChainEpoch, Option<ChainEpoch>)> {
+    let (from, to) = range
+        .split_once("..")
+        .ok_or_else(|| anyhow::anyhow!("expected a range such as `1000..2000`"))?;
+    let to = match to {
+        "" => None,
+        to => Some(to.parse()?),
+    };
+    Ok((from.parse()?, to))
 }
 
 impl StateCommands {
@@ -46,6 +76,24 @@ impl StateCommands {
                         .map_err(handle_rpc_err)?
                 );
             }
+            Self::IndexMessages { from, to } => {
+                let added = state_index_messages(
+                    (from, to.unwrap_or(ChainEpoch::MAX)),
+                    &config.client.rpc_token,
+                )
+                .await
+                .map_err(handle_rpc_err)?;
+                println!("Added {added} message index entries");
+            }
+            Self::Compute { range: (from, to) } => {
+                let computed = state_compute_range(
+                    (from, to.unwrap_or(ChainEpoch::MAX)),
+                    &config.client.rpc_token,
+                )
+                .await
+                .map_err(handle_rpc_err)?;
+                println!("Computed the states of {computed} tipsets");
+            }
         }
         Ok(())
     }
@@ -6,6 +6,8 @@ use std::{
     time::Duration,
 };
 
+use crate::blocks::TipsetKeys;
+use crate::rpc_api::data_types::ForkTreeBranch;
 use crate::rpc_client::*;
 use crate::{chain_sync::SyncStage, lotus_json::LotusJson};
 use cid::Cid;
@@ -13,7 +15,7 @@ use clap::Subcommand;
 use ticker::Ticker;
 
 use super::Config;
-use crate::cli::subcommands::{format_vec_pretty, handle_rpc_err};
+use crate::cli::subcommands::{format_vec_pretty, handle_rpc_err, prompt_confirm};
 
 #[derive(Debug, Subcommand)]
 pub enum SyncCommands {
@@ -37,6 +39,21 @@ pub enum SyncCommands {
         #[arg(short)]
         cid: String,
     },
+    /// List the tips of the forks recently received from the network, with
+    /// their weights and why they are not the head
+    Forks,
+    /// Switch to the fork of the given tipset. The head is reset to the tipset
+    /// the fork branches off from, and the fork is synced from there. The node
+    /// switches back if the current chain stays heavier, unless its blocks are
+    /// marked as bad
+    Checkout {
+        /// CIDs of the blocks of the tipset to switch to
+        #[arg(num_args = 1.., required = true)]
+        cids: Vec<Cid>,
+        /// Skip confirmation dialogue
+        #[arg(long)]
+        force: bool,
+    },
 }
 
 impl SyncCommands {
@@ -152,6 +169,71 @@ impl SyncCommands {
                 println!("OK");
                 Ok(())
             }
+            Self::Forks => {
+                let fork_tree = sync_fork_tree((), &config.client.rpc_token)
+                    .await
+                    .map_err(handle_rpc_err)?;
+                println!("Head: {}", format_tipset_keys(&fork_tree.head));
+                println!(
+                    "{:>10} {:>10} {:<11} {:<40} Tip",
+                    "Height", "Fork", "Status", "Weight"
+                );
+                for branch in fork_tree.branches {
+                    println!(
+                        "{:>10} {:>10} {:<11} {:<40} {}",
+                        branch.height,
+                        branch
+                            .fork_height
+                            .map(|height| height.to_string())
+                            .unwrap_or_else(|| "?".into()),
+                        format!("{:?}", branch.status),
+                        format_weight(&branch),
+                        format_tipset_keys(&branch.tip)
+                    );
+                    if let Some(reason) = &branch.reason {
+                        println!("{:>10} {reason}", "");
+                    }
+                }
+                Ok(())
+            }
+            Self::Checkout { cids, force } => {
+                let tsk = TipsetKeys::from_iter(cids);
+                println!(
+                    "Switching to the fork of tipset {}",
+                    format_tipset_keys(&tsk)
+                );
+                if !force && !prompt_confirm() {
+                    println!("Aborted.");
+                    return Ok(());
+                }
+                sync_checkout((LotusJson(tsk),), &config.client.rpc_token)
+                    .await
+                    .map_err(handle_rpc_err)?;
+                println!("OK");
+                Ok(())
+            }
         }
     }
 }
+
+fn format_tipset_keys(tsk: &TipsetKeys) -> String {
+    format_vec_pretty(
+        tsk.cids
+            .clone()
+            .into_iter()
+            .map(|cid| cid.to_string())
+            .collect(),
+    )
+}
+
+/// Formats the total weight of the tip of a fork, along with its components
+/// when known.
+fn format_weight(branch: &ForkTreeBranch) -> String {
+    match &branch.weight {
+        Some(weight) => format!(
+            "{} = {} + {} + {} ({} wins)",
+            weight.total, branch.parent_weight, weight.power, weight.election, weight.win_count
+        ),
+        None => format!("{} + ?", branch.parent_weight),
+    }
+}
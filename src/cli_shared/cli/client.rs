@@ -48,6 +48,13 @@ pub struct Client {
     pub snapshot: bool,
     /// If this is true, delete the snapshot at `snapshot_path` if it's a local file.
     pub consume_snapshot: bool,
+    /// If this is true and `snapshot_path` is an `http(s)` URL, read the
+    /// snapshot with range requests instead of importing it.
+    pub remote_snapshot: bool,
+    /// Addresses of the snapshot publishers whose signed manifests are
+    /// trusted. If this isn't empty, imported snapshots must come with a
+    /// manifest signed by one of them.
+    pub trusted_snapshot_publishers: Vec<String>,
     pub snapshot_height: Option<i64>,
     pub snapshot_head: Option<i64>,
     pub snapshot_path: Option<PathBuf>,
@@ -73,6 +80,15 @@ pub struct Client {
     pub token_exp: Duration,
     /// Display progress bars mode. Auto will display if TTY.
     pub show_progress_bars: ProgressBarVisibility,
+    /// Index the messages of new tipsets by sender and recipient, which is
+    /// required by `Filecoin.StateListMessages`.
+    pub enable_message_index: bool,
+    /// Migrate the state ahead of network upgrades in the background, so that
+    /// the migration at the upgrade epoch only has to handle the latest changes.
+    pub enable_pre_migrations: bool,
+    /// Log RPC calls taking longer than this many milliseconds, with their
+    /// parameters redacted. Disabled if unset.
+    pub rpc_slow_call_threshold_ms: Option<u32>,
 }
 
 impl Default for Client {
@@ -86,6 +102,8 @@ impl Default for Client {
             snapshot_path: None,
             snapshot: false,
             consume_snapshot: false,
+            remote_snapshot: false,
+            trusted_snapshot_publishers: vec![],
             snapshot_height: None,
             snapshot_head: None,
             skip_load: false,
@@ -96,6 +114,9 @@ impl Default for Client {
             rpc_address: SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), DEFAULT_PORT),
             token_exp: Duration::seconds(5184000), // 60 Days = 5184000 Seconds
             show_progress_bars: Default::default(),
+            enable_message_index: false,
+            enable_pre_migrations: true,
+            rpc_slow_call_threshold_ms: None,
         }
     }
 }
@@ -37,21 +37,42 @@ impl Default for DaemonConfig {
     }
 }
 
+/// Configuration of the traces exported with `--otlp`
+#[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+#[serde(default)]
+pub struct TracingConfig {
+    /// Fraction of traces to export, between 0 and 1. Child spans follow the
+    /// decision of their root, so traces are always complete.
+    pub sample_ratio: f64,
+}
+
+impl Default for TracingConfig {
+    fn default() -> Self {
+        Self { sample_ratio: 1.0 }
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Default, Debug, Clone)]
 #[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
 #[serde(default)]
 pub struct Config {
     pub client: Client,
+    pub db: crate::db::backend::DbBackendConfig,
     pub parity_db: crate::db::parity_db_config::ParityDbConfig,
     pub network: Libp2pConfig,
     pub sync: SyncConfig,
     pub chain: Arc<ChainConfig>,
     pub daemon: DaemonConfig,
+    pub tracing: TracingConfig,
 }
 
 impl Config {
-    pub fn db_config(&self) -> &DbConfig {
-        &self.parity_db
+    pub fn db_config(&self) -> DbConfig {
+        DbConfig {
+            backend: self.db.backend,
+            parity_db: self.parity_db.clone(),
+        }
     }
 }
 
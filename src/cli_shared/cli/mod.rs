@@ -80,6 +80,11 @@ pub struct CliOpts {
     /// Import a snapshot from a local CAR file and delete it, or from a URL
     #[arg(long)]
     pub consume_snapshot: Option<String>,
+    /// Read the `.forest.car.zst` snapshot given by an `http(s)` URL to
+    /// `--import-snapshot` with range requests instead of downloading it. The
+    /// snapshot is not stored, so it has to be given again after a restart.
+    #[arg(long, requires = "import_snapshot")]
+    pub remote_snapshot: bool,
     /// Halt with exit code 0 after successfully importing a snapshot
     #[arg(long)]
     pub halt_after_import: bool,
@@ -97,6 +102,19 @@ pub struct CliOpts {
     /// network head is
     #[arg(long)]
     pub tipset_sample_size: Option<u8>,
+    /// File of trusted checkpoints, in the format printed by
+    /// `forest-tool archive checkpoints`, used to download the headers of
+    /// long tipset ranges in concurrent segments
+    #[arg(long)]
+    pub checkpoint_file: Option<PathBuf>,
+    /// Sync from the CAR files of a directory, such as rolling `.forest.car.zst`
+    /// segments, instead of from libp2p peers
+    #[arg(long, conflicts_with = "sync_from_rpc")]
+    pub sync_from_car_dir: Option<PathBuf>,
+    /// Sync from a trusted Forest node instead of from libp2p peers, given its
+    /// API info in the `[token:]multiaddress` format
+    #[arg(long)]
+    pub sync_from_rpc: Option<String>,
     /// Amount of Peers we want to be connected to (default is 75)
     #[arg(long)]
     pub target_peer_count: Option<u32>,
@@ -129,6 +147,14 @@ pub struct CliOpts {
     /// Endpoint of `grafana loki`
     #[arg(long, default_value = "http://127.0.0.1:3100")]
     pub loki_endpoint: String,
+    /// Export traces to an `OpenTelemetry` collector (e.g., Jaeger or Tempo).
+    /// See `[tracing]` in the configuration for sampling. Not supported with
+    /// `--detach`.
+    #[arg(long)]
+    pub otlp: bool,
+    /// `OTLP/HTTP` endpoint of the `OpenTelemetry` collector
+    #[arg(long, default_value = "http://127.0.0.1:4318/v1/traces")]
+    pub otlp_endpoint: String,
     /// Specify a directory into which rolling log files should be appended
     #[arg(long)]
     pub log_dir: Option<PathBuf>,
@@ -201,6 +227,7 @@ impl CliOpts {
             cfg.client.snapshot_path = Some(snapshot_path.into());
             cfg.client.snapshot = true;
         }
+        cfg.client.remote_snapshot = self.remote_snapshot;
         if let Some(snapshot_path) = &self.consume_snapshot {
             cfg.client.snapshot_path = Some(snapshot_path.into());
             cfg.client.snapshot = true;
@@ -234,6 +261,15 @@ impl CliOpts {
         if let Some(tipset_sample_size) = self.tipset_sample_size {
             cfg.sync.tipset_sample_size = tipset_sample_size.into();
         }
+        if let Some(checkpoint_file) = &self.checkpoint_file {
+            cfg.sync.checkpoint_file = Some(checkpoint_file.clone());
+        }
+        if let Some(car_source_dir) = &self.sync_from_car_dir {
+            cfg.sync.car_source_dir = Some(car_source_dir.clone());
+        }
+        if let Some(rpc_source) = &self.sync_from_rpc {
+            cfg.sync.rpc_source = Some(rpc_source.clone());
+        }
         if let Some(encrypt_keystore) = self.encrypt_keystore {
             cfg.client.encrypt_keystore = encrypt_keystore;
         }
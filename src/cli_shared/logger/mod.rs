@@ -1,13 +1,37 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use opentelemetry::sdk::{
+    trace::{self, Sampler},
+    Resource,
+};
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig as _;
 use tracing_chrome::{ChromeLayerBuilder, FlushGuard};
 use tracing_subscriber::{filter::LevelFilter, prelude::*, EnvFilter};
 
-use crate::cli_shared::cli::CliOpts;
+use crate::cli_shared::cli::{CliOpts, TracingConfig};
 use crate::utils::misc::LoggingColor;
 
-pub fn setup_logger(opts: &CliOpts) -> (Option<tracing_loki::BackgroundTask>, Option<FlushGuard>) {
+/// Flushes the pending spans to the `OpenTelemetry` collector when dropped.
+/// The exporter runs on its own runtime, as the logger is set up before the
+/// main one is started.
+pub struct OtlpGuard(tokio::runtime::Runtime);
+
+impl Drop for OtlpGuard {
+    fn drop(&mut self) {
+        opentelemetry::global::shutdown_tracer_provider();
+    }
+}
+
+pub fn setup_logger(
+    opts: &CliOpts,
+    tracing_config: &TracingConfig,
+) -> (
+    Option<tracing_loki::BackgroundTask>,
+    Option<FlushGuard>,
+    Option<OtlpGuard>,
+) {
     let mut loki_task = None;
     let tracing_tokio_console = if opts.tokio_console {
         Some(
@@ -41,6 +65,45 @@ pub fn setup_logger(opts: &CliOpts) -> (Option<tracing_loki::BackgroundTask>, Op
     } else {
         None
     };
+    let (tracing_otlp, otlp_guard) = if opts.otlp {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("otlp-exporter")
+            .enable_all()
+            .build()
+            .map_err(|e| format!("Unable to create OTLP exporter runtime: {e}"))
+            .unwrap();
+        // The batch exporter spawns its task on the current runtime
+        let runtime_guard = runtime.enter();
+        let tracer = opentelemetry_otlp::new_pipeline()
+            .tracing()
+            .with_exporter(
+                opentelemetry_otlp::new_exporter()
+                    .http()
+                    .with_endpoint(&opts.otlp_endpoint),
+            )
+            .with_trace_config(
+                trace::config()
+                    .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+                        tracing_config.sample_ratio,
+                    ))))
+                    .with_resource(Resource::new([KeyValue::new("service.name", "forest")])),
+            )
+            .install_batch(opentelemetry::runtime::Tokio)
+            .map_err(|e| format!("Unable to create OTLP exporter: {e}"))
+            .unwrap();
+        drop(runtime_guard);
+        (
+            Some(
+                tracing_opentelemetry::layer()
+                    .with_tracer(tracer)
+                    .with_filter(get_env_filter(default_env_filter())),
+            ),
+            Some(OtlpGuard(runtime)),
+        )
+    } else {
+        (None, None)
+    };
     let tracing_rolling_file = if let Some(log_dir) = &opts.log_dir {
         let file_appender = tracing_appender::rolling::hourly(log_dir, "forest.log");
         Some(
@@ -67,6 +130,7 @@ pub fn setup_logger(opts: &CliOpts) -> (Option<tracing_loki::BackgroundTask>, Op
     tracing_subscriber::registry()
         .with(tracing_tokio_console)
         .with(tracing_loki)
+        .with(tracing_otlp)
         .with(tracing_rolling_file)
         .with(chrome_layer)
         .with(
@@ -75,7 +139,7 @@ pub fn setup_logger(opts: &CliOpts) -> (Option<tracing_loki::BackgroundTask>, Op
                 .with_filter(get_env_filter(default_env_filter())),
         )
         .init();
-    (loki_task, flush_guard)
+    (loki_task, flush_guard, otlp_guard)
 }
 
 // Log warnings to stderr
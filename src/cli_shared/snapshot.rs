@@ -18,6 +18,8 @@ use url::Url;
 
 use crate::cli_shared::snapshot::parse::ParsedFilename;
 
+pub mod manifest;
+
 /// Who hosts the snapshot on the web?
 /// See [`stable_url`].
 #[derive(
@@ -61,20 +63,22 @@ pub fn filename(
     .to_string()
 }
 
-/// Returns the path to the downloaded file.
+/// Returns the path to the downloaded file and the URL it was downloaded from,
+/// which the stable URL of the vendor redirected to.
 pub async fn fetch(
     directory: &Path,
     chain: &NetworkChain,
     vendor: TrustedVendor,
-) -> anyhow::Result<PathBuf> {
+) -> anyhow::Result<(PathBuf, Url)> {
     let (_len, path) = peek(vendor, chain).await?;
     let (date, height, forest_format) = ParsedFilename::parse_str(&path)
         .context("unexpected path format")?
         .date_and_height_and_forest();
-    let url = stable_url(vendor, chain)?;
+    let url = crate::utils::net::resolve_redirects(&stable_url(vendor, chain)?).await?;
     let filename = filename(vendor, chain, date, height, forest_format);
 
-    download_file_with_retry(&url, directory, &filename).await
+    let path = download_file_with_retry(&url, directory, &filename).await?;
+    Ok((path, url))
 }
 
 pub async fn download_file_with_retry(
@@ -0,0 +1,299 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Signed manifests are published next to snapshots, at the snapshot path with
+//! a [`MANIFEST_FILE_EXTENSION`] suffix. They record where a snapshot comes
+//! from and what it contains, so that nodes can check a snapshot against the
+//! publishers they trust before importing it.
+
+use std::{
+    fs::File,
+    io::{self, Read as _},
+    path::{Path, PathBuf},
+};
+
+use crate::blocks::{Tipset, TipsetKeys};
+use crate::shim::address::{Address, Protocol};
+use crate::shim::clock::ChainEpoch;
+use crate::shim::crypto::{Signature, SignatureType};
+use crate::utils::net::global_http_client;
+use anyhow::{bail, ensure, Context as _};
+use hex::ToHex as _;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use tracing::info;
+use url::Url;
+
+pub const MANIFEST_FILE_EXTENSION: &str = ".manifest.json";
+
+/// Provenance and checksum of an exported snapshot.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SnapshotManifest {
+    /// Name of the network the snapshot belongs to, e.g. `calibnet`.
+    pub network: String,
+    /// Key of the heaviest tipset in the snapshot.
+    #[serde(with = "crate::lotus_json")]
+    pub head: TipsetKeys,
+    /// Oldest epoch whose state-root is included in the snapshot.
+    pub start_epoch: ChainEpoch,
+    /// Epoch of `head`.
+    pub end_epoch: ChainEpoch,
+    /// Hex-encoded SHA-256 checksum of the snapshot file.
+    pub sha256: String,
+    /// Version of Forest that exported the snapshot.
+    pub forest_version: String,
+}
+
+impl SnapshotManifest {
+    pub fn new(network: String, head: &Tipset, depth: ChainEpoch, sha256: String) -> Self {
+        SnapshotManifest {
+            network,
+            head: head.key().clone(),
+            start_epoch: (head.epoch() - depth).max(0),
+            end_epoch: head.epoch(),
+            sha256,
+            forest_version: crate::utils::version::FOREST_VERSION_STRING.clone(),
+        }
+    }
+
+    /// The JSON payload of a [`SignedSnapshotManifest`], to be signed.
+    pub fn to_payload(&self) -> String {
+        serde_json::to_string(self).expect("serializing a manifest can't fail")
+    }
+}
+
+/// A manifest together with the signature of its publisher.
+///
+/// The manifest is kept as the exact JSON payload that was signed, so that
+/// verification doesn't depend on how it is serialized again.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SignedSnapshotManifest {
+    /// A [`SnapshotManifest`] serialized with [`SnapshotManifest::to_payload`]
+    pub payload: String,
+    #[serde(with = "crate::lotus_json")]
+    pub signer: Address,
+    #[serde(with = "crate::lotus_json")]
+    pub signature: Signature,
+}
+
+impl SignedSnapshotManifest {
+    /// Check that the manifest is signed by one of the `trusted` publishers,
+    /// and return it.
+    pub fn verify(&self, trusted: &[Address]) -> anyhow::Result<SnapshotManifest> {
+        ensure!(
+            trusted.contains(&self.signer),
+            "snapshot manifest is signed by {}, which is not a trusted publisher",
+            self.signer
+        );
+        // Delegated signatures aren't verified by `Signature::verify`
+        match (self.signer.protocol(), self.signature.signature_type()) {
+            (Protocol::BLS, SignatureType::Bls)
+            | (Protocol::Secp256k1, SignatureType::Secp256k1) => {}
+            (protocol, sig_type) => {
+                bail!("unsupported {sig_type:?} signature by a {protocol:?} address")
+            }
+        }
+        self.signature
+            .verify(self.payload.as_bytes(), &self.signer)
+            .map_err(|e| anyhow::anyhow!("invalid snapshot manifest signature: {e}"))?;
+        serde_json::from_str(&self.payload).context("couldn't parse signed snapshot manifest")
+    }
+
+    pub async fn save(&self, path: &Path) -> anyhow::Result<()> {
+        tokio::fs::write(path, serde_json::to_vec_pretty(self)?)
+            .await
+            .with_context(|| format!("couldn't write snapshot manifest to {}", path.display()))
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("couldn't open snapshot manifest {}", path.display()))?;
+        serde_json::from_reader(io::BufReader::new(file))
+            .with_context(|| format!("couldn't parse snapshot manifest {}", path.display()))
+    }
+
+    /// Fetch the manifest published next to the snapshot at `snapshot_url`.
+    ///
+    /// `snapshot_url` must be the URL the snapshot was downloaded from, after
+    /// redirects (see [`crate::utils::net::resolve_redirects`]), as stable URLs
+    /// may point to a newer snapshot by now.
+    pub async fn fetch(snapshot_url: &Url) -> anyhow::Result<Self> {
+        let manifest_url = manifest_url(snapshot_url)?;
+        info!(%manifest_url, "fetching snapshot manifest");
+        global_http_client()
+            .get(manifest_url.clone())
+            .send()
+            .await?
+            .error_for_status()
+            .with_context(|| format!("couldn't fetch snapshot manifest {manifest_url}"))?
+            .json()
+            .await
+            .with_context(|| format!("couldn't parse snapshot manifest {manifest_url}"))
+    }
+}
+
+/// Checks snapshots against their signed manifests before they are used.
+#[derive(Debug, Clone)]
+pub struct ManifestVerifier {
+    /// The network snapshots must belong to.
+    pub network: String,
+    pub trusted_publishers: Vec<Address>,
+}
+
+impl ManifestVerifier {
+    /// Verify the manifest of the snapshot that was published at `location`
+    /// (a local path or the URL it was downloaded from, after redirects) and
+    /// has been copied to `snapshot`.
+    ///
+    /// Callers still have to check that the head of the snapshot matches
+    /// [`SnapshotManifest::head`].
+    pub async fn verify_snapshot(
+        &self,
+        location: &Path,
+        snapshot: &Path,
+    ) -> anyhow::Result<SnapshotManifest> {
+        let signed = match Url::parse(&location.display().to_string()) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {
+                SignedSnapshotManifest::fetch(&url).await?
+            }
+            _ => SignedSnapshotManifest::load(&manifest_path(location))?,
+        };
+        let manifest = signed.verify(&self.trusted_publishers)?;
+        ensure!(
+            manifest.network == self.network,
+            "snapshot manifest is for {}, expected {}",
+            manifest.network,
+            self.network
+        );
+
+        info!("verifying the checksum of {}", snapshot.display());
+        let snapshot = snapshot.to_owned();
+        let sha256 = tokio::task::spawn_blocking(move || file_sha256(&snapshot)).await??;
+        ensure!(
+            sha256 == manifest.sha256,
+            "snapshot checksum {sha256} doesn't match the manifest checksum {}",
+            manifest.sha256
+        );
+        Ok(manifest)
+    }
+}
+
+/// The path of the manifest of the snapshot at `snapshot`.
+pub fn manifest_path(snapshot: &Path) -> PathBuf {
+    let mut path = snapshot.as_os_str().to_owned();
+    path.push(MANIFEST_FILE_EXTENSION);
+    path.into()
+}
+
+fn manifest_url(snapshot_url: &Url) -> anyhow::Result<Url> {
+    let mut url = snapshot_url.clone();
+    let path = url.path().to_owned();
+    ensure!(
+        !path.ends_with('/'),
+        "{snapshot_url} doesn't point to a snapshot file"
+    );
+    url.set_path(&format!("{path}{MANIFEST_FILE_EXTENSION}"));
+    url.set_query(None);
+    Ok(url)
+}
+
+/// Hex-encoded SHA-256 checksum of the file at `path`.
+pub fn file_sha256(path: &Path) -> anyhow::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 1024 * 1024];
+    loop {
+        let read = file.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+    }
+    Ok(hasher.finalize().encode_hex())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_management::generate_key;
+
+    fn manifest() -> SnapshotManifest {
+        SnapshotManifest {
+            network: "calibnet".into(),
+            head: TipsetKeys::default(),
+            start_epoch: 100,
+            end_epoch: 1000,
+            sha256: "00".repeat(32),
+            forest_version: "0.0.0".into(),
+        }
+    }
+
+    fn sign(manifest: SnapshotManifest, sig_type: SignatureType) -> SignedSnapshotManifest {
+        let key = generate_key(sig_type).unwrap();
+        let payload = manifest.to_payload();
+        let signature =
+            crate::key_management::sign(sig_type, key.key_info.private_key(), payload.as_bytes())
+                .unwrap();
+        SignedSnapshotManifest {
+            payload,
+            signer: key.address,
+            signature,
+        }
+    }
+
+    #[test]
+    fn signed_manifest_verification() {
+        for sig_type in [SignatureType::Bls, SignatureType::Secp256k1] {
+            let signed = sign(manifest(), sig_type);
+            assert_eq!(signed.verify(&[signed.signer]).unwrap(), manifest());
+            // untrusted publisher
+            assert!(signed.verify(&[]).is_err());
+            // tampered manifest
+            let mut tampered = signed.clone();
+            tampered.payload = tampered
+                .payload
+                .replace("\"EndEpoch\":1000", "\"EndEpoch\":1001");
+            assert_ne!(tampered.payload, signed.payload);
+            assert!(tampered.verify(&[signed.signer]).is_err());
+            // the payload is verified as published, not as serialized again
+            let mut reformatted = signed.clone();
+            reformatted.payload = serde_json::to_string_pretty(&manifest()).unwrap();
+            assert!(reformatted.verify(&[signed.signer]).is_err());
+            // delegated signatures are rejected
+            let mut delegated = signed.clone();
+            delegated.signature = Signature::new(SignatureType::Delegated, vec![]);
+            assert!(delegated.verify(&[signed.signer]).is_err());
+        }
+    }
+
+    #[test]
+    fn signed_manifest_json_roundtrip() {
+        let signed = sign(manifest(), SignatureType::Secp256k1);
+        let json = serde_json::to_string(&signed).unwrap();
+        assert_eq!(
+            serde_json::from_str::<SignedSnapshotManifest>(&json).unwrap(),
+            signed
+        );
+    }
+
+    #[test]
+    fn manifest_locations() {
+        assert_eq!(
+            manifest_path(Path::new("/tmp/snapshot.forest.car.zst")),
+            Path::new("/tmp/snapshot.forest.car.zst.manifest.json")
+        );
+        assert_eq!(
+            manifest_url(
+                &"https://example.com/latest/snapshot.car.zst?x=1"
+                    .parse()
+                    .unwrap()
+            )
+            .unwrap()
+            .as_str(),
+            "https://example.com/latest/snapshot.car.zst.manifest.json"
+        );
+        assert!(manifest_url(&"https://example.com/latest/".parse().unwrap()).is_err());
+    }
+}
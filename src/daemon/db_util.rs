@@ -3,6 +3,7 @@
 
 use crate::blocks::Tipset;
 use crate::cli_shared::snapshot;
+use crate::cli_shared::snapshot::manifest::ManifestVerifier;
 use crate::db::car::forest::FOREST_CAR_FILE_EXTENSION;
 use crate::db::car::{ForestCar, ManyCar};
 use crate::utils::db::car_stream::CarStream;
@@ -50,10 +51,13 @@ pub fn load_all_forest_cars<T>(store: &ManyCar<T>, forest_car_db_dir: &Path) ->
 
 /// This function validates and stores the CAR binary from `from_path`(either local path or URL) into the `{DB_ROOT}/car_db/`
 /// (automatically trans-code into `.forest.car.zst` format when needed), and returns its final file path and the heaviest tipset.
+/// If a `verifier` is given, the snapshot is checked against its signed manifest before it's stored.
+#[tracing::instrument(skip(forest_car_db_dir, consume_snapshot_file, verifier))]
 pub async fn import_chain_as_forest_car(
     from_path: &Path,
     forest_car_db_dir: &Path,
     consume_snapshot_file: bool,
+    verifier: Option<&ManifestVerifier>,
 ) -> anyhow::Result<(PathBuf, Tipset)> {
     info!("Importing chain from snapshot at: {}", from_path.display());
 
@@ -61,11 +65,29 @@ pub async fn import_chain_as_forest_car(
 
     let downloaded_car_temp_path =
         tempfile::NamedTempFile::new_in(forest_car_db_dir)?.into_temp_path();
-    if let Ok(url) = Url::parse(&from_path.display().to_string()) {
+    // The location the snapshot was actually read from, to find its manifest
+    let location = if let Ok(url) = Url::parse(&from_path.display().to_string()) {
+        let url = match verifier {
+            // Stable URLs may redirect to another snapshot by the time the
+            // manifest is fetched
+            Some(_) => crate::utils::net::resolve_redirects(&url).await?,
+            None => url,
+        };
         download_to(&url, &downloaded_car_temp_path).await?;
+        PathBuf::from(url.as_str())
     } else {
         move_or_copy_file(from_path, &downloaded_car_temp_path, consume_snapshot_file)?;
-    }
+        from_path.to_owned()
+    };
+
+    let manifest = match verifier {
+        Some(verifier) => Some(
+            verifier
+                .verify_snapshot(&location, &downloaded_car_temp_path)
+                .await?,
+        ),
+        None => None,
+    };
 
     let forest_car_db_path = forest_car_db_dir.join(format!(
         "{}{FOREST_CAR_FILE_EXTENSION}",
@@ -85,6 +107,20 @@ pub async fn import_chain_as_forest_car(
     }
 
     let ts = ForestCar::try_from(forest_car_db_path.as_path())?.heaviest_tipset()?;
+    if let Some(manifest) = manifest {
+        if manifest.head != *ts.key() {
+            fs::remove_file(&forest_car_db_path)?;
+            anyhow::bail!(
+                "snapshot head {} doesn't match the manifest head {}",
+                ts.key(),
+                manifest.head
+            );
+        }
+        info!(
+            "Verified snapshot manifest, exported by Forest {}",
+            manifest.forest_version
+        );
+    }
     info!(
         "Imported snapshot in: {}s, heaviest tipset epoch: {}",
         stopwatch.elapsed().as_secs(),
@@ -180,7 +216,7 @@ mod test {
     async fn import_snapshot_from_file(file_path: &str) -> anyhow::Result<()> {
         let temp = tempfile::Builder::new().tempdir()?;
         let (path, ts) =
-            import_chain_as_forest_car(Path::new(file_path), temp.path(), false).await?;
+            import_chain_as_forest_car(Path::new(file_path), temp.path(), false, None).await?;
         assert!(path.is_file());
         assert!(ts.epoch() > 0);
         Ok(())
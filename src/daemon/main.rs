@@ -85,10 +85,15 @@ where
 
     let (cfg, path) = opts.to_config().context("Error parsing config")?;
 
+    // The exporter thread would not survive the fork into the background
+    if opts.otlp && opts.detach {
+        anyhow::bail!("--otlp is not supported with --detach");
+    }
+
     // Run forest as a daemon if no other subcommands are used. Otherwise, run the
     // subcommand.
 
-    let (loki_task, _chrome_flush_guard) = logger::setup_logger(&opts);
+    let (loki_task, _chrome_flush_guard, _otlp_guard) = logger::setup_logger(&opts, &cfg.tracing);
     ProgressBar::set_progress_bars_visibility(cfg.client.show_progress_bars);
 
     if let Some(path) = &path {
@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 pub mod bundle;
-mod db_util;
+pub mod db_util;
 pub mod main;
 
 use crate::auth::{create_token, generate_priv_key, ADMIN, JWT_IDENTIFIER};
@@ -10,30 +10,34 @@ use crate::blocks::Tipset;
 use crate::chain::ChainStore;
 use crate::chain_sync::ChainMuxer;
 use crate::cli_shared::snapshot;
+use crate::cli_shared::snapshot::manifest::ManifestVerifier;
 use crate::cli_shared::{
     chain_path,
     cli::{CliOpts, Config},
 };
 
 use crate::daemon::db_util::{import_chain_as_forest_car, load_all_forest_cars};
-use crate::db::car::ManyCar;
+use crate::db::car::{AnyCar, ManyCar};
 use crate::db::db_engine::{db_root, open_proxy_db};
 use crate::db::rolling::DbGarbageCollector;
 use crate::genesis::{get_network_name_from_genesis, read_genesis_header};
 use crate::key_management::{
     KeyStore, KeyStoreConfig, ENCRYPTED_KEYSTORE_NAME, FOREST_KEYSTORE_PHRASE_ENV,
 };
-use crate::libp2p::{Libp2pConfig, Libp2pService, PeerId, PeerManager};
+use crate::libp2p::{
+    Libp2pConfig, Libp2pService, NetworkMessage, PeerId, PeerManager, BITSWAP_TIMEOUT,
+};
 use crate::message_pool::{MessagePool, MpoolConfig, MpoolRpcProvider};
 use crate::rpc::start_rpc;
 use crate::rpc_api::data_types::RPCState;
-use crate::shim::address::{CurrentNetwork, Network};
+use crate::shim::address::{Address, CurrentNetwork, Network};
 use crate::shim::clock::ChainEpoch;
 use crate::shim::version::NetworkVersion;
-use crate::state_manager::StateManager;
+use crate::state_manager::{MessageIndex, StateManager};
+use crate::state_migration::run_pre_migrations;
 use crate::utils::{
-    monitoring::MemStatsTracker, proofs_api::paramfetch::ensure_params_downloaded,
-    version::FOREST_VERSION_STRING,
+    io::parse_http_url, monitoring::MemStatsTracker,
+    proofs_api::paramfetch::ensure_params_downloaded, version::FOREST_VERSION_STRING,
 };
 use anyhow::{bail, Context as _};
 use bundle::load_actor_bundles;
@@ -44,9 +48,11 @@ use once_cell::sync::Lazy;
 use raw_sync_2::events::{Event, EventInit as _, EventState};
 use shared_memory::ShmemConf;
 use std::path::Path;
-use std::{cell::RefCell, net::TcpListener, path::PathBuf, sync::Arc};
+use std::str::FromStr as _;
+use std::{cell::RefCell, net::TcpListener, path::PathBuf, sync::Arc, time::Duration};
 use tempfile::{Builder, TempPath};
 use tokio::{
+    runtime::RuntimeFlavor,
     signal::{
         ctrl_c,
         unix::{signal, SignalKind},
@@ -177,7 +183,7 @@ pub(super) async fn start(
     let db_root_dir = db_root(&chain_data_path)?;
     let db = Arc::new(ManyCar::new(Arc::new(open_proxy_db(
         db_root_dir.clone(),
-        config.db_config().clone(),
+        config.db_config(),
     )?)));
     let forest_car_db_dir = db_root_dir.join("car_db");
     load_all_forest_cars(&db, &forest_car_db_dir)?;
@@ -251,13 +257,38 @@ pub(super) async fn start(
         let db_garbage_collector = db_garbage_collector.clone();
         async move { db_garbage_collector.collect_loop_event().await }
     });
+    services.spawn({
+        let db_garbage_collector = db_garbage_collector.clone();
+        async move { db_garbage_collector.backup_loop_event().await }
+    });
 
     let publisher = chain_store.publisher();
 
     // Initialize StateManager
-    let sm = StateManager::new(Arc::clone(&chain_store), Arc::clone(&config.chain))?;
+    let sm = StateManager::new(Arc::clone(&chain_store), Arc::clone(&config.chain))?
+        .with_tipset_state_store(db.writer().clone());
 
     let state_manager = Arc::new(sm);
+    services.spawn(state_manager.clone().prune_reverted_tipset_states());
+
+    if config.client.enable_pre_migrations {
+        services.spawn(run_pre_migrations(
+            state_manager.chain_config(),
+            state_manager.blockstore_owned(),
+            publisher.subscribe(),
+        ));
+    }
+
+    let msg_index = if config.client.enable_message_index {
+        let msg_index = Arc::new(MessageIndex::new(
+            Arc::clone(&state_manager),
+            db.writer().clone(),
+        ));
+        services.spawn(msg_index.clone().run());
+        Some(msg_index)
+    } else {
+        None
+    };
 
     let network_name = get_network_name_from_genesis(&genesis_header, &state_manager)?;
 
@@ -302,6 +333,31 @@ pub(super) async fn start(
     let network_rx = p2p_service.network_receiver();
     let network_send = p2p_service.network_sender();
 
+    // Fetch the state missing from the database, e.g. left out of state-light
+    // snapshots, from peers when reading actors
+    chain_store.set_state_fetcher(Arc::new({
+        let network_send = network_send.clone();
+        move |cid| {
+            let (tx, rx) = flume::bounded(1);
+            if network_send
+                .send(NetworkMessage::BitswapRequest {
+                    cid,
+                    response_channel: tx,
+                })
+                .is_err()
+            {
+                return false;
+            }
+            let wait = || rx.recv_timeout(BITSWAP_TIMEOUT).unwrap_or_default();
+            match tokio::runtime::Handle::try_current() {
+                Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+                    tokio::task::block_in_place(wait)
+                }
+                _ => wait(),
+            }
+        }
+    }));
+
     // Initialize mpool
     let provider = MpoolRpcProvider::new(publisher.clone(), Arc::clone(&state_manager));
     let mpool = MessagePool::new(
@@ -318,17 +374,22 @@ pub(super) async fn start(
     // Initialize ChainMuxer
     let chain_muxer = ChainMuxer::new(
         Arc::clone(&state_manager),
-        peer_manager,
+        peer_manager.clone(),
         mpool.clone(),
         network_send.clone(),
         network_rx,
         Arc::new(Tipset::from(genesis_header)),
-        tipset_sink,
+        tipset_sink.clone(),
         tipset_stream,
         config.sync.clone(),
     )?;
     let bad_blocks = chain_muxer.bad_blocks_cloned();
     let sync_state = chain_muxer.sync_state_cloned();
+    let fork_tree = chain_muxer.fork_tree_cloned();
+    services.spawn(chain_muxer.peer_heads_task());
+    if let Some(tipset_source) = chain_muxer.tipset_source_cloned() {
+        services.spawn(tipset_source.announce_heads(p2p_service.network_event_sender()));
+    }
     services.spawn(async { Err(anyhow::anyhow!("{}", chain_muxer.await)) });
 
     // Start services
@@ -344,6 +405,7 @@ pub(super) async fn start(
         let rpc_chain_store = Arc::clone(&chain_store);
 
         let gc_event_tx = db_garbage_collector.get_tx();
+        let db_backup_tx = db_garbage_collector.get_backup_tx();
         services.spawn(async move {
             info!("JSON-RPC endpoint started at {}", config.client.rpc_address);
             let beacon = Arc::new(
@@ -358,16 +420,25 @@ pub(super) async fn start(
                     mpool,
                     bad_blocks,
                     sync_state,
+                    fork_tree,
+                    tipset_sender: tipset_sink,
                     network_send,
+                    peer_manager,
                     network_name,
                     start_time,
                     beacon,
                     chain_store: rpc_chain_store,
                     gc_event_tx,
+                    db_backup_tx,
+                    msg_index,
                 }),
                 rpc_listen,
                 FOREST_VERSION_STRING.as_str(),
                 shutdown_send,
+                config
+                    .client
+                    .rpc_slow_call_threshold_ms
+                    .map(|ms| Duration::from_millis(ms.into())),
             )
             .await
             .map_err(|err| anyhow::anyhow!("{:?}", serde_json::to_string(&err)))
@@ -401,14 +472,43 @@ pub(super) async fn start(
     // Import chain if needed
     if !opts.skip_load.unwrap_or_default() {
         if let Some(path) = &config.client.snapshot_path {
-            let (car_db_path, ts) = import_chain_as_forest_car(
-                path,
-                &forest_car_db_dir,
-                config.client.consume_snapshot,
-            )
-            .await?;
-            db.read_only_files(std::iter::once(car_db_path.clone()))?;
-            debug!("Loaded car DB at {}", car_db_path.display());
+            let verifier = match config.client.trusted_snapshot_publishers.is_empty() {
+                true => None,
+                false => Some(ManifestVerifier {
+                    network: config.chain.network.to_string(),
+                    trusted_publishers: config
+                        .client
+                        .trusted_snapshot_publishers
+                        .iter()
+                        .map(|address| {
+                            Address::from_str(address).with_context(|| {
+                                format!("Invalid trusted snapshot publisher: {address}")
+                            })
+                        })
+                        .collect::<anyhow::Result<_>>()?,
+                }),
+            };
+            let ts = if config.client.remote_snapshot && parse_http_url(path).is_some() {
+                if verifier.is_some() {
+                    bail!("remote snapshots can't be verified against trusted publishers");
+                }
+                let car = AnyCar::open(path)?;
+                let ts = car.heaviest_tipset()?;
+                db.read_only(car);
+                info!("Reading remote snapshot at {}", path.display());
+                ts
+            } else {
+                let (car_db_path, ts) = import_chain_as_forest_car(
+                    path,
+                    &forest_car_db_dir,
+                    config.client.consume_snapshot,
+                    verifier.as_ref(),
+                )
+                .await?;
+                db.read_only_files(std::iter::once(car_db_path.clone()))?;
+                debug!("Loaded car DB at {}", car_db_path.display());
+                ts
+            };
             state_manager
                 .chain_store()
                 .set_heaviest_tipset(Arc::new(ts))?;
@@ -0,0 +1,419 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! The key-value engines the generations of a
+//! [`RollingDB`](crate::db::rolling::RollingDB) can be stored in. The engine
+//! is selected with the `backend` key of the `[db]` configuration section,
+//! and existing databases are moved between engines with
+//! `forest-tool db convert`.
+
+use std::path::{Path, PathBuf};
+
+use super::parity_db::ParityDb;
+use super::parity_db_config::ParityDbConfig;
+use super::redb::RedbDb;
+use super::{DBStatistics, ReplaceBlock, SettingsStore, TipsetStateStore};
+use crate::libp2p_bitswap::{BitswapStoreRead, BitswapStoreReadWrite};
+use anyhow::Context as _;
+use cid::Cid;
+use clap::ValueEnum as _;
+use fvm_ipld_blockstore::Blockstore;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+/// Number of blocks written at once when copying a database.
+const CONVERT_BATCH_SIZE: usize = 10_000;
+
+/// A key-value engine that stores blocks, settings and tipset states.
+pub trait DbBackend:
+    Blockstore
+    + ReplaceBlock
+    + SettingsStore
+    + TipsetStateStore
+    + BitswapStoreReadWrite
+    + DBStatistics
+    + Send
+    + Sync
+{
+    /// Open the database at `path`, creating it if it doesn't exist.
+    fn open(path: &Path, config: &DbConfig) -> anyhow::Result<Self>
+    where
+        Self: Sized;
+
+    /// Visit every block in the database, in no particular order.
+    fn for_each_block(
+        &self,
+        f: &mut dyn FnMut(Cid, Vec<u8>) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()>;
+}
+
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    clap::ValueEnum,
+    strum::Display,
+)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum DbBackendKind {
+    #[default]
+    ParityDb,
+    Redb,
+}
+
+impl DbBackendKind {
+    /// The engine of the database at `path`, if there is one.
+    pub fn detect(path: &Path) -> Option<Self> {
+        if RedbDb::exists(path) {
+            Some(DbBackendKind::Redb)
+        } else if ParityDb::exists(path) {
+            Some(DbBackendKind::ParityDb)
+        } else {
+            None
+        }
+    }
+}
+
+/// The `[db]` configuration section.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+#[serde(default)]
+pub struct DbBackendConfig {
+    pub backend: DbBackendKind,
+}
+
+/// Everything needed to open a database.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct DbConfig {
+    pub backend: DbBackendKind,
+    pub parity_db: ParityDbConfig,
+}
+
+/// A database of the configured [`DbBackendKind`].
+pub enum Db {
+    ParityDb(ParityDb),
+    Redb(RedbDb),
+}
+
+macro_rules! dispatch {
+    ($self:ident, $db:ident => $body:expr) => {
+        match $self {
+            Db::ParityDb($db) => $body,
+            Db::Redb($db) => $body,
+        }
+    };
+}
+
+impl DbBackend for Db {
+    /// Fails if there is a database of another engine at `path`.
+    fn open(path: &Path, config: &DbConfig) -> anyhow::Result<Self> {
+        recover_conversion(path)?;
+        if let Some(existing) = DbBackendKind::detect(path) {
+            anyhow::ensure!(
+                existing == config.backend,
+                "the database at {} is stored in {existing}, but the configured backend is {}. \
+                Convert it with `forest-tool db convert --to {}`",
+                path.display(),
+                config.backend,
+                config.backend
+            );
+        }
+        Ok(match config.backend {
+            DbBackendKind::ParityDb => Db::ParityDb(ParityDb::open(path, &config.parity_db)?),
+            DbBackendKind::Redb => Db::Redb(RedbDb::open(path)?),
+        })
+    }
+
+    fn for_each_block(
+        &self,
+        f: &mut dyn FnMut(Cid, Vec<u8>) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        dispatch!(self, db => db.for_each_block(f))
+    }
+}
+
+impl SettingsStore for Db {
+    fn read_bin(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        dispatch!(self, db => db.read_bin(key))
+    }
+
+    fn write_bin(&self, key: &str, value: &[u8]) -> anyhow::Result<()> {
+        dispatch!(self, db => db.write_bin(key, value))
+    }
+
+    fn exists(&self, key: &str) -> anyhow::Result<bool> {
+        dispatch!(self, db => SettingsStore::exists(db, key))
+    }
+
+    fn setting_keys(&self) -> anyhow::Result<Vec<String>> {
+        dispatch!(self, db => db.setting_keys())
+    }
+}
+
+impl TipsetStateStore for Db {
+    fn read_tipset_state(&self, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        dispatch!(self, db => db.read_tipset_state(key))
+    }
+
+    fn write_tipset_state(&self, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        dispatch!(self, db => db.write_tipset_state(key, value))
+    }
+
+    fn delete_tipset_state(&self, key: &[u8]) -> anyhow::Result<()> {
+        dispatch!(self, db => db.delete_tipset_state(key))
+    }
+}
+
+impl Blockstore for Db {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        dispatch!(self, db => Blockstore::get(db, k))
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        dispatch!(self, db => db.put_keyed(k, block))
+    }
+
+    fn has(&self, k: &Cid) -> anyhow::Result<bool> {
+        dispatch!(self, db => db.has(k))
+    }
+
+    fn put_many_keyed<D, I>(&self, blocks: I) -> anyhow::Result<()>
+    where
+        Self: Sized,
+        D: AsRef<[u8]>,
+        I: IntoIterator<Item = (Cid, D)>,
+    {
+        dispatch!(self, db => db.put_many_keyed(blocks))
+    }
+}
+
+impl ReplaceBlock for Db {
+    fn replace_block(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        dispatch!(self, db => db.replace_block(k, block))
+    }
+}
+
+impl BitswapStoreRead for Db {
+    fn contains(&self, cid: &Cid) -> anyhow::Result<bool> {
+        dispatch!(self, db => db.contains(cid))
+    }
+
+    fn get(&self, cid: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        dispatch!(self, db => BitswapStoreRead::get(db, cid))
+    }
+}
+
+impl BitswapStoreReadWrite for Db {
+    type Params = libipld::DefaultParams;
+
+    fn insert(&self, block: &libipld::Block<Self::Params>) -> anyhow::Result<()> {
+        dispatch!(self, db => db.insert(block))
+    }
+}
+
+impl DBStatistics for Db {
+    fn get_statistics(&self) -> Option<String> {
+        dispatch!(self, db => db.get_statistics())
+    }
+}
+
+/// Copy the blocks and settings of `src` to `dst`. Tipset states are
+/// recomputed when needed, and aren't copied. Returns the number of copied
+/// blocks.
+pub fn copy_db(src: &impl DbBackend, dst: &impl DbBackend) -> anyhow::Result<u64> {
+    let blocks = copy_blocks(src, dst)?;
+    for key in src.setting_keys()? {
+        if let Some(value) = src.read_bin(&key)? {
+            dst.write_bin(&key, &value)?;
+        }
+    }
+    Ok(blocks)
+}
+
+/// Copy the blocks of `src` to `dst`. Returns the number of copied blocks.
+fn copy_blocks(src: &impl DbBackend, dst: &impl DbBackend) -> anyhow::Result<u64> {
+    let mut buffer = Vec::with_capacity(CONVERT_BATCH_SIZE);
+    let mut blocks = 0;
+    src.for_each_block(&mut |cid: Cid, data: Vec<u8>| -> anyhow::Result<()> {
+        buffer.push((cid, data));
+        blocks += 1;
+        if buffer.len() == CONVERT_BATCH_SIZE {
+            dst.put_many_keyed(std::mem::take(&mut buffer))?;
+        }
+        Ok(())
+    })?;
+    dst.put_many_keyed(buffer)?;
+    Ok(blocks)
+}
+
+/// Convert the database at `path` to the `to` engine, in place. Other
+/// settings are taken from `config`.
+pub fn convert_db(path: &Path, to: DbBackendKind, config: &DbConfig) -> anyhow::Result<()> {
+    recover_conversion(path)?;
+    let from = DbBackendKind::detect(path)
+        .with_context(|| format!("no database found at {}", path.display()))?;
+    if from == to {
+        return Ok(());
+    }
+    let converted = converted_path(path, to);
+    info!("converting {} from {from} to {to}", path.display());
+    {
+        let src = Db::open(
+            path,
+            &DbConfig {
+                backend: from,
+                ..config.clone()
+            },
+        )?;
+        let dst = Db::open(
+            &converted,
+            &DbConfig {
+                backend: to,
+                ..config.clone()
+            },
+        )?;
+        let blocks = copy_db(&src, &dst)?;
+        info!("copied {blocks} blocks");
+    }
+    // See `recover_conversion` for interruptions between these steps
+    let backup = backup_path(path);
+    std::fs::rename(path, &backup)?;
+    std::fs::rename(&converted, path)?;
+    std::fs::remove_dir_all(&backup)?;
+    Ok(())
+}
+
+/// Where the database at `path` is converted to the `to` engine.
+fn converted_path(path: &Path, to: DbBackendKind) -> PathBuf {
+    path.with_extension(format!("{to}.tmp"))
+}
+
+/// Where the database at `path` is kept while the converted database is moved
+/// in its place.
+fn backup_path(path: &Path) -> PathBuf {
+    path.with_extension("bak")
+}
+
+/// Clean up after a conversion of the database at `path` that was interrupted.
+/// If the original database had already been moved aside, the conversion is
+/// finished if the converted database is in place, and rolled back otherwise.
+pub fn recover_conversion(path: &Path) -> anyhow::Result<()> {
+    let backup = backup_path(path);
+    if backup.exists() {
+        if DbBackendKind::detect(path).is_some() {
+            std::fs::remove_dir_all(&backup)?;
+        } else {
+            warn!(
+                "restoring {} after an interrupted conversion",
+                path.display()
+            );
+            if path.exists() {
+                std::fs::remove_dir_all(path)?;
+            }
+            std::fs::rename(&backup, path)?;
+        }
+    }
+    for kind in DbBackendKind::value_variants() {
+        let converted = converted_path(path, *kind);
+        if converted.exists() {
+            std::fs::remove_dir_all(&converted)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::SettingsStoreExt as _;
+    use cid::multihash::{Code, MultihashDigest as _};
+    use fvm_ipld_encoding::{DAG_CBOR, IPLD_RAW};
+
+    #[test]
+    fn convert_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db");
+        let blocks = [
+            (DAG_CBOR, Code::Blake2b256, b"dagon".to_vec()),
+            (DAG_CBOR, Code::Sha2_256, b"hydra".to_vec()),
+            (IPLD_RAW, Code::Blake2b256, b"cthulhu".to_vec()),
+        ]
+        .map(|(codec, code, data)| (Cid::new_v1(codec, code.digest(&data)), data));
+        {
+            let db = Db::open(&path, &DbConfig::default()).unwrap();
+            db.put_many_keyed(blocks.clone()).unwrap();
+            db.write_obj("key", &"value").unwrap();
+        }
+
+        for to in [DbBackendKind::Redb, DbBackendKind::ParityDb] {
+            convert_db(&path, to, &DbConfig::default()).unwrap();
+            assert_eq!(DbBackendKind::detect(&path), Some(to));
+            let config = DbConfig {
+                backend: to,
+                ..Default::default()
+            };
+            let db = Db::open(&path, &config).unwrap();
+            for (cid, data) in &blocks {
+                assert_eq!(Blockstore::get(&db, cid).unwrap().as_ref(), Some(data));
+            }
+            assert_eq!(
+                db.read_obj::<String>("key").unwrap().as_deref(),
+                Some("value")
+            );
+        }
+    }
+
+    #[test]
+    fn recover_interrupted_conversions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db");
+        let data = b"dagon".to_vec();
+        let cid = Cid::new_v1(IPLD_RAW, Code::Blake2b256.digest(&data));
+        drop(
+            Db::open(&path, &DbConfig::default())
+                .and_then(|db| db.put_keyed(&cid, &data).map(|_| db))
+                .unwrap(),
+        );
+        let redb = DbConfig {
+            backend: DbBackendKind::Redb,
+            ..Default::default()
+        };
+
+        // Interrupted between moving the database aside and moving the
+        // converted database in place: the conversion is rolled back
+        drop(Db::open(&converted_path(&path, DbBackendKind::Redb), &redb).unwrap());
+        std::fs::rename(&path, backup_path(&path)).unwrap();
+        let db = Db::open(&path, &DbConfig::default()).unwrap();
+        assert_eq!(Blockstore::get(&db, &cid).unwrap(), Some(data.clone()));
+        drop(db);
+        assert!(!backup_path(&path).exists());
+        assert!(!converted_path(&path, DbBackendKind::Redb).exists());
+
+        // Interrupted before removing the original database: the conversion is
+        // finished
+        drop(Db::open(&converted_path(&path, DbBackendKind::Redb), &redb).unwrap());
+        convert_db(&path, DbBackendKind::Redb, &DbConfig::default()).unwrap();
+        std::fs::create_dir(backup_path(&path)).unwrap();
+        let db = Db::open(&path, &redb).unwrap();
+        assert_eq!(Blockstore::get(&db, &cid).unwrap(), Some(data));
+        assert!(!backup_path(&path).exists());
+    }
+
+    #[test]
+    fn open_with_another_backend() {
+        let dir = tempfile::tempdir().unwrap();
+        drop(Db::open(dir.path(), &DbConfig::default()).unwrap());
+        let config = DbConfig {
+            backend: DbBackendKind::Redb,
+            ..Default::default()
+        };
+        assert!(Db::open(dir.path(), &config).is_err());
+    }
+}
@@ -0,0 +1,409 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Online backups of a [`RollingDB`], taken with `forest-cli db backup` and
+//! restored with `forest-tool db restore`. A backup is a directory holding a
+//! [`BackupManifest`] and a copy of the versioned database, whose generations
+//! are merged into one. The `.forest.car.zst` archives of the database, such as
+//! imported snapshots, are hard-linked into the backup (or copied, across file
+//! systems) rather than merged, as they never change.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::backend::DbBackendKind;
+use super::car::forest::FOREST_CAR_FILE_EXTENSION;
+use super::car::ManyCar;
+use super::db_mode::get_latest_versioned_database;
+use super::migration::ensure_migration_possible;
+use super::rolling::RollingDB;
+use super::setting_keys::HEAD_KEY;
+use super::MemoryDB;
+use super::{SettingsStore, SettingsStoreExt as _};
+use crate::blocks::{Tipset, TipsetKeys};
+use crate::ipld::stream_graph;
+use crate::utils::version::{FOREST_VERSION, FOREST_VERSION_STRING};
+use anyhow::Context as _;
+use futures::TryStreamExt as _;
+use fvm_ipld_blockstore::Blockstore;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+/// Number of blocks written to the backup at once.
+const BACKUP_BATCH_SIZE: usize = 10_000;
+
+/// Name of the [`BackupManifest`] file in a backup directory.
+pub const BACKUP_MANIFEST: &str = "backup.json";
+
+/// Directory of the `.forest.car.zst` archives in a versioned database, which
+/// the daemon loads on startup.
+const CAR_DB_DIR: &str = "car_db";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BackupManifest {
+    /// Version of the backed up database, see [`crate::db::migration`].
+    pub db_version: String,
+    pub backend: DbBackendKind,
+    /// Version of the node that took the backup.
+    pub forest_version: String,
+    /// Head of the chain when the backup was taken.
+    #[serde(with = "crate::lotus_json")]
+    pub head: Option<TipsetKeys>,
+    /// File names of the archives in the `car_db` directory of the backup.
+    #[serde(default)]
+    pub car_files: Vec<String>,
+}
+
+impl BackupManifest {
+    pub fn load(backup_dir: &Path) -> anyhow::Result<Self> {
+        let path = backup_dir.join(BACKUP_MANIFEST);
+        let file = fs::File::open(&path)
+            .with_context(|| format!("{} is not a database backup", backup_dir.display()))?;
+        serde_json::from_reader(file).with_context(|| format!("failed to parse {}", path.display()))
+    }
+
+    pub fn db_version(&self) -> anyhow::Result<Version> {
+        Version::parse(&self.db_version)
+            .with_context(|| format!("invalid database version {}", self.db_version))
+    }
+}
+
+/// Back up the settings of `db` and the blocks reachable from its head to
+/// `backup_dir`, which must not exist or be empty. Blocks are looked up in the
+/// archives of the database too, and the archives are backed up as they are.
+/// Tipset states aren't backed up, they are recomputed when needed.
+///
+/// Settings are read before blocks. As blocks are written before the settings
+/// that refer to them, and are read back like any other read, the backup is
+/// consistent with its settings even if `db` is written to meanwhile. The
+/// generations of `db` must not be rotated during the backup.
+pub fn backup(db: &RollingDB, backup_dir: &Path) -> anyhow::Result<BackupManifest> {
+    let db_version = db
+        .db_root()
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| Version::parse(name).ok())
+        .with_context(|| {
+            format!(
+                "{} is not a versioned database, and can't be backed up",
+                db.db_root().display()
+            )
+        })?;
+    if backup_dir.exists() {
+        anyhow::ensure!(
+            fs::read_dir(backup_dir)?.next().is_none(),
+            "{} is not empty",
+            backup_dir.display()
+        );
+    }
+
+    let copy = RollingDB::load_or_create(
+        backup_dir.join(db_version.to_string()),
+        db.db_config().clone(),
+    )?;
+    for key in db.setting_keys()? {
+        if let Some(value) = db.read_bin(&key)? {
+            copy.write_bin(&key, &value)?;
+        }
+    }
+    let head = copy.read_obj::<TipsetKeys>(HEAD_KEY)?;
+    info!(
+        "backing up database {db_version} to {}",
+        backup_dir.display()
+    );
+    let car_files = forest_car_files(&db.db_root().join(CAR_DB_DIR))?;
+    let car_db = backup_dir.join(db_version.to_string()).join(CAR_DB_DIR);
+    fs::create_dir_all(&car_db)?;
+    let mut car_file_names = vec![];
+    for file in &car_files {
+        let name = file
+            .file_name()
+            .and_then(|name| name.to_str())
+            .with_context(|| format!("invalid archive name {}", file.display()))?;
+        // Hard links fail across file systems
+        if fs::hard_link(file, car_db.join(name)).is_err() {
+            fs::copy(file, car_db.join(name))?;
+        }
+        car_file_names.push(name.to_owned());
+    }
+    let blocks = match &head {
+        Some(head) => copy_graph(db, &car_files, &copy, head)?,
+        None => 0,
+    };
+    info!("copied {blocks} blocks and {} archives", car_files.len());
+
+    // Written last, so that only complete backups can be restored
+    let manifest = BackupManifest {
+        db_version: db_version.to_string(),
+        backend: db.db_config().backend,
+        forest_version: FOREST_VERSION_STRING.clone(),
+        head,
+        car_files: car_file_names,
+    };
+    serde_json::to_writer_pretty(
+        fs::File::create(backup_dir.join(BACKUP_MANIFEST))?,
+        &manifest,
+    )?;
+    Ok(manifest)
+}
+
+/// The `.forest.car.zst` archives in `car_db`, sorted by name.
+fn forest_car_files(car_db: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    if !car_db.is_dir() {
+        return Ok(vec![]);
+    }
+    let mut files = vec![];
+    for entry in fs::read_dir(car_db)? {
+        let path = entry?.path();
+        let is_forest_car = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map_or(false, |name| name.ends_with(FOREST_CAR_FILE_EXTENSION));
+        if is_forest_car {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Copy the blocks reachable from `head` in `db` and `car_files` to `copy`,
+/// walking the graph like snapshot exports do. Blocks of the archives aren't
+/// copied, as the archives are backed up themselves. Returns the number of
+/// copied blocks.
+fn copy_graph(
+    db: &RollingDB,
+    car_files: &[PathBuf],
+    copy: &RollingDB,
+    head: &TipsetKeys,
+) -> anyhow::Result<u64> {
+    let cars = ManyCar::<MemoryDB>::default().with_read_only_files(car_files.iter().cloned())?;
+    let store = ManyCar::new(db).with_read_only_files(car_files.iter().cloned())?;
+    let head = Tipset::load_required(&store, head)?;
+    // Dead links are skipped, the backup holds what the database has
+    let mut stream = stream_graph(&store, head.chain(&store), 0);
+    let mut buffer = Vec::with_capacity(BACKUP_BATCH_SIZE);
+    let mut blocks = 0;
+    futures::executor::block_on(async {
+        while let Some(block) = stream.try_next().await? {
+            if cars.has(&block.cid)? {
+                continue;
+            }
+            buffer.push((block.cid, block.data));
+            blocks += 1;
+            if buffer.len() == BACKUP_BATCH_SIZE {
+                copy.put_many_keyed(std::mem::take(&mut buffer))?;
+            }
+        }
+        copy.put_many_keyed(buffer)
+    })?;
+    Ok(blocks)
+}
+
+/// Restore the backup in `backup_dir` to the chain data directory, where the
+/// daemon picks it up and migrates it to the version of this binary. An
+/// existing database of the same version is only replaced if `force` is set.
+/// Returns the path of the restored database.
+pub fn restore(backup_dir: &Path, chain_data_path: &Path, force: bool) -> anyhow::Result<PathBuf> {
+    let manifest = BackupManifest::load(backup_dir)?;
+    let version = manifest.db_version()?;
+    anyhow::ensure!(
+        version <= *FOREST_VERSION,
+        "the backup is of database version {version}, which is newer than this binary ({})",
+        *FOREST_VERSION
+    );
+    ensure_migration_possible(&version, &FOREST_VERSION)?;
+    if chain_data_path.exists() {
+        if let Some(latest) = get_latest_versioned_database(chain_data_path)? {
+            anyhow::ensure!(
+                latest <= version,
+                "database version {latest} in {} is newer than the backup, and would be used \
+                instead. Remove it with `forest-tool db destroy` first",
+                chain_data_path.display()
+            );
+        }
+    }
+
+    let car_db = backup_dir.join(&manifest.db_version).join(CAR_DB_DIR);
+    for name in &manifest.car_files {
+        anyhow::ensure!(
+            car_db.join(name).is_file(),
+            "the backup is incomplete, {name} is missing from {}",
+            car_db.display()
+        );
+    }
+
+    let target = chain_data_path.join(version.to_string());
+    if target.exists() {
+        anyhow::ensure!(force, "{} already exists", target.display());
+        fs::remove_dir_all(&target)?;
+    }
+    fs::create_dir_all(chain_data_path)?;
+    fs_extra::dir::copy(
+        backup_dir.join(&manifest.db_version),
+        chain_data_path,
+        &fs_extra::dir::CopyOptions::new(),
+    )?;
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::car::{forest, PlainCar};
+    use crate::db::check::check_graph;
+    use crate::db::db_engine::open_proxy_db;
+    use crate::networks::calibnet;
+    use crate::utils::db::car_stream::CarStream;
+    use crate::utils::db::car_util::load_car;
+    use cid::multihash::{Code, MultihashDigest as _};
+    use cid::Cid;
+    use fvm_ipld_encoding::DAG_CBOR;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use tokio::io::AsyncWriteExt as _;
+
+    /// Opens a database holding the calibnet genesis as its head.
+    async fn genesis_db(path: PathBuf) -> (RollingDB, Tipset) {
+        let db = open_proxy_db(path, Default::default()).unwrap();
+        load_car(&db, calibnet::DEFAULT_GENESIS).await.unwrap();
+        let head = PlainCar::try_from(calibnet::DEFAULT_GENESIS)
+            .unwrap()
+            .heaviest_tipset()
+            .unwrap();
+        db.write_obj(HEAD_KEY, head.key()).unwrap();
+        (db, head)
+    }
+
+    #[tokio::test]
+    async fn backup_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db, head) = genesis_db(dir.path().join("src").join(FOREST_VERSION.to_string())).await;
+        db.write_obj("key", &"value").unwrap();
+
+        let backup_dir = dir.path().join("backup");
+        let manifest = backup(&db, &backup_dir).unwrap();
+        assert_eq!(manifest.head.as_ref(), Some(head.key()));
+        assert_eq!(BackupManifest::load(&backup_dir).unwrap(), manifest);
+        assert!(backup(&db, &backup_dir).is_err());
+
+        let chain_data = dir.path().join("dst");
+        let restored = restore(&backup_dir, &chain_data, false).unwrap();
+        assert!(restore(&backup_dir, &chain_data, false).is_err());
+        let restored = open_proxy_db(restored, Default::default()).unwrap();
+        assert!(check_graph(&restored, head, 0, None)
+            .await
+            .unwrap()
+            .is_consistent());
+        assert_eq!(
+            restored.read_obj::<String>("key").unwrap().as_deref(),
+            Some("value")
+        );
+    }
+
+    #[tokio::test]
+    async fn backup_and_restore_archives() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_root = dir.path().join("src").join(FOREST_VERSION.to_string());
+        let db = open_proxy_db(db_root.clone(), Default::default()).unwrap();
+        // The head and its state are only in an imported snapshot
+        let stream = CarStream::new(calibnet::DEFAULT_GENESIS).await.unwrap();
+        let roots = stream.header.roots.clone();
+        let frames = forest::Encoder::compress_stream_default(stream.map_err(anyhow::Error::from));
+        fs::create_dir_all(db_root.join(CAR_DB_DIR)).unwrap();
+        let car_name = format!("genesis{FOREST_CAR_FILE_EXTENSION}");
+        let mut file = tokio::fs::File::create(db_root.join(CAR_DB_DIR).join(&car_name))
+            .await
+            .unwrap();
+        forest::Encoder::write(&mut file, roots, frames)
+            .await
+            .unwrap();
+        file.flush().await.unwrap();
+        let head = PlainCar::try_from(calibnet::DEFAULT_GENESIS)
+            .unwrap()
+            .heaviest_tipset()
+            .unwrap();
+        db.write_obj(HEAD_KEY, head.key()).unwrap();
+
+        let backup_dir = dir.path().join("backup");
+        let manifest = backup(&db, &backup_dir).unwrap();
+        assert_eq!(manifest.car_files, vec![car_name.clone()]);
+
+        let restored = restore(&backup_dir, &dir.path().join("dst"), false).unwrap();
+        let car_files = forest_car_files(&restored.join(CAR_DB_DIR)).unwrap();
+        assert_eq!(car_files.len(), 1);
+        let restored_db = open_proxy_db(restored, Default::default()).unwrap();
+        // The blocks of the archive aren't duplicated
+        assert!(!restored_db.has(head.min_ticket_block().cid()).unwrap());
+        let restored = ManyCar::new(restored_db)
+            .with_read_only_files(car_files.into_iter())
+            .unwrap();
+        assert!(check_graph(&restored, head, 0, None)
+            .await
+            .unwrap()
+            .is_consistent());
+
+        // Backups missing an archive aren't restored
+        fs::remove_file(
+            backup_dir
+                .join(FOREST_VERSION.to_string())
+                .join(CAR_DB_DIR)
+                .join(car_name),
+        )
+        .unwrap();
+        assert!(restore(&backup_dir, &dir.path().join("dst2"), false).is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn backup_while_writing() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db, head) = genesis_db(dir.path().join(FOREST_VERSION.to_string())).await;
+        let db = Arc::new(db);
+        let done = Arc::new(AtomicBool::new(false));
+        let writer = std::thread::spawn({
+            let (db, done) = (db.clone(), done.clone());
+            move || {
+                let mut written = 0u64;
+                while !done.load(Ordering::Relaxed) {
+                    let data = written.to_be_bytes();
+                    let cid = Cid::new_v1(DAG_CBOR, Code::Blake2b256.digest(&data));
+                    db.put_keyed(&cid, &data).unwrap();
+                    written += 1;
+                }
+            }
+        });
+
+        let backup_dir = dir.path().join("backup");
+        let manifest = backup(&db, &backup_dir);
+        done.store(true, Ordering::Relaxed);
+        writer.join().unwrap();
+        manifest.unwrap();
+
+        let restored = restore(&backup_dir, &dir.path().join("dst"), false).unwrap();
+        let restored = open_proxy_db(restored, Default::default()).unwrap();
+        assert!(check_graph(&restored, head, 0, None)
+            .await
+            .unwrap()
+            .is_consistent());
+    }
+
+    #[test]
+    fn restore_newer_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = BackupManifest {
+            db_version: Version::new(FOREST_VERSION.major + 1, 0, 0).to_string(),
+            backend: DbBackendKind::ParityDb,
+            forest_version: FOREST_VERSION_STRING.clone(),
+            head: None,
+            car_files: vec![],
+        };
+        serde_json::to_writer(
+            fs::File::create(dir.path().join(BACKUP_MANIFEST)).unwrap(),
+            &manifest,
+        )
+        .unwrap();
+        assert!(restore(dir.path(), &dir.path().join("chain"), true).is_err());
+    }
+}
@@ -1,44 +1,67 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-//! There are three different CAR formats: `.car`, `.car.zst` and
-//! `.forest.car.zst`. [`AnyCar`] identifies the format by inspecting the CAR
-//! header and the first key-value block, and picks the appropriate block store
-//! (either [`super::ForestCar`] or [`super::PlainCar`]).
-//!
-//! CARv2 is not supported yet.
+//! There are four different CAR formats: `.car`, `.car.zst`,
+//! `.forest.car.zst` and CARv2. [`AnyCar`] identifies the format by inspecting
+//! the CAR header and the first key-value block, and picks the appropriate
+//! block store (either [`super::ForestCar`], [`super::CarV2`] or
+//! [`super::PlainCar`]).
 
 use super::{CacheKey, ZstdFrameCache};
 use crate::blocks::Tipset;
-use crate::utils::io::EitherMmapOrRandomAccessFile;
+use crate::utils::io::{
+    parse_http_url, EitherMmapOrRandomAccessFile, HttpReader, TempFile, TempFileReader,
+    WithProgress,
+};
 use cid::Cid;
 use fvm_ipld_blockstore::Blockstore;
 use parking_lot::Mutex;
 use positioned_io::ReadAt;
-use std::io::{Error, ErrorKind, Result};
-use std::path::Path;
+use std::io::{BufWriter, Error, ErrorKind, Result, Write as _};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+/// Environment variable that sets the directory `.car.zst` archives are
+/// decompressed to. Defaults to the directory of the archive, or to the
+/// working directory for archives that aren't local files.
+pub const FOREST_CAR_DECOMPRESS_DIR: &str = "FOREST_CAR_DECOMPRESS_DIR";
+
 pub enum AnyCar<ReaderT> {
     Plain(super::PlainCar<ReaderT>),
     Forest(super::ForestCar<ReaderT>),
-    Memory(super::PlainCar<Vec<u8>>),
+    CarV2(super::CarV2<ReaderT>),
+    Decompressed(super::PlainCar<TempFileReader>),
 }
 
 impl<ReaderT: super::RandomAccessFileReader> AnyCar<ReaderT> {
-    /// Open an archive. May be formatted as `.car`, `.car.zst` or
-    /// `.forest.car.zst`. This call may block for an indeterminate amount of
-    /// time while data is decoded and indexed.
+    /// Open an archive. May be formatted as `.car`, `.car.zst`,
+    /// `.forest.car.zst` or CARv2. This call may block for an indeterminate
+    /// amount of time while data is decoded and indexed. `.car.zst` archives
+    /// are decompressed into a temporary file, see
+    /// [`FOREST_CAR_DECOMPRESS_DIR`].
     pub fn new(reader: ReaderT) -> Result<Self> {
+        Self::new_in(reader, &decompress_dir(None))
+    }
+
+    /// Like [`AnyCar::new`], decompressing `.car.zst` archives into a
+    /// temporary file in `decompress_dir`.
+    pub fn new_in(reader: ReaderT, decompress_dir: &Path) -> Result<Self> {
         if super::ForestCar::is_valid(&reader) {
             let forest_car = super::ForestCar::new(reader)?;
             return Ok(AnyCar::Forest(forest_car));
         }
 
-        // Maybe use a tempfile for this in the future.
-        if let Ok(decompressed) = zstd::stream::decode_all(positioned_io::Cursor::new(&reader)) {
-            if let Ok(mem_car) = super::PlainCar::new(decompressed) {
-                return Ok(AnyCar::Memory(mem_car));
+        if super::CarV2::is_valid(&reader) {
+            return Ok(AnyCar::CarV2(super::CarV2::new(reader)?));
+        }
+
+        // Don't mistake archives from a newer version of Forest for `.car.zst`
+        super::ForestCar::check_footer_version(&reader)?;
+
+        if is_zstd(&reader) {
+            let decompressed = decompress_to_temp_file(&reader, decompress_dir)?;
+            if let Ok(plain_car) = super::PlainCar::new(decompressed) {
+                return Ok(AnyCar::Decompressed(plain_car));
             }
         }
 
@@ -47,7 +70,7 @@ impl<ReaderT: super::RandomAccessFileReader> AnyCar<ReaderT> {
         }
         Err(Error::new(
             ErrorKind::InvalidData,
-            "input not recognized as any kind of CAR data (.car, .car.zst, .forest.car)",
+            "input not recognized as any kind of CAR data (.car, .car.zst, .forest.car, CARv2)",
         ))
     }
 
@@ -57,17 +80,19 @@ impl<ReaderT: super::RandomAccessFileReader> AnyCar<ReaderT> {
         match self {
             AnyCar::Forest(forest) => forest.heaviest_tipset(),
             AnyCar::Plain(plain) => plain.heaviest_tipset(),
-            AnyCar::Memory(mem) => mem.heaviest_tipset(),
+            AnyCar::CarV2(car) => car.heaviest_tipset(),
+            AnyCar::Decompressed(plain) => plain.heaviest_tipset(),
         }
     }
 
-    /// Return the identified CAR format variant. There are three variants:
-    /// `CARv1`, `CARv1.zst` and `ForestCARv1.zst`.
+    /// Return the identified CAR format variant. There are four variants:
+    /// `CARv1`, `CARv1.zst`, `ForestCARv1.zst` and `CARv2`.
     pub fn variant(&self) -> &'static str {
         match self {
             AnyCar::Forest(_) => "ForestCARv1.zst",
             AnyCar::Plain(_) => "CARv1",
-            AnyCar::Memory(_) => "CARv1.zst",
+            AnyCar::CarV2(_) => "CARv2",
+            AnyCar::Decompressed(_) => "CARv1.zst",
         }
     }
 
@@ -76,7 +101,8 @@ impl<ReaderT: super::RandomAccessFileReader> AnyCar<ReaderT> {
         match self {
             AnyCar::Forest(f) => AnyCar::Forest(f.into_dyn()),
             AnyCar::Plain(p) => AnyCar::Plain(p.into_dyn()),
-            AnyCar::Memory(m) => AnyCar::Memory(m),
+            AnyCar::CarV2(c) => AnyCar::CarV2(c.into_dyn()),
+            AnyCar::Decompressed(d) => AnyCar::Decompressed(d),
         }
     }
 
@@ -85,11 +111,67 @@ impl<ReaderT: super::RandomAccessFileReader> AnyCar<ReaderT> {
         match self {
             AnyCar::Forest(f) => AnyCar::Forest(f.with_cache(cache, key)),
             AnyCar::Plain(p) => AnyCar::Plain(p),
-            AnyCar::Memory(m) => AnyCar::Memory(m),
+            AnyCar::CarV2(c) => AnyCar::CarV2(c),
+            AnyCar::Decompressed(d) => AnyCar::Decompressed(d),
         }
     }
 }
 
+impl AnyCar<Box<dyn super::RandomAccessFileReader>> {
+    /// Open a local archive or, given an `http(s)` URL, a remote archive which
+    /// is read with HTTP range requests. Only the parts of a remote
+    /// `.forest.car.zst` archive that are needed are downloaded, whereas other
+    /// formats are downloaded in full to be indexed.
+    pub fn open(location: &Path) -> Result<Self> {
+        match parse_http_url(location) {
+            Some(url) => Ok(AnyCar::new(HttpReader::open(url)?)?.into_dyn()),
+            None => Ok(AnyCar::try_from(location)?.into_dyn()),
+        }
+    }
+}
+
+/// Check the zstd frame header at the start of `reader`. The header has a
+/// maximum size of 18 bytes.
+fn is_zstd(reader: &impl ReadAt) -> bool {
+    let mut header = [0; 18];
+    match reader.read_at(0, &mut header) {
+        Ok(read) => zstd::zstd_safe::get_frame_content_size(&header[..read]).is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Decompress a `.car.zst` archive into a temporary file, so that it can be
+/// indexed without holding the decompressed archive in memory. The file is
+/// created in `dir` rather than in [`std::env::temp_dir`], which is often too
+/// small to hold a decompressed snapshot.
+fn decompress_to_temp_file(
+    reader: &impl super::RandomAccessFileReader,
+    dir: &Path,
+) -> Result<TempFileReader> {
+    let temp_file = TempFile::new_in(dir)?;
+    let compressed = WithProgress::wrap_read(
+        "decompressing",
+        positioned_io::Cursor::new(reader),
+        reader.size()?.unwrap_or_default(),
+    );
+    let mut writer = BufWriter::new(temp_file);
+    zstd::stream::copy_decode(compressed, &mut writer)?;
+    writer.flush()?;
+    writer.into_inner()?.into_reader()
+}
+
+/// Directory to decompress `.car.zst` archives to, see
+/// [`FOREST_CAR_DECOMPRESS_DIR`].
+fn decompress_dir(archive_dir: Option<&Path>) -> PathBuf {
+    match std::env::var_os(FOREST_CAR_DECOMPRESS_DIR) {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => match archive_dir {
+            Some(dir) if dir != Path::new("") => dir.to_owned(),
+            _ => PathBuf::from("."),
+        },
+    }
+}
+
 impl TryFrom<&'static [u8]> for AnyCar<&'static [u8]> {
     type Error = std::io::Error;
     fn try_from(bytes: &'static [u8]) -> std::io::Result<Self> {
@@ -100,7 +182,10 @@ impl TryFrom<&'static [u8]> for AnyCar<&'static [u8]> {
 impl TryFrom<&Path> for AnyCar<EitherMmapOrRandomAccessFile> {
     type Error = std::io::Error;
     fn try_from(path: &Path) -> std::io::Result<Self> {
-        AnyCar::new(EitherMmapOrRandomAccessFile::open(path)?)
+        AnyCar::new_in(
+            EitherMmapOrRandomAccessFile::open(path)?,
+            &decompress_dir(path.parent()),
+        )
     }
 }
 
@@ -112,7 +197,8 @@ where
         match self {
             AnyCar::Forest(forest) => forest.get(k),
             AnyCar::Plain(plain) => plain.get(k),
-            AnyCar::Memory(mem) => mem.get(k),
+            AnyCar::CarV2(car) => car.get(k),
+            AnyCar::Decompressed(plain) => plain.get(k),
         }
     }
 
@@ -120,7 +206,8 @@ where
         match self {
             AnyCar::Forest(forest) => forest.put_keyed(k, block),
             AnyCar::Plain(plain) => plain.put_keyed(k, block),
-            AnyCar::Memory(mem) => mem.put_keyed(k, block),
+            AnyCar::CarV2(car) => car.put_keyed(k, block),
+            AnyCar::Decompressed(plain) => plain.put_keyed(k, block),
         }
     }
 }
@@ -131,6 +218,12 @@ impl<ReaderT> From<super::ForestCar<ReaderT>> for AnyCar<ReaderT> {
     }
 }
 
+impl<ReaderT> From<super::CarV2<ReaderT>> for AnyCar<ReaderT> {
+    fn from(car: super::CarV2<ReaderT>) -> Self {
+        Self::CarV2(car)
+    }
+}
+
 impl<ReaderT> From<super::PlainCar<ReaderT>> for AnyCar<ReaderT> {
     fn from(car: super::PlainCar<ReaderT>) -> Self {
         Self::Plain(car)
@@ -39,6 +39,23 @@
 //! encoded as skippable frames that are (as the name suggests) skipped by tools
 //! that don't understand them.
 //!
+//! # Dictionary compression
+//!
+//! Small z-frames compress repetitive data like HAMT nodes and block headers
+//! poorly. Archives written with [`Encoder::compress_stream_with_dictionary`]
+//! contain a zstd dictionary, trained on the first blocks of the archive, in a
+//! skippable frame right after the header z-frame. All block z-frames are
+//! compressed with this dictionary, so these archives can't be decompressed by
+//! other tools (or streamed with
+//! [`CarStream`](crate::utils::db::car_stream::CarStream)) and are only
+//! readable with [`ForestCar`].
+//!
+//! The offset of the dictionary is stored in a versioned footer, which ends
+//! with a format version and `FOOTER_MAGIC`. Footers of archives without a
+//! dictionary keep the original, unversioned layout. Archives with a footer
+//! version newer than `FOOTER_VERSION` are rejected with an error asking for
+//! a newer version of Forest.
+//!
 //! # Additional reading
 //!
 //! `zstd` frame format: <https://github.com/facebook/zstd/blob/dev/doc/zstd_compression_format.md>
@@ -56,7 +73,7 @@ use crate::utils::io::EitherMmapOrRandomAccessFile;
 use ahash::{HashMap, HashMapExt};
 use bytes::{buf::Writer, BufMut as _, Bytes, BytesMut};
 use cid::Cid;
-use futures::{Stream, TryStream, TryStreamExt as _};
+use futures::{Stream, StreamExt as _, TryStream, TryStreamExt as _};
 use fvm_ipld_blockstore::Blockstore;
 use fvm_ipld_encoding::to_vec;
 use parking_lot::{Mutex, RwLock};
@@ -71,11 +88,25 @@ use std::{
 };
 use tokio::io::{AsyncWrite, AsyncWriteExt};
 use tokio_util::codec::{Decoder, Encoder as _};
+use tracing::debug;
 use unsigned_varint::codec::UviBytes;
 
 pub const FOREST_CAR_FILE_EXTENSION: &str = ".forest.car.zst";
 pub const DEFAULT_FOREST_CAR_FRAME_SIZE: usize = 8000_usize.next_power_of_two();
 pub const DEFAULT_FOREST_CAR_COMPRESSION_LEVEL: u16 = zstd::DEFAULT_COMPRESSION_LEVEL as _;
+/// Maximum size of a trained zstd dictionary.
+pub const DEFAULT_FOREST_CAR_DICTIONARY_SIZE: usize = 112 * 1024;
+/// Blocks are sampled for dictionary training until they add up to this size.
+const DICTIONARY_SAMPLE_SIZE: usize = 100 * DEFAULT_FOREST_CAR_DICTIONARY_SIZE;
+/// Magic number at the start of zstd dictionaries.
+const DICTIONARY_MAGIC: [u8; 4] = 0xEC30A437_u32.to_le_bytes();
+/// Magic number at the end of versioned footers.
+const FOOTER_MAGIC: [u8; 4] = *b"FCAR";
+/// The newest footer version this version of Forest can read. Unversioned
+/// footers are version 1.
+const FOOTER_VERSION: u32 = 2;
+/// Skippable zstd frames start with this magic number.
+const SKIP_FRAME_MAGIC: [u8; 4] = [0x50, 0x2A, 0x4D, 0x18];
 
 pub trait ReaderGen<V>: Fn() -> io::Result<V> + Send + Sync + 'static {}
 impl<ReaderT, X: Fn() -> io::Result<ReaderT> + Send + Sync + 'static> ReaderGen<ReaderT> for X {}
@@ -88,12 +119,18 @@ pub struct ForestCar<ReaderT> {
     frame_cache: Arc<Mutex<ZstdFrameCache>>,
     write_cache: Arc<RwLock<ahash::HashMap<Cid, Vec<u8>>>>,
     roots: Vec<Cid>,
+    // Dictionary of the z-frames, if the archive has one
+    dictionary: Option<Arc<[u8]>>,
 }
 
 impl<ReaderT: super::RandomAccessFileReader> ForestCar<ReaderT> {
     pub fn new(reader: ReaderT) -> io::Result<Self> {
         let (header, footer) = Self::validate_car(&reader)?;
 
+        let dictionary = footer
+            .dictionary
+            .map(|offset| read_dictionary(&reader, offset))
+            .transpose()?;
         let index = CarIndex::open(reader, footer.index)?;
 
         Ok(ForestCar {
@@ -102,6 +139,7 @@ impl<ReaderT: super::RandomAccessFileReader> ForestCar<ReaderT> {
             frame_cache: Arc::new(Mutex::new(ZstdFrameCache::default())),
             write_cache: Arc::new(RwLock::new(ahash::HashMap::default())),
             roots: header.roots,
+            dictionary,
         })
     }
 
@@ -109,22 +147,20 @@ impl<ReaderT: super::RandomAccessFileReader> ForestCar<ReaderT> {
         Self::validate_car(reader).is_ok()
     }
 
-    fn validate_car(reader: &ReaderT) -> io::Result<(CarHeader, ForestCarFooter)> {
-        let mut cursor = SizeCursor::new(&reader);
-        cursor.seek(SeekFrom::End(-(ForestCarFooter::SIZE as i64)))?;
-
-        let mut footer_buffer = [0; ForestCarFooter::SIZE];
-        cursor.read_exact(&mut footer_buffer)?;
+    /// Fail if `reader` ends with a footer written by a newer version of
+    /// Forest, which this version can't read.
+    pub fn check_footer_version(reader: &ReaderT) -> io::Result<()> {
+        match read_footer(reader) {
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => Err(e),
+            _ => Ok(()),
+        }
+    }
 
-        let footer = ForestCarFooter::try_from_le_bytes(footer_buffer).ok_or_else(|| {
-            invalid_data(format!(
-                "not recognizable as a `{}` file",
-                FOREST_CAR_FILE_EXTENSION
-            ))
-        })?;
+    fn validate_car(reader: &ReaderT) -> io::Result<(CarHeader, ForestCarFooter)> {
+        let footer = read_footer(reader)?;
 
         let cursor = Cursor::new_pos(&reader, 0);
-        let mut header_zstd_frame = decode_zstd_single_frame(cursor)?;
+        let mut header_zstd_frame = decode_zstd_single_frame(cursor, None)?;
         let block_frame = UviBytes::<Bytes>::default()
             .decode(&mut header_zstd_frame)?
             .ok_or_else(|| invalid_data("malformed uvibytes"))?;
@@ -155,6 +191,7 @@ impl<ReaderT: super::RandomAccessFileReader> ForestCar<ReaderT> {
             frame_cache: self.frame_cache,
             write_cache: self.write_cache,
             roots: self.roots,
+            dictionary: self.dictionary,
         }
     }
 
@@ -174,30 +211,27 @@ impl TryFrom<&Path> for ForestCar<EitherMmapOrRandomAccessFile> {
     }
 }
 
-impl<ReaderT> Blockstore for ForestCar<ReaderT>
-where
-    ReaderT: ReadAt,
-{
-    #[tracing::instrument(level = "trace", skip(self))]
-    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
-        // Return immediately if the value is cached.
-        if let Some(value) = self.write_cache.read().get(k) {
-            return Ok(Some(value.clone()));
-        }
+impl<ReaderT: ReadAt> ForestCar<ReaderT> {
+    /// Offset of the z-frame that holds the block `k`, if the archive has it.
+    pub fn frame_offset(&self, k: &Cid) -> anyhow::Result<Option<FrameOffset>> {
+        Ok(self.get_with_frame_offset(k)?.map(|(position, _)| position))
+    }
 
+    fn get_with_frame_offset(&self, k: &Cid) -> anyhow::Result<Option<(FrameOffset, Vec<u8>)>> {
         let indexed = &self.indexed;
         for position in indexed.lookup(*k)?.into_iter() {
             let reader = indexed.reader();
             let cache_query = self.frame_cache.lock().get(position, self.cache_key, *k);
             match cache_query {
                 // Frame cache hit, found value.
-                Some(Some(val)) => return Ok(Some(val)),
+                Some(Some(val)) => return Ok(Some((position, val))),
                 // Frame cache hit, no value. This only happens when hashes collide
                 Some(None) => {}
                 None => {
                     // Decode entire frame into memory, "position" arg is the frame start offset.
                     let cursor = Cursor::new_pos(reader, position);
-                    let mut zstd_frame = decode_zstd_single_frame(cursor)?;
+                    let mut zstd_frame =
+                        decode_zstd_single_frame(cursor, self.dictionary.as_deref())?;
                     // Parse all key-value pairs and insert them into a map
                     let mut block_map = HashMap::new();
                     while let Some(block_frame) =
@@ -213,13 +247,28 @@ where
 
                     // This lookup only fails in case of a hash collision
                     if let Some(value) = get_result {
-                        return Ok(Some(value));
+                        return Ok(Some((position, value)));
                     }
                 }
             }
         }
         Ok(None)
     }
+}
+
+impl<ReaderT> Blockstore for ForestCar<ReaderT>
+where
+    ReaderT: ReadAt,
+{
+    #[tracing::instrument(level = "trace", skip(self))]
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        // Return immediately if the value is cached.
+        if let Some(value) = self.write_cache.read().get(k) {
+            return Ok(Some(value.clone()));
+        }
+
+        Ok(self.get_with_frame_offset(k)?.map(|(_, value)| value))
+    }
 
     #[tracing::instrument(level = "trace", skip(self, block))]
     fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
@@ -233,16 +282,106 @@ where
     }
 }
 
-fn decode_zstd_single_frame<ReaderT: Read>(reader: ReaderT) -> io::Result<BytesMut> {
+fn decode_zstd_single_frame<ReaderT: Read>(
+    reader: ReaderT,
+    dictionary: Option<&[u8]>,
+) -> io::Result<BytesMut> {
     let mut zstd_frame = vec![];
 
-    zstd::Decoder::new(reader)?
-        .single_frame()
-        .read_to_end(&mut zstd_frame)?;
+    let decoder = match dictionary {
+        Some(dictionary) => zstd::Decoder::with_dictionary(io::BufReader::new(reader), dictionary)?,
+        None => zstd::Decoder::new(reader)?,
+    };
+    decoder.single_frame().read_to_end(&mut zstd_frame)?;
     // This unnecessarily copies the zstd frame. :(
     Ok(BytesMut::from(zstd_frame.as_slice()))
 }
 
+/// Read the footer at the end of `reader`. Footers of a newer version than
+/// [`FOOTER_VERSION`] result in an [`io::ErrorKind::Unsupported`] error.
+fn read_footer(reader: &(impl ReadAt + positioned_io::Size)) -> io::Result<ForestCarFooter> {
+    let not_recognized = || {
+        invalid_data(format!(
+            "not recognizable as a `{}` file",
+            FOREST_CAR_FILE_EXTENSION
+        ))
+    };
+    let mut cursor = SizeCursor::new(reader);
+    cursor.seek(SeekFrom::End(-(ForestCarFooter::SIZE as i64)))?;
+
+    let mut footer_buffer = [0; ForestCarFooter::SIZE];
+    cursor.read_exact(&mut footer_buffer)?;
+
+    if !footer_buffer.ends_with(&FOOTER_MAGIC) {
+        return ForestCarFooter::try_from_le_bytes(&footer_buffer).ok_or_else(not_recognized);
+    }
+
+    // All versioned footers end with their version and the footer magic number
+    let version = u32::from_le_bytes(footer_buffer[8..12].try_into().expect("infallible"));
+    if version > FOOTER_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "`{FOREST_CAR_FILE_EXTENSION}` file has format version {version}, \
+                 which requires a newer version of Forest"
+            ),
+        ));
+    }
+    let mut footer_buffer = [0; ForestCarFooter::VERSIONED_SIZE];
+    cursor.seek(SeekFrom::End(-(ForestCarFooter::VERSIONED_SIZE as i64)))?;
+    cursor.read_exact(&mut footer_buffer)?;
+    ForestCarFooter::try_from_le_bytes(&footer_buffer).ok_or_else(not_recognized)
+}
+
+/// Read the dictionary in the skippable frame at `offset`.
+fn read_dictionary(reader: &impl ReadAt, offset: u64) -> io::Result<Arc<[u8]>> {
+    let mut frame_header = [0; 8];
+    reader.read_exact_at(offset, &mut frame_header)?;
+    if frame_header[0..4] != SKIP_FRAME_MAGIC {
+        return Err(invalid_data("malformed dictionary frame"));
+    }
+    let len = u32::from_le_bytes(frame_header[4..8].try_into().expect("infallible"));
+    let mut dictionary = vec![0; len as usize];
+    reader.read_exact_at(offset + 8, &mut dictionary)?;
+    if !dictionary.starts_with(&DICTIONARY_MAGIC) {
+        return Err(invalid_data("malformed dictionary"));
+    }
+    Ok(dictionary.into())
+}
+
+/// Wrap `dictionary` in a skippable frame.
+fn dictionary_frame(dictionary: &[u8]) -> Bytes {
+    let mut frame = BytesMut::with_capacity(8 + dictionary.len());
+    frame.put_slice(&SKIP_FRAME_MAGIC);
+    frame.put_u32_le(dictionary.len() as u32);
+    frame.put_slice(dictionary);
+    frame.freeze()
+}
+
+pub(crate) fn is_dictionary_frame(frame: &[u8]) -> bool {
+    frame.len() >= 12 && frame[0..4] == SKIP_FRAME_MAGIC && frame[8..12] == DICTIONARY_MAGIC
+}
+
+/// Train a zstd dictionary on the encoded `blocks`. Returns `None` if the
+/// sample is too small for training.
+fn train_dictionary(blocks: &[CarBlock]) -> Option<Vec<u8>> {
+    let samples = blocks
+        .iter()
+        .map(|block| {
+            let mut sample = vec![];
+            block.write(&mut sample).map(|()| sample)
+        })
+        .collect::<io::Result<Vec<_>>>()
+        .ok()?;
+    match zstd::dict::from_samples(&samples, DEFAULT_FOREST_CAR_DICTIONARY_SIZE) {
+        Ok(dictionary) => Some(dictionary),
+        Err(e) => {
+            debug!("not using a zstd dictionary: {e}");
+            None
+        }
+    }
+}
+
 pub struct Encoder {}
 
 impl Encoder {
@@ -254,7 +393,7 @@ impl Encoder {
         let mut offset = 0;
 
         // Write CARv1 header
-        let mut header_encoder = new_encoder(3)?;
+        let mut header_encoder = new_encoder(3, None)?;
 
         let header = CarHeader { roots, version: 1 };
         let mut header_uvi_frame = BytesMut::new();
@@ -269,7 +408,11 @@ impl Encoder {
 
         // Write seekable zstd and collect a mapping of CIDs to frame_offset+data_offset.
         let mut cid_mapping = Vec::new();
+        let mut dictionary = None;
         while let Some((cids, zstd_frame)) = stream.try_next().await? {
+            if cids.is_empty() && dictionary.is_none() && is_dictionary_frame(&zstd_frame) {
+                dictionary = Some(offset as u64);
+            }
             for cid in cids {
                 cid_mapping.push((Hash::from(cid), offset as FrameOffset));
             }
@@ -286,6 +429,7 @@ impl Encoder {
         // Write ForestCAR.zst footer, it's a valid ZSTD skip-frame
         let footer = ForestCarFooter {
             index: index_offset,
+            dictionary,
         };
         sink.write_all(&footer.to_le_bytes()).await?;
         Ok(())
@@ -309,56 +453,116 @@ impl Encoder {
         zstd_compression_level: u16,
         stream: impl TryStream<Ok = CarBlock, Error = anyhow::Error>,
     ) -> impl TryStream<Ok = (Vec<Cid>, Bytes), Error = anyhow::Error> {
-        let mut encoder_store = new_encoder(zstd_compression_level);
-        let mut frame_cids = vec![];
+        compress_frames(
+            zstd_frame_size_tripwire,
+            zstd_compression_level,
+            None,
+            stream,
+        )
+    }
 
+    /// Like [`Encoder::compress_stream`], but train a zstd dictionary on the
+    /// first blocks and compress all z-frames with it. The dictionary is
+    /// emitted first, in a skippable frame. If there are too few blocks to
+    /// train a dictionary, the frames are compressed without one.
+    pub fn compress_stream_with_dictionary(
+        zstd_frame_size_tripwire: usize,
+        zstd_compression_level: u16,
+        stream: impl TryStream<Ok = CarBlock, Error = anyhow::Error>,
+    ) -> impl TryStream<Ok = (Vec<Cid>, Bytes), Error = anyhow::Error> {
         let mut stream = Box::pin(stream.into_stream());
-        futures::stream::poll_fn(move |cx| {
-            let encoder = match encoder_store.as_mut() {
-                Err(e) => {
-                    let dummy_error =
-                        io::Error::new(io::ErrorKind::Other, "Error already consumed.");
-                    return Poll::Ready(Some(Err(anyhow::Error::from(std::mem::replace(
-                        e,
-                        dummy_error,
-                    )))));
-                }
-                Ok(encoder) => encoder,
-            };
-            loop {
-                // Emit frame if compressed_len > zstd_frame_size_tripwire
-                if compressed_len(encoder) > zstd_frame_size_tripwire {
-                    let cids = std::mem::take(&mut frame_cids);
-                    let frame = finalize_frame(zstd_compression_level, encoder)?;
-                    return Poll::Ready(Some(Ok((cids, frame))));
-                }
-                // No frame to emit, let's get another block
-                let ret = futures::ready!(stream.as_mut().poll_next(cx));
-                match ret {
-                    // End-of-stream
-                    None => {
-                        // If there's anything in the zstd buffer, emit it.
-                        if compressed_len(encoder) > 0 {
-                            let cids = std::mem::take(&mut frame_cids);
-                            let frame = finalize_frame(zstd_compression_level, encoder)?;
-                            return Poll::Ready(Some(Ok((cids, frame))));
-                        } else {
-                            // Otherwise we're all done.
-                            return Poll::Ready(None);
+        Box::pin(
+            futures::stream::once(async move {
+                let mut samples = vec![];
+                let mut sample_size = 0;
+                while sample_size < DICTIONARY_SAMPLE_SIZE {
+                    match stream.try_next().await? {
+                        Some(block) => {
+                            sample_size += block.data.len();
+                            samples.push(block);
                         }
+                        None => break,
                     }
-                    // Pass errors through
-                    Some(Err(e)) => return Poll::Ready(Some(Err(e))),
-                    // Got element, add to encoder and emit block position
-                    Some(Ok(block)) => {
-                        frame_cids.push(block.cid);
-                        block.write(encoder)?;
-                        encoder.flush()?;
+                }
+                let dictionary = train_dictionary(&samples);
+                let dictionary_frame = dictionary
+                    .as_deref()
+                    .map(|dictionary| Ok((vec![], dictionary_frame(dictionary))));
+                let blocks = futures::stream::iter(samples.into_iter().map(Ok)).chain(stream);
+                anyhow::Ok(
+                    futures::stream::iter(dictionary_frame).chain(
+                        compress_frames(
+                            zstd_frame_size_tripwire,
+                            zstd_compression_level,
+                            dictionary,
+                            blocks,
+                        )
+                        .into_stream(),
+                    ),
+                )
+            })
+            .try_flatten(),
+        )
+    }
+}
+
+/// Consume stream of blocks, emit a new position of each block and a stream
+/// of zstd frames, optionally compressed with `dictionary`.
+fn compress_frames(
+    zstd_frame_size_tripwire: usize,
+    zstd_compression_level: u16,
+    dictionary: Option<Vec<u8>>,
+    stream: impl TryStream<Ok = CarBlock, Error = anyhow::Error>,
+) -> impl TryStream<Ok = (Vec<Cid>, Bytes), Error = anyhow::Error> {
+    let mut encoder_store = new_encoder(zstd_compression_level, dictionary.as_deref());
+    let mut frame_cids = vec![];
+
+    let mut stream = Box::pin(stream.into_stream());
+    futures::stream::poll_fn(move |cx| {
+        let encoder = match encoder_store.as_mut() {
+            Err(e) => {
+                let dummy_error = io::Error::new(io::ErrorKind::Other, "Error already consumed.");
+                return Poll::Ready(Some(Err(anyhow::Error::from(std::mem::replace(
+                    e,
+                    dummy_error,
+                )))));
+            }
+            Ok(encoder) => encoder,
+        };
+        loop {
+            // Emit frame if compressed_len > zstd_frame_size_tripwire
+            if compressed_len(encoder) > zstd_frame_size_tripwire {
+                let cids = std::mem::take(&mut frame_cids);
+                let frame = finalize_frame(zstd_compression_level, dictionary.as_deref(), encoder)?;
+                return Poll::Ready(Some(Ok((cids, frame))));
+            }
+            // No frame to emit, let's get another block
+            let ret = futures::ready!(stream.as_mut().poll_next(cx));
+            match ret {
+                // End-of-stream
+                None => {
+                    // If there's anything in the zstd buffer, emit it.
+                    if compressed_len(encoder) > 0 {
+                        let cids = std::mem::take(&mut frame_cids);
+                        let frame =
+                            finalize_frame(zstd_compression_level, dictionary.as_deref(), encoder)?;
+                        return Poll::Ready(Some(Ok((cids, frame))));
+                    } else {
+                        // Otherwise we're all done.
+                        return Poll::Ready(None);
                     }
                 }
+                // Pass errors through
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                // Got element, add to encoder and emit block position
+                Some(Ok(block)) => {
+                    frame_cids.push(block.cid);
+                    block.write(encoder)?;
+                    encoder.flush()?;
+                }
             }
-        })
-    }
+        }
+    })
 }
 
 fn invalid_data(inner: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
@@ -371,43 +575,72 @@ fn compressed_len(encoder: &zstd::Encoder<'static, Writer<BytesMut>>) -> usize {
 
 fn finalize_frame(
     zstd_compression_level: u16,
+    dictionary: Option<&[u8]>,
     encoder: &mut zstd::Encoder<'static, Writer<BytesMut>>,
 ) -> io::Result<Bytes> {
-    let prev_encoder = std::mem::replace(encoder, new_encoder(zstd_compression_level)?);
+    let prev_encoder = std::mem::replace(encoder, new_encoder(zstd_compression_level, dictionary)?);
     Ok(prev_encoder.finish()?.into_inner().freeze())
 }
 
 fn new_encoder(
     zstd_compression_level: u16,
+    dictionary: Option<&[u8]>,
 ) -> io::Result<zstd::Encoder<'static, Writer<BytesMut>>> {
-    zstd::Encoder::new(BytesMut::new().writer(), i32::from(zstd_compression_level))
+    let writer = BytesMut::new().writer();
+    let level = i32::from(zstd_compression_level);
+    match dictionary {
+        Some(dictionary) => zstd::Encoder::with_dictionary(writer, level, dictionary),
+        None => zstd::Encoder::new(writer, level),
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 #[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
 struct ForestCarFooter {
     index: u64,
+    // Offset of the dictionary frame. Archives with a dictionary have a
+    // versioned footer.
+    dictionary: Option<u64>,
 }
 
 impl ForestCarFooter {
+    /// Size of unversioned footers.
     pub const SIZE: usize = 16;
+    /// Size of version 2 footers.
+    pub const VERSIONED_SIZE: usize = 32;
 
-    pub fn to_le_bytes(&self) -> [u8; Self::SIZE] {
-        let footer_data_len: u32 = 8;
+    pub fn to_le_bytes(&self) -> Vec<u8> {
+        let size = match self.dictionary {
+            None => Self::SIZE,
+            Some(_) => Self::VERSIONED_SIZE,
+        };
+        let footer_data_len = (size - 8) as u32;
 
-        let mut buffer = [0; 16];
+        let mut buffer = Vec::with_capacity(size);
         // Skippable frames start with 50 2A 4D 18
-        buffer[0..4].copy_from_slice(&[0x50, 0x2A, 0x4D, 0x18]);
+        buffer.extend_from_slice(&SKIP_FRAME_MAGIC);
         // Then a u32 containing the length of the data in the frame
-        buffer[4..8].copy_from_slice(&footer_data_len.to_le_bytes());
+        buffer.extend_from_slice(&footer_data_len.to_le_bytes());
         // And finally the metadata we want to store
-        buffer[8..16].copy_from_slice(&self.index.to_le_bytes());
+        buffer.extend_from_slice(&self.index.to_le_bytes());
+        if let Some(dictionary) = self.dictionary {
+            buffer.extend_from_slice(&dictionary.to_le_bytes());
+            buffer.extend_from_slice(&FOOTER_VERSION.to_le_bytes());
+            buffer.extend_from_slice(&FOOTER_MAGIC);
+        }
         buffer
     }
 
-    pub fn try_from_le_bytes(bytes: [u8; Self::SIZE]) -> Option<ForestCarFooter> {
-        let index = u64::from_le_bytes(bytes[8..16].try_into().expect("infallible"));
-        let footer = ForestCarFooter { index };
+    pub fn try_from_le_bytes(bytes: &[u8]) -> Option<ForestCarFooter> {
+        let index = u64::from_le_bytes(bytes.get(8..16)?.try_into().expect("infallible"));
+        let dictionary = match bytes.len() {
+            Self::SIZE => None,
+            Self::VERSIONED_SIZE => Some(u64::from_le_bytes(
+                bytes[16..24].try_into().expect("infallible"),
+            )),
+            _ => return None,
+        };
+        let footer = ForestCarFooter { index, dictionary };
         if bytes == footer.to_le_bytes() {
             Some(footer)
         } else {
@@ -485,10 +718,80 @@ mod tests {
 
     #[quickcheck]
     fn forest_footer_roundtrip(footer: ForestCarFooter) {
-        let footer_recoded = ForestCarFooter::try_from_le_bytes(footer.to_le_bytes());
+        let footer_recoded = ForestCarFooter::try_from_le_bytes(&footer.to_le_bytes());
         assert_eq!(footer_recoded, Some(footer));
     }
 
+    #[test]
+    fn forest_car_with_dictionary() {
+        use cid::multihash::{Code::Blake2b256, MultihashDigest};
+
+        let blocks = (0..5000_u32)
+            .map(|i| {
+                let data = to_vec(&(i, "repetitive block content", i % 7)).unwrap();
+                CarBlock {
+                    cid: Cid::new_v1(fvm_ipld_encoding::DAG_CBOR, Blake2b256.digest(&data)),
+                    data,
+                }
+            })
+            .collect::<Vec<_>>();
+        let encoded = block_on(async {
+            let frames = Encoder::compress_stream_with_dictionary(
+                DEFAULT_FOREST_CAR_FRAME_SIZE,
+                3,
+                futures::stream::iter(blocks.clone().into_iter().map(Ok)),
+            );
+            let mut encoded = vec![];
+            Encoder::write(&mut encoded, vec![], frames).await.unwrap();
+            encoded
+        });
+
+        let forest_car = ForestCar::new(encoded.clone()).unwrap();
+        assert!(forest_car.dictionary.is_some());
+        for block in blocks {
+            assert_eq!(forest_car.get(&block.cid).unwrap(), Some(block.data));
+        }
+
+        // Streaming decoders can't use the dictionary
+        let error = block_on(crate::utils::db::car_stream::CarStream::new(
+            encoded.as_slice(),
+        ))
+        .err()
+        .unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::Unsupported);
+
+        // Archives with a newer footer version are rejected
+        let mut newer = encoded;
+        let version_offset = newer.len() - 8;
+        newer[version_offset..version_offset + 4]
+            .copy_from_slice(&(FOOTER_VERSION + 1).to_le_bytes());
+        let error = ForestCar::new(newer.clone()).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::Unsupported);
+        assert!(ForestCar::check_footer_version(&newer).is_err());
+    }
+
+    #[test]
+    fn forest_car_dictionary_fallback() {
+        // Too few samples to train a dictionary
+        let block = CarBlock {
+            cid: Cid::default(),
+            data: vec![1, 2, 3],
+        };
+        let encoded = block_on(async {
+            let frames = Encoder::compress_stream_with_dictionary(
+                DEFAULT_FOREST_CAR_FRAME_SIZE,
+                3,
+                futures::stream::iter([Ok(block.clone())]),
+            );
+            let mut encoded = vec![];
+            Encoder::write(&mut encoded, vec![], frames).await.unwrap();
+            encoded
+        });
+        let forest_car = ForestCar::new(encoded).unwrap();
+        assert!(forest_car.dictionary.is_none());
+        assert_eq!(forest_car.get(&block.cid).unwrap(), Some(block.data));
+    }
+
     // Two colliding hashes in separate zstd-frames should not affect each other.
     #[test]
     fn encode_hash_collisions() {
@@ -7,11 +7,13 @@
 //! requests are only forwarded to the writable store.
 //!
 //! A single z-frame cache is shared between all read-only stores.
+//!
+//! Read-only stores may be remote archives, given by `http(s)` URLs, which are
+//! read with HTTP range requests.
 
 use super::{AnyCar, ZstdFrameCache};
-use crate::db::{MemoryDB, SettingsStore};
+use crate::db::{MemoryDB, ReplaceBlock, SettingsStore};
 use crate::libp2p_bitswap::BitswapStoreReadWrite;
-use crate::utils::io::EitherMmapOrRandomAccessFile;
 use crate::{blocks::Tipset, libp2p_bitswap::BitswapStoreRead};
 use anyhow::Context as _;
 use cid::Cid;
@@ -71,7 +73,7 @@ impl<WriterT> ManyCar<WriterT> {
 
     pub fn read_only_files(&self, files: impl Iterator<Item = PathBuf>) -> io::Result<()> {
         for file in files {
-            self.read_only(AnyCar::new(EitherMmapOrRandomAccessFile::open(file)?)?);
+            self.read_only(AnyCar::open(&file)?);
         }
 
         Ok(())
@@ -122,6 +124,14 @@ impl<WriterT: Blockstore> Blockstore for ManyCar<WriterT> {
     }
 }
 
+impl<WriterT: ReplaceBlock> ReplaceBlock for ManyCar<WriterT> {
+    /// Blocks are only replaced in the writer, the read-only archives are
+    /// left as they are.
+    fn replace_block(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        self.writer.replace_block(k, block)
+    }
+}
+
 impl<WriterT: BitswapStoreRead + Blockstore> BitswapStoreRead for ManyCar<WriterT> {
     fn contains(&self, cid: &Cid) -> anyhow::Result<bool> {
         Blockstore::has(self, cid)
@@ -4,11 +4,13 @@ mod any;
 pub mod forest;
 mod many;
 pub mod plain;
+pub mod v2;
 
 pub use any::AnyCar;
 pub use forest::ForestCar;
 pub use many::ManyCar;
 pub use plain::PlainCar;
+pub use v2::CarV2;
 
 use crate::utils::db::car_index::FrameOffset;
 use ahash::HashMap;
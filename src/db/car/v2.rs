@@ -0,0 +1,471 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! # CARv2 layout
+//!
+//! A [CARv2](https://ipld.io/specs/transport/car/carv2/) archive wraps a
+//! CARv1 _data payload_ and, optionally, an _index_ of the blocks in that
+//! payload:
+//!
+//! ```text
+//! ├──────┬──────┬───────┬──────────────────┬───────┬─────┐
+//! │pragma│header│padding│CARv1 data payload│padding│index│
+//! └──────┴──────┴───────┴──────────────────┴───────┴─────┘
+//! ```
+//!
+//! The _pragma_ is a fixed 11-byte sequence which doubles as a CARv1 header
+//! with `version: 2`. The _header_ holds the offset and size of the data
+//! payload and the offset of the index, as little-endian integers.
+//!
+//! # Index
+//!
+//! Two index formats are in use: `IndexSorted` and `MultihashIndexSorted`. Both
+//! map the multihash digest of a block to the offset of its varint frame,
+//! relative to the start of the data payload. Entries are grouped into buckets
+//! of equal digest width and sorted by digest within a bucket, so lookups are
+//! binary searches that read from disk and [`CarV2`] never loads the index into
+//! memory. `MultihashIndexSorted` additionally groups the buckets by multihash
+//! code.
+//!
+//! ```text
+//! IndexSorted:          │codec│bucket count│bucket│...│
+//! MultihashIndexSorted: │codec│code count│code│bucket count│bucket│...│...│
+//! bucket:               │width│byte length│digest│offset│...│
+//! ```
+//!
+//! Archives without an index are indexed in memory when opened.
+
+use crate::blocks::{Tipset, TipsetKeys};
+use crate::cid_collections::CidHashMap;
+use crate::utils::db::car_stream::CarBlock;
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use integer_encoding::{VarInt as _, VarIntReader as _};
+use parking_lot::RwLock;
+use positioned_io::{Cursor, ReadAt};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::io::{
+    self, BufReader,
+    ErrorKind::{InvalidData, Unsupported},
+    Read, Seek, SeekFrom,
+};
+use tracing::debug;
+
+/// The first bytes of every CARv2 archive. It decodes as a CARv1 header with
+/// `version: 2` and no roots.
+pub const CARV2_PRAGMA: [u8; 11] = [
+    0x0a, 0xa1, 0x67, 0x76, 0x65, 0x72, 0x73, 0x69, 0x6f, 0x6e, 0x02,
+];
+
+/// Multicodec of the `IndexSorted` index format.
+pub const INDEX_SORTED: u64 = 0x0400;
+/// Multicodec of the `MultihashIndexSorted` index format.
+pub const MULTIHASH_INDEX_SORTED: u64 = 0x0401;
+
+/// The fixed-size header following the [`CARV2_PRAGMA`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CarV2Header {
+    pub characteristics: [u8; 16],
+    /// Offset of the CARv1 data payload from the start of the archive
+    pub data_offset: u64,
+    /// Size of the CARv1 data payload in bytes
+    pub data_size: u64,
+    /// Offset of the index from the start of the archive, or `0` if there is
+    /// no index
+    pub index_offset: u64,
+}
+
+impl CarV2Header {
+    pub const SIZE: usize = 40;
+
+    /// Offset of the data payload when it directly follows the header.
+    pub const DEFAULT_DATA_OFFSET: u64 = (CARV2_PRAGMA.len() + Self::SIZE) as u64;
+
+    pub fn to_le_bytes(self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[0..16].copy_from_slice(&self.characteristics);
+        bytes[16..24].copy_from_slice(&self.data_offset.to_le_bytes());
+        bytes[24..32].copy_from_slice(&self.data_size.to_le_bytes());
+        bytes[32..40].copy_from_slice(&self.index_offset.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_le_bytes(bytes: [u8; Self::SIZE]) -> Self {
+        let u64_at = |offset: usize| {
+            u64::from_le_bytes(bytes[offset..offset + 8].try_into().expect("infallible"))
+        };
+        CarV2Header {
+            characteristics: bytes[0..16].try_into().expect("infallible"),
+            data_offset: u64_at(16),
+            data_size: u64_at(24),
+            index_offset: u64_at(32),
+        }
+    }
+}
+
+/// Encodes an `IndexSorted` index of `(multihash digest, frame offset)`
+/// entries. Frame offsets are relative to the start of the data payload.
+pub fn encode_index_sorted(mut entries: Vec<(Vec<u8>, u64)>) -> Vec<u8> {
+    entries.sort();
+    entries.dedup_by(|a, b| a.0 == b.0);
+    let mut buckets = BTreeMap::<usize, Vec<_>>::new();
+    for entry in entries {
+        buckets.entry(entry.0.len()).or_default().push(entry);
+    }
+
+    let mut index = INDEX_SORTED.encode_var_vec();
+    index.extend((buckets.len() as i32).to_le_bytes());
+    for (digest_length, entries) in buckets {
+        let width = digest_length + 8;
+        index.extend((width as u32).to_le_bytes());
+        index.extend(((entries.len() * width) as i64).to_le_bytes());
+        for (digest, offset) in entries {
+            index.extend(digest);
+            index.extend(offset.to_le_bytes());
+        }
+    }
+    index
+}
+
+/// **Note that all operations on this store are blocking**.
+///
+/// An implementer of [`Blockstore`] that wraps a CARv2 archive. Lookups use the
+/// embedded index; archives without one are indexed in memory when opened.
+///
+/// Writes are cached in-memory.
+///
+/// See [module documentation](mod@self) for more.
+pub struct CarV2<ReaderT> {
+    reader: ReaderT,
+    header: CarV2Header,
+    roots: Vec<Cid>,
+    index: CarV2Index,
+    write_cache: RwLock<CidHashMap<Vec<u8>>>,
+}
+
+enum CarV2Index {
+    /// Buckets of the embedded index, searched on disk
+    Embedded(Vec<IndexBucket>),
+    /// Frame offsets of an archive without an embedded index
+    Memory(CidHashMap<u64>),
+}
+
+/// A group of sorted index entries of equal width.
+struct IndexBucket {
+    /// Multihash code of the entries, if the index groups entries by code
+    code: Option<u64>,
+    /// Size of an entry: the digest length plus 8 bytes for the frame offset
+    width: u32,
+    /// Offset of the first entry from the start of the archive
+    offset: u64,
+    count: u64,
+}
+
+impl IndexBucket {
+    /// Binary-searches the bucket for `digest`, returning the frame offset
+    /// of the matching entry.
+    fn search(&self, reader: &impl ReadAt, digest: &[u8]) -> io::Result<Option<u64>> {
+        let mut entry = vec![0; self.width as usize];
+        let (mut low, mut high) = (0, self.count);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            reader.read_exact_at(self.offset + middle * u64::from(self.width), &mut entry)?;
+            let (entry_digest, frame_offset) = entry.split_at(digest.len());
+            match entry_digest.cmp(digest) {
+                Ordering::Less => low = middle + 1,
+                Ordering::Greater => high = middle,
+                Ordering::Equal => {
+                    return Ok(Some(u64::from_le_bytes(
+                        frame_offset.try_into().expect("infallible"),
+                    )))
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<ReaderT: super::RandomAccessFileReader> CarV2<ReaderT> {
+    /// Check whether `reader` starts with the [`CARV2_PRAGMA`].
+    pub fn is_valid(reader: &ReaderT) -> bool {
+        let mut pragma = [0; CARV2_PRAGMA.len()];
+        reader.read_exact_at(0, &mut pragma).is_ok() && pragma == CARV2_PRAGMA
+    }
+
+    /// To be correct:
+    /// - `reader` must read immutable data. e.g if it is a file, it should be
+    ///   [`flock`](https://linux.die.net/man/2/flock)ed.
+    ///   [`Blockstore`] API calls may panic if this is not upheld.
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub fn new(reader: ReaderT) -> io::Result<Self> {
+        if !Self::is_valid(&reader) {
+            return Err(io::Error::new(InvalidData, "missing CARv2 pragma"));
+        }
+        let mut header_bytes = [0; CarV2Header::SIZE];
+        reader.read_exact_at(CARV2_PRAGMA.len() as u64, &mut header_bytes)?;
+        let header = CarV2Header::from_le_bytes(header_bytes);
+        let data_end = header
+            .data_offset
+            .checked_add(header.data_size)
+            .ok_or_else(|| io::Error::new(InvalidData, "CARv2 data payload out of bounds"))?;
+        if reader.size()?.is_some_and(|size| data_end > size) {
+            return Err(io::Error::new(
+                InvalidData,
+                "CARv2 data payload out of bounds",
+            ));
+        }
+
+        let roots =
+            super::plain::get_roots_from_v1_header(Cursor::new_pos(&reader, header.data_offset))?;
+
+        let index = match header.index_offset {
+            0 => CarV2Index::Memory(index_data_payload(&reader, &header)?),
+            index_offset => {
+                CarV2Index::Embedded(read_index_buckets(Cursor::new_pos(&reader, index_offset))?)
+            }
+        };
+        debug!(embedded_index = header.index_offset != 0, "opened CARv2");
+
+        Ok(CarV2 {
+            reader,
+            header,
+            roots,
+            index,
+            write_cache: RwLock::new(CidHashMap::new()),
+        })
+    }
+
+    pub fn roots(&self) -> Vec<Cid> {
+        self.roots.clone()
+    }
+
+    pub fn heaviest_tipset(&self) -> anyhow::Result<Tipset> {
+        Tipset::load_required(self, &TipsetKeys::from_iter(self.roots()))
+    }
+
+    pub fn into_dyn(self) -> CarV2<Box<dyn super::RandomAccessFileReader>> {
+        CarV2 {
+            reader: Box::new(self.reader),
+            header: self.header,
+            roots: self.roots,
+            index: self.index,
+            write_cache: self.write_cache,
+        }
+    }
+}
+
+impl<ReaderT: ReadAt> CarV2<ReaderT> {
+    /// Look up the offset of the frame holding `k`, relative to the start of
+    /// the data payload. The embedded index is keyed by digest, so the frame
+    /// may hold a block with a different CID.
+    fn frame_offset(&self, k: &Cid) -> io::Result<Option<u64>> {
+        match &self.index {
+            CarV2Index::Memory(index) => Ok(index.get(k).copied()),
+            CarV2Index::Embedded(buckets) => {
+                let digest = k.hash().digest();
+                for bucket in buckets.iter().filter(|bucket| {
+                    bucket.width as usize == digest.len() + 8
+                        && bucket.code.map_or(true, |code| code == k.hash().code())
+                }) {
+                    if let Some(offset) = bucket.search(&self.reader, digest)? {
+                        return Ok(Some(offset));
+                    }
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    fn read_frame(&self, offset: u64) -> io::Result<CarBlock> {
+        let mut cursor = Cursor::new_pos(&self.reader, self.header.data_offset + offset);
+        let body_length = cursor.read_varint::<u64>()?;
+        if body_length > self.header.data_size {
+            return Err(io::Error::new(InvalidData, "CARv2 frame out of bounds"));
+        }
+        let mut body = vec![0; body_length as usize];
+        cursor.read_exact(&mut body)?;
+        CarBlock::from_bytes(body)
+    }
+}
+
+impl<ReaderT> Blockstore for CarV2<ReaderT>
+where
+    ReaderT: ReadAt,
+{
+    #[tracing::instrument(level = "trace", skip(self))]
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        if let Some(data) = self.write_cache.read().get(k) {
+            return Ok(Some(data.clone()));
+        }
+        match self.frame_offset(k)? {
+            Some(offset) => {
+                let block = self.read_frame(offset)?;
+                Ok((block.cid.hash() == k.hash()).then_some(block.data))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        self.write_cache.write().insert(*k, Vec::from(block));
+        Ok(())
+    }
+}
+
+/// Index the frames of the data payload of an archive without an embedded
+/// index.
+fn index_data_payload(reader: &impl ReadAt, header: &CarV2Header) -> io::Result<CidHashMap<u64>> {
+    let data_end = header.data_offset + header.data_size;
+    let mut cursor = BufReader::with_capacity(1024, Cursor::new_pos(reader, header.data_offset));
+    // Skip the CARv1 header frame
+    let header_length = cursor.read_varint::<u64>()?;
+    cursor.seek(SeekFrom::Current(header_length as i64))?;
+
+    let mut index = CidHashMap::new();
+    loop {
+        let frame_offset = cursor.stream_position()?;
+        if frame_offset >= data_end {
+            break;
+        }
+        let body_length = cursor.read_varint::<u64>()?;
+        let body_offset = cursor.stream_position()?;
+        let cid = Cid::read_bytes(&mut cursor).map_err(|e| io::Error::new(InvalidData, e))?;
+        index.insert(cid, frame_offset - header.data_offset);
+        cursor.seek(SeekFrom::Start(body_offset + body_length))?;
+    }
+    Ok(index)
+}
+
+fn read_index_buckets(mut reader: impl Read + Seek) -> io::Result<Vec<IndexBucket>> {
+    match reader.read_varint::<u64>()? {
+        INDEX_SORTED => read_width_buckets(&mut reader, None),
+        MULTIHASH_INDEX_SORTED => {
+            let mut buckets = vec![];
+            for _ in 0..read_u32_le(&mut reader)? {
+                let code = read_u64_le(&mut reader)?;
+                buckets.extend(read_width_buckets(&mut reader, Some(code))?);
+            }
+            Ok(buckets)
+        }
+        codec => Err(io::Error::new(
+            Unsupported,
+            format!("unsupported CARv2 index codec: {codec:#x}"),
+        )),
+    }
+}
+
+fn read_width_buckets(
+    mut reader: impl Read + Seek,
+    code: Option<u64>,
+) -> io::Result<Vec<IndexBucket>> {
+    (0..read_u32_le(&mut reader)?)
+        .map(|_| {
+            let width = read_u32_le(&mut reader)?;
+            let length = read_u64_le(&mut reader)?;
+            if width <= 8 || length % u64::from(width) != 0 {
+                return Err(io::Error::new(InvalidData, "malformed CARv2 index bucket"));
+            }
+            let offset = reader.stream_position()?;
+            reader.seek(SeekFrom::Current(length as i64))?;
+            Ok(IndexBucket {
+                code,
+                width,
+                offset,
+                count: length / u64::from(width),
+            })
+        })
+        .collect()
+}
+
+fn read_u32_le(mut reader: impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64_le(mut reader: impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::networks::calibnet;
+    use crate::utils::db::car_stream::{CarStream, CarV2Writer};
+    use futures::{StreamExt as _, TryStreamExt as _};
+
+    async fn to_carv2(car: &[u8]) -> Vec<u8> {
+        let stream = CarStream::new(car).await.unwrap();
+        let roots = stream.header.roots.clone();
+        let mut carv2 = std::io::Cursor::new(vec![]);
+        stream
+            .forward(CarV2Writer::new(roots, &mut carv2).unwrap())
+            .await
+            .unwrap();
+        carv2.into_inner()
+    }
+
+    async fn blocks(car: &[u8]) -> Vec<CarBlock> {
+        CarStream::new(car)
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn carv2_embedded_index_roundtrip() {
+        let carv2 = to_carv2(calibnet::DEFAULT_GENESIS).await;
+        let store = CarV2::new(carv2.clone()).unwrap();
+        assert!(matches!(store.index, CarV2Index::Embedded(_)));
+        for block in blocks(calibnet::DEFAULT_GENESIS).await {
+            assert_eq!(store.get(&block.cid).unwrap(), Some(block.data));
+        }
+        assert_eq!(
+            blocks(&carv2).await,
+            blocks(calibnet::DEFAULT_GENESIS).await
+        );
+    }
+
+    #[tokio::test]
+    async fn carv2_without_index() {
+        let mut carv2 = to_carv2(calibnet::DEFAULT_GENESIS).await;
+        let mut header = CarV2Header::from_le_bytes(
+            carv2[CARV2_PRAGMA.len()..CarV2Header::DEFAULT_DATA_OFFSET as usize]
+                .try_into()
+                .unwrap(),
+        );
+        carv2.truncate(header.index_offset as usize);
+        header.index_offset = 0;
+        carv2[CARV2_PRAGMA.len()..CarV2Header::DEFAULT_DATA_OFFSET as usize]
+            .copy_from_slice(&header.to_le_bytes());
+
+        let store = CarV2::new(carv2).unwrap();
+        assert!(matches!(store.index, CarV2Index::Memory(_)));
+        for block in blocks(calibnet::DEFAULT_GENESIS).await {
+            assert_eq!(store.get(&block.cid).unwrap(), Some(block.data));
+        }
+    }
+
+    #[tokio::test]
+    async fn carv2_missing_block() {
+        let store = CarV2::new(to_carv2(calibnet::DEFAULT_GENESIS).await).unwrap();
+        assert_eq!(store.get(&Cid::default()).unwrap(), None);
+        assert!(!CarV2::is_valid(&calibnet::DEFAULT_GENESIS));
+    }
+
+    #[test]
+    fn carv2_header_roundtrip() {
+        let header = CarV2Header {
+            characteristics: [1; 16],
+            data_offset: 51,
+            data_size: 1024,
+            index_offset: 1075,
+        };
+        assert_eq!(CarV2Header::from_le_bytes(header.to_le_bytes()), header);
+    }
+}
@@ -0,0 +1,226 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Integrity check of the blockstore. The graph reachable from a tipset is
+//! walked with [`stream_graph`], and every block on the way is checked to
+//! exist and to hash to its CID. Missing and corrupt blocks may be refetched
+//! from a repair source and written back to the blockstore.
+
+use std::collections::BTreeSet;
+
+use crate::blocks::{Tipset, TipsetKeys};
+use crate::db::ReplaceBlock;
+use crate::ipld::stream_graph;
+use crate::shim::clock::ChainEpoch;
+use crate::utils::db::car_stream::CarBlock;
+use cid::Cid;
+use futures::TryStreamExt as _;
+use fvm_ipld_blockstore::Blockstore;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+/// Fetches a block to replace a missing or corrupt one. Returns `None` if the
+/// block isn't available.
+pub type RepairFn<'a> = dyn Fn(&Cid) -> anyhow::Result<Option<Vec<u8>>> + Sync + 'a;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CheckReport {
+    /// Key of the tipset the walk started from.
+    #[serde(with = "crate::lotus_json")]
+    pub head: TipsetKeys,
+    pub head_epoch: ChainEpoch,
+    /// Number of blocks that were reached and are intact.
+    pub blocks_checked: u64,
+    /// Reachable blocks that aren't in the blockstore.
+    #[serde(with = "crate::lotus_json")]
+    pub missing: Vec<Cid>,
+    /// Reachable blocks whose data doesn't hash to their CID.
+    #[serde(with = "crate::lotus_json")]
+    pub corrupt: Vec<Cid>,
+    /// Missing and corrupt blocks that have been replaced.
+    #[serde(with = "crate::lotus_json")]
+    pub repaired: Vec<Cid>,
+}
+
+impl CheckReport {
+    /// Missing and corrupt blocks that haven't been repaired.
+    pub fn unrepaired(&self) -> Vec<Cid> {
+        let repaired = self.repaired.iter().collect::<BTreeSet<_>>();
+        self.missing
+            .iter()
+            .chain(&self.corrupt)
+            .filter(|cid| !repaired.contains(cid))
+            .copied()
+            .collect()
+    }
+
+    pub fn is_consistent(&self) -> bool {
+        self.unrepaired().is_empty()
+    }
+}
+
+/// Check the graph reachable from `head`. State-trees are only walked for
+/// tipsets after `stateroot_limit`, older tipsets are checked up to their
+/// headers. If `repair` is given, missing and corrupt blocks are fetched with
+/// it and written to `db`, replacing the corrupt ones.
+///
+/// Children of blocks that are missing or corrupt (and can't be repaired) are
+/// not reachable, so a repaired database may reveal further problems when it
+/// is checked again.
+pub async fn check_graph<DB: Blockstore + ReplaceBlock>(
+    db: &DB,
+    head: Tipset,
+    stateroot_limit: ChainEpoch,
+    repair: Option<&RepairFn<'_>>,
+) -> anyhow::Result<CheckReport> {
+    let checked = CheckedStore {
+        db,
+        repair,
+        report: Mutex::new(CheckReport {
+            head: head.key().clone(),
+            head_epoch: head.epoch(),
+            ..Default::default()
+        }),
+    };
+    // Like `Tipset::chain`, but the parents of genesis aren't loaded, as they
+    // aren't a tipset
+    let tipsets = itertools::unfold(Some(head), |tipset| {
+        let child = tipset.take()?;
+        if child.epoch() > 0 {
+            *tipset = Tipset::load(&checked, child.parents()).ok().flatten();
+        }
+        Some(child)
+    });
+    let mut stream = stream_graph(&checked, tipsets, stateroot_limit);
+    let mut blocks_checked = 0;
+    while stream.try_next().await?.is_some() {
+        blocks_checked += 1;
+        if blocks_checked % 1_000_000 == 0 {
+            info!("checked {blocks_checked} blocks");
+        }
+    }
+    drop(stream);
+    let mut report = checked.report.into_inner();
+    report.blocks_checked = blocks_checked;
+    Ok(report)
+}
+
+/// Records missing and corrupt blocks as they are read, and replaces them if
+/// possible. Corrupt blocks that can't be replaced are read as missing, so
+/// that their links aren't followed.
+struct CheckedStore<'a, DB> {
+    db: &'a DB,
+    repair: Option<&'a RepairFn<'a>>,
+    report: Mutex<CheckReport>,
+}
+
+impl<DB: Blockstore + ReplaceBlock> CheckedStore<'_, DB> {
+    fn try_repair(&self, cid: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        let Some(repair) = self.repair else {
+            return Ok(None);
+        };
+        match repair(cid)? {
+            Some(data) => {
+                let block = CarBlock { cid: *cid, data };
+                if !block.valid() {
+                    return Ok(None);
+                }
+                self.db.replace_block(cid, &block.data)?;
+                // Corrupt copies may still shadow the block, e.g. in
+                // read-only archives, so the repair only counts once it can
+                // be read back
+                if self.db.get(cid)?.as_ref() == Some(&block.data) {
+                    self.report.lock().repaired.push(*cid);
+                }
+                Ok(Some(block.data))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+impl<DB: Blockstore + ReplaceBlock> Blockstore for CheckedStore<'_, DB> {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        match self.db.get(k)? {
+            Some(data) => {
+                let block = CarBlock { cid: *k, data };
+                if block.valid() {
+                    return Ok(Some(block.data));
+                }
+                self.report.lock().corrupt.push(*k);
+            }
+            None => self.report.lock().missing.push(*k),
+        }
+        self.try_repair(k)
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        self.db.put_keyed(k, block)
+    }
+
+    fn has(&self, k: &Cid) -> anyhow::Result<bool> {
+        self.db.has(k)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::car::PlainCar;
+    use crate::db::db_engine::open_proxy_db;
+    use crate::db::MemoryDB;
+    use crate::networks::calibnet;
+    use crate::utils::db::car_util::load_car;
+
+    async fn load_genesis(db: &impl Blockstore) -> Tipset {
+        load_car(db, calibnet::DEFAULT_GENESIS).await.unwrap();
+        PlainCar::try_from(calibnet::DEFAULT_GENESIS)
+            .unwrap()
+            .heaviest_tipset()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn check_intact_graph() {
+        let db = MemoryDB::default();
+        let head = load_genesis(&db).await;
+        let report = check_graph(&db, head, 0, None).await.unwrap();
+        assert!(report.is_consistent());
+        assert!(report.blocks_checked > 1);
+    }
+
+    async fn check_and_repair(db: impl Blockstore + ReplaceBlock) {
+        let head = load_genesis(&db).await;
+        let state_root = *head.parent_state();
+        let original = db.get(&state_root).unwrap().unwrap();
+        db.replace_block(&state_root, b"corrupt").unwrap();
+
+        let report = check_graph(&db, head.clone(), 0, None).await.unwrap();
+        assert_eq!(report.corrupt, vec![state_root]);
+        assert_eq!(report.unrepaired(), vec![state_root]);
+
+        let source = |cid: &Cid| anyhow::Ok((*cid == state_root).then(|| original.clone()));
+        let report = check_graph(&db, head.clone(), 0, Some(&source))
+            .await
+            .unwrap();
+        assert_eq!(report.repaired, vec![state_root]);
+        assert!(report.is_consistent());
+        assert!(check_graph(&db, head, 0, None)
+            .await
+            .unwrap()
+            .is_consistent());
+    }
+
+    #[tokio::test]
+    async fn check_and_repair_memory_db() {
+        check_and_repair(MemoryDB::default()).await;
+    }
+
+    #[tokio::test]
+    async fn check_and_repair_parity_db() {
+        let dir = tempfile::tempdir().unwrap();
+        check_and_repair(open_proxy_db(dir.path().to_owned(), Default::default()).unwrap()).await;
+    }
+}
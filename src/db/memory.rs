@@ -8,12 +8,13 @@ use fvm_ipld_blockstore::Blockstore;
 use itertools::Itertools;
 use parking_lot::RwLock;
 
-use super::SettingsStore;
+use super::{ReplaceBlock, SettingsStore, TipsetStateStore};
 
 #[derive(Debug, Default)]
 pub struct MemoryDB {
     blockchain_db: RwLock<HashMap<Vec<u8>, Vec<u8>>>,
     settings_db: RwLock<HashMap<String, Vec<u8>>>,
+    tipset_states_db: RwLock<HashMap<Vec<u8>, Vec<u8>>>,
 }
 
 impl SettingsStore for MemoryDB {
@@ -37,6 +38,24 @@ impl SettingsStore for MemoryDB {
     }
 }
 
+impl TipsetStateStore for MemoryDB {
+    fn read_tipset_state(&self, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        Ok(self.tipset_states_db.read().get(key).cloned())
+    }
+
+    fn write_tipset_state(&self, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        self.tipset_states_db
+            .write()
+            .insert(key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    fn delete_tipset_state(&self, key: &[u8]) -> anyhow::Result<()> {
+        self.tipset_states_db.write().remove(key);
+        Ok(())
+    }
+}
+
 impl Blockstore for MemoryDB {
     fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
         Ok(self.blockchain_db.read().get(&k.to_bytes()).cloned())
@@ -50,6 +69,12 @@ impl Blockstore for MemoryDB {
     }
 }
 
+impl ReplaceBlock for MemoryDB {
+    fn replace_block(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        self.put_keyed(k, block)
+    }
+}
+
 impl BitswapStoreRead for MemoryDB {
     fn contains(&self, cid: &Cid) -> anyhow::Result<bool> {
         Ok(self.blockchain_db.read().contains_key(&cid.to_bytes()))
@@ -142,6 +142,14 @@ pub(super) fn create_migration_chain(
     create_migration_chain_from_migrations(start, goal, &MIGRATIONS)
 }
 
+/// Fails if a database of version `start` can't be migrated to `goal`.
+pub fn ensure_migration_possible(start: &Version, goal: &Version) -> anyhow::Result<()> {
+    if start != goal {
+        create_migration_chain(start, goal)?;
+    }
+    Ok(())
+}
+
 /// Same as [`create_migration_chain`], but uses any provided migrations map.
 fn create_migration_chain_from_migrations(
     start: &Version,
@@ -7,3 +7,4 @@ mod v0_12_1;
 mod void_migration;
 
 pub use db_migration::DbMigration;
+pub use migration_map::ensure_migration_possible;
@@ -1,10 +1,14 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+pub mod backend;
+pub mod backup;
 pub mod car;
+pub mod check;
 mod memory;
 pub mod parity_db;
 pub mod parity_db_config;
+pub mod redb;
 pub mod rolling;
 pub use memory::MemoryDB;
 mod db_mode;
@@ -21,6 +25,8 @@ pub mod setting_keys {
     pub const ESTIMATED_RECORDS_KEY: &str = "estimated_reachable_records";
     /// Key used to store the memory pool configuration in the settings store.
     pub const MPOOL_CONFIG_KEY: &str = "/mpool/config";
+    /// History of database usage samples. This is expected to be a list of [`crate::chain::usage::DbUsageSample`]
+    pub const DB_USAGE_HISTORY_KEY: &str = "/db/usage_history";
 }
 
 /// Interface used to store and retrieve settings from the database.
@@ -59,6 +65,47 @@ impl<T: SettingsStore> SettingsStore for Arc<T> {
     }
 }
 
+/// Interface used to persist the computed states of tipsets, so that they
+/// survive restarts. Keys and values are opaque to the store.
+pub trait TipsetStateStore {
+    /// Reads the state of the tipset with the given key.
+    fn read_tipset_state(&self, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>>;
+
+    /// Writes the state of the tipset with the given key.
+    fn write_tipset_state(&self, key: &[u8], value: &[u8]) -> anyhow::Result<()>;
+
+    /// Removes the state of the tipset with the given key, if any.
+    fn delete_tipset_state(&self, key: &[u8]) -> anyhow::Result<()>;
+}
+
+impl<T: TipsetStateStore> TipsetStateStore for Arc<T> {
+    fn read_tipset_state(&self, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        TipsetStateStore::read_tipset_state(self.as_ref(), key)
+    }
+
+    fn write_tipset_state(&self, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        TipsetStateStore::write_tipset_state(self.as_ref(), key, value)
+    }
+
+    fn delete_tipset_state(&self, key: &[u8]) -> anyhow::Result<()> {
+        TipsetStateStore::delete_tipset_state(self.as_ref(), key)
+    }
+}
+
+/// Blockstores whose blocks can be overwritten, to repair corrupt ones.
+/// [`fvm_ipld_blockstore::Blockstore::put_keyed`] doesn't suffice, as
+/// [`parity_db::ParityDb`] skips writes of keys it already has.
+pub trait ReplaceBlock {
+    /// Writes `block` under `k`, replacing any block already stored under it.
+    fn replace_block(&self, k: &cid::Cid, block: &[u8]) -> anyhow::Result<()>;
+}
+
+impl<T: ReplaceBlock> ReplaceBlock for Arc<T> {
+    fn replace_block(&self, k: &cid::Cid, block: &[u8]) -> anyhow::Result<()> {
+        ReplaceBlock::replace_block(self.as_ref(), k, block)
+    }
+}
+
 /// Extension trait for the [`SettingsStore`] trait. It is implemented for all types that implement
 /// [`SettingsStore`].
 /// It provides methods for writing and reading any serializable object from the store.
@@ -107,10 +154,10 @@ pub mod db_engine {
 
     use crate::db::rolling::*;
 
+    use super::backend::DbBackend as _;
     use super::db_mode::choose_db;
 
-    pub type Db = crate::db::parity_db::ParityDb;
-    pub type DbConfig = crate::db::parity_db_config::ParityDbConfig;
+    pub use super::backend::{Db, DbConfig};
 
     /// Returns the path to the database directory to be used by the daemon.
     pub fn db_root(chain_data_root: &Path) -> anyhow::Result<PathBuf> {
@@ -118,7 +165,7 @@ pub mod db_engine {
     }
 
     pub(in crate::db) fn open_db(path: &Path, config: &DbConfig) -> anyhow::Result<Db> {
-        Db::open(path, config).map_err(Into::into)
+        Db::open(path, config)
     }
 
     pub fn open_proxy_db(db_root: PathBuf, db_config: DbConfig) -> anyhow::Result<RollingDB> {
@@ -1,15 +1,17 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use super::SettingsStore;
+use super::{ReplaceBlock, SettingsStore, TipsetStateStore};
 
+use crate::db::backend::{DbBackend, DbConfig};
 use crate::db::{parity_db_config::ParityDbConfig, DBStatistics};
 use crate::libp2p_bitswap::{BitswapStoreRead, BitswapStoreReadWrite};
 
 use anyhow::{anyhow, Context as _};
 use cid::multihash::Code::Blake2b256;
+use cid::multihash::MultihashDigest as _;
 
 use cid::Cid;
 
@@ -20,7 +22,7 @@ use fvm_ipld_encoding::DAG_CBOR;
 use parity_db::{CompressionType, Db, Operation, Options};
 use strum::{Display, EnumIter, FromRepr, IntoEnumIterator};
 
-use tracing::warn;
+use tracing::{info, warn};
 
 /// This is specific to Forest's `ParityDb` usage.
 /// It is used to determine which column to use for a given entry type.
@@ -37,6 +39,9 @@ enum DbColumn {
     GraphFull,
     /// Column for storing Forest-specific settings.
     Settings,
+    /// Column for storing the computed states of tipsets. Databases created
+    /// before this column existed get it added when opened.
+    TipsetStates,
 }
 
 impl DbColumn {
@@ -65,6 +70,12 @@ impl DbColumn {
                         compression,
                         ..Default::default()
                     },
+                    DbColumn::TipsetStates => parity_db::ColumnOptions {
+                        // entries are overwritten and deleted
+                        preimage: false,
+                        compression,
+                        ..Default::default()
+                    },
                 }
             })
             .collect()
@@ -91,12 +102,40 @@ impl ParityDb {
 
     pub fn open(path: impl Into<PathBuf>, config: &ParityDbConfig) -> anyhow::Result<Self> {
         let opts = Self::to_options(path.into(), config);
+        Self::add_missing_columns(&opts)?;
         Ok(Self {
             db: Db::open_or_create(&opts)?,
             statistics_enabled: opts.stats,
         })
     }
 
+    /// Whether `path` holds a [`ParityDb`].
+    pub fn exists(path: &Path) -> bool {
+        matches!(Options::load_metadata(path), Ok(Some(_)))
+    }
+
+    /// Adds the columns which were introduced after an existing database was
+    /// created. Columns are only ever appended to [`DbColumn`].
+    fn add_missing_columns(opts: &Options) -> anyhow::Result<()> {
+        let Some(metadata) = Options::load_metadata(&opts.path)? else {
+            return Ok(());
+        };
+        let existing = metadata.columns.len();
+        if existing >= opts.columns.len() {
+            return Ok(());
+        }
+        let mut existing_opts = Options {
+            columns: opts.columns[..existing].to_vec(),
+            ..opts.clone()
+        };
+        for (index, column) in opts.columns.iter().enumerate().skip(existing) {
+            let name = DbColumn::from_repr(index as u8).map(|column| column.to_string());
+            info!("Adding column {} to the database", name.unwrap_or_default());
+            Db::add_column(&mut existing_opts, column.clone())?;
+        }
+        Ok(())
+    }
+
     /// Returns an appropriate column variant based on the information
     /// in the Cid.
     fn choose_column(cid: &Cid) -> DbColumn {
@@ -127,6 +166,16 @@ impl ParityDb {
             .commit(tx)
             .map_err(|e| anyhow!("error writing to column {column}: {e}"))
     }
+
+    fn delete_from_column<K>(&self, key: K, column: DbColumn) -> anyhow::Result<()>
+    where
+        K: AsRef<[u8]>,
+    {
+        let tx = [(column as u8, key.as_ref(), None)];
+        self.db
+            .commit(tx)
+            .map_err(|e| anyhow!("error deleting from column {column}: {e}"))
+    }
 }
 
 impl SettingsStore for ParityDb {
@@ -155,6 +204,20 @@ impl SettingsStore for ParityDb {
     }
 }
 
+impl TipsetStateStore for ParityDb {
+    fn read_tipset_state(&self, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        self.read_from_column(key, DbColumn::TipsetStates)
+    }
+
+    fn write_tipset_state(&self, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        self.write_to_column(key, value, DbColumn::TipsetStates)
+    }
+
+    fn delete_tipset_state(&self, key: &[u8]) -> anyhow::Result<()> {
+        self.delete_from_column(key, DbColumn::TipsetStates)
+    }
+}
+
 impl Blockstore for ParityDb {
     fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
         let column = Self::choose_column(k);
@@ -162,7 +225,7 @@ impl Blockstore for ParityDb {
             DbColumn::GraphDagCborBlake2b256 | DbColumn::GraphFull => {
                 self.read_from_column(k.to_bytes(), column)
             }
-            DbColumn::Settings => panic!("invalid column for IPLD data"),
+            DbColumn::Settings | DbColumn::TipsetStates => panic!("invalid column for IPLD data"),
         }
     }
 
@@ -174,7 +237,7 @@ impl Blockstore for ParityDb {
             DbColumn::GraphDagCborBlake2b256 | DbColumn::GraphFull => {
                 self.write_to_column(k.to_bytes(), block, column)
             }
-            DbColumn::Settings => panic!("invalid column for IPLD data"),
+            DbColumn::Settings | DbColumn::TipsetStates => panic!("invalid column for IPLD data"),
         }
     }
 
@@ -197,6 +260,16 @@ impl Blockstore for ParityDb {
     }
 }
 
+impl ReplaceBlock for ParityDb {
+    /// Blocks are stored in preimage columns, where existing keys can't be
+    /// written to, so the block is deleted first.
+    fn replace_block(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        let column = Self::choose_column(k);
+        self.delete_from_column(k.to_bytes(), column)?;
+        self.write_to_column(k.to_bytes(), block, column)
+    }
+}
+
 impl BitswapStoreRead for ParityDb {
     fn contains(&self, cid: &Cid) -> anyhow::Result<bool> {
         // We need to check both columns because we don't know which one
@@ -254,6 +327,34 @@ impl DBStatistics for ParityDb {
     }
 }
 
+impl DbBackend for ParityDb {
+    fn open(path: &Path, config: &DbConfig) -> anyhow::Result<Self> {
+        ParityDb::open(path, &config.parity_db)
+    }
+
+    fn for_each_block(
+        &self,
+        f: &mut dyn FnMut(Cid, Vec<u8>) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        // The keys of this column are hashed. As it only holds `DAG_CBOR`
+        // blocks hashed with `Blake2b256`, the CIDs are recomputed instead.
+        let mut result = Ok(());
+        self.db
+            .iter_column_while(DbColumn::GraphDagCborBlake2b256 as u8, |entry| {
+                let cid = Cid::new_v1(DAG_CBOR, Blake2b256.digest(&entry.value));
+                result = f(cid, entry.value);
+                result.is_ok()
+            })?;
+        result?;
+
+        let mut iter = self.db.iter(DbColumn::GraphFull as u8)?;
+        while let Some((key, value)) = iter.next()? {
+            f(Cid::try_from(key)?, value)?;
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use cid::multihash::Code::Sha2_256;
@@ -300,7 +401,9 @@ mod test {
             let other_column = match column {
                 DbColumn::GraphDagCborBlake2b256 => DbColumn::GraphFull,
                 DbColumn::GraphFull => DbColumn::GraphDagCborBlake2b256,
-                DbColumn::Settings => panic!("invalid column for IPLD data"),
+                DbColumn::Settings | DbColumn::TipsetStates => {
+                    panic!("invalid column for IPLD data")
+                }
             };
             let actual = db.read_from_column(cid.to_bytes(), other_column).unwrap();
             assert!(actual.is_none());
@@ -322,6 +425,29 @@ mod test {
         assert_eq!(b"bloop", actual.as_bytes());
     }
 
+    #[test]
+    fn adds_missing_columns() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("paritydb");
+        let config = ParityDbConfig::default();
+        let mut opts = ParityDb::to_options(path.clone(), &config);
+        opts.columns.truncate(DbColumn::TipsetStates as usize);
+        let db = Db::open_or_create(&opts).unwrap();
+        db.commit([(DbColumn::Settings as u8, b"key", Some(b"value".to_vec()))])
+            .unwrap();
+        drop(db);
+
+        let db = ParityDb::open(path, &config).unwrap();
+        assert_eq!(db.read_bin("key").unwrap(), Some(b"value".to_vec()));
+        db.write_tipset_state(b"tipset", b"state").unwrap();
+        assert_eq!(
+            db.read_tipset_state(b"tipset").unwrap(),
+            Some(b"state".to_vec())
+        );
+        db.delete_tipset_state(b"tipset").unwrap();
+        assert_eq!(db.read_tipset_state(b"tipset").unwrap(), None);
+    }
+
     #[test]
     fn choose_column_test() {
         let data = [0u8; 32];
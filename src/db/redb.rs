@@ -0,0 +1,260 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! A [`DbBackend`] on top of [`redb`], an embedded B-tree store. Unlike
+//! `ParityDb`, it never pauses to reindex, at the cost of larger writes.
+//!
+//! Blocks and tipset states are committed with [`Durability::Eventual`], so
+//! they skip the two `fsync` calls of an immediate commit. Settings, such as
+//! the chain head, are still committed immediately, which also persists every
+//! eventual commit before them. A crash may therefore lose blocks and states
+//! written since the last settings write, but never ones the persisted head
+//! depends on, and the database always reopens in a consistent state.
+
+use std::path::{Path, PathBuf};
+
+use super::backend::{DbBackend, DbConfig};
+use super::{DBStatistics, ReplaceBlock, SettingsStore, TipsetStateStore};
+use crate::libp2p_bitswap::{BitswapStoreRead, BitswapStoreReadWrite};
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use redb::{Database, Durability, ReadableTable as _, TableDefinition, TableHandle as _};
+
+/// Name of the database file in the directory of a [`RedbDb`].
+pub const REDB_FILE_NAME: &str = "forest.redb";
+
+type Table = TableDefinition<'static, &'static [u8], &'static [u8]>;
+
+/// Blocks, keyed by CID.
+const BLOCKS: Table = TableDefinition::new("blocks");
+/// Forest-specific settings.
+const SETTINGS: Table = TableDefinition::new("settings");
+/// The computed states of tipsets.
+const TIPSET_STATES: Table = TableDefinition::new("tipset_states");
+
+pub struct RedbDb {
+    db: Database,
+}
+
+impl RedbDb {
+    pub fn open(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let path = path.into();
+        std::fs::create_dir_all(&path)?;
+        let db = Database::create(path.join(REDB_FILE_NAME))?;
+        // Tables have to exist before they can be read
+        let tx = db.begin_write()?;
+        for table in [BLOCKS, SETTINGS, TIPSET_STATES] {
+            tx.open_table(table)?;
+        }
+        tx.commit()?;
+        Ok(Self { db })
+    }
+
+    /// Whether `path` holds a [`RedbDb`].
+    pub fn exists(path: &Path) -> bool {
+        path.join(REDB_FILE_NAME).is_file()
+    }
+
+    fn read(&self, table: Table, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(table)?;
+        let value = table.get(key)?.map(|value| value.value().to_vec());
+        Ok(value)
+    }
+
+    fn write<'a>(
+        &self,
+        table: Table,
+        entries: impl IntoIterator<Item = (&'a [u8], &'a [u8])>,
+    ) -> anyhow::Result<()> {
+        let mut tx = self.db.begin_write()?;
+        tx.set_durability(durability(table));
+        {
+            let mut table = tx.open_table(table)?;
+            for (key, value) in entries {
+                table.insert(key, value)?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn delete(&self, table: Table, key: &[u8]) -> anyhow::Result<()> {
+        let mut tx = self.db.begin_write()?;
+        tx.set_durability(durability(table));
+        tx.open_table(table)?.remove(key)?;
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+/// Only settings are worth an `fsync` per commit, see the module documentation.
+fn durability(table: Table) -> Durability {
+    if table.name() == SETTINGS.name() {
+        Durability::Immediate
+    } else {
+        Durability::Eventual
+    }
+}
+
+impl SettingsStore for RedbDb {
+    fn read_bin(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        self.read(SETTINGS, key.as_bytes())
+    }
+
+    fn write_bin(&self, key: &str, value: &[u8]) -> anyhow::Result<()> {
+        self.write(SETTINGS, [(key.as_bytes(), value)])
+    }
+
+    fn exists(&self, key: &str) -> anyhow::Result<bool> {
+        Ok(self.read(SETTINGS, key.as_bytes())?.is_some())
+    }
+
+    fn setting_keys(&self) -> anyhow::Result<Vec<String>> {
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(SETTINGS)?;
+        let mut keys = vec![];
+        for entry in table.iter()? {
+            let (key, _) = entry?;
+            keys.push(String::from_utf8(key.value().to_vec())?);
+        }
+        Ok(keys)
+    }
+}
+
+impl TipsetStateStore for RedbDb {
+    fn read_tipset_state(&self, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        self.read(TIPSET_STATES, key)
+    }
+
+    fn write_tipset_state(&self, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        self.write(TIPSET_STATES, [(key, value)])
+    }
+
+    fn delete_tipset_state(&self, key: &[u8]) -> anyhow::Result<()> {
+        self.delete(TIPSET_STATES, key)
+    }
+}
+
+impl ReplaceBlock for RedbDb {
+    fn replace_block(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        self.put_keyed(k, block)
+    }
+}
+
+impl Blockstore for RedbDb {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        self.read(BLOCKS, &k.to_bytes())
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        self.write(BLOCKS, [(k.to_bytes().as_slice(), block)])
+    }
+
+    fn put_many_keyed<D, I>(&self, blocks: I) -> anyhow::Result<()>
+    where
+        Self: Sized,
+        D: AsRef<[u8]>,
+        I: IntoIterator<Item = (Cid, D)>,
+    {
+        // All blocks are written in a single transaction
+        let blocks = blocks
+            .into_iter()
+            .map(|(k, v)| (k.to_bytes(), v))
+            .collect::<Vec<_>>();
+        self.write(
+            BLOCKS,
+            blocks.iter().map(|(k, v)| (k.as_slice(), v.as_ref())),
+        )
+    }
+}
+
+impl BitswapStoreRead for RedbDb {
+    fn contains(&self, cid: &Cid) -> anyhow::Result<bool> {
+        Ok(self.read(BLOCKS, &cid.to_bytes())?.is_some())
+    }
+
+    fn get(&self, cid: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        Blockstore::get(self, cid)
+    }
+}
+
+impl BitswapStoreReadWrite for RedbDb {
+    type Params = libipld::DefaultParams;
+
+    fn insert(&self, block: &libipld::Block<Self::Params>) -> anyhow::Result<()> {
+        self.put_keyed(block.cid(), block.data())
+    }
+}
+
+impl DBStatistics for RedbDb {}
+
+impl DbBackend for RedbDb {
+    fn open(path: &Path, _config: &DbConfig) -> anyhow::Result<Self> {
+        RedbDb::open(path)
+    }
+
+    fn for_each_block(
+        &self,
+        f: &mut dyn FnMut(Cid, Vec<u8>) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(BLOCKS)?;
+        for entry in table.iter()? {
+            let (key, value) = entry?;
+            f(Cid::try_from(key.value())?, value.value().to_vec())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tests::subtests;
+    use crate::utils::cid::CidCborExt as _;
+
+    #[test]
+    fn settings_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = RedbDb::open(dir.path()).unwrap();
+        subtests::write_bin(&db);
+        subtests::read_bin(&db);
+        subtests::exists(&db);
+        subtests::does_not_exist(&db);
+        subtests::write_read_obj(&db);
+        assert!(RedbDb::exists(dir.path()));
+    }
+
+    #[test]
+    fn tipset_states() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = RedbDb::open(dir.path()).unwrap();
+        db.write_tipset_state(b"tipset", b"state").unwrap();
+        assert_eq!(
+            db.read_tipset_state(b"tipset").unwrap(),
+            Some(b"state".to_vec())
+        );
+        db.delete_tipset_state(b"tipset").unwrap();
+        assert_eq!(db.read_tipset_state(b"tipset").unwrap(), None);
+    }
+
+    #[test]
+    fn eventual_writes_persist_with_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        let block = b"block".as_slice();
+        let cid = Cid::from_cbor_blake2b256(&block).unwrap();
+        {
+            let db = RedbDb::open(dir.path()).unwrap();
+            db.put_keyed(&cid, block).unwrap();
+            db.write_tipset_state(b"tipset", b"state").unwrap();
+            db.write_bin("head", b"tipset").unwrap();
+        }
+        let db = RedbDb::open(dir.path()).unwrap();
+        assert_eq!(Blockstore::get(&db, &cid).unwrap(), Some(block.to_vec()));
+        assert_eq!(
+            db.read_tipset_state(b"tipset").unwrap(),
+            Some(b"state".to_vec())
+        );
+    }
+}
@@ -42,7 +42,14 @@
 //! 1. GC is triggered automatically when total DB size is greater than `2x` of
 //! the last reachable data size
 //! 2. GC can be triggered manually by `forest-cli db gc` command
-//! 3. There's a global GC lock to ensure at most one GC job is running
+//! 3. There's a global GC lock to ensure at most one GC job is running, and
+//! that no GC job runs during a `forest-cli db backup`
+//!
+//! ## Usage analytics
+//! While walking the reachable data, each GC job classifies it with a
+//! [`UsageSampler`], and the sample is recorded in the usage history. As the
+//! `old` DB space is still in place, the sample includes the garbage that is
+//! about to be collected
 //!
 //! ## Performance
 //! GC performance is typically `1x-1.5x` of `snapshot export`, depending on
@@ -68,6 +75,8 @@
 //! ```
 
 use crate::blocks::Tipset;
+use crate::chain::usage::{record_usage_sample, DbUsageSample, UsageSampler};
+use crate::db::backup::backup;
 use crate::db::setting_keys::ESTIMATED_RECORDS_KEY;
 use crate::db::SettingsStoreExt;
 use crate::ipld::util::*;
@@ -77,6 +86,7 @@ use chrono::Utc;
 use fvm_ipld_blockstore::Blockstore;
 use human_repr::HumanCount;
 use std::{
+    path::PathBuf,
     sync::atomic::{self, AtomicU64, AtomicUsize},
     time::Duration,
 };
@@ -95,6 +105,8 @@ where
     lock: Mutex<()>,
     gc_tx: flume::Sender<flume::Sender<anyhow::Result<()>>>,
     gc_rx: flume::Receiver<flume::Sender<anyhow::Result<()>>>,
+    backup_tx: flume::Sender<(PathBuf, flume::Sender<anyhow::Result<()>>)>,
+    backup_rx: flume::Receiver<(PathBuf, flume::Sender<anyhow::Result<()>>)>,
     last_reachable_bytes: AtomicU64,
 }
 
@@ -109,6 +121,7 @@ where
         get_tipset: F,
     ) -> Self {
         let (gc_tx, gc_rx) = flume::unbounded();
+        let (backup_tx, backup_rx) = flume::unbounded();
 
         Self {
             db,
@@ -118,6 +131,8 @@ where
             lock: Default::default(),
             gc_tx,
             gc_rx,
+            backup_tx,
+            backup_rx,
             last_reachable_bytes: AtomicU64::new(0),
         }
     }
@@ -126,6 +141,10 @@ where
         self.gc_tx.clone()
     }
 
+    pub fn get_backup_tx(&self) -> flume::Sender<(PathBuf, flume::Sender<anyhow::Result<()>>)> {
+        self.backup_tx.clone()
+    }
+
     /// This loop automatically triggers `collect_once` when the total DB size
     /// is greater than `2x` of the last reachable data size
     pub async fn collect_loop_passive(&self) -> anyhow::Result<()> {
@@ -185,6 +204,32 @@ where
         Ok(())
     }
 
+    /// This loop listens on events emitted by `forest-cli db backup` and backs
+    /// up the database to the given directory
+    pub async fn backup_loop_event(self: &Arc<Self>) -> anyhow::Result<()> {
+        info!("Listening on database backup events");
+        while let Ok((dir, responder)) = self.backup_rx.recv_async().await {
+            let this = self.clone();
+            tokio::spawn(async move {
+                let result = this.backup_once(dir).await;
+                if let Err(e) = responder.send(result) {
+                    warn!("{e}");
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Backs up the database while holding the GC lock, so that the DB spaces
+    /// aren't rotated or deleted until the backup is complete
+    async fn backup_once(&self, dir: PathBuf) -> anyhow::Result<()> {
+        let _guard = self.lock.lock().await;
+        let db = self.db.writer().clone();
+        tokio::task::spawn_blocking(move || backup(&db, &dir)).await??;
+        Ok(())
+    }
+
     /// ## GC workflow
     /// 1. Walk back from the current heaviest tipset to the genesis block,
     /// collect all the blocks that are reachable from the snapshot
@@ -204,6 +249,7 @@ where
     /// collection only contains immutable or finalized part of the chain,
     /// from which all block data that is marked as unreachable will not
     /// become reachable because of the chain being mutated later.
+    #[tracing::instrument(skip_all)]
     async fn collect_once(&self) -> anyhow::Result<()> {
         let tipset = (self.get_tipset)();
 
@@ -216,6 +262,16 @@ where
             anyhow::bail!("Another garbage collection task is in progress.");
         }
 
+        // Usage is sampled along the way, before the `old` DB space is deleted
+        let db_bytes = self.db.writer().total_size_in_bytes();
+        let sampler = match UsageSampler::new(&self.db, &tipset) {
+            Ok(sampler) => Some(Arc::new(sampler)),
+            Err(e) => {
+                warn!("Sampling database usage failed: {e}");
+                None
+            }
+        };
+
         let start = Utc::now();
         let reachable_bytes = Arc::new(AtomicUsize::new(0));
 
@@ -236,10 +292,14 @@ where
                 let db = db.clone();
                 let tx = tx.clone();
                 let reachable_bytes = reachable_bytes.clone();
+                let sampler = sampler.clone();
                 async move {
                     let block = db
                         .get(&cid)?
                         .with_context(|| format!("Cid {cid} not found in blockstore"))?;
+                    if let Some(sampler) = sampler {
+                        sampler.add(&cid, &block);
+                    }
 
                     let pair = (cid, block.clone());
                     if db.writer().has(&cid)? {
@@ -270,6 +330,13 @@ where
         let reachable_bytes = reachable_bytes.load(atomic::Ordering::Relaxed);
         self.last_reachable_bytes
             .store(reachable_bytes as _, atomic::Ordering::Relaxed);
+        if let Some(sampler) = sampler {
+            if let Err(e) = db_bytes.and_then(|db_bytes| {
+                self.record_usage(sampler.finish(db_bytes, reachable_bytes as _))
+            }) {
+                warn!("Sampling database usage failed: {e}");
+            }
+        }
         info!(
             "Garbage collection finished at epoch {}, took {}s, paritydb reachable data size: {}",
             tipset.epoch(),
@@ -282,6 +349,17 @@ where
 
         Ok(())
     }
+
+    /// Records a usage sample in the usage history
+    fn record_usage(&self, sample: DbUsageSample) -> anyhow::Result<()> {
+        info!(
+            "Database usage at epoch {}: reachable data size: {}, unreachable data size: {}",
+            sample.epoch,
+            sample.reachable_total().bytes.human_count_bytes(),
+            sample.unreachable_bytes.human_count_bytes(),
+        );
+        record_usage_sample(self.db.writer().as_ref(), sample)
+    }
 }
 
 fn gc_trigger_factor() -> f64 {
@@ -70,6 +70,24 @@ impl Blockstore for RollingDB {
     }
 }
 
+impl ReplaceBlock for RollingDB {
+    /// Replaces the block in every generation that has it, so that no
+    /// generation keeps a corrupt copy.
+    fn replace_block(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        let mut replaced = false;
+        for db in self.db_queue() {
+            if Blockstore::has(&db, k)? {
+                ReplaceBlock::replace_block(&db, k, block)?;
+                replaced = true;
+            }
+        }
+        if !replaced {
+            Blockstore::put_keyed(&self.current(), k, block)?;
+        }
+        Ok(())
+    }
+}
+
 impl SettingsStore for RollingDB {
     fn read_bin(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
         for db in self.db_queue() {
@@ -104,6 +122,29 @@ impl SettingsStore for RollingDB {
     }
 }
 
+impl TipsetStateStore for RollingDB {
+    fn read_tipset_state(&self, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        for db in self.db_queue() {
+            if let Some(v) = TipsetStateStore::read_tipset_state(db.as_ref(), key)? {
+                return Ok(Some(v));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn write_tipset_state(&self, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        TipsetStateStore::write_tipset_state(self.current.read().as_ref(), key, value)
+    }
+
+    fn delete_tipset_state(&self, key: &[u8]) -> anyhow::Result<()> {
+        for db in self.db_queue() {
+            TipsetStateStore::delete_tipset_state(db.as_ref(), key)?;
+        }
+        Ok(())
+    }
+}
+
 impl BitswapStoreRead for RollingDB {
     fn contains(&self, cid: &Cid) -> anyhow::Result<bool> {
         for db in self.db_queue() {
@@ -218,6 +259,16 @@ impl RollingDB {
         self.current.read().clone()
     }
 
+    /// Directory of the database, named after its version for versioned
+    /// databases.
+    pub fn db_root(&self) -> &Path {
+        &self.db_root
+    }
+
+    pub fn db_config(&self) -> &DbConfig {
+        &self.db_config
+    }
+
     fn db_queue(&self) -> [Arc<Db>; 2] {
         [self.current.read().clone(), self.old.read().clone()]
     }
@@ -16,6 +16,8 @@ mod metrics;
 mod validation;
 mod weight;
 
+pub use weight::WeightBreakdown;
+
 #[derive(Debug, Error)]
 pub enum FilecoinConsensusError {
     #[error("Block must have an election proof included in tipset")]
@@ -93,3 +95,10 @@ where
 {
     weight::weight(&Arc::new(db), ts).map_err(|s| anyhow!(s))
 }
+
+pub fn weight_breakdown<DB>(db: &DB, ts: &Tipset) -> Result<WeightBreakdown, anyhow::Error>
+where
+    DB: Blockstore,
+{
+    weight::weight_breakdown(&Arc::new(db), ts).map_err(|s| anyhow!(s))
+}
@@ -18,9 +18,40 @@ const W_RATIO_DEN: u64 = 2;
 /// Blocks epoch allowed
 const BLOCKS_PER_EPOCH: u64 = 5;
 
+/// The components of the weight of a [Tipset], which add up to its total
+/// weight.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WeightBreakdown {
+    /// Weight of the parent tipset
+    pub parent_weight: BigInt,
+    /// Weight added for the total network power, `log2(power) << 8`
+    pub power: BigInt,
+    /// Weight added for the election wins of the blocks in the tipset
+    pub election: BigInt,
+    /// Number of election wins of the blocks in the tipset
+    pub win_count: i64,
+}
+
+impl WeightBreakdown {
+    pub fn total(&self) -> BigInt {
+        &self.parent_weight + &self.power + &self.election
+    }
+}
+
 /// Returns the weight of provided [Tipset]. This function will load power actor
 /// state and calculate the total weight of the [Tipset].
 pub(in crate::fil_cns) fn weight<DB>(db: &Arc<DB>, ts: &Tipset) -> Result<BigInt, String>
+where
+    DB: Blockstore,
+{
+    weight_breakdown(db, ts).map(|breakdown| breakdown.total())
+}
+
+/// Returns the components of the weight of provided [Tipset].
+pub(in crate::fil_cns) fn weight_breakdown<DB>(
+    db: &Arc<DB>,
+    ts: &Tipset,
+) -> Result<WeightBreakdown, String>
 where
     DB: Blockstore,
 {
@@ -54,12 +85,15 @@ where
             .win_count;
     }
 
-    let mut out = ts.weight().to_owned();
-    out += &log2_p << 8;
+    let power = &log2_p << 8;
     let mut e_weight: BigInt = log2_p * W_RATIO_NUM;
     e_weight <<= 8;
     e_weight *= total_j;
     e_weight = e_weight.div_floor(&(BigInt::from(BLOCKS_PER_EPOCH * W_RATIO_DEN)));
-    out += &e_weight;
-    Ok(out)
+    Ok(WeightBreakdown {
+        parent_weight: ts.weight().to_owned(),
+        power,
+        election: e_weight,
+        win_count: total_j,
+    })
 }
@@ -7,11 +7,9 @@ use std::ops::SubAssign;
 
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
-#[cfg(test)]
 use Selector::*;
 
 pub use self::walk::*;
-#[cfg(test)]
 use super::Ipld;
 
 /// Selectors are expressions that identify and select a subset of data from an
@@ -201,7 +199,6 @@ pub enum Condition {
     Or,
 }
 
-#[cfg(test)]
 impl Selector {
     /// Processes and returns resultant selector node
     pub fn explore(self, ipld: &Ipld, p: &str) -> Option<Selector> {
@@ -308,7 +305,6 @@ impl Selector {
     }
 }
 
-#[cfg(test)]
 fn replace_recursive_edge(next_sel: Selector, replace: Option<Selector>) -> Option<Selector> {
     match next_sel {
         ExploreRecursiveEdge => replace,
@@ -324,7 +320,6 @@ fn replace_recursive_edge(next_sel: Selector, replace: Option<Selector>) -> Opti
         _ => Some(next_sel),
     }
 }
-#[cfg(test)]
 fn has_recursive_edge(next_sel: &Selector) -> bool {
     match next_sel {
         ExploreRecursiveEdge { .. } => true,
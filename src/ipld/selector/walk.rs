@@ -1,10 +1,18 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::collections::VecDeque;
+
+use ahash::{HashMap, HashSet};
 use async_trait::async_trait;
 use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::{DAG_CBOR, IPLD_RAW};
 
 use super::super::{Ipld, Path};
+use super::Selector;
+use crate::utils::db::car_stream::CarBlock;
+use crate::utils::encoding::from_slice_with_fallback;
 
 #[async_trait]
 pub trait LinkResolver {
@@ -26,3 +34,122 @@ pub struct LastBlockInfo {
     pub path: Path,
     pub link: Cid,
 }
+
+/// Blocks covered by a selector, see [`walk_covered_blocks`]. Blocks are read
+/// from the block store as the walk reaches them, so only their CIDs are kept
+/// in memory.
+pub struct CoveredBlocks<'a, DB> {
+    db: &'a DB,
+    /// Blocks that have been loaded but not yielded yet
+    ready: VecDeque<CarBlock>,
+    /// Blocks that have been yielded
+    yielded: HashSet<Cid>,
+    /// Blocks may be reached with different selectors, each of which has to
+    /// be applied
+    explored: HashMap<Cid, Vec<Selector>>,
+    stack: Vec<(Ipld, Selector)>,
+    missing: Vec<Cid>,
+}
+
+/// Walk the blocks that are traversed (the "covered" set) when `selector` is
+/// applied to the DAG rooted at `root`, in the order they are reached. Links to
+/// missing blocks are not followed, see [`CoveredBlocks::missing`].
+pub fn walk_covered_blocks<DB: Blockstore>(
+    db: &DB,
+    root: Cid,
+    selector: Selector,
+) -> anyhow::Result<CoveredBlocks<'_, DB>> {
+    let mut covered = CoveredBlocks {
+        db,
+        ready: VecDeque::new(),
+        yielded: HashSet::default(),
+        explored: HashMap::default(),
+        stack: vec![],
+        missing: vec![],
+    };
+    covered.load(root, selector)?;
+    Ok(covered)
+}
+
+impl<DB: Blockstore> CoveredBlocks<'_, DB> {
+    /// Links to blocks that aren't in the block store, among the blocks walked
+    /// so far.
+    pub fn missing(&self) -> &[Cid] {
+        &self.missing
+    }
+
+    fn load(&mut self, cid: Cid, selector: Selector) -> anyhow::Result<()> {
+        let selectors = self.explored.entry(cid).or_default();
+        if selectors.contains(&selector) {
+            return Ok(());
+        }
+        selectors.push(selector.clone());
+        match self.db.get(&cid)? {
+            Some(data) => {
+                self.stack.push((decode_block(&cid, &data)?, selector));
+                if self.yielded.insert(cid) {
+                    self.ready.push_back(CarBlock { cid, data });
+                }
+            }
+            None if !self.missing.contains(&cid) => self.missing.push(cid),
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Apply the next selector on the stack, loading the blocks it links to.
+    fn explore_next(&mut self) -> anyhow::Result<bool> {
+        let Some((ipld, selector)) = self.stack.pop() else {
+            return Ok(false);
+        };
+        let children: Vec<(String, &Ipld)> = match &ipld {
+            Ipld::Map(map) => map
+                .iter()
+                .map(|(key, value)| (key.clone(), value))
+                .collect(),
+            Ipld::List(list) => list
+                .iter()
+                .enumerate()
+                .map(|(index, value)| (index.to_string(), value))
+                .collect(),
+            _ => return Ok(true),
+        };
+        for (segment, child) in children {
+            let Some(next) = selector.clone().explore(&ipld, &segment) else {
+                continue;
+            };
+            match child {
+                Ipld::Link(cid) => self.load(*cid, next)?,
+                _ => self.stack.push((child.clone(), next)),
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl<DB: Blockstore> Iterator for CoveredBlocks<'_, DB> {
+    type Item = anyhow::Result<CarBlock>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(block) = self.ready.pop_front() {
+                return Some(Ok(block));
+            }
+            match self.explore_next() {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Decode a block into IPLD. Blocks of other codecs than `DAG-CBOR` and raw
+/// are opaque.
+fn decode_block(cid: &Cid, data: &[u8]) -> anyhow::Result<Ipld> {
+    Ok(match cid.codec() {
+        DAG_CBOR => from_slice_with_fallback(data)?,
+        IPLD_RAW => Ipld::Bytes(data.to_vec()),
+        _ => Ipld::Null,
+    })
+}
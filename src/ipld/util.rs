@@ -210,6 +210,48 @@ fn should_save_block_to_snapshot(cid: Cid) -> bool {
     }
 }
 
+/// Returns the links of a state root or of a node of the actors HAMT, and
+/// separately the links of the actors in the node to their state.
+///
+/// A state root is either a `[version, actors, info]` tuple or, for version 0,
+/// the root node of the actors HAMT. HAMT nodes are `[bitfield, pointers]`
+/// tuples, where pointers are either links to child nodes or buckets of
+/// `[address, actor]` pairs. Actors are `[code, state, sequence, balance, ..]`
+/// tuples.
+fn actor_level_links(data: &[u8]) -> anyhow::Result<(Vec<Cid>, Vec<Cid>)> {
+    let Ipld::List(fields) = from_slice_with_fallback::<Ipld>(data)? else {
+        return Ok(Default::default());
+    };
+    match fields.as_slice() {
+        [Ipld::Integer(_), Ipld::Link(actors), Ipld::Link(info)] => {
+            Ok((vec![*actors, *info], vec![]))
+        }
+        [Ipld::Bytes(_), Ipld::List(pointers)] => {
+            let mut nodes = vec![];
+            let mut actor_states = vec![];
+            for pointer in pointers {
+                match pointer {
+                    Ipld::Link(cid) => nodes.push(*cid),
+                    Ipld::List(bucket) => {
+                        for entry in bucket {
+                            if let Ipld::List(entry) = entry {
+                                if let [_, Ipld::List(actor)] = entry.as_slice() {
+                                    if let Some(Ipld::Link(state)) = actor.get(1) {
+                                        actor_states.push(*state);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            Ok((nodes, actor_states))
+        }
+        _ => Ok(Default::default()),
+    }
+}
+
 /// Depth-first-search iterator for `ipld` leaf nodes.
 ///
 /// This iterator consumes the given `ipld` structure and returns leaf nodes (i.e.,
@@ -276,6 +318,10 @@ enum Task {
     Emit(Cid),
     // Visit all the elements, recursively.
     Iterate(VecDeque<Cid>),
+    // Visit the state root and the nodes of its actors HAMT, collecting the
+    // state of the actors in the visited nodes, then visit the collected
+    // actor states recursively.
+    IterateActors(VecDeque<Cid>, VecDeque<Cid>),
 }
 
 pin_project! {
@@ -286,6 +332,8 @@ pin_project! {
         seen: CidHashSet,
         stateroot_limit: ChainEpoch,
         fail_on_dead_links: bool,
+        state_light: bool,
+        full_state_walked: bool,
     }
 }
 
@@ -294,6 +342,20 @@ impl<DB, T> ChainStream<DB, T> {
         ChainStream { seen, ..self }
     }
 
+    /// Only walk the full state of the first tipset and of genesis. For the
+    /// other tipsets within the `stateroot_limit`, the actors HAMT is walked
+    /// first, then the state of the actors found in the HAMT nodes which
+    /// changed. As nodes shared with newer state roots are only streamed once,
+    /// only the HAMT nodes which changed, and the subtrees of the actors whose
+    /// state differs from the next newer state root, are streamed for these
+    /// tipsets.
+    pub fn with_state_light(self, state_light: bool) -> Self {
+        ChainStream {
+            state_light,
+            ..self
+        }
+    }
+
     #[allow(dead_code)]
     pub fn into_seen(self) -> CidHashSet {
         self.seen
@@ -323,6 +385,8 @@ pub fn stream_chain<DB: Blockstore, T: Iterator<Item = Tipset> + Unpin>(
         seen: CidHashSet::default(),
         stateroot_limit,
         fail_on_dead_links: true,
+        state_light: false,
+        full_state_walked: false,
     }
 }
 
@@ -340,6 +404,8 @@ pub fn stream_graph<DB: Blockstore, T: Iterator<Item = Tipset> + Unpin>(
         seen: CidHashSet::default(),
         stateroot_limit,
         fail_on_dead_links: false,
+        state_light: false,
+        full_state_walked: false,
     }
 }
 
@@ -398,6 +464,34 @@ impl<DB: Blockstore, T: Iterator<Item = Tipset> + Unpin> Stream for ChainStream<
                         }
                         this.dfs.pop_front();
                     }
+                    IterateActors(cid_vec, actor_states) => {
+                        while let Some(cid) = cid_vec.pop_front() {
+                            if should_save_block_to_snapshot(cid) && this.seen.insert(cid) {
+                                if let Some(data) = this.db.get(&cid)? {
+                                    if cid.codec() == fvm_ipld_encoding::DAG_CBOR {
+                                        let (new_values, states) = actor_level_links(&data)?;
+                                        cid_vec.reserve(new_values.len());
+
+                                        for v in new_values.into_iter().rev() {
+                                            cid_vec.push_front(v)
+                                        }
+                                        // Actor states already seen are those
+                                        // of a newer state root
+                                        actor_states.extend(states);
+                                    }
+                                    return Poll::Ready(Some(Ok(CarBlock { cid, data })));
+                                } else if *this.fail_on_dead_links {
+                                    return Poll::Ready(Some(Err(anyhow::anyhow!(
+                                        "missing key: {}",
+                                        cid
+                                    ))));
+                                }
+                            }
+                        }
+                        let actor_states = std::mem::take(actor_states);
+                        this.dfs.pop_front();
+                        this.dfs.push_front(Iterate(actor_states));
+                    }
                 }
             }
 
@@ -429,16 +523,24 @@ impl<DB: Blockstore, T: Iterator<Item = Tipset> + Unpin> Stream for ChainStream<
                         // Visit the block if it's within required depth. And a special case for `0`
                         // epoch to match Lotus' implementation.
                         if block.epoch() == 0 || block.epoch() > stateroot_limit {
-                            // NOTE: In the original `walk_snapshot` implementation we walk the dag
-                            // immediately. Which is what we do here as well, but using a queue.
-                            this.dfs.push_back(Iterate(
-                                DfsIter::from(*block.state_root())
-                                    .filter_map(ipld_to_cid)
-                                    .collect(),
-                            ));
+                            if *this.state_light && *this.full_state_walked && block.epoch() != 0 {
+                                this.dfs.push_back(IterateActors(
+                                    VecDeque::from([*block.state_root()]),
+                                    VecDeque::new(),
+                                ));
+                            } else {
+                                // NOTE: In the original `walk_snapshot` implementation we walk the dag
+                                // immediately. Which is what we do here as well, but using a queue.
+                                this.dfs.push_back(Iterate(
+                                    DfsIter::from(*block.state_root())
+                                        .filter_map(ipld_to_cid)
+                                        .collect(),
+                                ));
+                            }
                         }
                     }
                 }
+                *this.full_state_walked = true;
             } else {
                 // That's it, nothing else to do. End of stream.
                 return Poll::Ready(None);
@@ -702,3 +804,134 @@ impl<DB: Blockstore + Send + Sync + 'static, T: Iterator<Item = Tipset> + Unpin>
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryDB;
+    use crate::shim::{
+        address::Address,
+        econ::TokenAmount,
+        state_tree::{ActorState, StateTree, StateTreeVersion},
+    };
+    use crate::utils::db::CborStoreExt as _;
+    use futures::TryStreamExt as _;
+    use fvm_ipld_encoding::CborStore as _;
+
+    // Writes a state tree of actors `1000`, `1001`, ... with the given states
+    fn state_root(db: &Arc<MemoryDB>, actor_states: &[Cid]) -> Cid {
+        let mut tree = StateTree::new(db.clone(), StateTreeVersion::V5).unwrap();
+        for (id, state) in actor_states.iter().enumerate() {
+            let actor = ActorState::new(*state, *state, TokenAmount::from_atto(id), 0, None);
+            tree.set_actor(&Address::new_id(1000 + id as u64), actor)
+                .unwrap();
+        }
+        tree.flush().unwrap()
+    }
+
+    // Writes the state of an actor, linking to a node of its own
+    fn actor_state(db: &MemoryDB, name: &str) -> Cid {
+        let node = db.put_cbor_default(&format!("{name} node")).unwrap();
+        db.put_cbor_default(&(name, node)).unwrap()
+    }
+
+    #[test]
+    fn actor_level_links_keep_actors_only() {
+        let db = Arc::new(MemoryDB::default());
+        let actor_state = db.put_cbor_default(&"actor state").unwrap();
+        let mut tree = StateTree::new(db.clone(), StateTreeVersion::V5).unwrap();
+        let addresses = (1000..1200).map(Address::new_id).collect::<Vec<_>>();
+        for (sequence, address) in addresses.iter().enumerate() {
+            let actor = ActorState::new(
+                actor_state,
+                actor_state,
+                TokenAmount::from_atto(sequence),
+                sequence as u64,
+                None,
+            );
+            tree.set_actor(address, actor).unwrap();
+        }
+        let root = tree.flush().unwrap();
+
+        // Copy the blocks reachable through actor-level links only
+        let light = Arc::new(MemoryDB::default());
+        let mut seen = CidHashSet::default();
+        let mut queue = VecDeque::from([root]);
+        while let Some(cid) = queue.pop_front() {
+            if !seen.insert(cid) {
+                continue;
+            }
+            let data = db.get(&cid).unwrap().unwrap();
+            let (nodes, actor_states) = actor_level_links(&data).unwrap();
+            queue.extend(nodes);
+            assert!(actor_states.iter().all(|state| *state == actor_state));
+            light.put_keyed(&cid, &data).unwrap();
+        }
+        assert!(!light.has(&actor_state).unwrap());
+        // The HAMT is deeper than its root node
+        assert!(seen.len() > 3);
+
+        let tree = StateTree::new_from_root(light, &root).unwrap();
+        for (sequence, address) in addresses.iter().enumerate() {
+            let actor = tree.get_actor(address).unwrap().unwrap();
+            assert_eq!(actor.sequence, sequence as u64);
+        }
+    }
+
+    #[tokio::test]
+    async fn state_light_streams_changed_actors() {
+        let db = Arc::new(MemoryDB::default());
+        let unchanged = actor_state(&db, "unchanged");
+        let states = ["genesis", "older", "head"].map(|name| actor_state(&db, name));
+        let mut parent: Option<Tipset> = None;
+        for (epoch, state) in states.iter().enumerate() {
+            let parents = parent
+                .as_ref()
+                .map(|parent| parent.key().clone())
+                .unwrap_or_default();
+            let header = BlockHeader::builder()
+                .miner_address(Address::new_id(0))
+                .parents(parents)
+                .epoch(epoch as ChainEpoch)
+                .state_root(state_root(&db, &[unchanged, *state]))
+                .build()
+                .unwrap();
+            db.put_cbor_default(&header).unwrap();
+            parent = Some(Tipset::from(header));
+        }
+        let head = parent.unwrap();
+
+        let blocks: Vec<CarBlock> = stream_graph(db.clone(), head.clone().chain(db.clone()), 0)
+            .with_state_light(true)
+            .try_collect()
+            .await
+            .unwrap();
+        let exported = Arc::new(MemoryDB::default());
+        for block in &blocks {
+            exported.put_keyed(&block.cid, &block.data).unwrap();
+        }
+        // Blocks are only streamed once
+        assert_eq!(
+            blocks.len(),
+            blocks
+                .iter()
+                .map(|block| block.cid)
+                .collect::<CidHashSet>()
+                .len()
+        );
+        // The state of the actor which changed is available at every epoch,
+        // down to its own nodes
+        for tipset in head.chain(&exported) {
+            let tree = StateTree::new_from_root(exported.clone(), tipset.parent_state()).unwrap();
+            let actor = tree.get_actor(&Address::new_id(1001)).unwrap().unwrap();
+            let (_, node) = exported
+                .get_cbor::<(String, Cid)>(&actor.state)
+                .unwrap()
+                .unwrap();
+            assert!(exported.has(&node).unwrap());
+            let actor = tree.get_actor(&Address::new_id(1000)).unwrap().unwrap();
+            assert_eq!(actor.state, unchanged);
+        }
+        assert!(exported.has(&unchanged).unwrap());
+    }
+}
@@ -151,8 +151,8 @@ pub struct TipsetBundle {
     /// The blocks in the tipset.
     pub blocks: Vec<BlockHeader>,
 
-    /// Compressed messages format.
-    pub messages: Option<CompactedMessages>,
+    /// Compressed messages format. Shared, so that servers can cache them.
+    pub messages: Option<Arc<CompactedMessages>>,
 }
 
 impl TryFrom<TipsetBundle> for Tipset {
@@ -176,6 +176,7 @@ impl TryFrom<TipsetBundle> for CompactedMessages {
 
     fn try_from(tsb: TipsetBundle) -> Result<Self, Self::Error> {
         tsb.messages
+            .map(|messages| Arc::try_unwrap(messages).unwrap_or_else(|m| m.as_ref().clone()))
             .ok_or_else(|| "Request contained no messages".to_string())
     }
 }
@@ -184,7 +185,7 @@ impl TryFrom<TipsetBundle> for FullTipset {
     type Error = String;
 
     fn try_from(tsb: TipsetBundle) -> Result<FullTipset, Self::Error> {
-        fts_from_bundle_parts(tsb.blocks, tsb.messages.as_ref())
+        fts_from_bundle_parts(tsb.blocks, tsb.messages.as_deref())
     }
 }
 
@@ -192,7 +193,7 @@ impl TryFrom<&TipsetBundle> for FullTipset {
     type Error = String;
 
     fn try_from(tsb: &TipsetBundle) -> Result<FullTipset, Self::Error> {
-        fts_from_bundle_parts(tsb.blocks.clone(), tsb.messages.as_ref())
+        fts_from_bundle_parts(tsb.blocks.clone(), tsb.messages.as_deref())
     }
 }
 
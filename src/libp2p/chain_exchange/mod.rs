@@ -4,9 +4,14 @@
 mod behaviour;
 mod message;
 mod provider;
+mod server;
 pub use behaviour::*;
 
-pub use self::{message::*, provider::*};
+pub use self::{
+    message::*,
+    provider::*,
+    server::{ChainExchangeServer, ChainExchangeServerConfig},
+};
 use super::rpc::CborRequestResponse;
 
 /// Libp2p protocol name for `ChainExchange`.
@@ -6,6 +6,7 @@ use crate::chain::{ChainStore, Error as ChainError};
 use ahash::{HashMap, HashMapExt};
 use cid::Cid;
 use fvm_ipld_blockstore::Blockstore;
+use std::sync::Arc;
 use tracing::debug;
 
 use super::{
@@ -21,13 +22,50 @@ pub fn make_chain_exchange_response<DB>(
 where
     DB: Blockstore + Send + Sync + 'static,
 {
+    chain_exchange_response(
+        request,
+        None,
+        |tsk| cs.tipset_from_keys(tsk),
+        |tipset| Ok((tipset_bundle(cs.blockstore(), tipset, request)?, 0)),
+    )
+}
+
+/// Builds chain exchange response out of the chain data in a block store, such
+/// as a CAR file which is not part of the chain store.
+pub fn make_chain_exchange_response_from_store<DB>(
+    db: &DB,
+    request: &ChainExchangeRequest,
+) -> ChainExchangeResponse
+where
+    DB: Blockstore,
+{
+    chain_exchange_response(
+        request,
+        None,
+        |tsk| Ok(Arc::new(Tipset::load_required(db, tsk)?)),
+        |tipset| Ok((tipset_bundle(db, tipset, request)?, 0)),
+    )
+}
+
+/// Builds chain exchange response with the given tipset and bundle loaders.
+/// `load_bundle` also returns the encoded size of the bundle, which is only
+/// used with `max_response_bytes`. Once the tipsets exceed
+/// `max_response_bytes`, the remaining ones are left out of the response. The
+/// first tipset is always included, so that large tipsets can still be
+/// fetched.
+pub(super) fn chain_exchange_response(
+    request: &ChainExchangeRequest,
+    max_response_bytes: Option<usize>,
+    load_tipset: impl Fn(&TipsetKeys) -> Result<Arc<Tipset>, ChainError>,
+    load_bundle: impl Fn(&Tipset) -> Result<(TipsetBundle, usize), ChainError>,
+) -> ChainExchangeResponse {
     let mut response_chain: Vec<TipsetBundle> = Vec::with_capacity(request.request_len as usize);
 
     let mut curr_tipset_cids = request.start.clone();
+    let mut response_bytes = 0;
 
     loop {
-        let mut tipset_bundle: TipsetBundle = TipsetBundle::default();
-        let tipset = match cs.tipset_from_keys(&TipsetKeys::from_iter(curr_tipset_cids)) {
+        let tipset = match load_tipset(&TipsetKeys::from_iter(curr_tipset_cids)) {
             Ok(tipset) => tipset,
             Err(err) => {
                 debug!("Cannot get tipset from keys: {}", err);
@@ -40,29 +78,27 @@ where
             }
         };
 
-        if request.include_messages() {
-            match compact_messages(cs.blockstore(), &tipset) {
-                Ok(compacted_messages) => tipset_bundle.messages = Some(compacted_messages),
-                Err(err) => {
-                    debug!("Cannot compact messages for tipset: {}", err);
-
-                    return ChainExchangeResponse {
-                        chain: vec![],
-                        status: ChainExchangeResponseStatus::InternalError,
-                        message: "Can not fulfil the request".into(),
-                    };
-                }
+        let (tipset_bundle, bundle_bytes) = match load_bundle(&tipset) {
+            Ok(bundle) => bundle,
+            Err(err) => {
+                debug!("Cannot compact messages for tipset: {}", err);
+
+                return ChainExchangeResponse {
+                    chain: vec![],
+                    status: ChainExchangeResponseStatus::InternalError,
+                    message: "Can not fulfil the request".into(),
+                };
             }
-        }
+        };
 
         curr_tipset_cids = tipset.parents().cids.clone().into_iter().collect();
         let tipset_epoch = tipset.epoch();
 
-        if request.include_blocks() {
-            // Cloning blocks isn't ideal, this can maybe be switched to serialize this
-            // data in the function. This may not be possible without overriding rpc in
-            // libp2p
-            tipset_bundle.blocks = tipset.blocks().to_vec();
+        if let Some(max_response_bytes) = max_response_bytes {
+            response_bytes += bundle_bytes;
+            if response_bytes > max_response_bytes && !response_chain.is_empty() {
+                break;
+            }
         }
 
         response_chain.push(tipset_bundle);
@@ -85,6 +121,28 @@ where
     }
 }
 
+/// Builds the bundle of `tipset` with the parts included by `request`.
+pub(super) fn tipset_bundle<DB>(
+    db: &DB,
+    tipset: &Tipset,
+    request: &ChainExchangeRequest,
+) -> Result<TipsetBundle, ChainError>
+where
+    DB: Blockstore,
+{
+    let mut tipset_bundle = TipsetBundle::default();
+    if request.include_messages() {
+        tipset_bundle.messages = Some(Arc::new(compact_messages(db, tipset)?));
+    }
+    if request.include_blocks() {
+        // Cloning blocks isn't ideal, this can maybe be switched to serialize this
+        // data in the function. This may not be possible without overriding rpc in
+        // libp2p
+        tipset_bundle.blocks = tipset.blocks().to_vec();
+    }
+    Ok(tipset_bundle)
+}
+
 // Builds CompactedMessages for given Tipset.
 fn compact_messages<DB>(db: &DB, tipset: &Tipset) -> Result<CompactedMessages, ChainError>
 where
@@ -0,0 +1,319 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::{io, num::NonZeroUsize, sync::Arc, time::Instant};
+
+use crate::blocks::TipsetKeys;
+use crate::chain::ChainStore;
+use crate::libp2p::{metrics, PeerId};
+use fvm_ipld_blockstore::Blockstore;
+use lru::LruCache;
+use nonzero_ext::nonzero;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use super::{
+    provider::{chain_exchange_response, make_chain_exchange_response, tipset_bundle},
+    ChainExchangeRequest, ChainExchangeResponse, ChainExchangeResponseStatus, TipsetBundle,
+};
+
+/// Number of peers whose request budgets are tracked. Budgets of the least
+/// recently seen peers are forgotten, which resets them.
+const BUDGETS_CACHE_SIZE: NonZeroUsize = nonzero!(4096usize);
+
+/// Tipset bundles and their encoded sizes, by tipset and request options
+type BundleCache = LruCache<(TipsetKeys, u64), (TipsetBundle, usize)>;
+
+/// Limits on the chain exchange requests served to other peers.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+pub struct ChainExchangeServerConfig {
+    /// Number of requests a peer may send per minute, in bursts of at most as
+    /// many requests. `0` disables the limit.
+    pub requests_per_minute: u32,
+    /// Size of the tipsets of a response above which the remaining tipsets
+    /// are left out, in bytes. The first tipset is always sent. `0` disables
+    /// the limit.
+    #[cfg_attr(test, arbitrary(gen(|g| u32::arbitrary(g) as _)))]
+    pub max_response_bytes: u64,
+    /// Number of recently served tipset bundles which are cached. `0`
+    /// disables the cache.
+    #[cfg_attr(test, arbitrary(gen(|g| u32::arbitrary(g) as _)))]
+    pub cache_size: usize,
+}
+
+impl Default for ChainExchangeServerConfig {
+    fn default() -> Self {
+        Self {
+            requests_per_minute: 120,
+            max_response_bytes: 32 * 1024 * 1024,
+            cache_size: 1024,
+        }
+    }
+}
+
+/// Serves the chain exchange requests of other peers.
+pub struct ChainExchangeServer<DB> {
+    cs: Arc<ChainStore<DB>>,
+    config: ChainExchangeServerConfig,
+    budgets: Mutex<LruCache<PeerId, Budget>>,
+    /// Recently served tipset bundles and their encoded sizes, by tipset and
+    /// request options. Cached bundles share their messages with responses.
+    bundles: Option<Mutex<BundleCache>>,
+}
+
+/// Token bucket refilled at `requests_per_minute`
+struct Budget {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl<DB> ChainExchangeServer<DB>
+where
+    DB: Blockstore + Send + Sync + 'static,
+{
+    pub fn new(cs: Arc<ChainStore<DB>>, config: ChainExchangeServerConfig) -> Self {
+        Self {
+            cs,
+            bundles: NonZeroUsize::new(config.cache_size)
+                .map(|size| Mutex::new(LruCache::new(size))),
+            config,
+            budgets: Mutex::new(LruCache::new(BUDGETS_CACHE_SIZE)),
+        }
+    }
+
+    /// Builds the response to a request of `peer`, unless it exceeded its
+    /// request budget.
+    pub fn serve(&self, peer: PeerId, request: &ChainExchangeRequest) -> ChainExchangeResponse {
+        if !self.spend_budget(peer) {
+            debug!("Refusing chain exchange request of {peer}: budget exceeded");
+            metrics::CHAIN_EXCHANGE_SERVER_REQUESTS_TOTAL
+                .with_label_values(&[metrics::values::REFUSED])
+                .inc();
+            return ChainExchangeResponse {
+                chain: vec![],
+                status: ChainExchangeResponseStatus::GoAway,
+                message: "Request budget exceeded".into(),
+            };
+        }
+
+        let response = match (&self.bundles, self.max_response_bytes()) {
+            (None, None) => make_chain_exchange_response(&self.cs, request),
+            (_, max_response_bytes) => self.limited_response(request, max_response_bytes),
+        };
+        metrics::CHAIN_EXCHANGE_SERVER_REQUESTS_TOTAL
+            .with_label_values(&[match response.status {
+                ChainExchangeResponseStatus::Success => metrics::values::SERVED,
+                ChainExchangeResponseStatus::PartialResponse => metrics::values::PARTIAL,
+                _ => metrics::values::FAILED,
+            }])
+            .inc();
+        response
+    }
+
+    /// Builds the response to `request` out of the cached bundles, if any,
+    /// limited to `max_response_bytes`, if any.
+    fn limited_response(
+        &self,
+        request: &ChainExchangeRequest,
+        max_response_bytes: Option<usize>,
+    ) -> ChainExchangeResponse {
+        chain_exchange_response(
+            request,
+            max_response_bytes,
+            |tsk| self.cs.tipset_from_keys(tsk),
+            |tipset| {
+                let Some(cache) = &self.bundles else {
+                    return self.tipset_bundle(tipset, request);
+                };
+                let key = (tipset.key().clone(), request.options);
+                if let Some(bundle) = cache.lock().get(&key) {
+                    metrics::CHAIN_EXCHANGE_SERVER_CACHE_TOTAL
+                        .with_label_values(&[metrics::values::HIT])
+                        .inc();
+                    return Ok(bundle.clone());
+                }
+                metrics::CHAIN_EXCHANGE_SERVER_CACHE_TOTAL
+                    .with_label_values(&[metrics::values::MISS])
+                    .inc();
+                let bundle = self.tipset_bundle(tipset, request)?;
+                cache.lock().put(key, bundle.clone());
+                Ok(bundle)
+            },
+        )
+    }
+
+    /// Builds the bundle of `tipset` and measures its encoded size, if
+    /// responses are limited.
+    fn tipset_bundle(
+        &self,
+        tipset: &crate::blocks::Tipset,
+        request: &ChainExchangeRequest,
+    ) -> Result<(TipsetBundle, usize), crate::chain::Error> {
+        let bundle = tipset_bundle(self.cs.blockstore(), tipset, request)?;
+        let size = match self.max_response_bytes() {
+            Some(_) => encoded_len(&bundle),
+            None => 0,
+        };
+        Ok((bundle, size))
+    }
+
+    fn max_response_bytes(&self) -> Option<usize> {
+        match self.config.max_response_bytes {
+            0 => None,
+            bytes => Some(usize::try_from(bytes).unwrap_or(usize::MAX)),
+        }
+    }
+
+    /// Takes a request out of the budget of `peer`, returning whether it had
+    /// any left.
+    fn spend_budget(&self, peer: PeerId) -> bool {
+        if self.config.requests_per_minute == 0 {
+            return true;
+        }
+        let capacity = f64::from(self.config.requests_per_minute);
+        let now = Instant::now();
+        let mut budgets = self.budgets.lock();
+        let budget = budgets.get_or_insert_mut(peer, || Budget {
+            tokens: capacity,
+            updated: now,
+        });
+        let refill = now.duration_since(budget.updated).as_secs_f64() * capacity / 60.;
+        budget.tokens = (budget.tokens + refill).min(capacity);
+        budget.updated = now;
+        if budget.tokens >= 1. {
+            budget.tokens -= 1.;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Length of the CBOR encoding of `bundle`, measured without buffering it.
+fn encoded_len(bundle: &TipsetBundle) -> usize {
+    struct Counter(usize);
+    impl io::Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0 += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+    let mut counter = Counter(0);
+    let _ = fvm_ipld_encoding::to_writer(&mut counter, bundle);
+    counter.0
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::blocks::BlockHeader;
+    use crate::db::MemoryDB;
+    use crate::genesis::EXPORT_SR_40;
+    use crate::networks::ChainConfig;
+    use crate::shim::address::Address;
+    use crate::utils::db::car_util::load_car;
+
+    use super::{
+        super::{HEADERS, MESSAGES},
+        *,
+    };
+
+    async fn make_server(
+        config: ChainExchangeServerConfig,
+    ) -> (ChainExchangeRequest, ChainExchangeServer<MemoryDB>) {
+        let db = Arc::new(MemoryDB::default());
+        let header = load_car(&db, EXPORT_SR_40).await.unwrap();
+        let gen_block = BlockHeader::builder()
+            .miner_address(Address::new_id(0))
+            .build()
+            .unwrap();
+        let cs =
+            ChainStore::new(db.clone(), db, Arc::new(ChainConfig::default()), gen_block).unwrap();
+        let request = ChainExchangeRequest {
+            start: header.roots,
+            request_len: 10,
+            options: HEADERS | MESSAGES,
+        };
+        (request, ChainExchangeServer::new(Arc::new(cs), config))
+    }
+
+    #[tokio::test]
+    async fn refuses_requests_over_budget() {
+        let (request, server) = make_server(ChainExchangeServerConfig {
+            requests_per_minute: 2,
+            ..Default::default()
+        })
+        .await;
+        let (peer, other_peer) = (PeerId::random(), PeerId::random());
+        for _ in 0..2 {
+            assert_eq!(
+                server.serve(peer, &request).status,
+                ChainExchangeResponseStatus::Success
+            );
+        }
+        assert_eq!(
+            server.serve(peer, &request).status,
+            ChainExchangeResponseStatus::GoAway
+        );
+        assert_eq!(
+            server.serve(other_peer, &request).status,
+            ChainExchangeResponseStatus::Success
+        );
+    }
+
+    #[tokio::test]
+    async fn truncates_large_responses() {
+        let (request, server) = make_server(ChainExchangeServerConfig {
+            max_response_bytes: 1,
+            ..Default::default()
+        })
+        .await;
+        let response = server.serve(PeerId::random(), &request);
+        assert_eq!(
+            response.status,
+            ChainExchangeResponseStatus::PartialResponse
+        );
+        assert_eq!(response.chain.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn cached_responses_match() {
+        let (request, server) = make_server(Default::default()).await;
+        let (_, uncached) = make_server(ChainExchangeServerConfig {
+            cache_size: 0,
+            ..Default::default()
+        })
+        .await;
+        let first = server.serve(PeerId::random(), &request);
+        assert_eq!(first, server.serve(PeerId::random(), &request));
+        assert_eq!(first, uncached.serve(PeerId::random(), &request));
+        // Bundles are cached per request options
+        let headers = ChainExchangeRequest {
+            options: HEADERS,
+            ..request
+        };
+        assert!(server
+            .serve(PeerId::random(), &headers)
+            .chain
+            .iter()
+            .all(|bundle| bundle.messages.is_none()));
+    }
+
+    #[test]
+    fn encoded_len_matches_encoding() {
+        let bundle = TipsetBundle {
+            blocks: vec![crate::blocks::BlockHeader::default()],
+            messages: None,
+        };
+        assert_eq!(
+            encoded_len(&bundle),
+            fvm_ipld_encoding::to_vec(&bundle).unwrap().len()
+        );
+    }
+}
@@ -1,6 +1,7 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use super::chain_exchange::ChainExchangeServerConfig;
 use libp2p::Multiaddr;
 use serde::{Deserialize, Serialize};
 #[cfg(test)]
@@ -33,6 +34,8 @@ pub struct Libp2pConfig {
     pub kademlia: bool,
     /// Target peer count.
     pub target_peer_count: u32,
+    /// Limits on the chain exchange requests served to other peers.
+    pub chain_exchange: ChainExchangeServerConfig,
 }
 
 impl Default for Libp2pConfig {
@@ -43,6 +46,7 @@ impl Default for Libp2pConfig {
             mdns: false,
             kademlia: true,
             target_peer_count: 75,
+            chain_exchange: Default::default(),
         }
     }
 }
@@ -2,7 +2,9 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use once_cell::sync::Lazy;
-use prometheus::core::{AtomicU64, GenericCounter, GenericGauge};
+use prometheus::core::{
+    AtomicI64, AtomicU64, GenericCounter, GenericCounterVec, GenericGauge, GenericGaugeVec, Opts,
+};
 
 pub static PEER_FAILURE_TOTAL: Lazy<Box<GenericCounter<AtomicU64>>> = Lazy::new(|| {
     let peer_failure_total = Box::new(
@@ -40,3 +42,100 @@ pub static BAD_PEERS: Lazy<Box<GenericGauge<AtomicU64>>> = Lazy::new(|| {
         .expect("Registering the bad_peers metric with the metrics registry must succeed");
     bad_peers
 });
+pub static CHAIN_EXCHANGE_SERVER_REQUESTS_TOTAL: Lazy<Box<GenericCounterVec<AtomicU64>>> =
+    Lazy::new(|| {
+        let chain_exchange_server_requests_total = Box::new(
+            GenericCounterVec::<AtomicU64>::new(
+                Opts::new(
+                    "chain_exchange_server_requests_total",
+                    "Total number of chain exchange requests from other peers, by result",
+                ),
+                &[labels::RESULT],
+            )
+            .expect("Defining the chain_exchange_server_requests_total metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(chain_exchange_server_requests_total.clone())
+            .expect(
+                "Registering the chain_exchange_server_requests_total metric with the metrics registry must succeed",
+            );
+        chain_exchange_server_requests_total
+    });
+pub static CHAIN_EXCHANGE_SERVER_CACHE_TOTAL: Lazy<Box<GenericCounterVec<AtomicU64>>> = Lazy::new(
+    || {
+        let chain_exchange_server_cache_total = Box::new(
+            GenericCounterVec::<AtomicU64>::new(
+                Opts::new(
+                    "chain_exchange_server_cache_total",
+                    "Total number of lookups of served tipset messages in the chain exchange cache, by result",
+                ),
+                &[labels::RESULT],
+            )
+            .expect("Defining the chain_exchange_server_cache_total metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(chain_exchange_server_cache_total.clone())
+            .expect(
+                "Registering the chain_exchange_server_cache_total metric with the metrics registry must succeed",
+            );
+        chain_exchange_server_cache_total
+    },
+);
+pub static PEER_HEAD_EPOCH: Lazy<Box<GenericGaugeVec<AtomicI64>>> = Lazy::new(|| {
+    let peer_head_epoch = Box::new(
+        GenericGaugeVec::<AtomicI64>::new(
+            Opts::new(
+                "peer_head_epoch",
+                "Spread of the head epochs reported by peers",
+            ),
+            &[labels::STAT],
+        )
+        .expect("Defining the peer_head_epoch metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(peer_head_epoch.clone())
+        .expect("Registering the peer_head_epoch metric with the metrics registry must succeed");
+    peer_head_epoch
+});
+pub static EVICTED_PEERS_TOTAL: Lazy<Box<GenericCounterVec<AtomicU64>>> = Lazy::new(|| {
+    let evicted_peers_total = Box::new(
+        GenericCounterVec::<AtomicU64>::new(
+            Opts::new(
+                "evicted_peers_total",
+                "Total number of peers evicted, by reason",
+            ),
+            &[labels::REASON],
+        )
+        .expect("Defining the evicted_peers_total metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(evicted_peers_total.clone())
+        .expect(
+            "Registering the evicted_peers_total metric with the metrics registry must succeed",
+        );
+    evicted_peers_total
+});
+
+pub mod labels {
+    pub const RESULT: &str = "result";
+    pub const STAT: &str = "stat";
+    pub const REASON: &str = "reason";
+}
+
+pub mod values {
+    // chain_exchange_server_requests_total
+    pub const SERVED: &str = "served";
+    pub const PARTIAL: &str = "partial";
+    pub const REFUSED: &str = "refused";
+    pub const FAILED: &str = "failed";
+    // chain_exchange_server_cache_total
+    pub const HIT: &str = "hit";
+    pub const MISS: &str = "miss";
+    // peer_head_epoch
+    pub const MIN: &str = "min";
+    pub const MEDIAN: &str = "median";
+    pub const MAX: &str = "max";
+    // evicted_peers_total
+    pub const LAGGING: &str = "lagging";
+    pub const GENESIS_MISMATCH: &str = "genesis_mismatch";
+}
@@ -4,10 +4,12 @@
 use std::{
     cmp::Ordering,
     sync::Arc,
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime},
 };
 
 use crate::blocks::Tipset;
+use crate::rpc_api::node_api::PeerHeadSpread;
+use crate::shim::clock::ChainEpoch;
 use ahash::{HashMap, HashSet};
 use flume::{Receiver, Sender};
 use rand::seq::SliceRandom;
@@ -27,11 +29,20 @@ const LOCAL_INV_ALPHA: u32 = 5;
 /// Global duration multiplier, affects duration delta change.
 const GLOBAL_INV_ALPHA: u32 = 20;
 
+/// Duration for which peers on another network are banned.
+pub(in crate::libp2p) const BAN_PEER_DURATION: Duration = Duration::from_secs(60 * 60); //1h
+
 #[derive(Debug, Default)]
 /// Contains info about the peer's head [Tipset], as well as the request stats.
 struct PeerInfo {
     /// Head tipset received from hello message.
     head: Option<Arc<Tipset>>,
+    /// Epoch of the latest head reported by the peer, through hello messages
+    /// or gossip, and when it was reported.
+    reported_head: Option<(ChainEpoch, SystemTime)>,
+    /// Number of consecutive head probes the peer failed, and when it last
+    /// failed one. A peer that can't serve the head of this node is behind it.
+    failed_head_probes: (u32, Option<SystemTime>),
     /// Number of successful requests.
     successes: u32,
     /// Number of failed requests.
@@ -43,7 +54,9 @@ struct PeerInfo {
 impl PeerInfo {
     fn new(head: Arc<Tipset>) -> Self {
         Self {
+            reported_head: Some((head.epoch(), SystemTime::now())),
             head: Some(head),
+            failed_head_probes: (0, None),
             successes: 0,
             failures: 0,
             average_time: Default::default(),
@@ -97,6 +110,7 @@ impl PeerManager {
         let mut peers = self.peers.write().await;
         trace!("Updating head for PeerId {}", &peer_id);
         if let Some(pi) = peers.full_peers.get_mut(&peer_id) {
+            pi.reported_head = Some((ts.epoch(), SystemTime::now()));
             pi.head = Some(ts);
         } else {
             peers.full_peers.insert(peer_id, PeerInfo::new(ts));
@@ -104,6 +118,125 @@ impl PeerManager {
         }
     }
 
+    /// Updates the head epoch reported by a peer, in a hello message or by
+    /// serving a tipset at that epoch. Peers which aren't in the set are only
+    /// added once their head tipset is known, see
+    /// [`PeerManager::update_peer_head`].
+    pub async fn update_peer_reported_head(&self, peer_id: PeerId, epoch: ChainEpoch) {
+        if let Some(info) = self.peers.write().await.full_peers.get_mut(&peer_id) {
+            info.reported_head = Some((epoch, SystemTime::now()));
+            info.failed_head_probes = (0, None);
+        }
+    }
+
+    /// Records that a peer failed to serve the head of this node, or returned
+    /// nothing for it.
+    pub async fn record_failed_head_probe(&self, peer_id: PeerId) {
+        if let Some(info) = self.peers.write().await.full_peers.get_mut(&peer_id) {
+            info.failed_head_probes = (info.failed_head_probes.0 + 1, Some(SystemTime::now()));
+        }
+    }
+
+    /// Returns the heaviest of the head tipsets of the peers.
+    pub async fn heaviest_peer_head(&self) -> Option<Arc<Tipset>> {
+        self.peers
+            .read()
+            .await
+            .full_peers
+            .values()
+            .filter_map(|info| info.head.clone())
+            .max_by(|a, b| a.weight().cmp(b.weight()))
+    }
+
+    /// Returns the peers of the set.
+    pub async fn full_peers(&self) -> Vec<PeerId> {
+        self.peers.read().await.full_peers.keys().copied().collect()
+    }
+
+    /// Returns the latest head epochs reported by the peers.
+    pub async fn peer_head_epochs(&self) -> HashMap<PeerId, ChainEpoch> {
+        self.peers
+            .read()
+            .await
+            .full_peers
+            .iter()
+            .filter_map(|(peer, info)| Some((*peer, info.reported_head?.0)))
+            .collect()
+    }
+
+    /// Returns how the head epochs reported by the peers are spread, and
+    /// updates the corresponding metrics.
+    pub async fn head_spread(&self) -> PeerHeadSpread {
+        let spread = head_spread(self.peer_head_epochs().await.into_values().collect());
+        for (stat, epoch) in [
+            (metrics::values::MIN, spread.min),
+            (metrics::values::MEDIAN, spread.median),
+            (metrics::values::MAX, spread.max),
+        ] {
+            metrics::PEER_HEAD_EPOCH
+                .with_label_values(&[stat])
+                .set(epoch);
+        }
+        spread
+    }
+
+    /// Evicts the peers whose head, reported since `fresh_since`, lagged more
+    /// than `max_lag` epochs behind the `expected_epoch` of the network at
+    /// that time, and the peers which failed `max_failed_probes` consecutive
+    /// head probes, the last one since `fresh_since`. Peers whose last report
+    /// is older are otherwise kept, as their head may have moved on since.
+    pub async fn evict_lagging_peers(
+        &self,
+        max_lag: ChainEpoch,
+        max_failed_probes: u32,
+        fresh_since: SystemTime,
+        expected_epoch: impl Fn(SystemTime) -> ChainEpoch,
+    ) {
+        let lagging: Vec<_> = self
+            .peers
+            .read()
+            .await
+            .full_peers
+            .iter()
+            .filter_map(|(peer, info)| {
+                if let (failed, Some(failed_at)) = info.failed_head_probes {
+                    if failed >= max_failed_probes && failed_at >= fresh_since {
+                        return Some((*peer, format!("Failed {failed} consecutive head probes")));
+                    }
+                }
+                let (epoch, reported_at) = info.reported_head?;
+                if reported_at < fresh_since {
+                    return None;
+                }
+                let lag = expected_epoch(reported_at) - epoch;
+                (lag > max_lag).then(|| {
+                    (
+                        *peer,
+                        format!("Head lagging {lag} epochs behind the network"),
+                    )
+                })
+            })
+            .collect();
+        for (peer, message) in lagging {
+            self.evict_peer(peer, EvictionReason::Lagging, message)
+                .await;
+        }
+    }
+
+    /// Removes a peer from the set and, depending on the `reason`, bans it
+    /// for a while. Peers which aren't banned are added back once they report
+    /// a new head.
+    pub async fn evict_peer(&self, peer_id: PeerId, reason: EvictionReason, message: String) {
+        self.remove_peer(&peer_id).await;
+        metrics::EVICTED_PEERS_TOTAL
+            .with_label_values(&[reason.label()])
+            .inc();
+        match reason.ban_duration() {
+            Some(duration) => self.ban_peer(peer_id, message, Some(duration)).await,
+            None => debug!("evicted peer {peer_id}: {message}"),
+        }
+    }
+
     /// Returns true if peer is not marked as bad or not already in set.
     pub async fn is_peer_new(&self, peer_id: &PeerId) -> bool {
         let peers = self.peers.read().await;
@@ -311,7 +444,145 @@ fn log_time(info: &mut PeerInfo, dur: Duration) {
     }
 }
 
+/// Computes the spread of the head epochs of peers.
+fn head_spread(mut epochs: Vec<ChainEpoch>) -> PeerHeadSpread {
+    epochs.sort_unstable();
+    match (epochs.first(), epochs.last()) {
+        (Some(min), Some(max)) => PeerHeadSpread {
+            peers: epochs.len(),
+            min: *min,
+            median: epochs[epochs.len() / 2],
+            max: *max,
+        },
+        _ => PeerHeadSpread::default(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvictionReason {
+    /// The peer is far behind the network
+    Lagging,
+    /// The peer is on another network
+    GenesisMismatch,
+}
+
+impl EvictionReason {
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Lagging => metrics::values::LAGGING,
+            Self::GenesisMismatch => metrics::values::GENESIS_MISMATCH,
+        }
+    }
+
+    fn ban_duration(&self) -> Option<Duration> {
+        match self {
+            // The peer may catch up
+            Self::Lagging => None,
+            Self::GenesisMismatch => Some(BAN_PEER_DURATION),
+        }
+    }
+}
+
 pub enum PeerOperation {
     Ban(PeerId, String),
     Unban(PeerId),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tipset_at(epoch: ChainEpoch) -> Arc<Tipset> {
+        let header = crate::blocks::BlockHeader::builder()
+            .miner_address(crate::shim::address::Address::new_id(0))
+            .epoch(epoch)
+            .build()
+            .unwrap();
+        Arc::new(Tipset::from(header))
+    }
+
+    #[tokio::test]
+    async fn evicts_lagging_peers() {
+        let peer_manager = PeerManager::default();
+        let (in_sync, lagging, stale) = (PeerId::random(), PeerId::random(), PeerId::random());
+        // Unknown peers are only added along with their head
+        peer_manager.update_peer_reported_head(in_sync, 1000).await;
+        assert!(peer_manager.is_peer_new(&in_sync).await);
+        for peer in [in_sync, lagging, stale] {
+            peer_manager.update_peer_head(peer, tipset_at(0)).await;
+        }
+        let fresh_since = SystemTime::now();
+        peer_manager
+            .peers
+            .write()
+            .await
+            .full_peers
+            .get_mut(&stale)
+            .unwrap()
+            .reported_head = Some((700, fresh_since - Duration::from_secs(60)));
+        peer_manager.update_peer_reported_head(in_sync, 1000).await;
+        peer_manager.update_peer_reported_head(lagging, 800).await;
+        assert_eq!(
+            head_spread(
+                peer_manager
+                    .peer_head_epochs()
+                    .await
+                    .into_values()
+                    .collect()
+            ),
+            PeerHeadSpread {
+                peers: 3,
+                min: 700,
+                median: 800,
+                max: 1000,
+            }
+        );
+
+        peer_manager
+            .evict_lagging_peers(100, 3, fresh_since, |_| 1010)
+            .await;
+        assert!(!peer_manager.is_peer_new(&in_sync).await);
+        assert!(!peer_manager.is_peer_new(&stale).await);
+        assert!(peer_manager.is_peer_new(&lagging).await);
+        // Lagging peers may catch up, and aren't banned
+        assert!(peer_manager.peer_ban_list.read().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn evicts_peers_failing_head_probes() {
+        let peer_manager = PeerManager::default();
+        let (flaky, lagging) = (PeerId::random(), PeerId::random());
+        let fresh_since = SystemTime::now() - Duration::from_secs(60);
+        for peer in [flaky, lagging] {
+            peer_manager.update_peer_head(peer, tipset_at(1000)).await;
+        }
+        // Reports older than `fresh_since` don't get long-connected peers
+        // evicted on their own
+        for info in peer_manager.peers.write().await.full_peers.values_mut() {
+            info.reported_head = Some((1000, fresh_since - Duration::from_secs(60)));
+        }
+        let evict = || peer_manager.evict_lagging_peers(100, 3, fresh_since, |_| 2000);
+
+        for _ in 0..2 {
+            peer_manager.record_failed_head_probe(flaky).await;
+            peer_manager.record_failed_head_probe(lagging).await;
+            evict().await;
+        }
+        assert!(!peer_manager.is_peer_new(&flaky).await);
+        assert!(!peer_manager.is_peer_new(&lagging).await);
+
+        // A successful probe resets the count
+        peer_manager.update_peer_reported_head(flaky, 2000).await;
+        peer_manager.record_failed_head_probe(flaky).await;
+        peer_manager.record_failed_head_probe(lagging).await;
+        evict().await;
+        assert!(!peer_manager.is_peer_new(&flaky).await);
+        assert!(peer_manager.is_peer_new(&lagging).await);
+        assert!(peer_manager.peer_ban_list.read().await.is_empty());
+    }
+
+    #[test]
+    fn head_spread_of_no_peers() {
+        assert_eq!(head_spread(vec![]), PeerHeadSpread::default());
+    }
+}
@@ -10,7 +10,10 @@ use crate::libp2p_bitswap::{
     request_manager::BitswapRequestManager, BitswapStoreRead, BitswapStoreReadWrite,
 };
 use crate::message::SignedMessage;
-use crate::{blocks::GossipBlock, rpc_api::net_api::NetInfoResult};
+use crate::{
+    blocks::{GossipBlock, TipsetKeys},
+    rpc_api::net_api::NetInfoResult,
+};
 use crate::{chain::ChainStore, utils::encoding::from_slice_with_fallback};
 use ahash::{HashMap, HashSet};
 use anyhow::Context as _;
@@ -37,15 +40,16 @@ use tokio_stream::wrappers::IntervalStream;
 use tracing::{debug, error, info, trace, warn};
 
 use super::{
-    chain_exchange::{make_chain_exchange_response, ChainExchangeRequest, ChainExchangeResponse},
+    chain_exchange::{ChainExchangeRequest, ChainExchangeResponse, ChainExchangeServer},
     ForestBehaviour, ForestBehaviourEvent, Libp2pConfig,
 };
 use crate::libp2p::{
     chain_exchange::ChainExchangeBehaviour,
     discovery::DiscoveryEvent,
     hello::{HelloBehaviour, HelloRequest, HelloResponse},
+    peer_manager::BAN_PEER_DURATION,
     rpc::RequestResponseError,
-    PeerManager, PeerOperation,
+    EvictionReason, PeerManager, PeerOperation,
 };
 
 pub(in crate::libp2p) mod metrics {
@@ -89,8 +93,6 @@ const PUBSUB_TOPICS: [&str; 2] = [PUBSUB_BLOCK_STR, PUBSUB_MSG_STR];
 
 pub const BITSWAP_TIMEOUT: Duration = Duration::from_secs(10);
 
-const BAN_PEER_DURATION: Duration = Duration::from_secs(60 * 60); //1h
-
 /// Events emitted by this Service.
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
@@ -127,6 +129,8 @@ pub enum NetworkEvent {
     },
     PeerConnected(PeerId),
     PeerDisconnected(PeerId),
+    /// The head of a tipset source, for nodes syncing without libp2p peers
+    TipsetSourceHead(TipsetKeys),
 }
 
 /// Message types that can come over `GossipSub`
@@ -180,6 +184,7 @@ pub struct Libp2pService<DB> {
     config: Libp2pConfig,
     swarm: Swarm<ForestBehaviour>,
     cs: Arc<ChainStore<DB>>,
+    chain_exchange_server: Arc<ChainExchangeServer<DB>>,
     peer_manager: Arc<PeerManager>,
     network_receiver_in: flume::Receiver<NetworkMessage>,
     network_sender_in: Sender<NetworkMessage>,
@@ -226,6 +231,10 @@ where
         let (network_sender_out, network_receiver_out) = flume::unbounded();
 
         Ok(Libp2pService {
+            chain_exchange_server: Arc::new(ChainExchangeServer::new(
+                cs.clone(),
+                config.chain_exchange.clone(),
+            )),
             config,
             swarm,
             cs,
@@ -285,6 +294,7 @@ where
                             &self.peer_manager,
                             event,
                             &self.cs,
+                            &self.chain_exchange_server,
                             &self.genesis_cid,
                             &self.network_sender_out,
                             cx_response_tx.clone(),
@@ -343,6 +353,12 @@ where
     pub fn network_receiver(&self) -> flume::Receiver<NetworkEvent> {
         self.network_receiver_out.clone()
     }
+
+    /// Returns a sender which allows emitting network events which didn't
+    /// come from the service, such as the heads of tipset sources.
+    pub fn network_event_sender(&self) -> Sender<NetworkEvent> {
+        self.network_sender_out.clone()
+    }
 }
 
 fn handle_peer_ops(swarm: &mut Swarm<ForestBehaviour>, peer_ops: PeerOperation) {
@@ -578,16 +594,19 @@ async fn handle_hello_event(
                 trace!("Received hello request: {:?}", request);
                 if &request.genesis_cid != genesis_cid {
                     peer_manager
-                        .ban_peer(
+                        .evict_peer(
                             peer,
+                            EvictionReason::GenesisMismatch,
                             format!(
                                 "Genesis hash mismatch: {} received, {genesis_cid} expected",
                                 request.genesis_cid
                             ),
-                            Some(BAN_PEER_DURATION),
                         )
                         .await;
                 } else {
+                    peer_manager
+                        .update_peer_reported_head(peer, request.heaviest_tipset_height)
+                        .await;
                     let sent = SystemTime::now()
                         .duration_since(UNIX_EPOCH)
                         .expect("System time before unix epoch")
@@ -672,7 +691,7 @@ async fn handle_ping_event(ping_event: ping::Event, peer_manager: &Arc<PeerManag
 async fn handle_chain_exchange_event<DB>(
     chain_exchange: &mut ChainExchangeBehaviour,
     ce_event: request_response::Event<ChainExchangeRequest, ChainExchangeResponse>,
-    db: &Arc<ChainStore<DB>>,
+    server: &Arc<ChainExchangeServer<DB>>,
     network_sender_out: &Sender<NetworkEvent>,
     cx_response_tx: Sender<(
         RequestId,
@@ -696,13 +715,11 @@ async fn handle_chain_exchange_event<DB>(
                         NetworkEvent::ChainExchangeRequestInbound { request_id },
                     )
                     .await;
-                    let db = db.clone();
+                    let server = server.clone();
                     tokio::task::spawn(async move {
-                        if let Err(e) = cx_response_tx.send((
-                            request_id,
-                            channel,
-                            make_chain_exchange_response(&db, &request),
-                        )) {
+                        if let Err(e) =
+                            cx_response_tx.send((request_id, channel, server.serve(peer, &request)))
+                        {
                             debug!("Failed to send ChainExchangeResponse: {e:?}");
                         }
                     });
@@ -756,6 +773,7 @@ async fn handle_forest_behaviour_event<DB>(
     peer_manager: &Arc<PeerManager>,
     event: ForestBehaviourEvent,
     db: &Arc<ChainStore<DB>>,
+    chain_exchange_server: &Arc<ChainExchangeServer<DB>>,
     genesis_cid: &Cid,
     network_sender_out: &Sender<NetworkEvent>,
     cx_response_tx: Sender<(
@@ -802,7 +820,7 @@ async fn handle_forest_behaviour_event<DB>(
             handle_chain_exchange_event(
                 &mut swarm.behaviour_mut().chain_exchange,
                 ce_event,
-                db,
+                chain_exchange_server,
                 network_sender_out,
                 cx_response_tx,
             )
@@ -1,7 +1,7 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::convert::TryFrom;
+use std::{convert::TryFrom, sync::Arc};
 
 use crate::blocks::{Block, BlockHeader, FullTipset};
 use crate::libp2p::chain_exchange::{
@@ -27,12 +27,12 @@ fn convert_single_tipset_bundle() {
     };
     let bundle = TipsetBundle {
         blocks: vec![block.header.clone()],
-        messages: Some(CompactedMessages {
+        messages: Some(Arc::new(CompactedMessages {
             bls_msgs: Vec::new(),
             bls_msg_includes: vec![Vec::new()],
             secp_msgs: Vec::new(),
             secp_msg_includes: vec![Vec::new()],
-        }),
+        })),
     };
 
     let res = ChainExchangeResponse {
@@ -100,12 +100,12 @@ fn tipset_bundle_to_full_tipset() {
 
     let mut tsb = TipsetBundle {
         blocks: vec![h0, h1],
-        messages: Some(CompactedMessages {
+        messages: Some(Arc::new(CompactedMessages {
             secp_msgs: vec![sa, sb, sc, sd],
             secp_msg_includes: vec![vec![0, 1, 3], vec![1, 2, 0]],
             bls_msgs: vec![ua, ub, uc, ud],
             bls_msg_includes: vec![vec![0, 1], vec![2, 3]],
-        }),
+        })),
     };
 
     assert_eq!(
@@ -114,7 +114,7 @@ fn tipset_bundle_to_full_tipset() {
     );
 
     let mut cloned = tsb.clone();
-    if let Some(m) = cloned.messages.as_mut() {
+    if let Some(m) = cloned.messages.as_mut().map(Arc::make_mut) {
         m.secp_msg_includes = vec![vec![0, 4], vec![0]];
     }
     // Invalidate tipset bundle by having invalid index
@@ -123,7 +123,7 @@ fn tipset_bundle_to_full_tipset() {
         "Invalid index should return error"
     );
 
-    if let Some(m) = tsb.messages.as_mut() {
+    if let Some(m) = tsb.messages.as_mut().map(Arc::make_mut) {
         // Invalidate tipset bundle by not having includes same length as number of
         // blocks
         m.secp_msg_includes = vec![vec![0]];
@@ -5,8 +5,9 @@ use super::*;
 use crate::shim::{address::Address, econ::TokenAmount, state_tree::ActorState};
 use ::cid::Cid;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
+#[schemars(rename = "ActorState")]
 pub struct ActorStateLotusJson {
     code: LotusJson<Cid>,
     head: LotusJson<Cid>,
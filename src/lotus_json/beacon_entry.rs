@@ -5,8 +5,9 @@ use crate::beacon::BeaconEntry;
 
 use super::*;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
+#[schemars(rename = "BeaconEntry")]
 pub struct BeaconEntryLotusJson {
     round: LotusJson<u64>,
     data: LotusJson<Vec<u8>>,
@@ -5,8 +5,9 @@ use crate::blocks::{ElectionProof, VRFProof};
 
 use super::*;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
+#[schemars(rename = "ElectionProof")]
 pub struct ElectionProofLotusJson {
     v_r_f_proof: LotusJson<VRFProof>,
     win_count: LotusJson<i64>,
@@ -6,8 +6,9 @@ use super::*;
 use crate::blocks::{BlockHeader, GossipBlock};
 use ::cid::Cid;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
+#[schemars(rename = "GossipBlock")]
 pub struct GossipBlockLotusJson {
     header: LotusJson<BlockHeader>,
     bls_messages: LotusJson<Vec<Cid>>,
@@ -4,8 +4,9 @@ use super::*;
 
 use crate::{key_management::KeyInfo, shim::crypto::SignatureType};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
+#[schemars(rename = "KeyInfo")]
 pub struct KeyInfoLotusJson {
     r#type: LotusJson<SignatureType>,
     private_key: LotusJson<Vec<u8>>,
@@ -58,7 +58,7 @@
 //! - Implement [`serde::Serialize`] on that object, normally using [`serde_tuple::Serialize_tuple`].
 //!   This corresponds to the CBOR representation.
 //! - Implement [`HasLotusJson`] on the domain object.
-//!   This attaches a separate JSON type, which should implement (`#[derive(...)]`) [`serde::Serialize`] and [`serde::Deserialize`] AND conversions to and from the domain object
+//!   This attaches a separate JSON type, which should implement (`#[derive(...)]`) [`serde::Serialize`], [`serde::Deserialize`] and [`schemars::JsonSchema`] AND conversions to and from the domain object
 //!   E.g [`gossip_block`]
 //!
 //! Whenever you need the lotus JSON of an object, use the [`LotusJson`] wrapper.
@@ -122,15 +122,19 @@
 //! - use a derive macro for simple compound structs
 
 use derive_more::From;
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
 use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::json;
-use std::{fmt::Display, str::FromStr};
+use std::{borrow::Cow, fmt::Display, str::FromStr};
 #[cfg(test)]
 use {pretty_assertions::assert_eq, quickcheck::quickcheck};
 
 pub trait HasLotusJson: Sized {
-    /// The struct representing JSON. You should `#[derive(Deserialize, Serialize)]` on it.
-    type LotusJson: Serialize + DeserializeOwned;
+    /// The struct representing JSON. You should `#[derive(Deserialize, Serialize, JsonSchema)]` on it.
+    ///
+    /// The schema is published in the `OpenRPC` document, so name it after the
+    /// domain type, e.g `#[schemars(rename = "Message")]`.
+    type LotusJson: Serialize + DeserializeOwned + JsonSchema;
     /// To ensure code quality, conversion to/from lotus JSON MUST be tested.
     /// Provide snapshots of the JSON, and the domain type it should serialize to.
     ///
@@ -337,6 +341,24 @@ impl<T> LotusJson<T> {
     }
 }
 
+impl<T: HasLotusJson> JsonSchema for LotusJson<T> {
+    fn is_referenceable() -> bool {
+        T::LotusJson::is_referenceable()
+    }
+
+    fn schema_name() -> String {
+        T::LotusJson::schema_name()
+    }
+
+    fn schema_id() -> Cow<'static, str> {
+        T::LotusJson::schema_id()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        T::LotusJson::json_schema(gen)
+    }
+}
+
 impl<T> LotusJson<Option<T>> {
     // don't want to impl Deref<T> for LotusJson<T>
     pub fn is_none(&self) -> bool {
@@ -355,6 +377,20 @@ impl<T> Stringify<T> {
     }
 }
 
+impl<T> JsonSchema for Stringify<T> {
+    fn is_referenceable() -> bool {
+        false
+    }
+
+    fn schema_name() -> String {
+        String::schema_name()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        String::json_schema(gen)
+    }
+}
+
 macro_rules! lotus_json_with_self {
     ($($domain_ty:ty),* $(,)?) => {
         $(
@@ -6,8 +6,9 @@ use fvm_shared3::sector::PoStProof as PoStProofV3;
 
 use super::*;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
+#[schemars(rename = "PoStProof")]
 pub struct PoStProofLotusJson {
     po_st_proof: LotusJson<RegisteredPoStProof>,
     proof_bytes: LotusJson<Vec<u8>>,
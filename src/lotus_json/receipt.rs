@@ -7,8 +7,9 @@ use fvm_ipld_encoding::RawBytes;
 use super::*;
 use crate::shim::executor::Receipt;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
+#[schemars(rename = "Receipt")]
 pub struct ReceiptLotusJson {
     exit_code: LotusJson<u32>,
     r#return: LotusJson<RawBytes>,
@@ -5,8 +5,9 @@ use super::*;
 use crate::shim::sector::{RegisteredSealProof, SectorInfo};
 use ::cid::Cid;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
+#[schemars(rename = "SectorInfo")]
 pub struct SectorInfoLotusJson {
     seal_proof: LotusJson<RegisteredSealProof>,
     sector_number: LotusJson<u64>,
@@ -4,8 +4,9 @@
 use super::*;
 use crate::shim::crypto::{Signature, SignatureType};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
+#[schemars(rename = "Signature")]
 pub struct SignatureLotusJson {
     r#type: LotusJson<SignatureType>,
     data: LotusJson<Vec<u8>>,
@@ -4,12 +4,13 @@
 use super::*;
 use crate::shim::crypto::SignatureType;
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 #[serde(untagged)] // try an int, then a string
+#[schemars(rename = "SignatureType")]
 pub enum SignatureTypeLotusJson {
     // Lotus also accepts ints when deserializing - we need this for our test vectors
     // https://github.com/filecoin-project/lotus/blob/v1.23.3/chain/types/keystore.go#L47
-    Integer(SignatureType),
+    Integer(#[schemars(with = "u8")] SignatureType),
     String(Stringify<SignatureType>),
 }
 
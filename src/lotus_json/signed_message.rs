@@ -7,8 +7,9 @@ use ::cid::Cid;
 
 use super::*;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
+#[schemars(rename = "SignedMessage")]
 pub struct SignedMessageLotusJson {
     message: LotusJson<Message>,
     signature: LotusJson<Signature>,
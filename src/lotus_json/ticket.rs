@@ -5,8 +5,9 @@ use crate::blocks::{Ticket, VRFProof};
 
 use super::*;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
+#[schemars(rename = "Ticket")]
 pub struct TicketLotusJson {
     v_r_f_proof: LotusJson<VRFProof>,
 }
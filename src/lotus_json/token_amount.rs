@@ -5,8 +5,9 @@ use super::*;
 use crate::shim::econ::TokenAmount;
 use num::BigInt;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(transparent)] // name the field for clarity
+#[schemars(rename = "TokenAmount")]
 pub struct TokenAmountLotusJson {
     attos: LotusJson<BigInt>,
 }
@@ -36,6 +36,20 @@ quickcheck! {
     }
 }
 
+impl<T: JsonSchema> JsonSchema for VecLotusJson<T> {
+    fn is_referenceable() -> bool {
+        false
+    }
+
+    fn schema_name() -> String {
+        Option::<Vec<T>>::schema_name()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        Option::<Vec<T>>::json_schema(gen)
+    }
+}
+
 impl<T> Serialize for VecLotusJson<T>
 where
     T: Serialize,
@@ -6,11 +6,17 @@ use super::*;
 // This code looks odd so we can
 // - use #[serde(with = "...")]
 // - de/ser empty vecs as null
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[schemars(rename = "Bytes")]
 pub struct VecU8LotusJson(Option<Inner>);
 
-#[derive(Serialize, Deserialize)]
-struct Inner(#[serde(with = "base64_standard")] Vec<u8>);
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[schemars(rename = "Base64Bytes")]
+struct Inner(
+    #[serde(with = "base64_standard")]
+    #[schemars(with = "String")]
+    Vec<u8>,
+);
 
 impl HasLotusJson for Vec<u8> {
     type LotusJson = VecU8LotusJson;
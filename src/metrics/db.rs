@@ -2,21 +2,30 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use std::path::PathBuf;
+use std::sync::Arc;
 
+use crate::chain::usage::{usage_history, ObjectKind};
+use crate::db::SettingsStore;
 use prometheus::{
     core::{Collector, Desc},
-    proto, Gauge, Opts,
+    proto, Gauge, GaugeVec, Opts,
 };
 use tracing::error;
 
+use super::labels;
+
 pub struct DBCollector {
     db_directory: PathBuf,
+    settings: Arc<dyn SettingsStore + Send + Sync>,
     descs: Vec<Desc>,
     db_size: Gauge,
+    usage_bytes: GaugeVec,
+    usage_objects: GaugeVec,
+    usage_epoch: Gauge,
 }
 
 impl DBCollector {
-    pub fn new(db_directory: PathBuf) -> Self {
+    pub fn new(db_directory: PathBuf, settings: Arc<dyn SettingsStore + Send + Sync>) -> Self {
         let mut descs: Vec<Desc> = vec![];
         let db_size = Gauge::with_opts(Opts::new(
             "forest_db_size",
@@ -24,12 +33,80 @@ impl DBCollector {
         ))
         .expect("Creating forest_db_size gauge must succeed");
         descs.extend(db_size.desc().into_iter().cloned());
+        let usage_bytes = GaugeVec::new(
+            Opts::new(
+                "forest_db_usage_bytes",
+                "Size in bytes of the reachable data by kind and actor code CID, and of the unreachable data, as of the last usage sample",
+            ),
+            &[labels::KIND, labels::ACTOR],
+        )
+        .expect("Creating forest_db_usage_bytes gauge must succeed");
+        descs.extend(usage_bytes.desc().into_iter().cloned());
+        let usage_objects = GaugeVec::new(
+            Opts::new(
+                "forest_db_usage_objects",
+                "Number of reachable objects by kind and actor code CID, as of the last usage sample",
+            ),
+            &[labels::KIND, labels::ACTOR],
+        )
+        .expect("Creating forest_db_usage_objects gauge must succeed");
+        descs.extend(usage_objects.desc().into_iter().cloned());
+        let usage_epoch = Gauge::with_opts(Opts::new(
+            "forest_db_usage_epoch",
+            "Epoch of the head the last usage sample was taken from",
+        ))
+        .expect("Creating forest_db_usage_epoch gauge must succeed");
+        descs.extend(usage_epoch.desc().into_iter().cloned());
         Self {
             db_directory,
+            settings,
             descs,
             db_size,
+            usage_bytes,
+            usage_objects,
+            usage_epoch,
         }
     }
+
+    fn collect_usage(&self) -> Vec<proto::MetricFamily> {
+        let sample = match usage_history(self.settings.as_ref()) {
+            Ok(mut history) => match history.pop() {
+                Some(sample) => sample,
+                None => return vec![],
+            },
+            Err(e) => {
+                error!("Reading DB usage history for metrics failed: {:?}", e);
+                return vec![];
+            }
+        };
+
+        // Kinds and actors may disappear between samples
+        self.usage_bytes.reset();
+        self.usage_objects.reset();
+        for (kind, usage) in &sample.reachable {
+            let actor = match kind {
+                ObjectKind::ActorState(code) => code.to_string(),
+                _ => String::new(),
+            };
+            let label_values = [kind.label(), actor.as_str()];
+            self.usage_bytes
+                .with_label_values(&label_values)
+                .set(usage.bytes as f64);
+            self.usage_objects
+                .with_label_values(&label_values)
+                .set(usage.count as f64);
+        }
+        self.usage_bytes
+            .with_label_values(&["unreachable", ""])
+            .set(sample.unreachable_bytes as f64);
+        self.usage_epoch.set(sample.epoch as f64);
+
+        let mut metric_families = vec![];
+        metric_families.extend(self.usage_bytes.collect());
+        metric_families.extend(self.usage_objects.collect());
+        metric_families.extend(self.usage_epoch.collect());
+        metric_families
+    }
 }
 
 impl Collector for DBCollector {
@@ -50,6 +127,7 @@ impl Collector for DBCollector {
 
         let mut metric_families = vec![];
         metric_families.extend(self.db_size.collect());
+        metric_families.extend(self.collect_usage());
         metric_families
     }
 }
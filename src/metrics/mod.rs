@@ -3,12 +3,12 @@
 
 pub mod db;
 
-use crate::db::DBStatistics;
+use crate::db::{DBStatistics, SettingsStore};
 use ahash::{HashMap, HashMapExt};
 use axum::{http::StatusCode, response::IntoResponse, routing::get, Router};
 use once_cell::sync::Lazy;
 use prometheus::core::{AtomicU64, GenericCounterVec, Opts};
-use prometheus::{Encoder, TextEncoder};
+use prometheus::{Encoder, HistogramOpts, HistogramVec, TextEncoder};
 use std::sync::Arc;
 use std::{net::TcpListener, path::PathBuf};
 use tokio::sync::RwLock;
@@ -43,6 +43,33 @@ pub static LRU_CACHE_MISS: Lazy<Box<GenericCounterVec<AtomicU64>>> = Lazy::new(|
     lru_cache_miss
 });
 
+pub static RPC_METHOD_TIME: Lazy<Box<HistogramVec>> = Lazy::new(|| {
+    let rpc_method_time = Box::new(
+        HistogramVec::new(
+            HistogramOpts::new("rpc_method_time", "Duration of RPC method calls in seconds"),
+            &[labels::METHOD, labels::TRANSPORT],
+        )
+        .expect("Defining the rpc_method_time metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(rpc_method_time.clone())
+        .expect("Registering the rpc_method_time metric with the metrics registry must succeed");
+    rpc_method_time
+});
+pub static RPC_METHOD_FAILURE: Lazy<Box<GenericCounterVec<AtomicU64>>> = Lazy::new(|| {
+    let rpc_method_failure = Box::new(
+        GenericCounterVec::<AtomicU64>::new(
+            Opts::new("rpc_method_failure", "Number of failed RPC method calls"),
+            &[labels::METHOD, labels::TRANSPORT],
+        )
+        .expect("Defining the rpc_method_failure metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(rpc_method_failure.clone())
+        .expect("Registering the rpc_method_failure metric with the metrics registry must succeed");
+    rpc_method_failure
+});
+
 pub async fn add_metrics_registry(name: String, registry: prometheus_client::registry::Registry) {
     REGISTRIES_EXT.write().await.insert(name, registry);
 }
@@ -53,12 +80,12 @@ pub async fn init_prometheus<DB>(
     db: Arc<DB>,
 ) -> anyhow::Result<()>
 where
-    DB: DBStatistics + Send + Sync + 'static,
+    DB: DBStatistics + SettingsStore + Send + Sync + 'static,
 {
     let registry = prometheus::default_registry();
 
     // Add the DBCollector to the registry
-    let db_collector = crate::metrics::db::DBCollector::new(db_directory);
+    let db_collector = crate::metrics::db::DBCollector::new(db_directory, db.clone());
     registry.register(Box::new(db_collector))?;
 
     // Create an configure HTTP server
@@ -118,7 +145,10 @@ where
 }
 
 pub mod labels {
+    pub const ACTOR: &str = "actor";
     pub const KIND: &str = "kind";
+    pub const METHOD: &str = "method";
+    pub const TRANSPORT: &str = "transport";
 }
 
 pub mod values {
@@ -126,4 +156,8 @@ pub mod values {
     pub const TIPSET: &str = "tipset";
     /// tipset cache in state manager
     pub const STATE_MANAGER_TIPSET: &str = "sm_tipset";
+    /// RPC calls over HTTP.
+    pub const HTTP: &str = "http";
+    /// RPC calls over `WebSocket`.
+    pub const WS: &str = "ws";
 }
@@ -82,6 +82,7 @@ where
             recent_roots,
             VoidAsyncWriter,
             CidHashSet::default(),
+            false,
             skip_checksum,
         )
         .await
@@ -93,6 +94,7 @@ where
             recent_roots,
             file,
             CidHashSet::default(),
+            false,
             skip_checksum,
         )
         .await
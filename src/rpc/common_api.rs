@@ -5,6 +5,7 @@
 use crate::rpc_api::{
     common_api::*,
     data_types::{APIVersion, RPCState, Version},
+    openrpc::openrpc_document,
 };
 use fvm_ipld_blockstore::Blockstore;
 use jsonrpc_v2::{Data, Error as JsonRpcError};
@@ -39,3 +40,9 @@ pub(in crate::rpc) async fn start_time<DB: Blockstore>(
 ) -> Result<StartTimeResult, JsonRpcError> {
     Ok(data.start_time)
 }
+
+pub(in crate::rpc) async fn discover(
+    forest_version: &'static str,
+) -> Result<DiscoverResult, JsonRpcError> {
+    Ok(openrpc_document(forest_version))
+}
@@ -14,3 +14,13 @@ pub(in crate::rpc) async fn db_gc<DB: Blockstore>(
     rx.recv_async().await??;
     Ok(())
 }
+
+pub(in crate::rpc) async fn db_backup<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+    Params((dir,)): Params<DBBackupParams>,
+) -> Result<DBBackupResult, JsonRpcError> {
+    let (tx, rx) = flume::bounded(1);
+    data.db_backup_tx.send_async((dir, tx)).await?;
+    rx.recv_async().await??;
+    Ok(())
+}
@@ -18,7 +18,7 @@ mod state_api;
 mod sync_api;
 mod wallet_api;
 
-use std::{net::TcpListener, sync::Arc};
+use std::{net::TcpListener, sync::Arc, time::Duration};
 
 use crate::rpc_api::{
     auth_api::*, beacon_api::*, chain_api::*, common_api::*, data_types::RPCState, db_api::*,
@@ -33,8 +33,9 @@ use tracing::info;
 
 use crate::rpc::{
     beacon_api::beacon_get_entry,
-    common_api::{shutdown, start_time, version},
+    common_api::{discover, shutdown, start_time, version},
     rpc_http_handler::rpc_http_handler,
+    rpc_util::SlowCallThreshold,
     rpc_ws_handler::rpc_ws_handler,
     state_api::*,
 };
@@ -46,6 +47,7 @@ pub async fn start_rpc<DB>(
     rpc_endpoint: TcpListener,
     forest_version: &'static str,
     shutdown_send: Sender<()>,
+    slow_call_threshold: Option<Duration>,
 ) -> Result<(), JSONRPCError>
 where
     DB: Blockstore + Send + Sync + 'static,
@@ -90,6 +92,8 @@ where
             .with_method(SYNC_CHECK_BAD, sync_check_bad::<DB>)
             .with_method(SYNC_MARK_BAD, sync_mark_bad::<DB>)
             .with_method(SYNC_STATE, sync_state::<DB>)
+            .with_method(SYNC_FORK_TREE, sync_fork_tree::<DB>)
+            .with_method(SYNC_CHECKOUT, sync_checkout::<DB>)
             // Wallet API
             .with_method(WALLET_BALANCE, wallet_balance::<DB>)
             .with_method(WALLET_DEFAULT_ADDRESS, wallet_default_address::<DB>)
@@ -113,6 +117,10 @@ where
             .with_method(STATE_GET_RECEIPT, state_get_receipt::<DB>)
             .with_method(STATE_WAIT_MSG, state_wait_msg::<DB>)
             .with_method(STATE_FETCH_ROOT, state_fetch_root::<DB>)
+            .with_method(STATE_DIFF, state_diff::<DB>)
+            .with_method(STATE_LIST_MESSAGES, state_list_messages::<DB>)
+            .with_method(STATE_INDEX_MESSAGES, state_index_messages::<DB>)
+            .with_method(STATE_COMPUTE_RANGE, state_compute_range::<DB>)
             // Gas API
             .with_method(GAS_ESTIMATE_FEE_CAP, gas_estimate_fee_cap::<DB>)
             .with_method(GAS_ESTIMATE_GAS_LIMIT, gas_estimate_gas_limit::<DB>)
@@ -122,6 +130,7 @@ where
             .with_method(VERSION, move || version(block_delay, forest_version))
             .with_method(SHUTDOWN, move || shutdown(shutdown_send.clone()))
             .with_method(START_TIME, start_time::<DB>)
+            .with_method(DISCOVER, move || discover(forest_version))
             // Net API
             .with_method(NET_ADDRS_LISTEN, net_api::net_addrs_listen::<DB>)
             .with_method(NET_PEERS, net_api::net_peers::<DB>)
@@ -130,6 +139,7 @@ where
             .with_method(NET_DISCONNECT, net_api::net_disconnect::<DB>)
             // DB API
             .with_method(DB_GC, db_api::db_gc::<DB>)
+            .with_method(DB_BACKUP, db_api::db_backup::<DB>)
             // Progress API
             .with_method(GET_PROGRESS, progress_api::get_progress)
             // Node API
@@ -140,6 +150,7 @@ where
     let app = axum::Router::new()
         .route("/rpc/v0", get(rpc_ws_handler))
         .route("/rpc/v0", post(rpc_http_handler))
+        .layer(axum::Extension(SlowCallThreshold(slow_call_threshold)))
         .with_state(rpc_server);
 
     info!("Ready for RPC connections");
@@ -28,6 +28,7 @@ pub(in crate::rpc) async fn net_addrs_listen<DB: Blockstore>(
     Ok(AddrInfo {
         id: id.to_string(),
         addrs,
+        head_epoch: None,
     })
 }
 
@@ -41,10 +42,12 @@ pub(in crate::rpc) async fn net_peers<DB: Blockstore>(
 
     data.network_send.send_async(req).await?;
     let peer_addresses = rx.await?;
+    let head_epochs = data.peer_manager.peer_head_epochs().await;
 
     let connections = peer_addresses
         .into_iter()
         .map(|(id, addrs)| AddrInfo {
+            head_epoch: head_epochs.get(&id).copied(),
             id: id.to_string(),
             addrs,
         })
@@ -69,7 +72,7 @@ pub(in crate::rpc) async fn net_connect<DB: Blockstore>(
     data: Data<RPCState<DB>>,
     Params(params): Params<NetConnectParams>,
 ) -> Result<NetConnectResult, JsonRpcError> {
-    let (AddrInfo { id, addrs },) = params;
+    let (AddrInfo { id, addrs, .. },) = params;
     let (_, id) = multibase::decode(format!("{}{}", "z", id))?;
     let peer_id = PeerId::from_bytes(&id)?;
 
@@ -30,6 +30,7 @@ pub(in crate::rpc) async fn node_status<DB: Blockstore>(
 
     node_status.sync_status.epoch = head.epoch() as u64;
     node_status.sync_status.behind = behind;
+    node_status.peer_status.head_spread = data.peer_manager.head_spread().await;
 
     if head.epoch() > chain_finality {
         let mut block_count = 0;
@@ -1,16 +1,20 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use crate::metrics::values::HTTP;
 use crate::rpc_api::data_types::JsonRpcServerState;
 use axum::response::IntoResponse;
 use http::{HeaderMap, StatusCode};
 use jsonrpc_v2::RequestObject as JsonRpcRequestObject;
 
-use crate::rpc::rpc_util::{call_rpc_str, check_permissions, get_auth_header, is_streaming_method};
+use crate::rpc::rpc_util::{
+    call_rpc_str, check_permissions, get_auth_header, is_streaming_method, SlowCallThreshold,
+};
 
 pub async fn rpc_http_handler(
     headers: HeaderMap,
     axum::extract::State(rpc_server): axum::extract::State<JsonRpcServerState>,
+    axum::Extension(slow_call_threshold): axum::Extension<SlowCallThreshold>,
     axum::Json(rpc_call): axum::Json<JsonRpcRequestObject>,
 ) -> impl IntoResponse {
     let response_headers = [("content-type", "application/json-rpc;charset=utf-8")];
@@ -32,7 +36,7 @@ pub async fn rpc_http_handler(
         );
     }
 
-    match call_rpc_str(rpc_server.clone(), rpc_call).await {
+    match call_rpc_str(rpc_server.clone(), rpc_call, HTTP, slow_call_threshold).await {
         Ok(result) => (StatusCode::OK, response_headers, result),
         Err(err) => (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -1,10 +1,23 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::metrics;
 use crate::rpc_api::{auth_api::*, check_access, data_types::JsonRpcServerState, ACCESS_MAP};
 use http::{HeaderMap, HeaderValue, StatusCode};
 use serde::de::DeserializeOwned;
-use tracing::{debug, error};
+use serde_json::Value;
+use tracing::{debug, error, info_span, warn, Instrument as _};
+
+/// Calls taking longer than this are logged, with their parameters redacted.
+/// Disabled if `None`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SlowCallThreshold(pub Option<Duration>);
+
+/// Identifies a call in the logs, as the JSON-RPC `id` is chosen by the client.
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(0);
 
 pub fn get_error_obj(code: i64, message: String) -> jsonrpc_v2::Error {
     debug!(
@@ -85,14 +98,90 @@ pub fn get_auth_header(headers: HeaderMap) -> Option<HeaderValue> {
 }
 
 // Calls an RPC method and returns the full response as a string.
+// The call is timed per method and transport, and runs in a span carrying a
+// request ID.
 pub async fn call_rpc_str(
     rpc_server: JsonRpcServerState,
     rpc_request: jsonrpc_v2::RequestObject,
+    transport: &'static str,
+    SlowCallThreshold(slow_call_threshold): SlowCallThreshold,
 ) -> anyhow::Result<String> {
-    let rpc_subscription_response = rpc_server.handle(rpc_request).await;
+    let method = rpc_request.method_ref().to_owned();
+    let request_id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
+    let span = info_span!("rpc", request_id, method, transport);
+    // The request is consumed by the call, so keep the parameters in case it's slow
+    let params = slow_call_threshold.and_then(|_| {
+        serde_json::to_value(&rpc_request)
+            .ok()
+            .and_then(|mut it| it.get_mut("params").map(Value::take))
+    });
+
+    let start = Instant::now();
+    let rpc_subscription_response = rpc_server
+        .handle(rpc_request)
+        .instrument(span.clone())
+        .await;
+    let elapsed = start.elapsed();
+
+    metrics::RPC_METHOD_TIME
+        .with_label_values(&[&method, transport])
+        .observe(elapsed.as_secs_f64());
+    let failures = count_errors(&rpc_subscription_response);
+    if failures > 0 {
+        metrics::RPC_METHOD_FAILURE
+            .with_label_values(&[&method, transport])
+            .inc_by(failures);
+    }
+    if slow_call_threshold.map_or(false, |threshold| elapsed > threshold) {
+        span.in_scope(|| {
+            warn!(
+                "Slow RPC call took {elapsed:?}, params: {}",
+                redact(&params.unwrap_or_default())
+            )
+        });
+    }
+
     Ok(serde_json::to_string(&rpc_subscription_response)?)
 }
 
+/// Number of error responses, including the ones in a batch.
+fn count_errors(response: &jsonrpc_v2::ResponseObjects) -> u64 {
+    let is_error =
+        |it: &jsonrpc_v2::ResponseObject| matches!(it, jsonrpc_v2::ResponseObject::Error { .. });
+    match response {
+        jsonrpc_v2::ResponseObjects::One(it) => is_error(it).into(),
+        jsonrpc_v2::ResponseObjects::Many(them) => {
+            them.iter().filter(|it| is_error(it)).count() as u64
+        }
+        jsonrpc_v2::ResponseObjects::Empty => 0,
+    }
+}
+
+/// Replaces the values in RPC parameters by their types, keeping the shape
+/// (the number of parameters and object keys) so that logs don't leak
+/// addresses, messages or keys.
+fn redact(value: &Value) -> Value {
+    const MAX_ITEMS: usize = 8;
+    match value {
+        Value::Null => Value::Null,
+        Value::Bool(_) => "<bool>".into(),
+        Value::Number(_) => "<number>".into(),
+        Value::String(_) => "<string>".into(),
+        Value::Array(items) => {
+            let mut redacted: Vec<_> = items.iter().take(MAX_ITEMS).map(redact).collect();
+            if items.len() > MAX_ITEMS {
+                redacted.push(format!("<{} more>", items.len() - MAX_ITEMS).into());
+            }
+            Value::Array(redacted)
+        }
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, value)| (key.clone(), redact(value)))
+                .collect(),
+        ),
+    }
+}
+
 // Returns both the RPC response string and the result value in a tuple.
 pub async fn call_rpc<T>(
     rpc_server: JsonRpcServerState,
@@ -144,3 +233,42 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn count_errors_in_batches() {
+        let ok = || jsonrpc_v2::ResponseObject::Result {
+            jsonrpc: jsonrpc_v2::V2,
+            result: Box::new(()),
+            id: jsonrpc_v2::Id::Null,
+        };
+        let err = || get_error_res(1, "failed".into());
+        assert_eq!(count_errors(&jsonrpc_v2::ResponseObjects::Empty), 0);
+        assert_eq!(count_errors(&jsonrpc_v2::ResponseObjects::One(ok())), 0);
+        assert_eq!(count_errors(&jsonrpc_v2::ResponseObjects::One(err())), 1);
+        assert_eq!(
+            count_errors(&jsonrpc_v2::ResponseObjects::Many(vec![err(), ok(), err()])),
+            2
+        );
+    }
+
+    #[test]
+    fn redact_keeps_shape() {
+        assert_eq!(
+            redact(&json!([{ "/": "bafy2bzace" }, null, 42, [1, 2, 3, 4, 5, 6, 7, 8, 9, 10]])),
+            json!([
+                { "/": "<string>" },
+                null,
+                "<number>",
+                [
+                    "<number>", "<number>", "<number>", "<number>", "<number>", "<number>",
+                    "<number>", "<number>", "<2 more>"
+                ]
+            ])
+        );
+    }
+}
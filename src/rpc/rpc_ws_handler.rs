@@ -3,6 +3,7 @@
 
 use std::sync::Arc;
 
+use crate::metrics::values::WS;
 use crate::rpc_api::data_types::JsonRpcServerState;
 use axum::{
     extract::{
@@ -17,7 +18,9 @@ use http::{HeaderMap, HeaderValue};
 use tokio::sync::RwLock;
 use tracing::{debug, error, info, warn};
 
-use crate::rpc::rpc_util::{call_rpc_str, check_permissions, get_auth_header, get_error_str};
+use crate::rpc::rpc_util::{
+    call_rpc_str, check_permissions, get_auth_header, get_error_str, SlowCallThreshold,
+};
 
 async fn rpc_ws_task(
     authorization_header: Option<HeaderValue>,
@@ -25,6 +28,7 @@ async fn rpc_ws_task(
     rpc_server: JsonRpcServerState,
     _is_socket_active: Arc<AtomicCell<bool>>,
     ws_sender: Arc<RwLock<SplitSink<WebSocket, Message>>>,
+    slow_call_threshold: SlowCallThreshold,
 ) -> anyhow::Result<()> {
     let call_method = rpc_call.method_ref();
     let _call_id = rpc_call.id_ref();
@@ -34,7 +38,7 @@ async fn rpc_ws_task(
         .map_err(|(_, e)| anyhow::Error::msg(e))?;
 
     info!("RPC WS called method: {}", call_method);
-    let response = call_rpc_str(rpc_server.clone(), rpc_call).await?;
+    let response = call_rpc_str(rpc_server.clone(), rpc_call, WS, slow_call_threshold).await?;
     ws_sender
         .write()
         .await
@@ -47,11 +51,18 @@ async fn rpc_ws_task(
 pub async fn rpc_ws_handler(
     headers: HeaderMap,
     axum::extract::State(rpc_server): axum::extract::State<JsonRpcServerState>,
+    axum::Extension(slow_call_threshold): axum::Extension<SlowCallThreshold>,
     ws: WebSocketUpgrade,
 ) -> impl IntoResponse {
     let authorization_header = get_auth_header(headers);
-    ws.on_upgrade(move |socket| async {
-        rpc_ws_handler_inner(socket, authorization_header, rpc_server).await
+    ws.on_upgrade(move |socket| async move {
+        rpc_ws_handler_inner(
+            socket,
+            authorization_header,
+            rpc_server,
+            slow_call_threshold,
+        )
+        .await
     })
 }
 
@@ -59,6 +70,7 @@ async fn rpc_ws_handler_inner(
     socket: WebSocket,
     authorization_header: Option<HeaderValue>,
     rpc_server: JsonRpcServerState,
+    slow_call_threshold: SlowCallThreshold,
 ) {
     info!("Accepted WS connection!");
     let (sender, mut receiver) = socket.split();
@@ -85,6 +97,7 @@ async fn rpc_ws_handler_inner(
                                 task_rpc_server,
                                 task_socket_active,
                                 task_ws_sender.clone(),
+                                slow_call_threshold,
                             )
                             .await
                             {
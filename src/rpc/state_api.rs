@@ -2,12 +2,13 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 #![allow(clippy::unused_async)]
 
+use crate::chain::index::ResolveNullTipset;
 use crate::cid_collections::CidHashSet;
 use crate::ipld::json::IpldJson;
 use crate::libp2p::NetworkMessage;
 use crate::lotus_json::LotusJson;
 use crate::rpc_api::{
-    data_types::{MarketDeal, MessageLookup, RPCState},
+    data_types::{MarketDeal, MessageLookup, MessageMatch, RPCState},
     state_api::*,
 };
 use crate::shim::address::Address;
@@ -173,6 +174,81 @@ pub(in crate::rpc) async fn state_wait_msg<DB: Blockstore + Send + Sync + 'stati
     })
 }
 
+/// returns the actor-level changes between two state roots
+pub(in crate::rpc) async fn state_diff<DB: Blockstore + Send + Sync + 'static>(
+    data: Data<RPCState<DB>>,
+    Params(params): Params<StateDiffParams>,
+) -> Result<StateDiffResult, JsonRpcError> {
+    let (LotusJson(pre_root), LotusJson(post_root)) = params;
+    let db = data.state_manager.blockstore_owned();
+    Ok(tokio::task::spawn_blocking(move || {
+        crate::statediff::state_diff(&db, &pre_root, &post_root)
+    })
+    .await??)
+}
+
+/// returns the messages sent from and/or to an address, looking back from the
+/// given tipset down to `to_height`. Requires the message index.
+pub(in crate::rpc) async fn state_list_messages<DB: Blockstore + Send + Sync + 'static>(
+    data: Data<RPCState<DB>>,
+    Params(params): Params<StateListMessagesParams>,
+) -> Result<StateListMessagesResult, JsonRpcError> {
+    let (MessageMatch { to, from }, LotusJson(tsk), to_height) = params;
+    let msg_index = data
+        .msg_index
+        .clone()
+        .context("the message index is disabled")?;
+    let tipset = data.chain_store.tipset_from_keys(&tsk)?;
+    let cids = tokio::task::spawn_blocking(move || {
+        msg_index.list_messages(from.as_ref(), to.as_ref(), tipset, to_height)
+    })
+    .await??;
+    Ok(LotusJson(cids))
+}
+
+/// indexes the messages of the current chain between two epochs (inclusive)
+pub(in crate::rpc) async fn state_index_messages<DB: Blockstore + Send + Sync + 'static>(
+    data: Data<RPCState<DB>>,
+    Params(params): Params<StateIndexMessagesParams>,
+) -> Result<StateIndexMessagesResult, JsonRpcError> {
+    let (from, to) = params;
+    let msg_index = data
+        .msg_index
+        .clone()
+        .context("the message index is disabled")?;
+    let heaviest = data.chain_store.heaviest_tipset();
+    let head = data.chain_store.chain_index.tipset_by_height(
+        to.min(heaviest.epoch()),
+        heaviest,
+        ResolveNullTipset::TakeOlder,
+    )?;
+    Ok(tokio::task::spawn_blocking(move || msg_index.backfill(head, from)).await??)
+}
+
+pub(in crate::rpc) async fn state_compute_range<DB: Blockstore + Send + Sync + 'static>(
+    data: Data<RPCState<DB>>,
+    Params(params): Params<StateComputeRangeParams>,
+) -> Result<StateComputeRangeResult, JsonRpcError> {
+    let (from, to) = params;
+    let heaviest = data.chain_store.heaviest_tipset();
+    let head = data.chain_store.chain_index.tipset_by_height(
+        to.min(heaviest.epoch()),
+        heaviest,
+        ResolveNullTipset::TakeOlder,
+    )?;
+    let tipsets: Vec<_> = data
+        .chain_store
+        .chain_index
+        .chain(head)
+        .take_while(|ts| ts.epoch() >= from)
+        .collect();
+    // Oldest first, so that an interrupted backfill leaves a contiguous range
+    for ts in tipsets.iter().rev() {
+        data.state_manager.tipset_state(ts).await?;
+    }
+    Ok(tipsets.len())
+}
+
 // Sample CIDs (useful for testing):
 //   Mainnet:
 //     1,594,681 bafy2bzaceaclaz3jvmbjg3piazaq5dcesoyv26cdpoozlkzdiwnsvdvm2qoqm OhSnap upgrade
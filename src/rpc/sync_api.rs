@@ -2,12 +2,16 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 #![allow(clippy::unused_async)]
 
-use crate::chain_sync::SyncState;
+use crate::chain_sync::{CanonicalChain, SyncState};
+use crate::fil_cns;
 use crate::lotus_json::LotusJson;
 use crate::rpc_api::{
-    data_types::{RPCState, RPCSyncState},
+    data_types::{
+        ForkTreeBranch, ForkTreeBranchStatus, ForkTreeWeight, RPCForkTree, RPCState, RPCSyncState,
+    },
     sync_api::*,
 };
+use anyhow::Context as _;
 use fvm_ipld_blockstore::Blockstore;
 use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
 use parking_lot::RwLock;
@@ -53,6 +57,128 @@ where
     Ok(RPCSyncState { active_syncs })
 }
 
+/// Returns the tips of the forks recently received from the network, along
+/// with their weights and why they are not the head.
+pub(in crate::rpc) async fn sync_fork_tree<DB>(
+    data: Data<RPCState<DB>>,
+) -> Result<SyncForkTreeResult, JsonRpcError>
+where
+    DB: Blockstore,
+{
+    let chain_store = &data.chain_store;
+    let head = chain_store.heaviest_tipset();
+    let head_weight = fil_cns::weight(chain_store.blockstore(), &head).ok();
+    let chain_finality = data.state_manager.chain_config().policy.chain_finality;
+    let canonical = CanonicalChain::new(chain_store, head.clone(), head.epoch() - chain_finality);
+
+    let branches = data
+        .fork_tree
+        .tips()
+        .into_iter()
+        .map(|tip| {
+            let weight = fil_cns::weight_breakdown(chain_store.blockstore(), &tip).ok();
+            let reason = tip.cids().iter().find_map(|cid| data.bad_blocks.peek(cid));
+            let status = if tip.key() == head.key() {
+                ForkTreeBranchStatus::Head
+            } else if reason.is_some() {
+                ForkTreeBranchStatus::Rejected
+            } else if canonical.contains(&tip) {
+                ForkTreeBranchStatus::Canonical
+            } else if !tip
+                .cids()
+                .iter()
+                .all(|cid| chain_store.is_block_validated(cid))
+            {
+                ForkTreeBranchStatus::Unvalidated
+            } else {
+                match (&weight, &head_weight) {
+                    (Some(weight), Some(head_weight)) if &weight.total() > head_weight => {
+                        ForkTreeBranchStatus::Heavier
+                    }
+                    _ => ForkTreeBranchStatus::Lighter,
+                }
+            };
+            let fork_height = data
+                .fork_tree
+                .fork_point(chain_store, &canonical, tip.clone())
+                .map(|fork_point| fork_point.epoch());
+            ForkTreeBranch {
+                tip: tip.key().clone(),
+                height: tip.epoch(),
+                fork_height,
+                parent_weight: tip.weight().clone(),
+                weight: weight.map(|weight| ForkTreeWeight {
+                    total: weight.total(),
+                    power: weight.power,
+                    election: weight.election,
+                    win_count: weight.win_count,
+                }),
+                status,
+                reason,
+            }
+        })
+        .collect();
+
+    Ok(RPCForkTree {
+        head: head.key().clone(),
+        branches,
+    })
+}
+
+/// Switches to the branch of the given tipset: resets the head to the tipset
+/// the branch forks from, and has the syncer sync the branch from there.
+/// Refuses tipsets marked bad, and branches forking beyond finality.
+pub(in crate::rpc) async fn sync_checkout<DB>(
+    data: Data<RPCState<DB>>,
+    Params((LotusJson(tsk),)): Params<SyncCheckoutParams>,
+) -> Result<SyncCheckoutResult, JsonRpcError>
+where
+    DB: Blockstore,
+{
+    let chain_store = &data.chain_store;
+    let tipset = match data.fork_tree.get(&tsk) {
+        Some(tipset) => tipset,
+        None => chain_store.tipset_from_keys(&tsk)?,
+    };
+    if let Some(reason) = tipset
+        .cids()
+        .iter()
+        .find_map(|cid| data.bad_blocks.peek(cid))
+    {
+        Err(anyhow::anyhow!("tipset is marked as bad: {reason}"))?;
+    }
+
+    let head = chain_store.heaviest_tipset();
+    let chain_finality = data.state_manager.chain_config().policy.chain_finality;
+    let canonical = CanonicalChain::new(chain_store, head.clone(), head.epoch() - chain_finality);
+    let fork_point = data
+        .fork_tree
+        .fork_point(chain_store, &canonical, tipset.clone())
+        .context("tipset doesn't fork from the current chain within finality")?;
+    if fork_point.key() == tipset.key() {
+        Err(anyhow::anyhow!(
+            "tipset is already part of the current chain"
+        ))?;
+    }
+
+    // Blocks above the fork point get validated again if synced again
+    for current in chain_store
+        .chain_index
+        .chain(head)
+        .take_while(|current| current.epoch() > fork_point.epoch())
+    {
+        for cid in current.cids() {
+            chain_store.unmark_block_as_validated(&cid);
+        }
+    }
+    chain_store.set_heaviest_tipset(fork_point)?;
+    data.tipset_sender
+        .send_async(tipset)
+        .await
+        .context("the syncer is not running")?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
@@ -132,6 +258,7 @@ mod tests {
         };
         let start_time = chrono::Utc::now();
         let (gc_event_tx, _) = flume::unbounded();
+        let (db_backup_tx, _) = flume::unbounded();
 
         let state = Arc::new(RPCState {
             state_manager,
@@ -139,12 +266,17 @@ mod tests {
             mpool: Arc::new(pool),
             bad_blocks: Default::default(),
             sync_state: Arc::new(parking_lot::RwLock::new(Default::default())),
+            fork_tree: Default::default(),
+            tipset_sender: flume::unbounded().0,
             network_send,
+            peer_manager: Default::default(),
             network_name: TEST_NET_NAME.to_owned(),
             start_time,
             chain_store: cs_for_chain.clone(),
             beacon,
             gc_event_tx,
+            db_backup_tx,
+            msg_index: None,
         });
         (state, network_rx)
     }
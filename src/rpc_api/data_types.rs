@@ -1,28 +1,34 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::path::PathBuf;
 use std::sync::Arc;
 
 use crate::beacon::BeaconSchedule;
-use crate::blocks::TipsetKeys;
+use crate::blocks::{Tipset, TipsetKeys};
 use crate::chain::ChainStore;
-use crate::chain_sync::{BadBlockCache, SyncState};
+use crate::chain_sync::{BadBlockCache, ForkTree, SyncState};
 use crate::ipld::json::IpldJson;
 use crate::key_management::KeyStore;
 pub use crate::libp2p::{Multiaddr, Protocol};
-use crate::libp2p::{Multihash, NetworkMessage};
+use crate::libp2p::{Multihash, NetworkMessage, PeerManager};
+use crate::lotus_json::LotusJson;
 use crate::message::signed_message::SignedMessage;
 use crate::message_pool::{MessagePool, MpoolRpcProvider};
+use crate::shim::address::Address;
+use crate::shim::clock::ChainEpoch;
 use crate::shim::executor::Receipt;
 use crate::shim::{econ::TokenAmount, message::Message};
-use crate::state_manager::StateManager;
+use crate::state_manager::{MessageIndex, StateManager};
 use ahash::HashSet;
 use chrono::Utc;
 use cid::Cid;
 use fil_actor_interface::market::{DealProposal, DealState};
 use fvm_ipld_blockstore::Blockstore;
 use jsonrpc_v2::{MapRouter as JsonRpcMapRouter, Server as JsonRpcServer};
+use num::BigInt;
 use parking_lot::RwLock as SyncRwLock;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 
@@ -38,67 +44,162 @@ where
     pub mpool: Arc<MessagePool<MpoolRpcProvider<DB>>>,
     pub bad_blocks: Arc<BadBlockCache>,
     pub sync_state: Arc<SyncRwLock<SyncState>>,
+    pub fork_tree: Arc<ForkTree>,
+    pub tipset_sender: flume::Sender<Arc<Tipset>>,
     pub network_send: flume::Sender<NetworkMessage>,
+    pub peer_manager: Arc<PeerManager>,
     pub network_name: String,
     pub start_time: chrono::DateTime<Utc>,
     pub beacon: Arc<BeaconSchedule>,
     pub gc_event_tx: flume::Sender<flume::Sender<anyhow::Result<()>>>,
+    pub db_backup_tx: flume::Sender<(PathBuf, flume::Sender<anyhow::Result<()>>)>,
+    pub msg_index: Option<Arc<MessageIndex<DB>>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct RPCSyncState {
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Vec<SyncState>>")]
     pub active_syncs: Vec<SyncState>,
 }
 
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct RPCForkTree {
+    #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<TipsetKeys>")]
+    pub head: TipsetKeys,
+    /// Tips of the recently observed forks, highest first
+    pub branches: Vec<ForkTreeBranch>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct ForkTreeBranch {
+    #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<TipsetKeys>")]
+    pub tip: TipsetKeys,
+    pub height: ChainEpoch,
+    /// Height of the latest tipset shared with the current chain, if within
+    /// finality
+    pub fork_height: Option<ChainEpoch>,
+    #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<BigInt>")]
+    pub parent_weight: BigInt,
+    /// Weight of the tip, if its parent state is available
+    pub weight: Option<ForkTreeWeight>,
+    pub status: ForkTreeBranchStatus,
+    /// Why the tip was rejected, if it was
+    pub reason: Option<String>,
+}
+
+/// Components of the weight of a tipset, see [`crate::fil_cns::WeightBreakdown`]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct ForkTreeWeight {
+    #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<BigInt>")]
+    pub power: BigInt,
+    #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<BigInt>")]
+    pub election: BigInt,
+    pub win_count: i64,
+    #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<BigInt>")]
+    pub total: BigInt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum ForkTreeBranchStatus {
+    /// The tip is the current head
+    Head,
+    /// The tip is an ancestor of the current head
+    Canonical,
+    /// The tip was validated and is heavier than the current head, which it
+    /// should replace shortly
+    Heavier,
+    /// The tip was validated but is not heavier than the current head
+    Lighter,
+    /// The tip was not validated, usually because it was not heavier than the
+    /// head when received
+    Unvalidated,
+    /// The tip or one of its ancestors was found to be invalid
+    Rejected,
+}
+
 pub type JsonRpcServerState = Arc<JsonRpcServer<JsonRpcMapRouter>>;
 
 // Chain API
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct BlockMessages {
     #[serde(rename = "BlsMessages", with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Vec<Message>>")]
     pub bls_msg: Vec<Message>,
     #[serde(rename = "SecpkMessages", with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Vec<SignedMessage>>")]
     pub secp_msg: Vec<SignedMessage>,
     #[serde(rename = "Cids", with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Vec<Cid>>")]
     pub cids: Vec<Cid>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct MessageSendSpec {
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<TokenAmount>")]
     max_fee: TokenAmount,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct MarketDeal {
+    #[schemars(with = "serde_json::Value")]
     pub proposal: DealProposal,
+    #[schemars(with = "serde_json::Value")]
     pub state: DealState,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct MessageLookup {
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Receipt>")]
     pub receipt: Receipt,
     #[serde(rename = "TipSet", with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<TipsetKeys>")]
     pub tipset: TipsetKeys,
     pub height: i64,
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Cid>")]
     pub message: Cid,
+    #[schemars(with = "serde_json::Value")]
     pub return_dec: IpldJson,
 }
 
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct MessageMatch {
+    #[serde(default, with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Option<Address>>")]
+    pub to: Option<Address>,
+    #[serde(default, with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Option<Address>>")]
+    pub from: Option<Address>,
+}
+
 // Net API
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct AddrInfo {
     #[serde(rename = "ID")]
     pub id: String,
+    #[schemars(with = "HashSet<String>")]
     pub addrs: HashSet<Multiaddr>,
+    /// Head epoch last reported by the peer, in `Filecoin.NetPeers` results
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub head_epoch: Option<ChainEpoch>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -107,7 +208,7 @@ pub struct PeerID {
 }
 
 /// Represents the current version of the API.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct APIVersion {
     pub version: String,
@@ -117,7 +218,7 @@ pub struct APIVersion {
 
 /// Integer based value on version information. Highest order bits for Major,
 /// Mid order for Minor and lowest for Patch.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct Version(u32);
 
 impl Version {
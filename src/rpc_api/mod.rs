@@ -13,6 +13,7 @@ use ahash::{HashMap, HashMapExt};
 use once_cell::sync::Lazy;
 
 pub mod data_types;
+pub mod openrpc;
 
 /// Access levels to be checked against JWT claims
 pub enum Access {
@@ -57,6 +58,8 @@ pub static ACCESS_MAP: Lazy<HashMap<&str, Access>> = Lazy::new(|| {
     access.insert(sync_api::SYNC_CHECK_BAD, Access::Read);
     access.insert(sync_api::SYNC_MARK_BAD, Access::Admin);
     access.insert(sync_api::SYNC_STATE, Access::Read);
+    access.insert(sync_api::SYNC_FORK_TREE, Access::Read);
+    access.insert(sync_api::SYNC_CHECKOUT, Access::Admin);
 
     // Wallet API
     access.insert(wallet_api::WALLET_BALANCE, Access::Write);
@@ -83,6 +86,10 @@ pub static ACCESS_MAP: Lazy<HashMap<&str, Access>> = Lazy::new(|| {
     access.insert(state_api::STATE_NETWORK_NAME, Access::Read);
     access.insert(state_api::STATE_NETWORK_VERSION, Access::Read);
     access.insert(state_api::STATE_FETCH_ROOT, Access::Read);
+    access.insert(state_api::STATE_DIFF, Access::Read);
+    access.insert(state_api::STATE_LIST_MESSAGES, Access::Read);
+    access.insert(state_api::STATE_INDEX_MESSAGES, Access::Admin);
+    access.insert(state_api::STATE_COMPUTE_RANGE, Access::Admin);
 
     // Gas API
     access.insert(gas_api::GAS_ESTIMATE_GAS_LIMIT, Access::Read);
@@ -94,6 +101,7 @@ pub static ACCESS_MAP: Lazy<HashMap<&str, Access>> = Lazy::new(|| {
     access.insert(common_api::VERSION, Access::Read);
     access.insert(common_api::SHUTDOWN, Access::Admin);
     access.insert(common_api::START_TIME, Access::Read);
+    access.insert(common_api::DISCOVER, Access::Read);
 
     // Net API
     access.insert(net_api::NET_ADDRS_LISTEN, Access::Read);
@@ -104,6 +112,7 @@ pub static ACCESS_MAP: Lazy<HashMap<&str, Access>> = Lazy::new(|| {
 
     // DB API
     access.insert(db_api::DB_GC, Access::Write);
+    access.insert(db_api::DB_BACKUP, Access::Admin);
 
     // Progress API
     access.insert(progress_api::GET_PROGRESS, Access::Read);
@@ -128,15 +137,17 @@ pub fn check_access(access: &Access, claims: &[String]) -> bool {
 /// Authorization API
 pub mod auth_api {
     use chrono::Duration;
+    use schemars::JsonSchema;
     use serde::{Deserialize, Serialize};
     use serde_with::{serde_as, DurationSeconds};
 
     pub const AUTH_NEW: &str = "Filecoin.AuthNew";
     #[serde_as]
-    #[derive(Deserialize, Serialize)]
+    #[derive(Deserialize, Serialize, JsonSchema)]
     pub struct AuthNewParams {
         pub perms: Vec<String>,
         #[serde_as(as = "DurationSeconds<i64>")]
+        #[schemars(with = "i64")]
         pub token_exp: Duration,
     }
     pub type AuthNewResult = Vec<u8>;
@@ -166,6 +177,7 @@ pub mod chain_api {
     use crate::shim::clock::ChainEpoch;
     use crate::shim::message::Message;
     use cid::Cid;
+    use schemars::JsonSchema;
     use serde::{Deserialize, Serialize};
 
     use crate::rpc_api::data_types::BlockMessages;
@@ -176,12 +188,13 @@ pub mod chain_api {
 
     pub const CHAIN_EXPORT: &str = "Filecoin.ChainExport";
 
-    #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
     pub struct ChainExportParams {
         pub epoch: ChainEpoch,
         pub recent_roots: i64,
         pub output_path: PathBuf,
         #[serde(with = "crate::lotus_json")]
+        #[schemars(with = "LotusJson<TipsetKeys>")]
         pub tipset_keys: TipsetKeys,
         pub skip_checksum: bool,
         pub dry_run: bool,
@@ -258,7 +271,11 @@ pub mod sync_api {
 
     use cid::Cid;
 
-    use crate::{lotus_json::LotusJson, rpc_api::data_types::RPCSyncState};
+    use crate::blocks::TipsetKeys;
+    use crate::{
+        lotus_json::LotusJson,
+        rpc_api::data_types::{RPCForkTree, RPCSyncState},
+    };
 
     pub const SYNC_CHECK_BAD: &str = "Filecoin.SyncCheckBad";
     pub type SyncCheckBadParams = (LotusJson<Cid>,);
@@ -271,6 +288,14 @@ pub mod sync_api {
     pub const SYNC_STATE: &str = "Filecoin.SyncState";
     pub type SyncStateParams = ();
     pub type SyncStateResult = RPCSyncState;
+
+    pub const SYNC_FORK_TREE: &str = "Filecoin.SyncForkTree";
+    pub type SyncForkTreeParams = ();
+    pub type SyncForkTreeResult = RPCForkTree;
+
+    pub const SYNC_CHECKOUT: &str = "Filecoin.SyncCheckout";
+    pub type SyncCheckoutParams = (LotusJson<TipsetKeys>,);
+    pub type SyncCheckoutResult = ();
 }
 
 /// Wallet API
@@ -332,14 +357,16 @@ pub mod state_api {
     use crate::blocks::TipsetKeys;
     use crate::lotus_json::LotusJson;
     use crate::shim::address::Address;
+    use crate::shim::clock::ChainEpoch;
     use crate::shim::executor::Receipt;
     use crate::shim::message::Message;
     use crate::shim::{state_tree::ActorState, version::NetworkVersion};
     use crate::state_manager::{InvocResult, MarketBalance};
+    use crate::statediff::StateDiff;
     use ahash::HashMap;
     use cid::Cid;
 
-    use crate::rpc_api::data_types::{MarketDeal, MessageLookup};
+    use crate::rpc_api::data_types::{MarketDeal, MessageLookup, MessageMatch};
 
     pub const STATE_CALL: &str = "Filecoin.StateCall";
     pub type StateCallParams = (LotusJson<Message>, LotusJson<TipsetKeys>);
@@ -380,6 +407,26 @@ pub mod state_api {
     pub const STATE_FETCH_ROOT: &str = "Filecoin.StateFetchRoot";
     pub type StateFetchRootParams = (LotusJson<Cid>, Option<PathBuf>);
     pub type StateFetchRootResult = String;
+
+    pub const STATE_DIFF: &str = "Filecoin.StateDiff";
+    pub type StateDiffParams = (LotusJson<Cid>, LotusJson<Cid>);
+    pub type StateDiffResult = StateDiff;
+
+    pub const STATE_LIST_MESSAGES: &str = "Filecoin.StateListMessages";
+    pub type StateListMessagesParams = (MessageMatch, LotusJson<TipsetKeys>, ChainEpoch);
+    pub type StateListMessagesResult = LotusJson<Vec<Cid>>;
+
+    /// Populates the message index between two epochs (inclusive) of the
+    /// current chain, returning the number of new index entries.
+    pub const STATE_INDEX_MESSAGES: &str = "Filecoin.StateIndexMessages";
+    pub type StateIndexMessagesParams = (ChainEpoch, ChainEpoch);
+    pub type StateIndexMessagesResult = usize;
+
+    /// Computes and persists the states of the tipsets between two epochs
+    /// (inclusive) of the current chain, returning the number of tipsets.
+    pub const STATE_COMPUTE_RANGE: &str = "Filecoin.StateComputeRange";
+    pub type StateComputeRangeParams = (ChainEpoch, ChainEpoch);
+    pub type StateComputeRangeResult = usize;
 }
 
 /// Gas API
@@ -430,10 +477,16 @@ pub mod common_api {
     #[allow(unused)] // https://github.com/ChainSafe/forest/issues/3029
     pub type StartTimeParams = ();
     pub type StartTimeResult = chrono::DateTime<Utc>;
+
+    /// Returns the `OpenRPC` document describing every method, see [`super::openrpc`].
+    pub const DISCOVER: &str = "rpc.discover";
+    pub type DiscoverParams = ();
+    pub type DiscoverResult = super::openrpc::OpenRpc;
 }
 
 /// Net API
 pub mod net_api {
+    use schemars::JsonSchema;
     use serde::{Deserialize, Serialize};
 
     use crate::rpc_api::data_types::AddrInfo;
@@ -449,7 +502,7 @@ pub mod net_api {
     pub const NET_INFO: &str = "Filecoin.NetInfo";
     pub type NetInfoParams = ();
 
-    #[derive(Debug, Default, Serialize, Deserialize)]
+    #[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
     pub struct NetInfoResult {
         pub num_peers: usize,
         pub num_connections: u32,
@@ -484,20 +537,27 @@ pub mod net_api {
 
 /// DB API
 pub mod db_api {
+    use std::path::PathBuf;
+
     pub const DB_GC: &str = "Filecoin.DatabaseGarbageCollection";
     pub type DBGCParams = ();
     pub type DBGCResult = ();
+
+    pub const DB_BACKUP: &str = "Filecoin.DatabaseBackup";
+    pub type DBBackupParams = (PathBuf,);
+    pub type DBBackupResult = ();
 }
 
 /// Progress API
 pub mod progress_api {
+    use schemars::JsonSchema;
     use serde::{Deserialize, Serialize};
 
     pub const GET_PROGRESS: &str = "Filecoin.GetProgress";
     pub type GetProgressParams = (GetProgressType,);
     pub type GetProgressResult = (u64, u64);
 
-    #[derive(Serialize, Deserialize)]
+    #[derive(Serialize, Deserialize, JsonSchema)]
     pub enum GetProgressType {
         DatabaseGarbageCollection,
     }
@@ -509,27 +569,39 @@ pub mod node_api {
     pub type NodeStatusParams = ();
     pub type NodeStatusResult = NodeStatus;
 
+    use crate::shim::clock::ChainEpoch;
+    use schemars::JsonSchema;
     use serde::{Deserialize, Serialize};
 
-    #[derive(Debug, Serialize, Deserialize, Default)]
+    #[derive(Debug, Serialize, Deserialize, Default, JsonSchema)]
     pub struct NodeSyncStatus {
         pub epoch: u64,
         pub behind: u64,
     }
 
-    #[derive(Debug, Serialize, Deserialize, Default)]
+    #[derive(Debug, Serialize, Deserialize, Default, JsonSchema)]
     pub struct NodePeerStatus {
         pub peers_to_publish_msgs: u32,
         pub peers_to_publish_blocks: u32,
+        pub head_spread: PeerHeadSpread,
+    }
+
+    /// Spread of the head epochs reported by peers
+    #[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq, JsonSchema)]
+    pub struct PeerHeadSpread {
+        pub peers: usize,
+        pub min: ChainEpoch,
+        pub median: ChainEpoch,
+        pub max: ChainEpoch,
     }
 
-    #[derive(Debug, Serialize, Deserialize, Default)]
+    #[derive(Debug, Serialize, Deserialize, Default, JsonSchema)]
     pub struct NodeChainStatus {
         pub blocks_per_tipset_last_100: f64,
         pub blocks_per_tipset_last_finality: f64,
     }
 
-    #[derive(Debug, Deserialize, Default, Serialize)]
+    #[derive(Debug, Deserialize, Default, Serialize, JsonSchema)]
     pub struct NodeStatus {
         pub sync_status: NodeSyncStatus,
         pub peer_status: NodePeerStatus,
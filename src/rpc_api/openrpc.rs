@@ -0,0 +1,415 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Generates an [OpenRPC](https://spec.open-rpc.org/) document from the method
+//! declarations in [`crate::rpc_api`].
+//!
+//! The `Params` and `Result` types of every method are turned into JSON Schema,
+//! so the document describes exactly what the server (de)serializes. Lotus JSON
+//! types are published under `components/schemas` with the name of their
+//! domain type, see [`crate::lotus_json::HasLotusJson`].
+//!
+//! When adding a method, add it to [`methods`] as well - the tests check that
+//! the document covers the [`ACCESS_MAP`](super::ACCESS_MAP).
+
+use ahash::HashMap;
+use itertools::Itertools as _;
+use schemars::{
+    gen::{SchemaGenerator, SchemaSettings},
+    schema::{InstanceType, Schema, SchemaObject, SingleOrVec},
+    JsonSchema,
+};
+use serde::{Deserialize, Serialize};
+
+use super::*;
+
+const OPENRPC_VERSION: &str = "1.2.6";
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenRpc {
+    pub openrpc: String,
+    pub info: Info,
+    pub methods: Vec<Method>,
+    #[serde(default)]
+    pub components: Components,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Info {
+    pub title: String,
+    pub version: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Method {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub param_structure: Option<ParamStructure>,
+    pub params: Vec<ContentDescriptor>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<ContentDescriptor>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ParamStructure {
+    ByName,
+    ByPosition,
+    Either,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ContentDescriptor {
+    pub name: String,
+    #[serde(default)]
+    pub required: bool,
+    pub schema: Schema,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Components {
+    #[serde(default)]
+    pub schemas: schemars::Map<String, Schema>,
+}
+
+/// Creates the `OpenRPC` document for every method served by Forest.
+pub fn openrpc_document(version: &str) -> OpenRpc {
+    let mut gen = SchemaSettings::draft07()
+        .with(|settings| settings.definitions_path = "#/components/schemas/".into())
+        .into_generator();
+    let methods = methods(&mut gen);
+    OpenRpc {
+        openrpc: OPENRPC_VERSION.into(),
+        info: Info {
+            title: "Forest RPC API".into(),
+            version: version.into(),
+        },
+        methods,
+        components: Components {
+            schemas: gen.take_definitions(),
+        },
+    }
+}
+
+impl Method {
+    fn new<P: JsonSchema, R: JsonSchema>(name: &str, gen: &mut SchemaGenerator) -> Self {
+        let (param_structure, params) = params(P::json_schema(gen));
+        Self {
+            name: name.into(),
+            param_structure: Some(param_structure),
+            params,
+            result: Some(ContentDescriptor {
+                name: R::schema_name(),
+                required: true,
+                schema: gen.subschema_for::<R>(),
+            }),
+        }
+    }
+}
+
+/// Splits the schema of a `Params` type into the individual parameters.
+///
+/// Tuples are positional, structs can be given either by name or position,
+/// and a sequence (e.g. `Vec<T>`) accepts any number of `T`s.
+fn params(schema: Schema) -> (ParamStructure, Vec<ContentDescriptor>) {
+    let descriptor = |(i, schema): (usize, Schema)| ContentDescriptor {
+        name: format!("p{}", i + 1),
+        required: !allows_null(&schema),
+        schema,
+    };
+    let Schema::Object(object) = schema else {
+        return (ParamStructure::ByPosition, vec![]);
+    };
+    if has_type(&object, InstanceType::Null) && !has_type(&object, InstanceType::Array) {
+        // `()`
+        return (ParamStructure::ByPosition, vec![]);
+    }
+    if let Some(array) = &object.array {
+        let items = match &array.items {
+            Some(SingleOrVec::Vec(items)) => items.clone(),
+            Some(SingleOrVec::Single(item)) => vec![(**item).clone()],
+            None => vec![],
+        };
+        return (
+            ParamStructure::ByPosition,
+            items.into_iter().enumerate().map(descriptor).collect(),
+        );
+    }
+    if let Some(obj) = &object.object {
+        let params = obj
+            .properties
+            .iter()
+            .map(|(name, schema)| ContentDescriptor {
+                name: name.clone(),
+                required: obj.required.contains(name),
+                schema: schema.clone(),
+            })
+            .collect();
+        return (ParamStructure::Either, params);
+    }
+    (
+        ParamStructure::ByPosition,
+        vec![descriptor((0, Schema::Object(object)))],
+    )
+}
+
+fn has_type(schema: &SchemaObject, ty: InstanceType) -> bool {
+    match &schema.instance_type {
+        Some(SingleOrVec::Single(it)) => **it == ty,
+        Some(SingleOrVec::Vec(it)) => it.contains(&ty),
+        None => false,
+    }
+}
+
+fn allows_null(schema: &Schema) -> bool {
+    match schema {
+        Schema::Bool(allowed) => *allowed,
+        Schema::Object(object) => {
+            has_type(object, InstanceType::Null)
+                || object
+                    .subschemas
+                    .as_ref()
+                    .and_then(|it| it.any_of.as_ref())
+                    .map_or(false, |any_of| any_of.iter().any(allows_null))
+        }
+    }
+}
+
+/// Differences between the method sets of two `OpenRPC` documents.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct MethodSetDiff {
+    /// Methods only in the other document.
+    pub missing: Vec<String>,
+    /// Methods only in our document.
+    pub extra: Vec<String>,
+    /// Methods in both documents whose number of parameters differ, as
+    /// `(name, ours, theirs)`.
+    pub param_count_mismatch: Vec<(String, usize, usize)>,
+}
+
+impl MethodSetDiff {
+    pub fn new(ours: &OpenRpc, theirs: &OpenRpc) -> Self {
+        let ours: HashMap<_, _> = ours.methods.iter().map(|it| (&it.name, it)).collect();
+        let theirs: HashMap<_, _> = theirs.methods.iter().map(|it| (&it.name, it)).collect();
+        Self {
+            missing: theirs
+                .keys()
+                .filter(|name| !ours.contains_key(*name))
+                .map(|name| name.to_string())
+                .sorted()
+                .collect(),
+            extra: ours
+                .keys()
+                .filter(|name| !theirs.contains_key(*name))
+                .map(|name| name.to_string())
+                .sorted()
+                .collect(),
+            param_count_mismatch: ours
+                .iter()
+                .filter_map(|(name, method)| {
+                    let other = theirs.get(name)?;
+                    (method.params.len() != other.params.len())
+                        .then(|| (name.to_string(), method.params.len(), other.params.len()))
+                })
+                .sorted()
+                .collect(),
+        }
+    }
+}
+
+macro_rules! methods {
+    ($($method:path => ($params:ty) -> $result:ty;)*) => {
+        fn methods(gen: &mut SchemaGenerator) -> Vec<Method> {
+            vec![$(Method::new::<$params, $result>($method, gen)),*]
+        }
+    };
+}
+
+methods! {
+    // Auth API
+    auth_api::AUTH_NEW => (auth_api::AuthNewParams) -> auth_api::AuthNewResult;
+    auth_api::AUTH_VERIFY => (auth_api::AuthVerifyParams) -> auth_api::AuthVerifyResult;
+    // Beacon API
+    beacon_api::BEACON_GET_ENTRY => (beacon_api::BeaconGetEntryParams) -> beacon_api::BeaconGetEntryResult;
+    // Chain API
+    chain_api::CHAIN_GET_MESSAGE => (chain_api::ChainGetMessageParams) -> chain_api::ChainGetMessageResult;
+    chain_api::CHAIN_EXPORT => (chain_api::ChainExportParams) -> chain_api::ChainExportResult;
+    chain_api::CHAIN_READ_OBJ => (chain_api::ChainReadObjParams) -> chain_api::ChainReadObjResult;
+    chain_api::CHAIN_HAS_OBJ => (chain_api::ChainHasObjParams) -> chain_api::ChainHasObjResult;
+    chain_api::CHAIN_GET_BLOCK_MESSAGES => (chain_api::ChainGetBlockMessagesParams) -> chain_api::ChainGetBlockMessagesResult;
+    chain_api::CHAIN_GET_TIPSET_BY_HEIGHT => (chain_api::ChainGetTipsetByHeightParams) -> chain_api::ChainGetTipsetByHeightResult;
+    chain_api::CHAIN_GET_GENESIS => (chain_api::ChainGetGenesisParams) -> chain_api::ChainGetGenesisResult;
+    chain_api::CHAIN_HEAD => (chain_api::ChainHeadParams) -> chain_api::ChainHeadResult;
+    chain_api::CHAIN_GET_BLOCK => (chain_api::ChainGetBlockParams) -> chain_api::ChainGetBlockResult;
+    chain_api::CHAIN_GET_TIPSET => (chain_api::ChainGetTipSetParams) -> chain_api::ChainGetTipSetResult;
+    chain_api::CHAIN_SET_HEAD => (chain_api::ChainSetHeadParams) -> chain_api::ChainSetHeadResult;
+    chain_api::CHAIN_GET_MIN_BASE_FEE => (chain_api::ChainGetMinBaseFeeParams) -> chain_api::ChainGetMinBaseFeeResult;
+    // Message Pool API
+    mpool_api::MPOOL_PENDING => (mpool_api::MpoolPendingParams) -> mpool_api::MpoolPendingResult;
+    mpool_api::MPOOL_PUSH => (mpool_api::MpoolPushParams) -> mpool_api::MpoolPushResult;
+    mpool_api::MPOOL_PUSH_MESSAGE => (mpool_api::MpoolPushMessageParams) -> mpool_api::MpoolPushMessageResult;
+    // Sync API
+    sync_api::SYNC_CHECK_BAD => (sync_api::SyncCheckBadParams) -> sync_api::SyncCheckBadResult;
+    sync_api::SYNC_MARK_BAD => (sync_api::SyncMarkBadParams) -> sync_api::SyncMarkBadResult;
+    sync_api::SYNC_STATE => (sync_api::SyncStateParams) -> sync_api::SyncStateResult;
+    sync_api::SYNC_FORK_TREE => (sync_api::SyncForkTreeParams) -> sync_api::SyncForkTreeResult;
+    sync_api::SYNC_CHECKOUT => (sync_api::SyncCheckoutParams) -> sync_api::SyncCheckoutResult;
+    // Wallet API
+    wallet_api::WALLET_BALANCE => (wallet_api::WalletBalanceParams) -> wallet_api::WalletBalanceResult;
+    wallet_api::WALLET_DEFAULT_ADDRESS => (wallet_api::WalletDefaultAddressParams) -> wallet_api::WalletDefaultAddressResult;
+    wallet_api::WALLET_EXPORT => (wallet_api::WalletExportParams) -> wallet_api::WalletExportResult;
+    wallet_api::WALLET_HAS => (wallet_api::WalletHasParams) -> wallet_api::WalletHasResult;
+    wallet_api::WALLET_IMPORT => (wallet_api::WalletImportParams) -> wallet_api::WalletImportResult;
+    wallet_api::WALLET_LIST => (wallet_api::WalletListParams) -> wallet_api::WalletListResult;
+    wallet_api::WALLET_NEW => (wallet_api::WalletNewParams) -> wallet_api::WalletNewResult;
+    wallet_api::WALLET_SET_DEFAULT => (wallet_api::WalletSetDefaultParams) -> wallet_api::WalletSetDefaultResult;
+    wallet_api::WALLET_SIGN => (wallet_api::WalletSignParams) -> wallet_api::WalletSignResult;
+    wallet_api::WALLET_VERIFY => (wallet_api::WalletVerifyParams) -> wallet_api::WalletVerifyResult;
+    wallet_api::WALLET_DELETE => (wallet_api::WalletDeleteParams) -> wallet_api::WalletDeleteResult;
+    // State API
+    state_api::STATE_CALL => (state_api::StateCallParams) -> state_api::StateCallResult;
+    state_api::STATE_REPLAY => (state_api::StateReplayParams) -> state_api::StateReplayResult;
+    state_api::STATE_NETWORK_NAME => (state_api::StateNetworkNameParams) -> state_api::StateNetworkNameResult;
+    state_api::STATE_NETWORK_VERSION => (state_api::StateNetworkVersionParams) -> state_api::StateNetworkVersionResult;
+    state_api::STATE_GET_ACTOR => (state_api::StateGetActorParams) -> state_api::StateGetActorResult;
+    state_api::STATE_MARKET_BALANCE => (state_api::StateMarketBalanceParams) -> state_api::StateMarketBalanceResult;
+    state_api::STATE_MARKET_DEALS => (state_api::StateMarketDealsParams) -> state_api::StateMarketDealsResult;
+    state_api::STATE_GET_RECEIPT => (state_api::StateGetReceiptParams) -> state_api::StateGetReceiptResult;
+    state_api::STATE_WAIT_MSG => (state_api::StateWaitMsgParams) -> state_api::StateWaitMsgResult;
+    state_api::STATE_FETCH_ROOT => (state_api::StateFetchRootParams) -> state_api::StateFetchRootResult;
+    state_api::STATE_DIFF => (state_api::StateDiffParams) -> state_api::StateDiffResult;
+    state_api::STATE_LIST_MESSAGES => (state_api::StateListMessagesParams) -> state_api::StateListMessagesResult;
+    state_api::STATE_INDEX_MESSAGES => (state_api::StateIndexMessagesParams) -> state_api::StateIndexMessagesResult;
+    state_api::STATE_COMPUTE_RANGE => (state_api::StateComputeRangeParams) -> state_api::StateComputeRangeResult;
+    // Gas API
+    gas_api::GAS_ESTIMATE_FEE_CAP => (gas_api::GasEstimateFeeCapParams) -> gas_api::GasEstimateFeeCapResult;
+    gas_api::GAS_ESTIMATE_GAS_PREMIUM => (gas_api::GasEstimateGasPremiumParams) -> gas_api::GasEstimateGasPremiumResult;
+    gas_api::GAS_ESTIMATE_GAS_LIMIT => (gas_api::GasEstimateGasLimitParams) -> gas_api::GasEstimateGasLimitResult;
+    gas_api::GAS_ESTIMATE_MESSAGE_GAS => (gas_api::GasEstimateMessageGasParams) -> gas_api::GasEstimateMessageGasResult;
+    // Common API
+    common_api::VERSION => (common_api::VersionParams) -> common_api::VersionResult;
+    common_api::SHUTDOWN => (common_api::ShutdownParams) -> common_api::ShutdownResult;
+    common_api::START_TIME => (common_api::StartTimeParams) -> common_api::StartTimeResult;
+    common_api::DISCOVER => (common_api::DiscoverParams) -> serde_json::Value;
+    // Net API
+    net_api::NET_ADDRS_LISTEN => (net_api::NetAddrsListenParams) -> net_api::NetAddrsListenResult;
+    net_api::NET_PEERS => (net_api::NetPeersParams) -> net_api::NetPeersResult;
+    net_api::NET_INFO => (net_api::NetInfoParams) -> net_api::NetInfoResult;
+    net_api::NET_CONNECT => (net_api::NetConnectParams) -> net_api::NetConnectResult;
+    net_api::NET_DISCONNECT => (net_api::NetDisconnectParams) -> net_api::NetDisconnectResult;
+    // DB API
+    db_api::DB_GC => (db_api::DBGCParams) -> db_api::DBGCResult;
+    db_api::DB_BACKUP => (db_api::DBBackupParams) -> db_api::DBBackupResult;
+    // Progress API
+    progress_api::GET_PROGRESS => (progress_api::GetProgressParams) -> progress_api::GetProgressResult;
+    // Node API
+    node_api::NODE_STATUS => (node_api::NodeStatusParams) -> node_api::NodeStatusResult;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ahash::HashSet;
+
+    fn refs(value: &serde_json::Value, acc: &mut Vec<String>) {
+        match value {
+            serde_json::Value::Object(map) => {
+                if let Some(serde_json::Value::String(it)) = map.get("$ref") {
+                    acc.push(it.clone());
+                }
+                map.values().for_each(|it| refs(it, acc));
+            }
+            serde_json::Value::Array(items) => items.iter().for_each(|it| refs(it, acc)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn covers_access_map() {
+        let doc = openrpc_document("0.0.0");
+        let documented: HashSet<_> = doc.methods.iter().map(|it| it.name.as_str()).collect();
+        let served: HashSet<_> = ACCESS_MAP.keys().copied().collect();
+        assert_eq!(documented.len(), doc.methods.len(), "duplicate methods");
+        assert_eq!(documented, served);
+    }
+
+    #[test]
+    fn all_refs_resolve() {
+        let doc = openrpc_document("0.0.0");
+        let json = serde_json::to_value(&doc).unwrap();
+        let mut all = vec![];
+        refs(&json, &mut all);
+        assert!(!all.is_empty());
+        for it in all {
+            let name = it.strip_prefix("#/components/schemas/").unwrap();
+            assert!(doc.components.schemas.contains_key(name), "{it}");
+        }
+    }
+
+    #[test]
+    fn param_shapes() {
+        let doc = openrpc_document("0.0.0");
+        let method = |name| doc.methods.iter().find(|it| it.name == name).unwrap();
+
+        let head = method(chain_api::CHAIN_HEAD);
+        assert!(head.params.is_empty());
+
+        let push = method(mpool_api::MPOOL_PUSH_MESSAGE);
+        assert_eq!(push.param_structure, Some(ParamStructure::ByPosition));
+        assert_eq!(
+            push.params
+                .iter()
+                .map(|it| (it.name.as_str(), it.required))
+                .collect::<Vec<_>>(),
+            [("p1", true), ("p2", false)]
+        );
+        assert_eq!(
+            serde_json::to_value(&push.params[0].schema).unwrap(),
+            serde_json::json!({ "$ref": "#/components/schemas/Message" })
+        );
+
+        let auth = method(auth_api::AUTH_NEW);
+        assert_eq!(auth.param_structure, Some(ParamStructure::Either));
+        assert_eq!(
+            auth.params
+                .iter()
+                .map(|it| it.name.as_str())
+                .collect::<Vec<_>>(),
+            ["perms", "token_exp"]
+        );
+    }
+
+    #[test]
+    fn diff() {
+        let ours = openrpc_document("0.0.0");
+        let mut theirs: OpenRpc =
+            serde_json::from_value(serde_json::to_value(openrpc_document("0.0.0")).unwrap())
+                .unwrap();
+        assert_eq!(MethodSetDiff::new(&ours, &theirs), MethodSetDiff::default());
+
+        theirs.methods.retain(|it| it.name != chain_api::CHAIN_HEAD);
+        theirs
+            .methods
+            .iter_mut()
+            .find(|it| it.name == chain_api::CHAIN_GET_TIPSET)
+            .unwrap()
+            .params
+            .clear();
+        theirs.methods.push(Method {
+            name: "Filecoin.ChainNotify".into(),
+            param_structure: None,
+            params: vec![],
+            result: None,
+        });
+        assert_eq!(
+            MethodSetDiff::new(&ours, &theirs),
+            MethodSetDiff {
+                missing: vec!["Filecoin.ChainNotify".into()],
+                extra: vec![chain_api::CHAIN_HEAD.into()],
+                param_count_mismatch: vec![(chain_api::CHAIN_GET_TIPSET.into(), 1, 0)],
+            }
+        );
+    }
+}
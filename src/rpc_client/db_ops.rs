@@ -9,3 +9,10 @@ use crate::rpc_client::call;
 pub async fn db_gc((): DBGCParams, auth_token: &Option<String>) -> Result<DBGCResult, Error> {
     call(DB_GC, (), auth_token).await
 }
+
+pub async fn db_backup(
+    params: DBBackupParams,
+    auth_token: &Option<String>,
+) -> Result<DBBackupResult, Error> {
+    call(DB_BACKUP, params, auth_token).await
+}
@@ -13,7 +13,7 @@ pub mod state_ops;
 pub mod sync_ops;
 pub mod wallet_ops;
 
-use std::env;
+use std::{env, str::FromStr};
 
 use crate::libp2p::{Multiaddr, Protocol};
 use crate::utils::net::global_http_client;
@@ -39,22 +39,29 @@ pub struct ApiInfo {
     pub token: Option<String>,
 }
 
+impl FromStr for ApiInfo {
+    type Err = anyhow::Error;
+
+    /// Parses API info in the `[token:]multiaddress` format of the
+    /// `FULLNODE_API_INFO` environment variable.
+    fn from_str(api_info: &str) -> Result<Self, Self::Err> {
+        let (multiaddr, token) = match api_info.split_once(':') {
+            // Typically this is when a JWT was provided
+            Some((jwt, host)) => (host.parse()?, Some(jwt.to_owned())),
+            // Use entire API_INFO env var as host string
+            None => (api_info.parse()?, None),
+        };
+        Ok(ApiInfo { multiaddr, token })
+    }
+}
+
 pub static API_INFO: Lazy<ApiInfo> = Lazy::new(|| {
     // Get API_INFO environment variable if exists, otherwise, use default
     // multiaddress
-    let api_info = env::var(API_INFO_KEY).unwrap_or_else(|_| DEFAULT_MULTIADDRESS.to_owned());
-
-    let (multiaddr, token) = match api_info.split_once(':') {
-        // Typically this is when a JWT was provided
-        Some((jwt, host)) => (
-            host.parse().expect("Parse multiaddress"),
-            Some(jwt.to_owned()),
-        ),
-        // Use entire API_INFO env var as host string
-        None => (api_info.parse().expect("Parse multiaddress"), None),
-    };
-
-    ApiInfo { multiaddr, token }
+    env::var(API_INFO_KEY)
+        .unwrap_or_else(|_| DEFAULT_MULTIADDRESS.to_owned())
+        .parse()
+        .expect("Parse multiaddress")
 });
 
 /// Error object in a response
@@ -144,29 +151,48 @@ where
     P: Serialize,
     R: DeserializeOwned,
 {
-    let rpc_req = RequestObject::request()
-        .with_method(method_name)
-        .with_params(serde_json::to_value(params)?)
-        .finish();
-
-    let api_url = multiaddress_to_url(API_INFO.multiaddr.to_owned());
-
-    debug!("Using JSON-RPC v2 HTTP URL: {}", api_url);
-
-    let request = global_http_client().post(api_url).json(&rpc_req);
-    let request = match (API_INFO.token.as_ref(), token) {
-        (Some(token), _) | (_, Some(token)) => request.header(http::header::AUTHORIZATION, token),
-        _ => request,
-    };
-
-    let rpc_res = request.send().await?.error_for_status()?.json().await?;
-
-    match rpc_res {
-        JsonRpcResponse::Result { result, .. } => Ok(result),
-        JsonRpcResponse::Error { error, .. } => Err(Error::Full {
-            data: None,
-            code: error.code,
-            message: error.message,
-        }),
+    API_INFO.call(method_name, params, token).await
+}
+
+impl ApiInfo {
+    /// Sends an RPC request over HTTP to the node at `self.multiaddr`,
+    /// authenticated with `self.token`, or else with `token`.
+    pub async fn call<P, R>(
+        &self,
+        method_name: &str,
+        params: P,
+        token: &Option<String>,
+    ) -> Result<R, Error>
+    where
+        P: Serialize,
+        R: DeserializeOwned,
+    {
+        let rpc_req = RequestObject::request()
+            .with_method(method_name)
+            .with_params(serde_json::to_value(params)?)
+            .finish();
+
+        let api_url = multiaddress_to_url(self.multiaddr.to_owned());
+
+        debug!("Using JSON-RPC v2 HTTP URL: {}", api_url);
+
+        let request = global_http_client().post(api_url).json(&rpc_req);
+        let request = match (self.token.as_ref(), token) {
+            (Some(token), _) | (_, Some(token)) => {
+                request.header(http::header::AUTHORIZATION, token)
+            }
+            _ => request,
+        };
+
+        let rpc_res = request.send().await?.error_for_status()?.json().await?;
+
+        match rpc_res {
+            JsonRpcResponse::Result { result, .. } => Ok(result),
+            JsonRpcResponse::Error { error, .. } => Err(Error::Full {
+                data: None,
+                code: error.code,
+                message: error.message,
+            }),
+        }
     }
 }
@@ -26,3 +26,17 @@ pub async fn state_network_name(
 ) -> Result<StateNetworkNameResult, Error> {
     call(STATE_NETWORK_NAME, (), auth_token).await
 }
+
+pub async fn state_index_messages(
+    params: StateIndexMessagesParams,
+    auth_token: &Option<String>,
+) -> Result<StateIndexMessagesResult, Error> {
+    call(STATE_INDEX_MESSAGES, params, auth_token).await
+}
+
+pub async fn state_compute_range(
+    params: StateComputeRangeParams,
+    auth_token: &Option<String>,
+) -> Result<StateComputeRangeResult, Error> {
+    call(STATE_COMPUTE_RANGE, params, auth_token).await
+}
@@ -26,3 +26,17 @@ pub async fn sync_status(
 ) -> Result<SyncStateResult, JsonRpcError> {
     call(SYNC_STATE, (), auth_token).await
 }
+
+pub async fn sync_fork_tree(
+    (): SyncForkTreeParams,
+    auth_token: &Option<String>,
+) -> Result<SyncForkTreeResult, JsonRpcError> {
+    call(SYNC_FORK_TREE, (), auth_token).await
+}
+
+pub async fn sync_checkout(
+    params: SyncCheckoutParams,
+    auth_token: &Option<String>,
+) -> Result<SyncCheckoutResult, JsonRpcError> {
+    call(SYNC_CHECKOUT, params, auth_token).await
+}
@@ -254,7 +254,11 @@ where
                 };
                 st.for_each(inner)
             }
-            StateTree::V0(_) => bail!("StateTree::for_each not supported on old state trees"),
+            StateTree::V0(st) => {
+                let inner =
+                    |address: Address, actor_state: &ActorStateV2| f(address, &actor_state.into());
+                st.for_each(inner)
+            }
         }
     }
 
@@ -70,6 +70,18 @@ where
         Ok(act)
     }
 
+    /// Iterates over every actor in the state tree.
+    pub fn for_each<F>(&self, mut f: F) -> anyhow::Result<()>
+    where
+        F: FnMut(Address, &ActorStateV2) -> anyhow::Result<()>,
+    {
+        self.hamt.for_each(|k, v| {
+            let addr = Address::from_bytes(&k.0)?;
+            f(addr, v)
+        })?;
+        Ok(())
+    }
+
     /// Get an ID address from any Address
     pub fn lookup_id(&self, addr: &Address) -> anyhow::Result<Option<Address>> {
         if addr.protocol() == fvm_shared4::address::Protocol::ID {
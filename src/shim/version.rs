@@ -6,6 +6,7 @@ use super::fvm_shared_latest::version::NetworkVersion as NetworkVersion_latest;
 pub use fvm_shared2::version::NetworkVersion as NetworkVersion_v2;
 use fvm_shared3::version::NetworkVersion as NetworkVersion_v3;
 use fvm_shared4::version::NetworkVersion as NetworkVersion_v4;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// Specifies the network version
@@ -24,10 +25,12 @@ use serde::{Deserialize, Serialize};
 /// // use `.into()` when FVM2 has to be specified.
 /// assert_eq!(fvm_shared2::version::NetworkVersion::V0, v0.into());
 /// ```
-#[derive(Debug, Eq, PartialEq, Clone, Copy, Ord, PartialOrd, Serialize, Deserialize)]
+#[derive(
+    Debug, Eq, PartialEq, Clone, Copy, Ord, PartialOrd, Serialize, Deserialize, JsonSchema,
+)]
 #[repr(transparent)]
 #[serde(transparent)]
-pub struct NetworkVersion(pub NetworkVersion_latest);
+pub struct NetworkVersion(#[schemars(with = "u32")] pub NetworkVersion_latest);
 
 impl NetworkVersion {
     pub const V0: Self = Self(NetworkVersion_latest::new(0));
@@ -0,0 +1,434 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! An optional index from actor addresses to the messages sent from or to
+//! them. It backs `Filecoin.StateListMessages`, which would otherwise have to
+//! load the messages of every tipset in the requested range.
+//!
+//! Entries are grouped per direction, address and day ([`EPOCHS_IN_DAY`]
+//! epochs). Every entry is stored as JSON in the settings store under
+//! `/msg_index/{from|to}/{address}/{day}/{n}`, and the number of entries of a
+//! group under `/msg_index/{from|to}/{address}/{day}`, so indexing a message
+//! only appends to its groups.
+//! Addresses are resolved to ID addresses with [`StateManager::lookup_id`]
+//! when possible, so querying either the ID or the robust address of an actor
+//! yields the same history.
+
+use std::sync::Arc;
+
+use crate::blocks::{Tipset, TipsetKeys};
+use crate::chain::{index::ResolveNullTipset, HeadChange};
+use crate::db::{SettingsStore, SettingsStoreExt};
+use crate::message::Message as _;
+use crate::shim::{
+    address::Address,
+    clock::{ChainEpoch, EPOCHS_IN_DAY},
+};
+use ahash::{HashMap, HashMapExt, HashSet};
+use anyhow::bail;
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, warn};
+
+use super::StateManager;
+
+/// Last head tipset processed by [`MessageIndex::run`].
+const INDEX_HEAD_KEY: &str = "/msg_index/head";
+
+/// Marks the tipsets with the given key CID as indexed.
+fn tipset_key(tsk: &TipsetKeys) -> anyhow::Result<String> {
+    Ok(format!("/msg_index/tipset/{}", tsk.cid()?))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct IndexEntry {
+    epoch: ChainEpoch,
+    #[serde(with = "crate::lotus_json")]
+    message: Cid,
+    /// A block of the including tipset, used to discard entries of tipsets
+    /// that are no longer part of the canonical chain.
+    #[serde(with = "crate::lotus_json")]
+    block: Cid,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Direction {
+    From,
+    To,
+}
+
+pub struct MessageIndex<DB> {
+    state_manager: Arc<StateManager<DB>>,
+    settings: Arc<dyn SettingsStore + Sync + Send>,
+    /// Serializes writers, which append to the same groups.
+    write_lock: Mutex<()>,
+}
+
+impl<DB> MessageIndex<DB>
+where
+    DB: Blockstore + Send + Sync + 'static,
+{
+    pub fn new(
+        state_manager: Arc<StateManager<DB>>,
+        settings: Arc<dyn SettingsStore + Sync + Send>,
+    ) -> Self {
+        Self {
+            state_manager,
+            settings,
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    fn bucket_key(direction: Direction, addr: &Address, day: ChainEpoch) -> String {
+        let direction = match direction {
+            Direction::From => "from",
+            Direction::To => "to",
+        };
+        format!("/msg_index/{direction}/{addr}/{day}")
+    }
+
+    fn entry_key(bucket_key: &str, n: u64) -> String {
+        format!("{bucket_key}/{n}")
+    }
+
+    /// Reads the entries of the group with the given key.
+    fn read_bucket(&self, bucket_key: &str) -> anyhow::Result<Vec<IndexEntry>> {
+        let len: u64 = self.settings.read_obj(bucket_key)?.unwrap_or_default();
+        (0..len)
+            .filter_map(|n| {
+                self.settings
+                    .read_obj(&Self::entry_key(bucket_key, n))
+                    .transpose()
+            })
+            .collect()
+    }
+
+    /// Resolves `addr` to an ID address in the parent state of `ts`, falling
+    /// back to `addr` itself for actors that don't exist (yet).
+    fn resolve(&self, addr: &Address, ts: &Tipset) -> Address {
+        self.state_manager
+            .lookup_id(addr, ts)
+            .ok()
+            .flatten()
+            .unwrap_or(*addr)
+    }
+
+    /// Adds the messages included in `ts` to the index and returns the number
+    /// of new entries. Indexing the same tipset again is a no-op.
+    pub fn index_tipset(&self, ts: &Tipset) -> anyhow::Result<usize> {
+        let _guard = self.write_lock.lock();
+        let indexed_key = tipset_key(ts.key())?;
+        if self.settings.exists(&indexed_key)? {
+            return Ok(0);
+        }
+
+        let day = ts.epoch() / EPOCHS_IN_DAY;
+        let block = *ts.min_ticket_block().cid();
+        let mut resolved = HashMap::new();
+        let mut resolve = |addr: Address| {
+            *resolved
+                .entry(addr)
+                .or_insert_with(|| self.resolve(&addr, ts))
+        };
+
+        let mut new_entries: HashMap<(Direction, Address), Vec<IndexEntry>> = HashMap::new();
+        let mut seen = HashSet::default();
+        for msg in self.state_manager.chain_store().messages_for_tipset(ts)? {
+            let message = msg.cid()?;
+            // Blocks of a tipset may include the same message
+            if !seen.insert(message) {
+                continue;
+            }
+            let entry = IndexEntry {
+                epoch: ts.epoch(),
+                message,
+                block,
+            };
+            new_entries
+                .entry((Direction::To, resolve(msg.to())))
+                .or_default()
+                .push(entry.clone());
+            new_entries
+                .entry((Direction::From, resolve(msg.from())))
+                .or_default()
+                .push(entry);
+        }
+
+        let mut added = 0;
+        for ((direction, addr), new_entries) in new_entries {
+            let key = Self::bucket_key(direction, &addr, day);
+            let mut len: u64 = self.settings.read_obj(&key)?.unwrap_or_default();
+            for entry in new_entries {
+                self.settings
+                    .write_obj(&Self::entry_key(&key, len), &entry)?;
+                len += 1;
+                added += 1;
+            }
+            self.settings.write_obj(&key, &len)?;
+        }
+        self.settings.write_obj(&indexed_key, &ts.epoch())?;
+        Ok(added)
+    }
+
+    /// Indexes every tipset from `head` down to `to_epoch` (inclusive) and
+    /// returns the number of new entries.
+    pub fn backfill(&self, head: Arc<Tipset>, to_epoch: ChainEpoch) -> anyhow::Result<usize> {
+        let mut added = 0;
+        for ts in self
+            .state_manager
+            .chain_store()
+            .chain_index
+            .chain(head)
+            .take_while(|ts| ts.epoch() >= to_epoch)
+        {
+            added += self.index_tipset(&ts)?;
+        }
+        Ok(added)
+    }
+
+    /// Returns the messages sent `from` and/or `to` the given addresses that
+    /// were included in the chain of `head` at or after `to_epoch`, newest
+    /// first. Only indexed tipsets are taken into account.
+    pub fn list_messages(
+        &self,
+        from: Option<&Address>,
+        to: Option<&Address>,
+        head: Arc<Tipset>,
+        to_epoch: ChainEpoch,
+    ) -> anyhow::Result<Vec<Cid>> {
+        let epochs = to_epoch.max(0)..=head.epoch();
+        let lookup = |direction, addr: &Address| -> anyhow::Result<Vec<IndexEntry>> {
+            let mut addrs = vec![*addr];
+            let resolved = self.resolve(addr, &head);
+            if resolved != *addr {
+                addrs.push(resolved);
+            }
+            let mut entries = vec![];
+            for day in epochs.start() / EPOCHS_IN_DAY..=epochs.end() / EPOCHS_IN_DAY {
+                for addr in &addrs {
+                    let bucket = self.read_bucket(&Self::bucket_key(direction, addr, day))?;
+                    entries.extend(
+                        bucket
+                            .into_iter()
+                            .filter(|entry| epochs.contains(&entry.epoch)),
+                    );
+                }
+            }
+            Ok(entries)
+        };
+        let mut entries = match (from, to) {
+            (Some(from), Some(to)) => {
+                let to: HashSet<Cid> = lookup(Direction::To, to)?
+                    .into_iter()
+                    .map(|entry| entry.message)
+                    .collect();
+                lookup(Direction::From, from)?
+                    .into_iter()
+                    .filter(|entry| to.contains(&entry.message))
+                    .collect()
+            }
+            (Some(from), None) => lookup(Direction::From, from)?,
+            (None, Some(to)) => lookup(Direction::To, to)?,
+            (None, None) => bail!("must specify at least To or From in message filter"),
+        };
+
+        let chain_index = &self.state_manager.chain_store().chain_index;
+        let mut canonical: HashMap<ChainEpoch, Option<Vec<Cid>>> = HashMap::new();
+        entries.retain(|entry| {
+            canonical
+                .entry(entry.epoch)
+                .or_insert_with(|| {
+                    chain_index
+                        .tipset_by_height(entry.epoch, head.clone(), ResolveNullTipset::TakeOlder)
+                        .ok()
+                        .filter(|ts| ts.epoch() == entry.epoch)
+                        .map(|ts| ts.cids())
+                })
+                .as_ref()
+                .is_some_and(|cids| cids.contains(&entry.block))
+        });
+        entries.sort_by(|a, b| b.epoch.cmp(&a.epoch));
+
+        let mut seen = HashSet::default();
+        Ok(entries
+            .into_iter()
+            .map(|entry| entry.message)
+            .filter(|cid| seen.insert(*cid))
+            .collect())
+    }
+
+    /// Indexes tipsets as they become the heaviest. Tipsets that were missed,
+    /// because the head-change channel lagged or the node was offline, are
+    /// caught up by walking back to the previously indexed head, at most
+    /// chain finality epochs. Older ranges have to be backfilled explicitly.
+    pub async fn run(self: Arc<Self>) -> anyhow::Result<()> {
+        let mut head_changes = self.state_manager.chain_store().publisher().subscribe();
+        loop {
+            match head_changes.recv().await {
+                Ok(HeadChange::Apply(head)) => {
+                    let this = Arc::clone(&self);
+                    tokio::task::spawn_blocking(move || this.catch_up(head)).await??;
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Message index lagged behind by {skipped} head changes");
+                }
+                Err(RecvError::Closed) => return Ok(()),
+            }
+        }
+    }
+
+    fn catch_up(&self, head: Arc<Tipset>) -> anyhow::Result<()> {
+        let chain_index = &self.state_manager.chain_store().chain_index;
+        let last_head = self
+            .settings
+            .read_obj::<TipsetKeys>(INDEX_HEAD_KEY)?
+            .and_then(|tsk| chain_index.load_tipset(&tsk).ok());
+        let is_indexed = |ts: &Tipset| {
+            last_head.as_ref().is_some_and(|last_head| {
+                ts.epoch() <= last_head.epoch()
+                    && chain_index
+                        .tipset_by_height(
+                            ts.epoch(),
+                            last_head.clone(),
+                            ResolveNullTipset::TakeOlder,
+                        )
+                        .is_ok_and(|ancestor| ancestor.key() == ts.key())
+            })
+        };
+
+        let finality = self.state_manager.chain_config().policy.chain_finality;
+        let mut added = 0;
+        for ts in chain_index
+            .chain(head.clone())
+            .take_while(|ts| head.epoch() - ts.epoch() <= finality)
+        {
+            if is_indexed(&ts) {
+                break;
+            }
+            added += self.index_tipset(&ts)?;
+        }
+        self.settings.write_obj(INDEX_HEAD_KEY, head.key())?;
+        debug!("Indexed {added} messages up to epoch {}", head.epoch());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::BlockHeader;
+    use crate::chain::ChainStore;
+    use crate::chain_sync::TipsetValidator;
+    use crate::db::MemoryDB;
+    use crate::networks::ChainConfig;
+    use crate::shim::{
+        message::{Message, Message_v3},
+        state_tree::{StateTree, StateTreeVersion},
+    };
+    use crate::utils::db::CborStoreExt;
+
+    fn setup() -> (Arc<MemoryDB>, MessageIndex<MemoryDB>, Arc<Tipset>) {
+        let db = Arc::new(MemoryDB::default());
+        let state_root = StateTree::new(db.clone(), StateTreeVersion::V5)
+            .unwrap()
+            .flush()
+            .unwrap();
+        let genesis = BlockHeader::builder()
+            .miner_address(Address::new_id(0))
+            .state_root(state_root)
+            .messages(TipsetValidator::compute_msg_root(db.as_ref(), &[], &[]).unwrap())
+            .timestamp(7777)
+            .build()
+            .unwrap();
+        db.put_cbor_default(&genesis).unwrap();
+        let chain_config = Arc::new(ChainConfig::default());
+        let cs = Arc::new(
+            ChainStore::new(
+                db.clone(),
+                db.clone(),
+                chain_config.clone(),
+                genesis.clone(),
+            )
+            .unwrap(),
+        );
+        let sm = Arc::new(StateManager::new(cs, chain_config).unwrap());
+        let index = MessageIndex::new(sm, db.clone());
+        (db, index, Arc::new(Tipset::from(genesis)))
+    }
+
+    /// Stores a block at epoch 1 on top of `parent` that includes a single
+    /// message from `from` to `to`.
+    fn child(db: &MemoryDB, parent: &Tipset, miner: u64, from: u64, to: u64) -> (Arc<Tipset>, Cid) {
+        let msg: Message = Message_v3 {
+            from: Address::new_id(from).into(),
+            to: Address::new_id(to).into(),
+            ..Default::default()
+        }
+        .into();
+        let msg_cid = db.put_cbor_default(&msg).unwrap();
+        let header = BlockHeader::builder()
+            .miner_address(Address::new_id(miner))
+            .parents(parent.key().clone())
+            .epoch(parent.epoch() + 1)
+            .state_root(*parent.parent_state())
+            .messages(TipsetValidator::compute_msg_root(db, &[msg], &[]).unwrap())
+            .build()
+            .unwrap();
+        db.put_cbor_default(&header).unwrap();
+        (Arc::new(Tipset::from(header)), msg_cid)
+    }
+
+    #[test]
+    fn lists_indexed_messages() {
+        let (db, index, genesis) = setup();
+        let (head, msg) = child(&db, &genesis, 1, 100, 101);
+
+        assert_eq!(index.index_tipset(&head).unwrap(), 2);
+        // Indexing the same tipset again doesn't duplicate entries.
+        assert_eq!(index.index_tipset(&head).unwrap(), 0);
+
+        let (from, to, other) = (
+            Address::new_id(100),
+            Address::new_id(101),
+            Address::new_id(102),
+        );
+        let list = |from: Option<&Address>, to: Option<&Address>| {
+            index.list_messages(from, to, head.clone(), 0).unwrap()
+        };
+        assert_eq!(list(Some(&from), None), vec![msg]);
+        assert_eq!(list(None, Some(&to)), vec![msg]);
+        assert_eq!(list(Some(&from), Some(&to)), vec![msg]);
+        assert!(list(Some(&to), None).is_empty());
+        assert!(list(Some(&to), Some(&from)).is_empty());
+        assert!(list(Some(&other), None).is_empty());
+        // Messages before `to_epoch` are skipped.
+        assert!(index
+            .list_messages(Some(&from), None, head.clone(), 2)
+            .unwrap()
+            .is_empty());
+        assert!(index.list_messages(None, None, head, 0).is_err());
+    }
+
+    #[test]
+    fn skips_messages_of_forks() {
+        let (db, index, genesis) = setup();
+        let (head, msg) = child(&db, &genesis, 1, 100, 101);
+        let (fork, fork_msg) = child(&db, &genesis, 2, 100, 102);
+
+        assert_eq!(index.backfill(head.clone(), 0).unwrap(), 2);
+        assert_eq!(index.backfill(fork.clone(), 0).unwrap(), 2);
+
+        let from = Address::new_id(100);
+        assert_eq!(
+            index.list_messages(Some(&from), None, head, 0).unwrap(),
+            vec![msg]
+        );
+        assert_eq!(
+            index.list_messages(Some(&from), None, fork, 0).unwrap(),
+            vec![fork_msg]
+        );
+    }
+}
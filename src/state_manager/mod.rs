@@ -3,7 +3,9 @@
 
 pub mod chain_rand;
 mod errors;
+mod message_index;
 mod metrics;
+mod persisted_states;
 mod utils;
 use crate::interpreter::{MessageCallbackCtx, VMTrace};
 use crate::state_migration::run_state_migrations;
@@ -13,6 +15,8 @@ use rayon::prelude::ParallelBridge;
 pub use utils::is_valid_for_sending;
 mod vm_circ_supply;
 pub use self::errors::*;
+pub use self::message_index::MessageIndex;
+pub use self::persisted_states::SharedTipsetStateStore;
 use crate::beacon::BeaconSchedule;
 use crate::blocks::{Tipset, TipsetKeys};
 use crate::chain::{
@@ -21,6 +25,7 @@ use crate::chain::{
 };
 use crate::interpreter::{resolve_to_key_addr, ExecutionContext, VM};
 use crate::interpreter::{BlockMessages, CalledAt};
+use crate::lotus_json::LotusJson;
 use crate::message::{ChainMessage, Message as MessageTrait};
 use crate::networks::ChainConfig;
 use crate::shim::clock::ChainEpoch;
@@ -46,6 +51,7 @@ use nonzero_ext::nonzero;
 use num::BigInt;
 use num_traits::identities::Zero;
 use parking_lot::Mutex as SyncMutex;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::ops::RangeInclusive;
 use std::{num::NonZeroUsize, sync::Arc};
@@ -76,6 +82,8 @@ impl Default for TipsetStateCacheInner {
 
 struct TipsetStateCache {
     cache: Arc<SyncMutex<TipsetStateCacheInner>>,
+    /// Computed states which survive restarts, looked up on cache misses
+    persisted: Option<SharedTipsetStateStore>,
 }
 
 enum Status {
@@ -87,6 +95,7 @@ impl TipsetStateCache {
     pub fn new() -> Self {
         Self {
             cache: Arc::new(SyncMutex::new(TipsetStateCacheInner::default())),
+            persisted: None,
         }
     }
 
@@ -98,11 +107,19 @@ impl TipsetStateCache {
         func(&mut lock)
     }
 
-    pub async fn get_or_else<F, Fut>(&self, key: &TipsetKeys, compute: F) -> anyhow::Result<CidPair>
+    /// Returns the cached or persisted state of `tipset`, or computes it.
+    /// Persisted states are only used if their blocks are still in `db`.
+    pub async fn get_or_else<F, Fut>(
+        &self,
+        db: &impl Blockstore,
+        tipset: &Tipset,
+        compute: F,
+    ) -> anyhow::Result<CidPair>
     where
         F: Fn() -> Fut,
         Fut: core::future::Future<Output = anyhow::Result<CidPair>>,
     {
+        let key = tipset.key();
         let status = self.with_inner(|inner| match inner.values.get(key) {
             Some(v) => Status::Done(*v),
             None => {
@@ -145,7 +162,36 @@ impl TipsetStateCache {
                             .with_label_values(&[crate::metrics::values::STATE_MANAGER_TIPSET])
                             .inc();
 
-                        let cid_pair = compute().await?;
+                        let persisted = match &self.persisted {
+                            Some(store) => persisted_states::load(store.as_ref(), db, tipset)
+                                .unwrap_or_else(|e| {
+                                    warn!(
+                                        "Dropping unreadable persisted state of tipset at epoch {}: {e:#}",
+                                        tipset.epoch()
+                                    );
+                                    if let Err(e) = persisted_states::remove(store.as_ref(), tipset)
+                                    {
+                                        warn!("Failed to drop persisted tipset state: {e:#}");
+                                    }
+                                    None
+                                }),
+                            None => None,
+                        };
+                        let cid_pair = match persisted {
+                            Some(cid_pair) => cid_pair,
+                            None => {
+                                let cid_pair = compute().await?;
+                                if let Some(store) = &self.persisted {
+                                    // The state is still valid, only a restart would recompute it
+                                    if let Err(e) =
+                                        persisted_states::save(store.as_ref(), tipset, cid_pair)
+                                    {
+                                        warn!("Failed to persist tipset state: {e:#}");
+                                    }
+                                }
+                                cid_pair
+                            }
+                        };
 
                         // Write back to cache, release lock and return value
                         self.insert(key.clone(), cid_pair);
@@ -166,15 +212,21 @@ impl TipsetStateCache {
             inner.values.put(key, value);
         });
     }
+
+    fn remove(&self, key: &TipsetKeys) {
+        self.with_inner(|inner| inner.values.pop(key));
+    }
 }
 
 /// Type to represent invocation of state call results.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct InvocResult {
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Message>")]
     pub msg: Message,
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Option<Receipt>>")]
     pub msg_rct: Option<Receipt>,
     pub error: Option<String>,
 }
@@ -183,10 +235,13 @@ pub struct InvocResult {
 type StateCallResult = Result<InvocResult, Error>;
 
 /// External format for returning market balance from state.
-#[derive(Default, Serialize, Deserialize)]
+/// Note that the amounts are not in Lotus JSON, so are serialized as raw bytes.
+#[derive(Default, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct MarketBalance {
+    #[schemars(with = "Vec<u8>")]
     escrow: TokenAmount,
+    #[schemars(with = "Vec<u8>")]
     locked: TokenAmount,
 }
 
@@ -230,6 +285,13 @@ where
         })
     }
 
+    /// Persists computed tipset states in `store`, and looks them up there
+    /// before computing them.
+    pub fn with_tipset_state_store(mut self, store: SharedTipsetStateStore) -> Self {
+        self.cache.persisted = Some(store);
+        self
+    }
+
     pub fn beacon_schedule(&self) -> Arc<BeaconSchedule> {
         Arc::clone(&self.beacon)
     }
@@ -243,9 +305,10 @@ where
         Arc::clone(&self.chain_config)
     }
 
-    /// Gets actor from given [`Cid`], if it exists.
+    /// Gets actor from given [`Cid`], if it exists. State missing from the
+    /// database is fetched with the state fetcher of the [`ChainStore`].
     pub fn get_actor(&self, addr: &Address, state_cid: Cid) -> anyhow::Result<Option<ActorState>> {
-        let state = StateTree::new_from_root(self.blockstore_owned(), &state_cid)?;
+        let state = StateTree::new_from_root(Arc::new(self.cs.state_blockstore()), &state_cid)?;
         state.get_actor(addr)
     }
 
@@ -345,13 +408,12 @@ where
     DB: Blockstore + Send + Sync + 'static,
 {
     /// Returns the pair of (parent state root, message receipt root). This will
-    /// either be cached or will be calculated and fill the cache. Tipset
-    /// state for a given tipset is guaranteed not to be computed twice.
+    /// either be cached, persisted or will be calculated and fill the cache.
+    /// Tipset state for a given tipset is guaranteed not to be computed twice.
     #[instrument(skip(self))]
     pub async fn tipset_state(self: &Arc<Self>, tipset: &Arc<Tipset>) -> anyhow::Result<CidPair> {
-        let key = tipset.key();
         self.cache
-            .get_or_else(key, || async move {
+            .get_or_else(self.blockstore(), tipset, || async move {
                 let ts_state = self
                     .compute_tipset_state(Arc::clone(tipset), NO_CALLBACK, VMTrace::NotTraced)
                     .await?;
@@ -431,6 +493,7 @@ where
 
     /// Computes message on the given [Tipset] state, after applying other
     /// messages and returns the values computed in the VM.
+    #[instrument(skip_all)]
     pub async fn call_with_gas(
         self: &Arc<Self>,
         message: &mut ChainMessage,
@@ -488,6 +551,7 @@ where
 
     /// Replays the given message and returns the result of executing the
     /// indicated message, assuming it was executed in the indicated tipset.
+    #[instrument(skip(self, ts))]
     pub async fn replay(
         self: &Arc<Self>,
         ts: &Arc<Tipset>,
@@ -609,7 +673,7 @@ where
     ///
     /// For details, see the documentation for [`apply_block_messages`].
     ///
-    #[instrument(skip_all)]
+    #[instrument(skip_all, fields(epoch = tipset.epoch()))]
     pub async fn compute_tipset_state(
         self: &Arc<Self>,
         tipset: Arc<Tipset>,
@@ -617,8 +681,10 @@ where
         enable_tracing: VMTrace,
     ) -> Result<CidPair, Error> {
         let this = Arc::clone(self);
+        // Keep the blocking work in the caller's span, e.g. that of an RPC request
+        let span = tracing::Span::current();
         tokio::task::spawn_blocking(move || {
-            this.compute_tipset_state_blocking(tipset, callback, enable_tracing)
+            span.in_scope(|| this.compute_tipset_state_blocking(tipset, callback, enable_tracing))
         })
         .await?
     }
@@ -1135,6 +1201,146 @@ where
         })
 }
 
+/// A tipset whose computed state differs from the state recorded by its child.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateMismatch {
+    pub epoch: ChainEpoch,
+    /// State and receipt roots recorded by the child tipset
+    pub expected: CidPair,
+    /// State and receipt roots computed by executing the tipset
+    pub actual: CidPair,
+}
+
+/// Validates the tipsets of the chain of `head` with epochs in `epochs`, split
+/// into windows of `window_size` epochs which are validated in parallel.
+///
+/// Only the first tipset of a window is executed from the parent state recorded
+/// in its header. Every following tipset is executed from the state computed for
+/// its parent, and each result is checked against the state recorded by its
+/// child, so a window only trusts the state it starts from. The head itself has
+/// no child and isn't validated. Unlike [`validate_tipsets`], mismatches don't
+/// stop the validation; they are all returned, ordered by epoch. `on_validated`
+/// is called with the epoch of every validated tipset.
+#[allow(clippy::too_many_arguments)]
+pub fn validate_tipset_windows<DB>(
+    genesis_timestamp: u64,
+    chain_index: Arc<ChainIndex<Arc<DB>>>,
+    chain_config: Arc<ChainConfig>,
+    beacon: Arc<BeaconSchedule>,
+    engine: &crate::shim::machine::MultiEngine,
+    head: Arc<Tipset>,
+    epochs: RangeInclusive<ChainEpoch>,
+    window_size: NonZeroUsize,
+    on_validated: impl Fn(ChainEpoch) + Sync,
+) -> anyhow::Result<Vec<StateMismatch>>
+where
+    DB: Blockstore + Send + Sync + 'static,
+{
+    validate_tipset_windows_with(
+        &chain_index,
+        head,
+        epochs,
+        window_size,
+        on_validated,
+        |tipset, parent_state| {
+            apply_block_messages_from(
+                genesis_timestamp,
+                chain_index.clone(),
+                chain_config.clone(),
+                beacon.clone(),
+                engine,
+                tipset,
+                parent_state,
+                NO_CALLBACK,
+                VMTrace::NotTraced,
+            )
+        },
+    )
+}
+
+/// [`validate_tipset_windows`], with `execute` computing the state of a
+/// tipset from the given parent state.
+fn validate_tipset_windows_with<DB>(
+    chain_index: &ChainIndex<Arc<DB>>,
+    head: Arc<Tipset>,
+    epochs: RangeInclusive<ChainEpoch>,
+    window_size: NonZeroUsize,
+    on_validated: impl Fn(ChainEpoch) + Sync,
+    execute: impl Fn(Arc<Tipset>, Cid) -> anyhow::Result<CidPair> + Sync,
+) -> anyhow::Result<Vec<StateMismatch>>
+where
+    DB: Blockstore + Send + Sync + 'static,
+{
+    use rayon::iter::{IntoParallelIterator as _, ParallelIterator as _};
+    let first = (*epochs.start()).max(0);
+    let last = (*epochs.end()).min(head.epoch() - 1);
+    let windows = epoch_windows(first..=last, window_size);
+    let mismatches = windows
+        .into_par_iter()
+        .map(|window| {
+            // The child of the last tipset of the window
+            let child = chain_index.tipset_by_height(
+                window.end() + 1,
+                head.clone(),
+                ResolveNullTipset::TakeNewer,
+            )?;
+            // Pairs of tipsets and their children, from the start of the window
+            let pairs = chain_index
+                .chain(child)
+                .tuple_windows()
+                .take_while(|(_, parent)| parent.epoch() >= *window.start())
+                .collect::<Vec<_>>();
+            let mut mismatches = vec![];
+            let mut state = None;
+            for (child, parent) in pairs.into_iter().rev() {
+                let parent_state = state.unwrap_or(*parent.parent_state());
+                let actual = execute(parent.clone(), parent_state).with_context(|| {
+                    format!("couldn't compute tipset state at epoch {}", parent.epoch())
+                })?;
+                let expected = (
+                    *child.parent_state(),
+                    *child.min_ticket_block().message_receipts(),
+                );
+                if actual != expected {
+                    error!(
+                        height = parent.epoch(),
+                        ?expected,
+                        ?actual,
+                        "state mismatch"
+                    );
+                    mismatches.push(StateMismatch {
+                        epoch: parent.epoch(),
+                        expected,
+                        actual,
+                    });
+                }
+                // Continue from the recorded state, so that a mismatch isn't
+                // carried over to the following epochs
+                state = Some(expected.0);
+                on_validated(parent.epoch());
+            }
+            anyhow::Ok(mismatches)
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(mismatches
+        .into_iter()
+        .flatten()
+        .sorted_by_key(|mismatch| mismatch.epoch)
+        .collect())
+}
+
+/// Splits `epochs` into consecutive windows of at most `window_size` epochs.
+fn epoch_windows(
+    epochs: RangeInclusive<ChainEpoch>,
+    window_size: NonZeroUsize,
+) -> Vec<RangeInclusive<ChainEpoch>> {
+    let last = *epochs.end();
+    epochs
+        .step_by(window_size.get())
+        .map(|start| start..=last.min(start + window_size.get() as ChainEpoch - 1))
+        .collect()
+}
+
 /// Messages are transactions that produce new states. The state (usually
 /// referred to as the 'state-tree') is a mapping from actor addresses to actor
 /// states. Each block contains the hash of the state-tree that should be used
@@ -1219,6 +1425,37 @@ pub fn apply_block_messages<DB>(
     beacon: Arc<BeaconSchedule>,
     engine: &crate::shim::machine::MultiEngine,
     tipset: Arc<Tipset>,
+    callback: Option<impl FnMut(&MessageCallbackCtx) -> anyhow::Result<()>>,
+    enable_tracing: VMTrace,
+) -> Result<CidPair, anyhow::Error>
+where
+    DB: Blockstore + Send + Sync + 'static,
+{
+    let parent_state = *tipset.parent_state();
+    apply_block_messages_from(
+        genesis_timestamp,
+        chain_index,
+        chain_config,
+        beacon,
+        engine,
+        tipset,
+        parent_state,
+        callback,
+        enable_tracing,
+    )
+}
+
+/// Like [`apply_block_messages`], but executes `tipset` from `parent_state`
+/// rather than from the parent state recorded in its header.
+#[allow(clippy::too_many_arguments)]
+fn apply_block_messages_from<DB>(
+    genesis_timestamp: u64,
+    chain_index: Arc<ChainIndex<Arc<DB>>>,
+    chain_config: Arc<ChainConfig>,
+    beacon: Arc<BeaconSchedule>,
+    engine: &crate::shim::machine::MultiEngine,
+    tipset: Arc<Tipset>,
+    mut parent_state: Cid,
     mut callback: Option<impl FnMut(&MessageCallbackCtx) -> anyhow::Result<()>>,
     enable_tracing: VMTrace,
 ) -> Result<CidPair, anyhow::Error>
@@ -1239,7 +1476,7 @@ where
         // magical genesis miner, this won't work properly, so we short circuit here
         // This avoids the question of 'who gets paid the genesis block reward'
         let message_receipts = tipset.min_ticket_block().message_receipts();
-        return Ok((*tipset.parent_state(), *message_receipts));
+        return Ok((parent_state, *message_receipts));
     }
 
     let _timer = metrics::APPLY_BLOCKS_TIME.start_timer();
@@ -1272,8 +1509,6 @@ where
         )
     };
 
-    let mut parent_state = *tipset.parent_state();
-
     let parent_epoch = Tipset::load_required(&chain_index.db, tipset.parents())?.epoch();
     let epoch = tipset.epoch();
 
@@ -1312,3 +1547,92 @@ where
 
     Ok((state_root, receipt_root))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::BlockHeader;
+    use crate::db::MemoryDB;
+    use crate::shim::address::Address;
+    use crate::utils::cid::CidCborExt as _;
+    use crate::utils::db::CborStoreExt as _;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A deterministic stand-in for the VM.
+    fn fake_state(tipset: &Tipset, parent_state: Cid) -> Cid {
+        Cid::from_cbor_blake2b256(&(parent_state, tipset.epoch())).unwrap()
+    }
+
+    /// A chain of `len` tipsets whose recorded states match [`fake_state`].
+    fn fake_chain(db: &MemoryDB, len: ChainEpoch) -> Arc<Tipset> {
+        let mut tipset = Arc::new(Tipset::from(
+            BlockHeader::builder()
+                .miner_address(Address::new_id(0))
+                .build()
+                .unwrap(),
+        ));
+        db.put_cbor_default(tipset.min_ticket_block()).unwrap();
+        for epoch in 1..len {
+            let header = BlockHeader::builder()
+                .miner_address(Address::new_id(0))
+                .parents(tipset.key().clone())
+                .epoch(epoch)
+                .state_root(fake_state(&tipset, *tipset.parent_state()))
+                .build()
+                .unwrap();
+            db.put_cbor_default(&header).unwrap();
+            tipset = Arc::new(Tipset::from(header));
+        }
+        tipset
+    }
+
+    #[test]
+    fn validate_tipset_windows_reports_each_mismatch_once() {
+        let db = Arc::new(MemoryDB::default());
+        let chain_index = ChainIndex::new(db.clone());
+        let head = fake_chain(&db, 10);
+        let validated = AtomicUsize::new(0);
+        let validate = |faulty: Option<ChainEpoch>| {
+            validate_tipset_windows_with(
+                &chain_index,
+                head.clone(),
+                0..=head.epoch(),
+                nonzero!(4usize),
+                |_| {
+                    validated.fetch_add(1, Ordering::Relaxed);
+                },
+                |tipset, parent_state| {
+                    let state = match Some(tipset.epoch()) == faulty {
+                        true => Cid::default(),
+                        false => fake_state(&tipset, parent_state),
+                    };
+                    Ok((state, *tipset.min_ticket_block().message_receipts()))
+                },
+            )
+            .unwrap()
+        };
+
+        assert!(validate(None).is_empty());
+        // Every tipset but the head is validated
+        assert_eq!(validated.swap(0, Ordering::Relaxed), 9);
+
+        // The wrong state isn't carried over to the rest of the window
+        let mismatches = validate(Some(5));
+        assert_eq!(validated.load(Ordering::Relaxed), 9);
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].epoch, 5);
+        assert_eq!(mismatches[0].actual.0, Cid::default());
+    }
+
+    #[test]
+    fn epoch_windows_cover_range() {
+        assert_eq!(
+            epoch_windows(10..=20, nonzero!(4usize)),
+            vec![10..=13, 14..=17, 18..=20]
+        );
+        assert_eq!(epoch_windows(10..=10, nonzero!(4usize)), vec![10..=10]);
+        // The range of a head without children to check against
+        let (first, last) = (10, 9);
+        assert!(epoch_windows(first..=last, nonzero!(4usize)).is_empty());
+    }
+}
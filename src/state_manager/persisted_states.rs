@@ -0,0 +1,347 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Computed tipset states persisted in a [`TipsetStateStore`], so that they
+//! don't have to be recomputed after a restart or when the head is set back to
+//! an older tipset.
+//!
+//! Entries are keyed by the CBOR encoding of the [`TipsetKeys`] and also hold
+//! the parent state of the tipset, which the computation started from. Entries
+//! whose parent state doesn't match the tipset are ignored.
+
+use std::sync::Arc;
+
+use crate::blocks::{Tipset, TipsetKeys};
+use crate::chain::{
+    index::{ChainIndex, ResolveNullTipset},
+    HeadChange,
+};
+use crate::db::TipsetStateStore;
+use crate::shim::clock::ChainEpoch;
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use serde_tuple::{self, Deserialize_tuple, Serialize_tuple};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, warn};
+
+use super::{CidPair, StateManager};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize_tuple, Deserialize_tuple)]
+struct PersistedTipsetState {
+    parent_state: Cid,
+    state_root: Cid,
+    receipt_root: Cid,
+}
+
+/// A [`TipsetStateStore`] shared by the state manager and the node.
+pub type SharedTipsetStateStore = Arc<dyn TipsetStateStore + Sync + Send>;
+
+fn store_key(key: &TipsetKeys) -> anyhow::Result<Vec<u8>> {
+    Ok(fvm_ipld_encoding::to_vec(key)?)
+}
+
+/// Returns the persisted state of `tipset`, if any. Entries whose state or
+/// receipts are no longer in `db`, e.g. after garbage collection, are removed.
+pub(super) fn load(
+    store: &dyn TipsetStateStore,
+    db: &impl Blockstore,
+    tipset: &Tipset,
+) -> anyhow::Result<Option<CidPair>> {
+    let Some(bytes) = store.read_tipset_state(&store_key(tipset.key())?)? else {
+        return Ok(None);
+    };
+    let entry: PersistedTipsetState = fvm_ipld_encoding::from_slice(&bytes)?;
+    if entry.parent_state != *tipset.parent_state() {
+        warn!(
+            "Ignoring persisted state of tipset at epoch {}: parent state mismatch",
+            tipset.epoch()
+        );
+        return Ok(None);
+    }
+    if !db.has(&entry.state_root)? || !db.has(&entry.receipt_root)? {
+        debug!(
+            "Dropping persisted state of tipset at epoch {}: state is missing",
+            tipset.epoch()
+        );
+        remove(store, tipset)?;
+        return Ok(None);
+    }
+    Ok(Some((entry.state_root, entry.receipt_root)))
+}
+
+/// Removes the persisted state of `tipset`, if any.
+pub(super) fn remove(store: &dyn TipsetStateStore, tipset: &Tipset) -> anyhow::Result<()> {
+    store.delete_tipset_state(&store_key(tipset.key())?)
+}
+
+/// Persists the computed state of `tipset`.
+pub(super) fn save(
+    store: &dyn TipsetStateStore,
+    tipset: &Tipset,
+    (state_root, receipt_root): CidPair,
+) -> anyhow::Result<()> {
+    let entry = PersistedTipsetState {
+        parent_state: *tipset.parent_state(),
+        state_root,
+        receipt_root,
+    };
+    store.write_tipset_state(
+        &store_key(tipset.key())?,
+        &fvm_ipld_encoding::to_vec(&entry)?,
+    )
+}
+
+impl<DB> StateManager<DB>
+where
+    DB: Blockstore + Send + Sync + 'static,
+{
+    /// Removes the persisted states of tipsets which were reverted by a switch
+    /// to another fork, at most chain finality epochs deep. Setting the head
+    /// back to one of its ancestors keeps the states, as the same tipsets are
+    /// likely to be applied again.
+    pub async fn prune_reverted_tipset_states(self: Arc<Self>) -> anyhow::Result<()> {
+        let Some(store) = self.cache.persisted.clone() else {
+            return Ok(());
+        };
+        let mut head_changes = self.cs.publisher().subscribe();
+        let mut last_head = self.cs.heaviest_tipset();
+        loop {
+            match head_changes.recv().await {
+                Ok(HeadChange::Apply(head)) => {
+                    let this = Arc::clone(&self);
+                    let store = Arc::clone(&store);
+                    let (reverted, last) = (last_head, head.clone());
+                    let result = tokio::task::spawn_blocking(move || {
+                        this.prune_reverted(store.as_ref(), &reverted, &last)
+                    })
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .and_then(|result| result);
+                    if let Err(e) = result {
+                        warn!("Failed to prune the states of reverted tipsets: {e:#}");
+                    }
+                    last_head = head;
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Tipset state pruning lagged behind by {skipped} head changes");
+                }
+                Err(RecvError::Closed) => return Ok(()),
+            }
+        }
+    }
+
+    fn prune_reverted(
+        &self,
+        store: &dyn TipsetStateStore,
+        old_head: &Arc<Tipset>,
+        new_head: &Arc<Tipset>,
+    ) -> anyhow::Result<()> {
+        let finality = self.chain_config.policy.chain_finality;
+        let pruned = prune_reverted(&self.cs.chain_index, store, finality, old_head, new_head)?;
+        for key in &pruned {
+            self.cache.remove(key);
+        }
+        debug!("Pruned the states of {} reverted tipsets", pruned.len());
+        Ok(())
+    }
+}
+
+/// Removes the persisted states of the tipsets of the chain of `old_head`
+/// which aren't part of the chain of `new_head`, unless `new_head` is an
+/// ancestor of `old_head`. Returns the keys of the pruned tipsets.
+fn prune_reverted<DB: Blockstore>(
+    chain_index: &ChainIndex<Arc<DB>>,
+    store: &dyn TipsetStateStore,
+    finality: ChainEpoch,
+    old_head: &Arc<Tipset>,
+    new_head: &Arc<Tipset>,
+) -> anyhow::Result<Vec<TipsetKeys>> {
+    let is_ancestor = |ts: &Tipset, head: &Arc<Tipset>| {
+        ts.epoch() <= head.epoch()
+            && chain_index
+                .tipset_by_height(ts.epoch(), head.clone(), ResolveNullTipset::TakeOlder)
+                .is_ok_and(|ancestor| ancestor.key() == ts.key())
+    };
+    if is_ancestor(new_head, old_head) {
+        return Ok(vec![]);
+    }
+
+    let mut pruned = vec![];
+    for ts in chain_index
+        .chain(old_head.clone())
+        .take_while(|ts| old_head.epoch() - ts.epoch() <= finality)
+    {
+        if is_ancestor(&ts, new_head) {
+            break;
+        }
+        store.delete_tipset_state(&store_key(ts.key())?)?;
+        pruned.push(ts.key().clone());
+    }
+    Ok(pruned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::TipsetStateCache;
+    use super::*;
+    use crate::blocks::BlockHeader;
+    use crate::db::MemoryDB;
+    use crate::shim::address::Address;
+    use crate::utils::db::CborStoreExt;
+
+    fn child(db: &MemoryDB, parent: &Tipset, miner: u64) -> Arc<Tipset> {
+        let header = BlockHeader::builder()
+            .miner_address(Address::new_id(miner))
+            .parents(parent.key().clone())
+            .epoch(parent.epoch() + 1)
+            .state_root(*parent.parent_state())
+            .build()
+            .unwrap();
+        db.put_cbor_default(&header).unwrap();
+        Arc::new(Tipset::from(header))
+    }
+
+    /// A state and receipt root that are in `db`.
+    fn stored_state(db: &MemoryDB) -> CidPair {
+        (
+            db.put_cbor_default(&"state").unwrap(),
+            db.put_cbor_default(&"receipts").unwrap(),
+        )
+    }
+
+    #[test]
+    fn prunes_states_of_reverted_forks() {
+        let db = Arc::new(MemoryDB::default());
+        let genesis = BlockHeader::builder()
+            .miner_address(Address::new_id(0))
+            .build()
+            .unwrap();
+        db.put_cbor_default(&genesis).unwrap();
+        let chain_index = ChainIndex::new(db.clone());
+
+        let genesis = Tipset::from(genesis);
+        let head = child(&db, &genesis, 1);
+        let head_child = child(&db, &head, 1);
+        let fork = child(&db, &genesis, 2);
+        let state = stored_state(&db);
+        for ts in [&head, &head_child, &fork] {
+            save(db.as_ref(), ts, state).unwrap();
+        }
+        let prune = |old_head, new_head| {
+            prune_reverted(&chain_index, db.as_ref(), 900, old_head, new_head).unwrap()
+        };
+
+        // Rewinding keeps the states
+        assert!(prune(&head_child, &head).is_empty());
+        assert_eq!(
+            load(db.as_ref(), db.as_ref(), &head_child).unwrap(),
+            Some(state)
+        );
+
+        // Switching forks prunes the reverted tipsets only
+        assert_eq!(
+            prune(&head_child, &fork),
+            vec![head_child.key().clone(), head.key().clone()]
+        );
+        assert_eq!(load(db.as_ref(), db.as_ref(), &head).unwrap(), None);
+        assert_eq!(load(db.as_ref(), db.as_ref(), &head_child).unwrap(), None);
+        assert_eq!(load(db.as_ref(), db.as_ref(), &fork).unwrap(), Some(state));
+    }
+
+    #[tokio::test]
+    async fn persisted_states_survive_restarts() {
+        let db = Arc::new(MemoryDB::default());
+        let genesis = Tipset::from(
+            BlockHeader::builder()
+                .miner_address(Address::new_id(0))
+                .build()
+                .unwrap(),
+        );
+        let tipset = child(&db, &genesis, 1);
+        let state = stored_state(&db);
+        let cache = || TipsetStateCache {
+            persisted: Some(db.clone()),
+            ..TipsetStateCache::new()
+        };
+
+        let computed = cache()
+            .get_or_else(db.as_ref(), &tipset, || async { Ok(state) })
+            .await
+            .unwrap();
+        assert_eq!(computed, state);
+        let loaded = cache()
+            .get_or_else(db.as_ref(), &tipset, || async {
+                anyhow::bail!("recomputed")
+            })
+            .await
+            .unwrap();
+        assert_eq!(loaded, state);
+    }
+
+    #[tokio::test]
+    async fn corrupt_persisted_states_are_recomputed() {
+        let db = Arc::new(MemoryDB::default());
+        let genesis = Tipset::from(
+            BlockHeader::builder()
+                .miner_address(Address::new_id(0))
+                .build()
+                .unwrap(),
+        );
+        let tipset = child(&db, &genesis, 1);
+        db.write_tipset_state(&store_key(tipset.key()).unwrap(), b"corrupt")
+            .unwrap();
+        let state = stored_state(&db);
+        let cache = TipsetStateCache {
+            persisted: Some(db.clone()),
+            ..TipsetStateCache::new()
+        };
+
+        let computed = cache
+            .get_or_else(db.as_ref(), &tipset, || async { Ok(state) })
+            .await
+            .unwrap();
+        assert_eq!(computed, state);
+        // The corrupt entry is replaced by the computed state
+        assert_eq!(
+            load(db.as_ref(), db.as_ref(), &tipset).unwrap(),
+            Some(state)
+        );
+    }
+
+    #[tokio::test]
+    async fn collected_persisted_states_are_recomputed() {
+        let db = Arc::new(MemoryDB::default());
+        let genesis = Tipset::from(
+            BlockHeader::builder()
+                .miner_address(Address::new_id(0))
+                .build()
+                .unwrap(),
+        );
+        let tipset = child(&db, &genesis, 1);
+        // The persisted state tree is no longer in the database
+        let collected = (Cid::default(), db.put_cbor_default(&"receipts").unwrap());
+        save(db.as_ref(), &tipset, collected).unwrap();
+        assert_eq!(load(db.as_ref(), db.as_ref(), &tipset).unwrap(), None);
+        assert_eq!(
+            db.read_tipset_state(&store_key(tipset.key()).unwrap())
+                .unwrap(),
+            None
+        );
+
+        save(db.as_ref(), &tipset, collected).unwrap();
+        let state = stored_state(&db);
+        let cache = TipsetStateCache {
+            persisted: Some(db.clone()),
+            ..TipsetStateCache::new()
+        };
+        let computed = cache
+            .get_or_else(db.as_ref(), &tipset, || async { Ok(state) })
+            .await
+            .unwrap();
+        assert_eq!(computed, state);
+        assert_eq!(
+            load(db.as_ref(), db.as_ref(), &tipset).unwrap(),
+            Some(state)
+        );
+    }
+}
@@ -6,6 +6,8 @@ use std::sync::Arc;
 use cid::Cid;
 use fvm_ipld_blockstore::Blockstore;
 
+use crate::shim::address::Address;
+
 use super::{ActorMigration, ActorMigrationInput, ActorMigrationOutput};
 
 /// Migrator which preserves the head CID and provides a fixed result code CID.
@@ -45,3 +47,59 @@ impl<BS: Blockstore> ActorMigration<BS> for DeferredMigrator {
         Ok(None)
     }
 }
+
+/// Migrator which caches the new head of an actor by its address and prior
+/// head, so that actors left untouched since a pre-migration are not migrated
+/// again at the upgrade epoch.
+pub(in crate::state_migration) struct CachedMigrator<M> {
+    new_code_cid: Cid,
+    migrator: M,
+}
+
+impl<BS: Blockstore, M: ActorMigration<BS>> ActorMigration<BS> for CachedMigrator<M> {
+    fn migrate_state(
+        &self,
+        store: &BS,
+        input: ActorMigrationInput,
+    ) -> anyhow::Result<Option<ActorMigrationOutput>> {
+        let cache = input.cache.clone();
+        let new_head =
+            cache.get_or_insert_with(actor_head_key(&input.address, &input.head), || {
+                let output = self.migrator.migrate_state(store, input)?.ok_or_else(|| {
+                    anyhow::anyhow!("cached migrators must produce a new actor state")
+                })?;
+                anyhow::ensure!(
+                    output.new_code_cid == self.new_code_cid,
+                    "unexpected code CID {}, expected {}",
+                    output.new_code_cid,
+                    self.new_code_cid
+                );
+                Ok(output.new_head)
+            })?;
+        Ok(Some(ActorMigrationOutput {
+            new_code_cid: self.new_code_cid,
+            new_head,
+        }))
+    }
+}
+
+/// Wraps a migrator producing actors of the `new_code_cid` code, caching its
+/// results in the [`super::MigrationCache`]. The wrapped migrator must only
+/// depend on the actor address and head.
+pub(in crate::state_migration) fn cached_migrator<BS, M>(
+    new_code_cid: Cid,
+    migrator: M,
+) -> Arc<dyn ActorMigration<BS> + Send + Sync>
+where
+    BS: Blockstore,
+    M: ActorMigration<BS> + Send + Sync + 'static,
+{
+    Arc::new(CachedMigrator {
+        new_code_cid,
+        migrator,
+    })
+}
+
+fn actor_head_key(address: &Address, head: &Cid) -> String {
+    format!("actorHead-{address}-{head}")
+}
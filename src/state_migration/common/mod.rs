@@ -4,7 +4,7 @@
 //! Common code that's shared across all migration code.
 //! Each network upgrade / state migration code lives in their own module.
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use crate::shim::{address::Address, clock::ChainEpoch, econ::TokenAmount, state_tree::StateTree};
 use ahash::HashMap;
@@ -17,7 +17,7 @@ pub(in crate::state_migration) mod migrators;
 mod state_migration;
 pub(in crate::state_migration) mod verifier;
 
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 pub(in crate::state_migration) use state_migration::StateMigration;
 pub(in crate::state_migration) type Migrator<BS> = Arc<dyn ActorMigration<BS> + Send + Sync>;
 
@@ -48,6 +48,66 @@ impl MigrationCache {
     pub fn insert(&self, key: String, value: Cid) {
         self.cache.write().insert(key, value);
     }
+
+    /// Checks that the blocks of all cached CIDs are in `store`.
+    pub fn all_in_store(&self, store: &impl Blockstore) -> anyhow::Result<bool> {
+        for cid in self.cache.read().values() {
+            if !store.has(cid)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Settings of a single state migration run.
+#[derive(Clone)]
+pub(in crate::state_migration) struct MigrationOptions {
+    /// Results of previous runs to reuse, e.g., from a pre-migration
+    pub cache: MigrationCache,
+    /// Whether to check the migration specification against the input state
+    pub verify: bool,
+    /// Per actor code statistics, collected if set
+    pub stats: Option<Arc<MigrationStats>>,
+}
+
+impl Default for MigrationOptions {
+    fn default() -> Self {
+        Self {
+            cache: Default::default(),
+            verify: true,
+            stats: None,
+        }
+    }
+}
+
+/// Number of actors migrated and time spent migrating them, per prior actor
+/// code.
+#[derive(Default)]
+pub struct MigrationStats {
+    by_code: Mutex<HashMap<Cid, (usize, Duration)>>,
+}
+
+impl MigrationStats {
+    pub(in crate::state_migration) fn record(&self, code: Cid, elapsed: Duration) {
+        let mut by_code = self.by_code.lock();
+        let (count, total) = by_code.entry(code).or_default();
+        *count += 1;
+        *total += elapsed;
+    }
+
+    /// Returns the actor codes with the number of actors and the cumulative
+    /// migration time, slowest first.
+    pub fn by_code(&self) -> Vec<(Cid, usize, Duration)> {
+        let mut by_code = self
+            .by_code
+            .lock()
+            .iter()
+            .map(|(code, (count, total))| (*code, *count, *total))
+            .collect::<Vec<_>>();
+        by_code.sort_by(|a, b| b.2.cmp(&a.2));
+        by_code
+    }
 }
 
 #[allow(dead_code)] // future migrations might need the fields.
@@ -104,9 +164,10 @@ pub(in crate::state_migration) struct TypeMigrator;
 
 #[cfg(test)]
 mod tests {
-    use super::MigrationCache;
+    use super::{MigrationCache, MigrationStats};
     use crate::utils::cid::CidCborExt;
     use cid::Cid;
+    use std::time::Duration;
 
     #[test]
     fn test_migration_cache() {
@@ -132,4 +193,22 @@ mod tests {
         assert_eq!(value, cid);
         assert_eq!(cache.get("Dagon"), Some(cid));
     }
+
+    #[test]
+    fn test_migration_stats() {
+        let stats = MigrationStats::default();
+        let miner = Cid::from_cbor_blake2b256(&"miner").unwrap();
+        let account = Cid::from_cbor_blake2b256(&"account").unwrap();
+        stats.record(account, Duration::from_millis(1));
+        stats.record(miner, Duration::from_millis(5));
+        stats.record(account, Duration::from_millis(2));
+        stats.record(miner, Duration::from_millis(5));
+        assert_eq!(
+            stats.by_code(),
+            vec![
+                (miner, 2, Duration::from_millis(10)),
+                (account, 2, Duration::from_millis(3)),
+            ]
+        );
+    }
 }
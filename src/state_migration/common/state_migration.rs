@@ -1,11 +1,11 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Instant};
 
 use crate::cid_collections::CidHashMap;
 use crate::shim::{clock::ChainEpoch, state_tree::StateTree};
-use crate::state_migration::common::MigrationCache;
+use crate::state_migration::common::MigrationOptions;
 use cid::Cid;
 use fvm_ipld_blockstore::Blockstore;
 
@@ -52,22 +52,25 @@ impl<BS: Blockstore> StateMigration<BS> {
 }
 
 impl<BS: Blockstore + Send + Sync> StateMigration<BS> {
+    /// Migrates every actor of `actors_in` into `actors_out`. Migrators may
+    /// reuse results from the cache of `options`, e.g., left there by a
+    /// pre-migration.
     pub(in crate::state_migration) fn migrate_state_tree(
         &self,
         store: &Arc<BS>,
         prior_epoch: ChainEpoch,
         actors_in: StateTree<BS>,
         mut actors_out: StateTree<BS>,
+        options: &MigrationOptions,
     ) -> anyhow::Result<Cid> {
         // Checks if the migration specification is correct
-        if let Some(verifier) = &self.verifier {
+        if let Some(verifier) = self.verifier.as_ref().filter(|_| options.verify) {
             verifier.verify_migration(store, &self.migrations, &actors_in)?;
         }
 
         // we need at least 3 threads for the migration to work
         let threads = num_cpus::get().max(3);
         let chan_size = threads / 2;
-        let cache = MigrationCache::default();
 
         tracing::info!("Using {threads} threads for migration and channel size of {chan_size}",);
 
@@ -95,19 +98,25 @@ impl<BS: Blockstore + Send + Sync> StateMigration<BS> {
                 while let Ok((address, state)) = state_rx.recv() {
                     let job_tx = job_tx.clone();
                     let migrator = self.migrations.get(&state.code).cloned().unwrap_or_else(|| panic!("migration failed with state code: {}", state.code));
-                    let cache_clone = cache.clone();
+                    let cache_clone = options.cache.clone();
+                    let stats = options.stats.clone();
                     scope.spawn(move |_| {
+                        let code = state.code;
                         let job = MigrationJob {
                             address,
                             actor_state: state,
                             actor_migration: migrator,
                         };
 
+                        let start_time = Instant::now();
                         let job_output = job.run(store, prior_epoch, cache_clone).unwrap_or_else(|e| {
                             panic!(
                                 "failed executing job for address: {address}, Reason: {e}"
                             )
                         });
+                        if let Some(stats) = stats {
+                            stats.record(code, start_time.elapsed());
+                        }
 
                         job_tx.send(job_output).unwrap_or_else(|_| {
                             panic!("failed sending job output for address: {address}")
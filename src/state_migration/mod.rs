@@ -10,6 +10,7 @@ use crate::networks::{ChainConfig, Height};
 use crate::shim::clock::ChainEpoch;
 use crate::shim::state_tree::StateRoot;
 use crate::utils::misc::reveal_five_trees;
+use anyhow::Context as _;
 use cid::Cid;
 use fvm_ipld_blockstore::Blockstore;
 use fvm_ipld_encoding::CborStore;
@@ -19,9 +20,44 @@ mod nv17;
 mod nv18;
 mod nv19;
 mod nv21;
+mod pre_migration;
 mod type_migrations;
 
-type RunMigration<DB> = fn(&ChainConfig, &Arc<DB>, &Cid, ChainEpoch) -> anyhow::Result<Cid>;
+pub use common::MigrationStats;
+use common::{MigrationCache, MigrationOptions};
+pub use pre_migration::run_pre_migrations;
+use pre_migration::PreMigrationSchedule;
+
+type RunMigration<DB> =
+    fn(&ChainConfig, &Arc<DB>, &Cid, ChainEpoch, &MigrationOptions) -> anyhow::Result<Cid>;
+
+fn get_migrations<DB>() -> [(Height, RunMigration<DB>); 4]
+where
+    DB: Blockstore + Send + Sync,
+{
+    [
+        (Height::Shark, nv17::run_migration::<DB>),
+        (Height::Hygge, nv18::run_migration::<DB>),
+        (Height::Lightning, nv19::run_migration::<DB>),
+        (Height::Watermelon, nv21::run_migration::<DB>),
+    ]
+}
+
+/// Network upgrades whose migration is run ahead of time, see
+/// [`run_pre_migrations`]. The schedules follow Lotus.
+fn get_pre_migrations<DB>() -> [(Height, RunMigration<DB>, PreMigrationSchedule); 1]
+where
+    DB: Blockstore + Send + Sync,
+{
+    [(
+        Height::Watermelon,
+        nv21::run_migration::<DB>,
+        PreMigrationSchedule {
+            start_within: 120,
+            dont_start_within: 15,
+        },
+    )]
+}
 
 /// Run state migrations
 pub fn run_state_migrations<DB>(
@@ -33,12 +69,7 @@ pub fn run_state_migrations<DB>(
 where
     DB: Blockstore + Send + Sync,
 {
-    let mappings: [(_, RunMigration<DB>); 4] = [
-        (Height::Shark, nv17::run_migration::<DB>),
-        (Height::Hygge, nv18::run_migration::<DB>),
-        (Height::Lightning, nv19::run_migration::<DB>),
-        (Height::Watermelon, nv21::run_migration::<DB>),
-    ];
+    let mappings = get_migrations::<DB>();
 
     // Make sure bundle is defined.
     static BUNDLE_CHECKED: AtomicBool = AtomicBool::new(false);
@@ -61,7 +92,21 @@ where
         if epoch == chain_config.epoch(height) {
             tracing::info!("Running {height} migration at epoch {epoch}");
             let start_time = std::time::Instant::now();
-            let new_state = migrate(chain_config, db, parent_state, epoch)?;
+            // Blocks written by the pre-migration are not reachable from the
+            // chain, so they might have been garbage collected in the meantime.
+            let cache = match pre_migration::take_cache(chain_config, height) {
+                Some(cache) if cache.all_in_store(db.as_ref())? => cache,
+                Some(_) => {
+                    tracing::warn!("Discarding the {height} pre-migration cache as its blocks have been garbage collected");
+                    MigrationCache::default()
+                }
+                None => MigrationCache::default(),
+            };
+            let options = MigrationOptions {
+                cache,
+                ..Default::default()
+            };
+            let new_state = migrate(chain_config, db, parent_state, epoch, &options)?;
             let elapsed = start_time.elapsed().as_secs_f32();
             // `new_state_actors` is the Go state migration output, log for comparision
             let new_state_actors = db
@@ -84,5 +129,38 @@ where
     Ok(None)
 }
 
+/// Runs the migration of the network upgrade at `height` on `state`,
+/// regardless of the current epoch, e.g., to rehearse an upgrade on a
+/// snapshot. Returns the new state root along with per actor code statistics.
+pub fn run_state_migration<DB>(
+    height: Height,
+    chain_config: &ChainConfig,
+    db: &Arc<DB>,
+    state: &Cid,
+    verify: bool,
+) -> anyhow::Result<(Cid, Arc<MigrationStats>)>
+where
+    DB: Blockstore + Send + Sync,
+{
+    let (_, migrate) = get_migrations::<DB>()
+        .into_iter()
+        .find(|(it, _)| *it == height)
+        .with_context(|| format!("no state migration at height {height}"))?;
+    let stats = Arc::new(MigrationStats::default());
+    let options = MigrationOptions {
+        verify,
+        stats: Some(Arc::clone(&stats)),
+        ..Default::default()
+    };
+    let new_state = migrate(
+        chain_config,
+        db,
+        state,
+        chain_config.epoch(height),
+        &options,
+    )?;
+    Ok((new_state, stats))
+}
+
 #[cfg(test)]
 mod tests;
@@ -17,7 +17,7 @@ use fvm_ipld_encoding::CborStore as _;
 
 use super::super::common::{
     migrators::{nil_migrator, DeferredMigrator},
-    StateMigration,
+    MigrationOptions, StateMigration,
 };
 use super::{
     datacap, miner, system, util::get_pending_verified_deals_and_total_size, verifier::Verifier,
@@ -134,11 +134,12 @@ impl<BS: Blockstore + Send + Sync> StateMigration<BS> {
 }
 
 /// Runs the migration for `NV17`. Returns the new state root.
-pub fn run_migration<DB>(
+pub(in crate::state_migration) fn run_migration<DB>(
     chain_config: &ChainConfig,
     blockstore: &Arc<DB>,
     state: &Cid,
     epoch: ChainEpoch,
+    options: &MigrationOptions,
 ) -> anyhow::Result<Cid>
 where
     DB: Blockstore + Send + Sync,
@@ -176,7 +177,8 @@ where
 
     let actors_out = StateTree::new(blockstore.clone(), StateTreeVersion::V4)?;
 
-    let new_state = migration.migrate_state_tree(blockstore, epoch, actors_in, actors_out)?;
+    let new_state =
+        migration.migrate_state_tree(blockstore, epoch, actors_in, actors_out, options)?;
 
     Ok(new_state)
 }
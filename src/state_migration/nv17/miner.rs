@@ -616,15 +616,27 @@ mod tests {
         if let Some(bundle) = &mut chain_config.height_infos[Height::Shark as usize].bundle {
             *bundle = new_manifest_cid;
         }
-        let new_state_cid =
-            super::super::run_migration(&chain_config, &store, &tree_root, 200).unwrap();
+        let new_state_cid = super::super::run_migration(
+            &chain_config,
+            &store,
+            &tree_root,
+            200,
+            &Default::default(),
+        )
+        .unwrap();
         let actors_out_state_root: StateRoot = store.get_cbor(&new_state_cid).unwrap().unwrap();
         assert_eq!(
             actors_out_state_root.actors.to_string(),
             "bafy2bzacedgtk3lnnyfxnzc32etqaj3zvi7ar7nxq2jtxd2qr36ftbsjoycqu"
         );
-        let new_state_cid2 =
-            super::super::run_migration(&chain_config, &store, &tree_root, 200).unwrap();
+        let new_state_cid2 = super::super::run_migration(
+            &chain_config,
+            &store,
+            &tree_root,
+            200,
+            &Default::default(),
+        )
+        .unwrap();
         assert_eq!(new_state_cid, new_state_cid2);
     }
 
@@ -649,8 +661,14 @@ mod tests {
         if let Some(bundle) = &mut chain_config.height_infos[Height::Shark as usize].bundle {
             *bundle = new_manifest_cid;
         }
-        let new_state_cid =
-            super::super::run_migration(&chain_config, &store, &state_tree_old_root, 200).unwrap();
+        let new_state_cid = super::super::run_migration(
+            &chain_config,
+            &store,
+            &state_tree_old_root,
+            200,
+            &Default::default(),
+        )
+        .unwrap();
         let actors_out_state_root: StateRoot = store.get_cbor(&new_state_cid).unwrap().unwrap();
         assert_eq!(
             actors_out_state_root.actors.to_string(),
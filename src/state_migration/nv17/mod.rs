@@ -13,7 +13,7 @@ mod verifreg_market;
 
 /// Run migration for `NV17`. This should be the only exported method in this
 /// module.
-pub use migration::run_migration;
+pub(in crate::state_migration) use migration::run_migration;
 
 use crate::{define_system_states, impl_system, impl_verifier};
 
@@ -19,7 +19,7 @@ use super::{
     eam::EamPostMigrator, eth_account::EthAccountPostMigrator, init, system, verifier::Verifier,
     SystemStateOld,
 };
-use crate::state_migration::common::{migrators::nil_migrator, StateMigration};
+use crate::state_migration::common::{migrators::nil_migrator, MigrationOptions, StateMigration};
 impl<BS: Blockstore> StateMigration<BS> {
     pub fn add_nv18_migrations(
         &mut self,
@@ -65,11 +65,12 @@ impl<BS: Blockstore> StateMigration<BS> {
 }
 
 /// Runs the migration for `NV18`. Returns the new state root.
-pub fn run_migration<DB>(
+pub(in crate::state_migration) fn run_migration<DB>(
     chain_config: &ChainConfig,
     blockstore: &Arc<DB>,
     state: &Cid,
     epoch: ChainEpoch,
+    options: &MigrationOptions,
 ) -> anyhow::Result<Cid>
 where
     DB: Blockstore + Send + Sync,
@@ -97,7 +98,8 @@ where
 
     let actors_in = StateTree::new_from_root(blockstore.clone(), state)?;
     let actors_out = StateTree::new(blockstore.clone(), StateTreeVersion::V5)?;
-    let new_state = migration.migrate_state_tree(blockstore, epoch, actors_in, actors_out)?;
+    let new_state =
+        migration.migrate_state_tree(blockstore, epoch, actors_in, actors_out, options)?;
 
     Ok(new_state)
 }
@@ -12,7 +12,7 @@ mod migration;
 
 /// Run migration for `NV18`. This should be the only exported method in this
 /// module.
-pub use migration::run_migration;
+pub(in crate::state_migration) use migration::run_migration;
 
 use crate::{define_system_states, impl_system, impl_verifier};
 
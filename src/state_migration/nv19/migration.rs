@@ -16,7 +16,7 @@ use fvm_ipld_blockstore::Blockstore;
 use fvm_ipld_encoding::CborStore as _;
 
 use super::{miner, power, system, verifier::Verifier, SystemStateOld};
-use crate::state_migration::common::{migrators::nil_migrator, StateMigration};
+use crate::state_migration::common::{migrators::nil_migrator, MigrationOptions, StateMigration};
 
 impl<BS: Blockstore> StateMigration<BS> {
     pub fn add_nv19_migrations(
@@ -64,11 +64,12 @@ impl<BS: Blockstore> StateMigration<BS> {
 }
 
 /// Runs the migration for `NV19`. Returns the new state root.
-pub fn run_migration<DB>(
+pub(in crate::state_migration) fn run_migration<DB>(
     chain_config: &ChainConfig,
     blockstore: &Arc<DB>,
     state: &Cid,
     epoch: ChainEpoch,
+    options: &MigrationOptions,
 ) -> anyhow::Result<Cid>
 where
     DB: Blockstore + Send + Sync,
@@ -96,7 +97,8 @@ where
 
     let actors_in = StateTree::new_from_root(blockstore.clone(), state)?;
     let actors_out = StateTree::new(blockstore.clone(), StateTreeVersion::V5)?;
-    let new_state = migration.migrate_state_tree(blockstore, epoch, actors_in, actors_out)?;
+    let new_state =
+        migration.migrate_state_tree(blockstore, epoch, actors_in, actors_out, options)?;
 
     Ok(new_state)
 }
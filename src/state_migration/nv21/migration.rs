@@ -18,7 +18,10 @@ use fvm_ipld_blockstore::Blockstore;
 use fvm_ipld_encoding::CborStore;
 
 use super::{miner, system, verifier::Verifier, SystemStateOld};
-use crate::state_migration::common::{migrators::nil_migrator, StateMigration};
+use crate::state_migration::common::{
+    migrators::{cached_migrator, nil_migrator},
+    MigrationOptions, StateMigration,
+};
 
 impl<BS: Blockstore> StateMigration<BS> {
     pub fn add_nv21_migrations(
@@ -81,9 +84,14 @@ impl<BS: Blockstore> StateMigration<BS> {
         let miner_old_code = current_manifest.get(BuiltinActor::Miner)?;
         let miner_new_code = new_manifest.get(BuiltinActor::Miner)?;
 
+        // Miner states are cached by head so that a pre-migration saves the
+        // work for miners left untouched until the upgrade epoch.
         self.add_migrator(
             miner_old_code,
-            miner::miner_migrator(&policy_old, &policy_new, store, miner_new_code)?,
+            cached_migrator(
+                miner_new_code,
+                miner::miner_migrator(&policy_old, &policy_new, store, miner_new_code)?,
+            ),
         );
 
         self.add_migrator(
@@ -96,11 +104,12 @@ impl<BS: Blockstore> StateMigration<BS> {
 }
 
 /// Runs the migration for `NV21`. Returns the new state root.
-pub fn run_migration<DB>(
+pub(in crate::state_migration) fn run_migration<DB>(
     chain_config: &ChainConfig,
     blockstore: &Arc<DB>,
     state: &Cid,
     epoch: ChainEpoch,
+    options: &MigrationOptions,
 ) -> anyhow::Result<Cid>
 where
     DB: Blockstore + Send + Sync,
@@ -126,7 +135,8 @@ where
 
     let actors_in = StateTree::new_from_root(blockstore.clone(), state)?;
     let actors_out = StateTree::new(blockstore.clone(), StateTreeVersion::V5)?;
-    let new_state = migration.migrate_state_tree(blockstore, epoch, actors_in, actors_out)?;
+    let new_state =
+        migration.migrate_state_tree(blockstore, epoch, actors_in, actors_out, options)?;
 
     Ok(new_state)
 }
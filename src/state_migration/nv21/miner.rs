@@ -40,7 +40,7 @@ pub(in crate::state_migration) fn miner_migrator<BS: Blockstore>(
     policy_new: &PolicyNew,
     store: &Arc<BS>,
     out_cid: Cid,
-) -> anyhow::Result<Arc<dyn ActorMigration<BS> + Send + Sync>> {
+) -> anyhow::Result<MinerMigrator> {
     let empty_deadline_v11 = DeadlineOld::new(store)?;
     let empty_deadline_v11 = store.put_cbor_default(&empty_deadline_v11)?;
 
@@ -53,14 +53,14 @@ pub(in crate::state_migration) fn miner_migrator<BS: Blockstore>(
     let empty_deadlines_v12 = DeadlinesNew::new(policy_new, empty_deadline_v12);
     let empty_deadlines_v12 = store.put_cbor_default(&empty_deadlines_v12)?;
 
-    Ok(Arc::new(MinerMigrator {
+    Ok(MinerMigrator {
         empty_deadline_v11,
         empty_deadlines_v11,
         empty_deadline_v12,
         empty_deadlines_v12,
         policy_new: policy_new.clone(),
         out_cid,
-    }))
+    })
 }
 
 impl<BS: Blockstore> ActorMigration<BS> for MinerMigrator {
@@ -301,6 +301,7 @@ mod tests {
         machine::{BuiltinActor, BuiltinActorManifest},
         state_tree::{ActorState, StateTree, StateTreeVersion},
     };
+    use crate::state_migration::common::MigrationOptions;
     use cid::multihash::MultihashDigest;
     use fvm_ipld_encoding::IPLD_RAW;
     use fvm_shared2::bigint::Zero;
@@ -368,11 +369,23 @@ mod tests {
         if let Some(bundle) = &mut chain_config.height_infos[Height::Watermelon as usize].bundle {
             *bundle = new_manifest_cid;
         }
-        let new_state_cid =
-            super::super::run_migration(&chain_config, &store, &tree_root, 200).unwrap();
+        let new_state_cid = super::super::run_migration(
+            &chain_config,
+            &store,
+            &tree_root,
+            200,
+            &Default::default(),
+        )
+        .unwrap();
 
-        let new_state_cid2 =
-            super::super::run_migration(&chain_config, &store, &tree_root, 200).unwrap();
+        let new_state_cid2 = super::super::run_migration(
+            &chain_config,
+            &store,
+            &tree_root,
+            200,
+            &Default::default(),
+        )
+        .unwrap();
 
         assert_eq!(new_state_cid, new_state_cid2);
 
@@ -405,6 +418,84 @@ mod tests {
             .unwrap();
     }
 
+    #[test]
+    fn test_nv21_miner_pre_migration() {
+        let store = Arc::new(crate::db::MemoryDB::default());
+        let (mut state_tree, manifest_old) = make_input_tree(&store);
+        let miner_cid_old = manifest_old.get(BuiltinActor::Miner).unwrap();
+
+        let set_miner = |state_tree: &mut StateTree<_>, addr_id, sector_count| {
+            let mut miner_state = make_base_miner_state(&store, addr_id, addr_id + 100);
+            let mut sectors =
+                ArrayOld::<fil_actor_miner_state::v11::SectorOnChainInfo, _>::new_with_bit_width(
+                    &store,
+                    fil_actor_miner_state::v11::SECTORS_AMT_BITWIDTH,
+                );
+            for sector_number in 0..sector_count {
+                sectors
+                    .set(
+                        sector_number,
+                        fil_actor_miner_state::v11::SectorOnChainInfo {
+                            sector_number,
+                            ..Default::default()
+                        },
+                    )
+                    .unwrap();
+            }
+            miner_state.sectors = sectors.flush().unwrap();
+            let head = store.put_cbor_default(&miner_state).unwrap();
+            let miner = ActorState::new(miner_cid_old, head, Zero::zero(), 0, None);
+            state_tree
+                .set_actor(&Address::new_id(addr_id), miner)
+                .unwrap();
+            state_tree.flush().unwrap()
+        };
+        set_miner(&mut state_tree, 10000, 2);
+        let pre_migration_root = set_miner(&mut state_tree, 20000, 2);
+        // Only the second miner changes between the pre-migration and the upgrade.
+        let upgrade_root = set_miner(&mut state_tree, 20000, 3);
+
+        let (new_manifest_cid, _new_manifest) = make_test_manifest(&store, "fil/12/");
+        let mut chain_config = ChainConfig::devnet();
+        if let Some(bundle) = &mut chain_config.height_infos[Height::Watermelon as usize].bundle {
+            *bundle = new_manifest_cid;
+        }
+
+        let options = MigrationOptions::default();
+        super::super::run_migration(&chain_config, &store, &pre_migration_root, 100, &options)
+            .unwrap();
+        let cache = &options.cache;
+        let cached_head = cache
+            .get(&format!("actorHead-{}-{}", Address::new_id(10000), {
+                let tree = StateTree::new_from_root(store.clone(), &pre_migration_root).unwrap();
+                tree.get_actor(&Address::new_id(10000))
+                    .unwrap()
+                    .unwrap()
+                    .state
+            }))
+            .unwrap();
+
+        let with_cache =
+            super::super::run_migration(&chain_config, &store, &upgrade_root, 200, &options)
+                .unwrap();
+        let from_scratch = super::super::run_migration(
+            &chain_config,
+            &store,
+            &upgrade_root,
+            200,
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(with_cache, from_scratch);
+
+        let new_state_tree = StateTree::new_from_root(store.clone(), &with_cache).unwrap();
+        let new_miner = new_state_tree
+            .get_actor(&Address::new_id(10000))
+            .unwrap()
+            .unwrap();
+        assert_eq!(new_miner.state, cached_head);
+    }
+
     fn make_input_tree<BS: Blockstore>(store: &Arc<BS>) -> (StateTree<BS>, BuiltinActorManifest) {
         let mut tree = StateTree::new(store.clone(), StateTreeVersion::V5).unwrap();
 
@@ -10,7 +10,7 @@ mod miner;
 
 /// Run migration for `NV21`. This should be the only exported method in this
 /// module.
-pub use migration::run_migration;
+pub(in crate::state_migration) use migration::run_migration;
 
 use crate::{define_system_states, impl_system, impl_verifier};
 
@@ -0,0 +1,152 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Pre-migrations run the state migration of an upcoming network upgrade in
+//! the background, against the state of a recent head. Migrators keep their
+//! results in a [`MigrationCache`] which the migration at the upgrade epoch
+//! then reuses, so that only the actors changed in between are migrated again.
+
+use std::sync::Arc;
+
+use ahash::{HashMap, HashSet, HashSetExt as _};
+use fvm_ipld_blockstore::Blockstore;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{info, warn};
+
+use super::{
+    common::{MigrationCache, MigrationOptions},
+    get_pre_migrations,
+};
+use crate::chain::HeadChange;
+use crate::networks::{ChainConfig, Height};
+use crate::shim::clock::ChainEpoch;
+
+/// When to run the pre-migration of a network upgrade, in epochs before the
+/// upgrade.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct PreMigrationSchedule {
+    /// Start the pre-migration once the head is this close to the upgrade.
+    pub start_within: ChainEpoch,
+    /// Do not start the pre-migration anymore once the head is this close to
+    /// the upgrade, as it would not finish in time.
+    pub dont_start_within: ChainEpoch,
+}
+
+impl PreMigrationSchedule {
+    fn should_start(&self, upgrade_epoch: ChainEpoch, head_epoch: ChainEpoch) -> bool {
+        let remaining = upgrade_epoch - head_epoch;
+        remaining <= self.start_within && remaining > self.dont_start_within
+    }
+}
+
+/// Caches left by pre-migrations, keyed by network name and upgrade.
+#[derive(Default)]
+struct PreMigrationCaches {
+    /// Caches of finished pre-migrations
+    caches: HashMap<(String, Height), MigrationCache>,
+    /// Upgrades which have been migrated already
+    migrated: HashSet<(String, Height)>,
+}
+
+static PRE_MIGRATION_CACHES: Lazy<Mutex<PreMigrationCaches>> = Lazy::new(Default::default);
+
+/// Takes the cache of the pre-migration for `height`, if one has finished, and
+/// marks the upgrade as migrated so that later pre-migrations don't keep their
+/// cache around.
+pub(super) fn take_cache(chain_config: &ChainConfig, height: Height) -> Option<MigrationCache> {
+    let key = (chain_config.network.to_string(), height);
+    let mut caches = PRE_MIGRATION_CACHES.lock();
+    caches.migrated.insert(key.clone());
+    caches.caches.remove(&key)
+}
+
+fn put_cache(chain_config: &ChainConfig, height: Height, cache: MigrationCache) {
+    let key = (chain_config.network.to_string(), height);
+    let mut caches = PRE_MIGRATION_CACHES.lock();
+    if !caches.migrated.contains(&key) {
+        caches.caches.insert(key, cache);
+    }
+}
+
+/// Runs the pre-migrations of upcoming network upgrades as new heads get close
+/// to them. Each pre-migration runs at most once.
+pub async fn run_pre_migrations<DB>(
+    chain_config: Arc<ChainConfig>,
+    db: Arc<DB>,
+    mut head_changes: broadcast::Receiver<HeadChange>,
+) -> anyhow::Result<()>
+where
+    DB: Blockstore + Send + Sync + 'static,
+{
+    let mut started = HashSet::new();
+    loop {
+        let head = match head_changes.recv().await {
+            Ok(HeadChange::Apply(head)) => head,
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => return Ok(()),
+        };
+        for (height, migrate, schedule) in get_pre_migrations::<DB>() {
+            if !schedule.should_start(chain_config.epoch(height), head.epoch())
+                || !started.insert(height)
+            {
+                continue;
+            }
+            let epoch = head.epoch();
+            let state = *head.parent_state();
+            info!("Running {height} pre-migration at epoch {epoch}");
+            let chain_config = Arc::clone(&chain_config);
+            let db = Arc::clone(&db);
+            tokio::task::spawn_blocking(move || {
+                let start_time = std::time::Instant::now();
+                let options = MigrationOptions::default();
+                match migrate(&chain_config, &db, &state, epoch, &options) {
+                    Ok(_) => {
+                        info!(
+                            "{height} pre-migration at epoch {epoch} took {}s",
+                            start_time.elapsed().as_secs_f32()
+                        );
+                        put_cache(&chain_config, height, options.cache);
+                    }
+                    Err(e) => warn!("{height} pre-migration at epoch {epoch} failed: {e:#}"),
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::cid::CidCborExt as _;
+    use cid::Cid;
+
+    #[test]
+    fn schedule() {
+        let schedule = PreMigrationSchedule {
+            start_within: 120,
+            dont_start_within: 15,
+        };
+        assert!(!schedule.should_start(1000, 879));
+        assert!(schedule.should_start(1000, 880));
+        assert!(schedule.should_start(1000, 984));
+        assert!(!schedule.should_start(1000, 985));
+        assert!(!schedule.should_start(1000, 1001));
+    }
+
+    #[test]
+    fn cache_is_dropped_once_migrated() {
+        let chain_config = ChainConfig::devnet();
+        let cache = MigrationCache::default();
+        cache.insert("key".into(), Cid::from_cbor_blake2b256(&42).unwrap());
+
+        put_cache(&chain_config, Height::Watermelon, cache);
+        let cache = take_cache(&chain_config, Height::Watermelon).unwrap();
+        assert!(cache.get("key").is_some());
+
+        // A pre-migration finishing after the upgrade doesn't keep its cache.
+        put_cache(&chain_config, Height::Watermelon, cache);
+        assert!(take_cache(&chain_config, Height::Watermelon).is_none());
+    }
+}
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 mod resolve;
+mod structured;
 
 use std::{
     fmt::Write as FmtWrite,
@@ -29,6 +30,8 @@ use resolve::resolve_cids_recursive;
 use serde::{Deserialize, Serialize};
 use similar::{ChangeTag, TextDiff};
 
+pub use structured::{state_diff, ActorChangeKind, ActorDiff, FieldDelta, StateDiff};
+
 #[derive(Serialize, Deserialize)]
 struct ActorStateResolved {
     #[serde(with = "crate::lotus_json")]
@@ -122,6 +125,48 @@ fn try_print_actor_states<BS: Blockstore>(
     Ok(())
 }
 
+/// A decoded built-in actor state, printed for humans or serialized for tools.
+trait KnownActorState: std::fmt::Debug {
+    fn to_json(&self) -> serde_json::Result<serde_json::Value>;
+}
+
+impl<T: std::fmt::Debug + Serialize> KnownActorState for T {
+    fn to_json(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(self)
+    }
+}
+
+/// Loads the state of a built-in actor known to [`fil_actor_interface`], returning
+/// the actor type name alongside the decoded state.
+fn load_known_actor_state(
+    bs: &impl Blockstore,
+    actor_state: &ActorState,
+) -> Option<(&'static str, Box<dyn KnownActorState>)> {
+    macro_rules! try_load {
+        ($($name:literal => $state:ty),* $(,)?) => {
+            $(
+                if let Ok(state) = <$state>::load(bs, actor_state.code, actor_state.state) {
+                    return Some(($name, Box::new(state)));
+                }
+            )*
+        };
+    }
+    try_load!(
+        "miner" => MinerState,
+        "cron" => CronState,
+        "account" => AccountState,
+        "power" => PowerState,
+        "init" => InitState,
+        "reward" => RewardState,
+        "system" => SystemState,
+        "multisig" => MultiSigState,
+        "market" => MarketState,
+        "datacap" => DatacapState,
+        "evm" => EvmState,
+    );
+    None
+}
+
 fn pp_actor_state(
     bs: &impl Blockstore,
     actor_state: &ActorState,
@@ -129,48 +174,8 @@ fn pp_actor_state(
 ) -> Result<String, anyhow::Error> {
     let mut buffer = String::new();
     writeln!(&mut buffer, "{actor_state:?}")?;
-    if let Ok(miner_state) = MinerState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{miner_state:?}")?;
-        return Ok(buffer);
-    }
-    if let Ok(cron_state) = CronState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{cron_state:?}")?;
-        return Ok(buffer);
-    }
-    if let Ok(account_state) = AccountState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{account_state:?}")?;
-        return Ok(buffer);
-    }
-    if let Ok(power_state) = PowerState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{power_state:?}")?;
-        return Ok(buffer);
-    }
-    if let Ok(init_state) = InitState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{init_state:?}")?;
-        return Ok(buffer);
-    }
-    if let Ok(reward_state) = RewardState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{reward_state:?}")?;
-        return Ok(buffer);
-    }
-    if let Ok(system_state) = SystemState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{system_state:?}")?;
-        return Ok(buffer);
-    }
-    if let Ok(multi_sig_state) = MultiSigState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{multi_sig_state:?}")?;
-        return Ok(buffer);
-    }
-    if let Ok(market_state) = MarketState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{market_state:?}")?;
-        return Ok(buffer);
-    }
-    if let Ok(datacap_state) = DatacapState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{datacap_state:?}")?;
-        return Ok(buffer);
-    }
-    if let Ok(evm_state) = EvmState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{evm_state:?}")?;
+    if let Some((_, state)) = load_known_actor_state(bs, actor_state) {
+        write!(&mut buffer, "{state:?}")?;
         return Ok(buffer);
     }
 
@@ -0,0 +1,342 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Machine-readable state diffs. Unlike [`super::print_state_diff`], which
+//! is meant for humans, the types in this module can be serialized and
+//! consumed by other tools (e.g., for reconciling balances between tipsets).
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::ipld::json::IpldJsonRef;
+use crate::lotus_json::LotusJson;
+use crate::shim::{
+    address::Address,
+    econ::TokenAmount,
+    state_tree::{ActorState, StateTree},
+};
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::CborStore;
+use libipld_core::ipld::Ipld;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{load_known_actor_state, root_to_state_map};
+
+/// Changes between two state trees, ordered by actor address.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct StateDiff {
+    #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Cid>")]
+    pub pre_state_root: Cid,
+    #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Cid>")]
+    pub post_state_root: Cid,
+    pub actors: Vec<ActorDiff>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum ActorChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// Changes to a single actor. Missing actors are treated as having a zero
+/// balance, so `balance_delta` is meaningful for added and removed actors too.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct ActorDiff {
+    #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Address>")]
+    pub address: Address,
+    pub change: ActorChangeKind,
+    /// Built-in actor type (e.g., `miner`), if the code CID is known.
+    pub actor_type: Option<String>,
+    #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Option<ActorState>>")]
+    pub pre: Option<ActorState>,
+    #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Option<ActorState>>")]
+    pub post: Option<ActorState>,
+    #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<TokenAmount>")]
+    pub balance_delta: TokenAmount,
+    pub code_changed: bool,
+    pub nonce_changed: bool,
+    /// Top-level fields of the actor state that differ. Empty if the state
+    /// head is unchanged.
+    pub fields: Vec<FieldDelta>,
+}
+
+/// A single changed field of an actor state. Values of known actors are
+/// rendered as the JSON of their decoded representation, other actors fall
+/// back to the IPLD JSON of the field.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct FieldDelta {
+    pub field: String,
+    pub pre: Option<String>,
+    pub post: Option<String>,
+}
+
+/// Computes the actor-level changes going from `pre_root` to `post_root`.
+pub fn state_diff<BS: Blockstore>(
+    bs: &Arc<BS>,
+    pre_root: &Cid,
+    post_root: &Cid,
+) -> anyhow::Result<StateDiff> {
+    let mut pre_actors = root_to_state_map(bs, pre_root)?;
+    let mut actors = Vec::new();
+
+    let post_tree = StateTree::new_from_root(bs.clone(), post_root)?;
+    post_tree.for_each(|address, post: &ActorState| {
+        match pre_actors.remove(&address) {
+            Some(pre) if &pre == post => {}
+            pre => actors.push(actor_diff(bs.as_ref(), address, pre, Some(post.clone()))),
+        }
+        Ok(())
+    })?;
+    for (address, pre) in pre_actors {
+        actors.push(actor_diff(bs.as_ref(), address, Some(pre), None));
+    }
+    actors.sort_by_key(|diff| diff.address);
+
+    Ok(StateDiff {
+        pre_state_root: *pre_root,
+        post_state_root: *post_root,
+        actors,
+    })
+}
+
+fn actor_diff(
+    bs: &impl Blockstore,
+    address: Address,
+    pre: Option<ActorState>,
+    post: Option<ActorState>,
+) -> ActorDiff {
+    let balance = |actor: &Option<ActorState>| {
+        actor
+            .as_ref()
+            .map(|actor| TokenAmount::from(actor.balance.clone()))
+            .unwrap_or_default()
+    };
+    let balance_delta = balance(&post) - &balance(&pre);
+
+    let (pre_type, pre_fields) = decode_fields(bs, pre.as_ref());
+    let (post_type, post_fields) = decode_fields(bs, post.as_ref());
+
+    let (change, code_changed, nonce_changed, fields) = match (&pre, &post) {
+        (Some(pre), Some(post)) => (
+            ActorChangeKind::Modified,
+            pre.code != post.code,
+            pre.sequence != post.sequence,
+            if pre.state == post.state {
+                vec![]
+            } else {
+                field_deltas(pre_fields, post_fields)
+            },
+        ),
+        (None, _) => (ActorChangeKind::Added, true, true, vec![]),
+        (_, None) => (ActorChangeKind::Removed, true, true, vec![]),
+    };
+
+    ActorDiff {
+        address,
+        change,
+        actor_type: post_type.or(pre_type).map(String::from),
+        pre,
+        post,
+        balance_delta,
+        code_changed,
+        nonce_changed,
+        fields,
+    }
+}
+
+fn field_deltas(
+    mut pre: BTreeMap<String, String>,
+    post: BTreeMap<String, String>,
+) -> Vec<FieldDelta> {
+    let mut deltas = vec![];
+    for (field, post) in post {
+        match pre.remove(&field) {
+            Some(pre) if pre == post => {}
+            pre => deltas.push(FieldDelta {
+                field,
+                pre,
+                post: Some(post),
+            }),
+        }
+    }
+    deltas.extend(pre.into_iter().map(|(field, pre)| FieldDelta {
+        field,
+        pre: Some(pre),
+        post: None,
+    }));
+    deltas.sort_by(|a, b| a.field.cmp(&b.field));
+    deltas
+}
+
+/// Splits the state of an actor into its top-level fields, keyed by field
+/// index. Known actors are decoded through [`fil_actor_interface`], everything
+/// else is treated as a raw IPLD tuple.
+fn decode_fields(
+    bs: &impl Blockstore,
+    actor: Option<&ActorState>,
+) -> (Option<&'static str>, BTreeMap<String, String>) {
+    let Some(actor) = actor else {
+        return (None, BTreeMap::new());
+    };
+    if let Some((actor_type, state)) = load_known_actor_state(bs, actor) {
+        let fields = state.to_json().map(json_fields).unwrap_or_default();
+        return (Some(actor_type), fields);
+    }
+    let fields = match bs.get_cbor::<Ipld>(&actor.state) {
+        Ok(Some(Ipld::List(list))) => list
+            .iter()
+            .enumerate()
+            .map(|(idx, ipld)| (idx.to_string(), ipld_to_string(ipld)))
+            .collect(),
+        Ok(Some(Ipld::Map(map))) => map
+            .iter()
+            .map(|(key, ipld)| (key.clone(), ipld_to_string(ipld)))
+            .collect(),
+        Ok(Some(ipld)) => BTreeMap::from([(String::new(), ipld_to_string(&ipld))]),
+        _ => BTreeMap::new(),
+    };
+    (None, fields)
+}
+
+fn ipld_to_string(ipld: &Ipld) -> String {
+    serde_json::to_string(&IpldJsonRef(ipld)).unwrap_or_else(|_| format!("{ipld:?}"))
+}
+
+/// Splits a serialized actor state into its top-level fields. Actor states
+/// are serialized as tuples, so their fields are keyed by index.
+fn json_fields(value: serde_json::Value) -> BTreeMap<String, String> {
+    match value {
+        serde_json::Value::Array(items) => items
+            .into_iter()
+            .enumerate()
+            .map(|(idx, item)| (idx.to_string(), item.to_string()))
+            .collect(),
+        serde_json::Value::Object(map) => map
+            .into_iter()
+            .map(|(key, item)| (key, item.to_string()))
+            .collect(),
+        other => BTreeMap::from([(String::new(), other.to_string())]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryDB;
+    use crate::shim::state_tree::StateTreeVersion;
+    use crate::utils::db::CborStoreExt;
+    use fil_actor_account_state::v10::State as AccountState;
+
+    // mainnet v10 account actor cid
+    const ACCOUNT_V10: &str = "bafk2bzaceampw4romta75hyz5p4cqriypmpbgnkxncgxgqn6zptv5lsp2w2bo";
+
+    fn account(db: &MemoryDB, id: u64, balance: u64, sequence: u64) -> ActorState {
+        let state = db
+            .put_cbor_default(&AccountState {
+                address: Address::new_id(id).into(),
+            })
+            .unwrap();
+        ActorState::new(
+            Cid::try_from(ACCOUNT_V10).unwrap(),
+            state,
+            TokenAmount::from_atto(balance),
+            sequence,
+            None,
+        )
+    }
+
+    fn state_root(db: &Arc<MemoryDB>, actors: &[(Address, ActorState)]) -> Cid {
+        let mut tree = StateTree::new(db.clone(), StateTreeVersion::V5).unwrap();
+        for (addr, actor) in actors {
+            tree.set_actor(addr, actor.clone()).unwrap();
+        }
+        tree.flush().unwrap()
+    }
+
+    #[test]
+    fn identical_roots_have_no_changes() {
+        let db = Arc::new(MemoryDB::default());
+        let root = state_root(&db, &[(Address::new_id(100), account(&db, 100, 10, 0))]);
+        let diff = state_diff(&db, &root, &root).unwrap();
+        assert!(diff.actors.is_empty());
+    }
+
+    #[test]
+    fn reports_added_removed_and_modified_actors() {
+        let db = Arc::new(MemoryDB::default());
+        let (a, b, c) = (
+            Address::new_id(100),
+            Address::new_id(101),
+            Address::new_id(102),
+        );
+        let pre = state_root(
+            &db,
+            &[(a, account(&db, 100, 10, 0)), (b, account(&db, 101, 5, 0))],
+        );
+        let post = state_root(
+            &db,
+            &[(a, account(&db, 100, 7, 1)), (c, account(&db, 102, 3, 0))],
+        );
+
+        let diff = state_diff(&db, &pre, &post).unwrap();
+        let summary = diff
+            .actors
+            .iter()
+            .map(|d| (d.address, d.change, d.balance_delta.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                (a, ActorChangeKind::Modified, TokenAmount::from_atto(-3)),
+                (b, ActorChangeKind::Removed, TokenAmount::from_atto(-5)),
+                (c, ActorChangeKind::Added, TokenAmount::from_atto(3)),
+            ]
+        );
+        let modified = &diff.actors[0];
+        assert_eq!(modified.actor_type.as_deref(), Some("account"));
+        assert!(modified.nonce_changed);
+        assert!(!modified.code_changed);
+        assert!(modified.fields.is_empty());
+
+        // The diff must survive a JSON round trip.
+        let json = serde_json::to_string(&diff).unwrap();
+        assert_eq!(serde_json::from_str::<StateDiff>(&json).unwrap(), diff);
+    }
+
+    #[test]
+    fn reports_decoded_field_changes() {
+        let db = Arc::new(MemoryDB::default());
+        let addr = Address::new_id(100);
+        let pre = state_root(&db, &[(addr, account(&db, 100, 0, 0))]);
+        let post = state_root(&db, &[(addr, account(&db, 200, 0, 0))]);
+
+        let diff = state_diff(&db, &pre, &post).unwrap();
+        let fields = &diff.actors[0].fields;
+        assert_eq!(fields.len(), 1);
+        // The address is the only field of the account state
+        assert_eq!(fields[0].field, "0");
+        let value = |json: &Option<String>| {
+            serde_json::from_str::<serde_json::Value>(json.as_deref().unwrap()).unwrap()
+        };
+        assert_eq!(
+            value(&fields[0].pre),
+            serde_json::to_value(Address::new_id(100)).unwrap()
+        );
+        assert_eq!(
+            value(&fields[0].post),
+            serde_json::to_value(Address::new_id(200)).unwrap()
+        );
+    }
+}
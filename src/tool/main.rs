@@ -30,6 +30,7 @@ where
                 Subcommand::Archive(cmd) => cmd.run().await,
                 Subcommand::DB(cmd) => cmd.run().await,
                 Subcommand::Car(cmd) => cmd.run().await,
+                Subcommand::Api(cmd) => cmd.run().await,
             }
         })
 }
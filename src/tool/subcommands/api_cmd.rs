@@ -0,0 +1,76 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::path::PathBuf;
+
+use anyhow::Context as _;
+use clap::Subcommand;
+
+use crate::rpc_api::openrpc::{openrpc_document, MethodSetDiff, OpenRpc};
+use crate::utils::version::FOREST_VERSION_STRING;
+
+#[derive(Debug, Subcommand)]
+pub enum ApiCommands {
+    /// Print the `OpenRPC` document of the Forest RPC API. This is the same
+    /// document a node returns for `rpc.discover`.
+    Openrpc {
+        /// Write the document to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Compare the methods of the Forest RPC API with a Lotus `OpenRPC`
+    /// document (e.g., `build/openrpc/full.json` in the Lotus repository)
+    CompareLotus {
+        /// Path to the uncompressed Lotus `OpenRPC` JSON document
+        lotus_openrpc: PathBuf,
+        /// Exit with an error if any method differs
+        #[arg(long)]
+        strict: bool,
+    },
+}
+
+impl ApiCommands {
+    pub async fn run(self) -> anyhow::Result<()> {
+        let forest = openrpc_document(FOREST_VERSION_STRING.as_str());
+        match self {
+            Self::Openrpc { output } => {
+                let json = serde_json::to_string_pretty(&forest)?;
+                match output {
+                    Some(path) => std::fs::write(path, json)?,
+                    None => println!("{json}"),
+                }
+                Ok(())
+            }
+            Self::CompareLotus {
+                lotus_openrpc,
+                strict,
+            } => {
+                let lotus: OpenRpc = serde_json::from_slice(
+                    &std::fs::read(&lotus_openrpc)
+                        .with_context(|| format!("couldn't read {}", lotus_openrpc.display()))?,
+                )
+                .context("not an OpenRPC document")?;
+                let diff = MethodSetDiff::new(&forest, &lotus);
+                println!("Missing in Forest ({}):", diff.missing.len());
+                for name in &diff.missing {
+                    println!("  {name}");
+                }
+                println!("Forest only ({}):", diff.extra.len());
+                for name in &diff.extra {
+                    println!("  {name}");
+                }
+                println!(
+                    "Different number of parameters ({}):",
+                    diff.param_count_mismatch.len()
+                );
+                for (name, forest, lotus) in &diff.param_count_mismatch {
+                    println!("  {name}: Forest {forest}, Lotus {lotus}");
+                }
+                if strict && diff != MethodSetDiff::default() {
+                    anyhow::bail!("the Forest and Lotus RPC APIs differ");
+                }
+                Ok(())
+            }
+        }
+    }
+}
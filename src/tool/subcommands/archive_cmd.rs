@@ -36,7 +36,8 @@ use tracing::info;
 pub enum ArchiveCommands {
     /// Show basic information about an archive.
     Info {
-        /// Path to an uncompressed archive (CAR)
+        /// Path to an archive (CAR), or an `http(s)` URL of a remote archive
+        /// which is read with range requests
         snapshot: PathBuf,
     },
     /// Trim a snapshot of the chain and write it to `<output_path>`
@@ -62,15 +63,27 @@ pub enum ArchiveCommands {
         /// state-roots are included if this flag is not set.
         #[arg(long)]
         diff_depth: Option<ChainEpochDelta>,
+        /// Only include the full state of the target epoch. Older state-roots
+        /// only include their actors, and the state of the actors which
+        /// changed since the next newer state-root. Nodes fetch state missing
+        /// from the snapshot from peers when reading actors.
+        #[arg(long, default_value_t = false)]
+        state_light: bool,
         /// Overwrite output file without prompting.
         #[arg(long, default_value_t = false)]
         force: bool,
     },
-    /// Print block headers at 30 day interval for a snapshot file
+    /// Print block headers at a fixed interval (30 days by default) for a
+    /// snapshot file
     Checkpoints {
         /// Path to snapshot file.
         #[arg(required = true)]
         snapshot_files: Vec<PathBuf>,
+        /// Number of epochs between checkpoints. Denser checkpoints let
+        /// `forest --checkpoint-file` download headers in more segments at
+        /// once.
+        #[arg(long, default_value_t = EPOCHS_IN_DAY * 30)]
+        interval: ChainEpochDelta,
     },
     /// Merge snapshot archives into a single file. The output snapshot refers
     /// to the heaviest tipset in the input set.
@@ -99,6 +112,10 @@ pub enum ArchiveCommands {
         // shown as different branch IDs.
         #[arg(long)]
         depth: Option<u64>,
+        /// Print the actor-level changes as JSON instead of a colored text
+        /// diff.
+        #[arg(long, default_value_t = false)]
+        json: bool,
     },
 }
 
@@ -106,10 +123,7 @@ impl ArchiveCommands {
     pub async fn run(self) -> anyhow::Result<()> {
         match self {
             Self::Info { snapshot } => {
-                println!(
-                    "{}",
-                    ArchiveInfo::from_store(AnyCar::try_from(snapshot.as_path())?)?
-                );
+                println!("{}", ArchiveInfo::from_store(AnyCar::open(&snapshot)?)?);
                 Ok(())
             }
             Self::Export {
@@ -119,6 +133,7 @@ impl ArchiveCommands {
                 depth,
                 diff,
                 diff_depth,
+                state_light,
                 force,
             } => {
                 let store = ManyCar::try_from(snapshot_files)?;
@@ -131,13 +146,15 @@ impl ArchiveCommands {
                     depth,
                     diff,
                     diff_depth,
+                    state_light,
                     force,
                 )
                 .await
             }
             Self::Checkpoints {
                 snapshot_files: snapshot,
-            } => print_checkpoints(snapshot),
+                interval,
+            } => print_checkpoints(snapshot, interval),
             Self::Merge {
                 snapshot_files,
                 output_path,
@@ -147,7 +164,8 @@ impl ArchiveCommands {
                 snapshot_files,
                 epoch,
                 depth,
-            } => show_tipset_diff(snapshot_files, epoch, depth).await,
+                json,
+            } => show_tipset_diff(snapshot_files, epoch, depth, json).await,
         }
     }
 }
@@ -260,7 +278,11 @@ impl ArchiveInfo {
 
 // Print a mapping of epochs to block headers in yaml format. This mapping can
 // be used by Forest to quickly identify tipsets.
-fn print_checkpoints(snapshot_files: Vec<PathBuf>) -> anyhow::Result<()> {
+fn print_checkpoints(
+    snapshot_files: Vec<PathBuf>,
+    interval: ChainEpochDelta,
+) -> anyhow::Result<()> {
+    anyhow::ensure!(interval > 0, "checkpoint interval must be positive");
     let store = ManyCar::try_from(snapshot_files).context("couldn't read input CAR file")?;
     let root = store.heaviest_tipset()?;
 
@@ -269,7 +291,7 @@ fn print_checkpoints(snapshot_files: Vec<PathBuf>) -> anyhow::Result<()> {
         NetworkChain::from_genesis(genesis.cid()).context("Unrecognizable genesis block")?;
 
     println!("{}:", chain_name);
-    for (epoch, cid) in list_checkpoints(store, root) {
+    for (epoch, cid) in list_checkpoints(store, root, interval) {
         println!("  {}: {}", epoch, cid);
     }
     Ok(())
@@ -278,8 +300,8 @@ fn print_checkpoints(snapshot_files: Vec<PathBuf>) -> anyhow::Result<()> {
 fn list_checkpoints(
     db: impl Blockstore,
     root: Tipset,
+    interval: ChainEpochDelta,
 ) -> impl Iterator<Item = (ChainEpoch, cid::Cid)> {
-    let interval = EPOCHS_IN_DAY * 30;
     let mut target_epoch = root.epoch() - root.epoch() % interval;
     root.chain(db).filter_map(move |tipset| {
         if tipset.epoch() <= target_epoch && tipset.epoch() != 0 {
@@ -325,6 +347,7 @@ async fn do_export(
     depth: ChainEpochDelta,
     diff: Option<ChainEpoch>,
     diff_depth: Option<ChainEpochDelta>,
+    state_light: bool,
     force: bool,
 ) -> anyhow::Result<()> {
     let ts = Arc::new(root);
@@ -407,7 +430,8 @@ async fn do_export(
     pb.enable_steady_tick(std::time::Duration::from_secs_f32(0.1));
     let writer = pb.wrap_async_write(writer);
 
-    crate::chain::export::<Sha256>(store.clone(), &ts, depth, writer, seen, true).await?;
+    crate::chain::export::<Sha256>(store.clone(), &ts, depth, writer, seen, state_light, true)
+        .await?;
 
     Ok(())
 }
@@ -472,6 +496,7 @@ async fn show_tipset_diff(
     snapshot_files: Vec<PathBuf>,
     epoch: ChainEpoch,
     depth: Option<u64>,
+    json: bool,
 ) -> anyhow::Result<()> {
     use colored::*;
 
@@ -520,7 +545,10 @@ async fn show_tipset_diff(
         VMTrace::NotTraced,
     )?;
 
-    if child_tipset.parent_state() != &state_root {
+    if json {
+        let diff = crate::statediff::state_diff(&store, child_tipset.parent_state(), &state_root)?;
+        println!("{}", serde_json::to_string_pretty(&diff)?);
+    } else if child_tipset.parent_state() != &state_root {
         println!(
             "{}",
             format!("- Expected state hash: {}", child_tipset.parent_state()).red()
@@ -571,6 +599,7 @@ mod tests {
             None,
             None,
             false,
+            false,
         )
         .await
         .unwrap();
@@ -2,9 +2,12 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-use clap::Subcommand;
-use futures::{StreamExt, TryStreamExt};
+use anyhow::Context as _;
+use cid::Cid;
+use clap::{Subcommand, ValueEnum};
+use futures::{Stream, StreamExt, TryStreamExt};
 use fvm_ipld_blockstore::Blockstore;
 use indicatif::{ProgressBar, ProgressStyle};
 use itertools::Itertools;
@@ -13,11 +16,16 @@ use tokio::{
     io::{AsyncWriteExt, BufReader},
 };
 
-use crate::db::car::ForestCar;
+use crate::chain::usage::{builtin_actor_names, ObjectClassifier, UsageStats};
+use crate::db::car::{AnyCar, ForestCar};
+use crate::ipld::json::IpldJsonRef;
+use crate::ipld::selector::{walk_covered_blocks, RecursionLimit, Selector};
+use crate::ipld::Ipld;
 use crate::utils::db::{
-    car_stream::CarStream,
+    car_stream::{CarBlock, CarStream, CarV2Writer, CarWriter},
     car_util::{dedup_block_stream, merge_car_streams},
 };
+use crate::utils::encoding::from_slice_with_fallback;
 
 #[derive(Debug, Subcommand)]
 pub enum CarCommands {
@@ -30,6 +38,18 @@ pub enum CarCommands {
         #[arg(short, long)]
         output: PathBuf,
     },
+    /// Convert a CAR archive to another CAR format
+    Convert {
+        /// CAR archive. Supported formats: `.car`, `.car.zst`, `.forest.car.zst`
+        /// and uncompressed CARv2
+        car_file: PathBuf,
+        /// The output file path
+        #[arg(short, long)]
+        output: PathBuf,
+        /// The output format
+        #[arg(long, value_enum, default_value_t = CarFormat::CarV2)]
+        format: CarFormat,
+    },
     /// Check the validity of a CAR archive. For Filecoin-specific checks, see
     /// `forest-tool snapshot validate`.
     Validate {
@@ -42,6 +62,58 @@ pub enum CarCommands {
         #[arg(long)]
         ignore_forest_index: bool,
     },
+    /// List the blocks in a CAR archive: CID, codec, size and, for
+    /// `.forest.car.zst` archives, the offset of the frame holding the block
+    Ls {
+        /// CAR archive. Supported formats: `.car`, `.car.zst`, `.forest.car.zst`
+        /// and uncompressed CARv2
+        car_file: PathBuf,
+    },
+    /// Print a block from a CAR archive
+    Get {
+        /// CAR archive. Supported formats: `.car`, `.car.zst`, `.forest.car.zst`
+        /// and uncompressed CARv2
+        car_file: PathBuf,
+        /// CID of the block
+        cid: Cid,
+        /// Print the block as DAG-JSON instead of raw bytes
+        #[arg(long)]
+        json: bool,
+    },
+    /// Export the blocks covered by an IPLD selector to a new CAR archive
+    DagExport {
+        /// CAR archive. Supported formats: `.car`, `.car.zst`, `.forest.car.zst`
+        /// and uncompressed CARv2
+        car_file: PathBuf,
+        /// Root of the exported DAG
+        cid: Cid,
+        /// IPLD selector in DAG-JSON form. Explores the whole DAG by default.
+        #[arg(long)]
+        selector: Option<String>,
+        /// The output file path
+        #[arg(short, long)]
+        output: PathBuf,
+        /// The output format
+        #[arg(long, value_enum, default_value_t = CarFormat::CarV1)]
+        format: CarFormat,
+    },
+    /// Show how much space block headers, messages, receipts and the state of
+    /// each actor type take up in a CAR archive
+    Stats {
+        /// CAR archive. Supported formats: `.car`, `.car.zst`, `.forest.car.zst`
+        /// and uncompressed CARv2
+        car_file: PathBuf,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CarFormat {
+    /// Uncompressed CARv1
+    CarV1,
+    /// Uncompressed CARv2 with an `IndexSorted` index
+    CarV2,
+    /// `.forest.car.zst`
+    Forest,
 }
 
 impl CarCommands {
@@ -69,16 +141,199 @@ impl CarCommands {
                 crate::db::car::forest::Encoder::write(&mut writer, all_roots, frames).await?;
                 writer.flush().await?;
             }
+            Self::Convert {
+                car_file,
+                output,
+                format,
+            } => convert(&car_file, &output, format).await?,
             Self::Validate {
                 car_file,
                 ignore_block_validity,
                 ignore_forest_index,
             } => validate(&car_file, ignore_block_validity, ignore_forest_index).await?,
+            Self::Ls { car_file } => ls(&car_file, &mut std::io::stdout().lock()).await?,
+            Self::Get {
+                car_file,
+                cid,
+                json,
+            } => get(&car_file, cid, json, &mut std::io::stdout().lock())?,
+            Self::DagExport {
+                car_file,
+                cid,
+                selector,
+                output,
+                format,
+            } => {
+                let selector = match selector {
+                    Some(selector) => {
+                        serde_json::from_str(&selector).context("invalid IPLD selector")?
+                    }
+                    None => explore_all(),
+                };
+                dag_export(&car_file, cid, selector, &output, format).await?
+            }
+            Self::Stats { car_file } => stats(&car_file).await?,
         }
         Ok(())
     }
 }
 
+async fn convert(car_file: &Path, output: &Path, format: CarFormat) -> anyhow::Result<()> {
+    let stream = CarStream::new(BufReader::new(File::open(car_file).await?)).await?;
+    let roots = stream.header.roots.clone();
+    write_car(roots, stream, output, format).await
+}
+
+/// Write `blocks` to a new archive at `output`.
+async fn write_car(
+    roots: Vec<Cid>,
+    blocks: impl Stream<Item = std::io::Result<CarBlock>>,
+    output: &Path,
+    format: CarFormat,
+) -> anyhow::Result<()> {
+    let mut writer = tokio::io::BufWriter::new(File::create(output).await?);
+    match format {
+        CarFormat::CarV1 => {
+            blocks.forward(CarWriter::new_carv1(roots, writer)?).await?;
+        }
+        CarFormat::CarV2 => {
+            blocks.forward(CarV2Writer::new(roots, writer)?).await?;
+        }
+        CarFormat::Forest => {
+            let frames = crate::db::car::forest::Encoder::compress_stream_default(
+                blocks.map_err(anyhow::Error::from),
+            );
+            crate::db::car::forest::Encoder::write(&mut writer, roots, frames).await?;
+            writer.flush().await?;
+        }
+    }
+    Ok(())
+}
+
+fn codec_name(codec: u64) -> String {
+    match codec {
+        0x55 => "raw".into(),
+        0x70 => "dag-pb".into(),
+        0x71 => "dag-cbor".into(),
+        0x0129 => "dag-json".into(),
+        other => format!("{other:#x}"),
+    }
+}
+
+async fn ls(car_file: &Path, out: &mut impl std::io::Write) -> anyhow::Result<()> {
+    // Frame offsets are only known for `.forest.car.zst` archives. Other
+    // archives are only streamed, rather than indexed first.
+    let forest_car = ForestCar::try_from(car_file).ok();
+    let mut stream = CarStream::new(BufReader::new(File::open(car_file).await?)).await?;
+    while let Some(block) = stream.try_next().await? {
+        let frame_offset = match &forest_car {
+            Some(forest_car) => forest_car.frame_offset(&block.cid)?,
+            None => None,
+        };
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}",
+            block.cid,
+            codec_name(block.cid.codec()),
+            block.data.len(),
+            frame_offset.map_or_else(|| "-".into(), |offset| offset.to_string())
+        )?;
+    }
+    Ok(())
+}
+
+fn get(car_file: &Path, cid: Cid, json: bool, out: &mut impl std::io::Write) -> anyhow::Result<()> {
+    let car = AnyCar::try_from(car_file)?;
+    let data = car
+        .get(&cid)?
+        .with_context(|| format!("{cid} is not in {}", car_file.display()))?;
+    if json {
+        let ipld: Ipld = from_slice_with_fallback(&data)
+            .with_context(|| format!("{cid} is not a DAG-CBOR block"))?;
+        writeln!(
+            out,
+            "{}",
+            serde_json::to_string_pretty(&IpldJsonRef(&ipld))?
+        )?;
+    } else {
+        out.write_all(&data)?;
+    }
+    Ok(())
+}
+
+/// Selector that explores every node reachable from the root.
+fn explore_all() -> Selector {
+    Selector::ExploreRecursive {
+        sequence: Box::new(Selector::ExploreAll {
+            next: Box::new(Selector::ExploreRecursiveEdge),
+        }),
+        limit: RecursionLimit::None,
+        stop_at: None,
+        current: None,
+    }
+}
+
+async fn dag_export(
+    car_file: &Path,
+    root: Cid,
+    selector: Selector,
+    output: &Path,
+    format: CarFormat,
+) -> anyhow::Result<()> {
+    let car = AnyCar::try_from(car_file)?;
+    anyhow::ensure!(car.has(&root)?, "{root} is not in {}", car_file.display());
+    let mut covered = walk_covered_blocks(&car, root, selector)?;
+    let blocks = covered.by_ref().map(|block| {
+        block.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, format!("{e:#}")))
+    });
+    write_car(vec![root], futures::stream::iter(blocks), output, format).await?;
+    if !covered.missing().is_empty() {
+        eprintln!(
+            "{} linked blocks are not in {} and have been skipped",
+            covered.missing().len(),
+            car_file.display()
+        );
+    }
+    Ok(())
+}
+
+async fn stats(car_file: &Path) -> anyhow::Result<()> {
+    use human_repr::HumanCount as _;
+
+    let car = Arc::new(AnyCar::try_from(car_file)?);
+    let mut classifier = ObjectClassifier::default();
+    // Archives that aren't Filecoin snapshots are still classified by shape
+    match car.heaviest_tipset() {
+        Ok(head) => {
+            if let Err(e) = classifier.add_state_tree(&car, head.parent_state()) {
+                eprintln!("couldn't load the state tree, actor state is not attributed: {e}");
+            }
+        }
+        Err(e) => eprintln!("no tipset found, actor state is not attributed: {e}"),
+    }
+
+    let mut stats = UsageStats::default();
+    let mut stream = CarStream::new(BufReader::new(File::open(car_file).await?)).await?;
+    while let Some(block) = stream.try_next().await? {
+        stats.add(
+            classifier.classify(&block.cid, &block.data),
+            block.data.len(),
+        );
+    }
+
+    let names = builtin_actor_names().await?;
+    let row = |kind: String, count: u64, bytes: u64| {
+        println!("{kind:<40} {count:>12} {:>12}", bytes.human_count_bytes());
+    };
+    println!("{:<40} {:>12} {:>12}", "kind", "count", "size");
+    for (kind, usage) in &stats.by_kind {
+        row(kind.describe(&names), usage.count, usage.bytes);
+    }
+    let total = stats.total();
+    row("total".into(), total.count, total.bytes);
+    Ok(())
+}
+
 /// At present, three properties are checked:
 /// - The CAR file is syntactically valid and all blocks can be streamed.
 /// - Each block CID is checked against the hash of the block.
@@ -120,13 +375,14 @@ async fn validate(
 
 #[cfg(test)]
 mod tests {
-    use super::validate;
-    use crate::db::car::forest;
+    use super::{convert, dag_export, explore_all, get, ls, validate, CarFormat};
+    use crate::db::car::{forest, AnyCar};
     use crate::networks::{calibnet, mainnet};
-    use crate::utils::db::car_stream::CarBlock;
+    use crate::utils::db::car_stream::{CarBlock, CarStream};
     use cid::multihash::{Code, MultihashDigest};
     use cid::Cid;
     use futures::{stream::iter, StreamExt, TryStreamExt};
+    use fvm_ipld_blockstore::Blockstore as _;
     use std::io::Write;
     use tempfile::{Builder, TempPath};
     use tokio::io::AsyncWriteExt;
@@ -241,4 +497,132 @@ mod tests {
         // Ignoring index validity should make the test pass.
         assert!(validate(&temp_path, false, true).await.is_ok());
     }
+
+    #[tokio::test]
+    async fn convert_roundtrip() {
+        let mut car_path = Builder::new().tempfile().unwrap();
+        car_path.write_all(calibnet::DEFAULT_GENESIS).unwrap();
+        let car_path = car_path.into_temp_path();
+        let carv2_path = Builder::new().tempfile().unwrap().into_temp_path();
+        let carv1_path = Builder::new().tempfile().unwrap().into_temp_path();
+
+        convert(&car_path, &carv2_path, CarFormat::CarV2)
+            .await
+            .unwrap();
+        let carv2 = AnyCar::try_from(&*carv2_path).unwrap();
+        assert_eq!(carv2.variant(), "CARv2");
+        assert!(carv2.has(&calibnet::GENESIS_CID).unwrap());
+
+        convert(&carv2_path, &carv1_path, CarFormat::CarV1)
+            .await
+            .unwrap();
+        let blocks = |bytes: Vec<u8>| async move {
+            CarStream::new(bytes.as_slice())
+                .await
+                .unwrap()
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap()
+        };
+        assert_eq!(
+            blocks(std::fs::read(&carv1_path).unwrap()).await,
+            blocks(calibnet::DEFAULT_GENESIS.to_vec()).await
+        );
+    }
+
+    #[tokio::test]
+    async fn ls_lists_every_block() {
+        let mut car_path = Builder::new().tempfile().unwrap();
+        car_path.write_all(calibnet::DEFAULT_GENESIS).unwrap();
+        let car_path = car_path.into_temp_path();
+
+        let mut out = vec![];
+        ls(&car_path, &mut out).await.unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let blocks = CarStream::new(calibnet::DEFAULT_GENESIS)
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(out.lines().count(), blocks.len());
+        for (line, block) in out.lines().zip(&blocks) {
+            let fields = line.split('\t').collect::<Vec<_>>();
+            assert_eq!(fields[0], block.cid.to_string());
+            assert_eq!(fields[2], block.data.len().to_string());
+            // Offsets are only known for `.forest.car.zst` archives
+            assert_eq!(fields[3], "-");
+        }
+        assert!(out.contains("\tdag-cbor\t"));
+    }
+
+    #[tokio::test]
+    async fn ls_forest_car_frame_offsets() {
+        let block = valid_block("this data _does_ match the CID");
+        let car_path = create_raw_car_file(vec![block.clone()], vec![]).await;
+
+        let mut out = vec![];
+        ls(&car_path, &mut out).await.unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let fields = out.trim_end().split('\t').collect::<Vec<_>>();
+        assert_eq!(fields[0], block.cid.to_string());
+        assert!(fields[3].parse::<u64>().is_ok());
+    }
+
+    #[tokio::test]
+    async fn get_genesis_block() {
+        let mut car_path = Builder::new().tempfile().unwrap();
+        car_path.write_all(calibnet::DEFAULT_GENESIS).unwrap();
+        let car_path = car_path.into_temp_path();
+        let car = AnyCar::try_from(&*car_path).unwrap();
+        let data = car.get(&calibnet::GENESIS_CID).unwrap().unwrap();
+
+        let mut raw = vec![];
+        get(&car_path, *calibnet::GENESIS_CID, false, &mut raw).unwrap();
+        assert_eq!(raw, data);
+
+        let mut json = vec![];
+        get(&car_path, *calibnet::GENESIS_CID, true, &mut json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert!(json.is_array());
+
+        // Missing blocks are an error
+        let missing = Cid::new_v1(0x55, Code::Blake2b256.digest(b"missing"));
+        assert!(get(&car_path, missing, false, &mut vec![]).is_err());
+    }
+
+    #[tokio::test]
+    async fn dag_export_genesis() {
+        let mut car_path = Builder::new().tempfile().unwrap();
+        car_path.write_all(calibnet::DEFAULT_GENESIS).unwrap();
+        let car_path = car_path.into_temp_path();
+        let output_path = Builder::new().tempfile().unwrap().into_temp_path();
+
+        dag_export(
+            &car_path,
+            *calibnet::GENESIS_CID,
+            explore_all(),
+            &output_path,
+            CarFormat::CarV1,
+        )
+        .await
+        .unwrap();
+        let exported = std::fs::read(&output_path).unwrap();
+        let stream = CarStream::new(exported.as_slice()).await.unwrap();
+        assert_eq!(stream.header.roots, vec![*calibnet::GENESIS_CID]);
+        let blocks = stream.try_collect::<Vec<_>>().await.unwrap();
+        assert_eq!(blocks[0].cid, *calibnet::GENESIS_CID);
+        assert!(blocks.iter().all(|block| block.valid()));
+
+        // A missing root is an error
+        assert!(dag_export(
+            &car_path,
+            Cid::new_v1(0x55, Code::Blake2b256.digest(b"missing")),
+            explore_all(),
+            &output_path,
+            CarFormat::CarV1,
+        )
+        .await
+        .is_err());
+    }
 }
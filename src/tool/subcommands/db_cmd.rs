@@ -1,13 +1,48 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
 use super::read_config;
+use crate::blocks::{Tipset, TipsetKeys};
+use crate::chain::usage::{
+    builtin_actor_names, record_usage_sample, sample_db_usage, usage_history, DbUsageSample,
+    ObjectKind,
+};
+use crate::chain::ChainStore;
 use crate::cli::subcommands::prompt_confirm;
-use crate::cli_shared::chain_path;
-use crate::db::db_engine::db_root;
+use crate::cli_shared::{chain_path, cli::Config};
+use crate::daemon::db_util::load_all_forest_cars;
+use crate::db::backend::{convert_db, recover_conversion, DbBackendKind};
+use crate::db::backup::{restore, BackupManifest};
+use crate::db::car::ManyCar;
+use crate::db::check::{check_graph, RepairFn};
+use crate::db::db_engine::{db_root, open_proxy_db};
+use crate::db::rolling::RollingDB;
+use crate::db::setting_keys::HEAD_KEY;
+use crate::db::{MemoryDB, SettingsStoreExt as _};
+use crate::genesis::{get_network_name_from_genesis, read_genesis_header};
+use crate::libp2p::{
+    Keypair, Libp2pService, NetworkEvent, NetworkMessage, PeerManager, BITSWAP_TIMEOUT,
+};
+use crate::libp2p_bitswap::{BitswapStoreRead, BitswapStoreReadWrite};
 use crate::networks::NetworkChain;
+use crate::shim::clock::ChainEpoch;
+use crate::state_manager::StateManager;
+use anyhow::Context as _;
+use cid::Cid;
 use clap::Subcommand;
-use tracing::error;
+use fvm_ipld_blockstore::Blockstore;
+use human_repr::HumanCount as _;
+use tracing::{error, info};
+
+/// How long to wait for peers before repairing blocks with bitswap.
+const PEER_CONNECTION_TIMEOUT: Duration = Duration::from_secs(30);
+/// Number of peers to wait for before repairing blocks with bitswap.
+const MIN_REPAIR_PEERS: usize = 8;
 
 #[derive(Debug, Subcommand)]
 pub enum DBCommands {
@@ -32,14 +67,84 @@ pub enum DBCommands {
         #[arg(long)]
         chain: Option<NetworkChain>,
     },
+    /// Check that every block reachable from the head of the chain is in the
+    /// database and hashes to its CID. The node must be stopped.
+    Check {
+        /// Optional TOML file containing forest daemon configuration
+        #[arg(short, long)]
+        config: Option<String>,
+        /// Optional chain, will override the chain section of configuration file if used
+        #[arg(long)]
+        chain: Option<NetworkChain>,
+        /// Number of recent state-trees to check. Defaults to the number of
+        /// state-trees the node keeps
+        #[arg(long)]
+        depth: Option<ChainEpoch>,
+        /// Refetch missing and corrupt blocks and write them to the database
+        #[arg(long)]
+        repair: bool,
+        /// CAR archives to repair blocks from
+        #[arg(long, requires = "repair")]
+        car: Vec<PathBuf>,
+        /// Repair blocks from peers, with bitswap
+        #[arg(long, requires = "repair")]
+        from_peers: bool,
+        /// Write a JSON report of the check to this file
+        #[arg(long)]
+        report: Option<PathBuf>,
+    },
+    /// Classify the data reachable from the head of the chain by object kind
+    /// and actor, and estimate the size of unreachable data. The sample is
+    /// added to the usage history, which the node also appends to before each
+    /// garbage collection. The node must be stopped.
+    Usage {
+        /// Optional TOML file containing forest daemon configuration
+        #[arg(short, long)]
+        config: Option<String>,
+        /// Optional chain, will override the chain section of configuration file if used
+        #[arg(long)]
+        chain: Option<NetworkChain>,
+        /// Number of recent state-trees to classify. Defaults to the number of
+        /// state-trees the node keeps
+        #[arg(long)]
+        depth: Option<ChainEpoch>,
+        /// Print the usage history instead of taking a sample
+        #[arg(long)]
+        history: bool,
+    },
+    /// Move the database to another backend. The node must be stopped.
+    Convert {
+        /// Optional TOML file containing forest daemon configuration
+        #[arg(short, long)]
+        config: Option<String>,
+        /// Optional chain, will override the chain section of configuration file if used
+        #[arg(long)]
+        chain: Option<NetworkChain>,
+        /// The backend to convert to
+        #[arg(long, value_enum)]
+        to: DbBackendKind,
+    },
+    /// Restore a backup taken with `forest-cli db backup`. Backups of older
+    /// databases are migrated when the node starts. The node must be stopped.
+    Restore {
+        /// Directory of the backup
+        backup: PathBuf,
+        /// Replace an existing database of the same version without prompting
+        #[arg(long)]
+        force: bool,
+        /// Optional TOML file containing forest daemon configuration
+        #[arg(short, long)]
+        config: Option<String>,
+        /// Optional chain, will override the chain section of configuration file if used
+        #[arg(long)]
+        chain: Option<NetworkChain>,
+    },
 }
 
 impl DBCommands {
     pub async fn run(&self) -> anyhow::Result<()> {
         match self {
             Self::Stats { config, chain } => {
-                use human_repr::HumanCount;
-
                 let config = read_config(config, chain)?;
 
                 let dir = db_root(&chain_path(&config))?;
@@ -48,6 +153,190 @@ impl DBCommands {
                 println!("Database size: {}", size.human_count_bytes());
                 Ok(())
             }
+            Self::Check {
+                config,
+                chain,
+                depth,
+                repair,
+                car,
+                from_peers,
+                report,
+            } => {
+                let config = read_config(config, chain)?;
+                anyhow::ensure!(
+                    !*repair || !car.is_empty() || *from_peers,
+                    "--repair requires --car or --from-peers"
+                );
+
+                let db_root_dir = db_root(&chain_path(&config))?;
+                let db = Arc::new(ManyCar::new(Arc::new(open_proxy_db(
+                    db_root_dir.clone(),
+                    config.db_config(),
+                )?)));
+                load_all_forest_cars(&db, &db_root_dir.join("car_db"))?;
+                let head_key = db
+                    .writer()
+                    .read_obj::<TipsetKeys>(HEAD_KEY)?
+                    .context("the database has no chain head")?;
+                let head = Tipset::load_required(&db, &head_key)?;
+                let stateroot_limit =
+                    head.epoch() - depth.unwrap_or(config.chain.recent_state_roots);
+
+                let cars = if car.is_empty() {
+                    None
+                } else {
+                    Some(
+                        ManyCar::new(MemoryDB::default())
+                            .with_read_only_files(car.iter().cloned())?,
+                    )
+                };
+                let network = if *from_peers {
+                    Some(connect_to_peers(&config, &db).await?)
+                } else {
+                    None
+                };
+                let fetch = |cid: &Cid| -> anyhow::Result<Option<Vec<u8>>> {
+                    if let Some(cars) = &cars {
+                        if let Some(data) = Blockstore::get(cars, cid)? {
+                            return Ok(Some(data));
+                        }
+                    }
+                    let Some((network_send, fetched)) = &network else {
+                        return Ok(None);
+                    };
+                    let (tx, rx) = flume::bounded(1);
+                    network_send.send(NetworkMessage::BitswapRequest {
+                        cid: *cid,
+                        response_channel: tx,
+                    })?;
+                    match tokio::task::block_in_place(|| rx.recv_timeout(BITSWAP_TIMEOUT)) {
+                        Ok(true) => Blockstore::get(&fetched.fetched, cid),
+                        _ => Ok(None),
+                    }
+                };
+
+                info!("checking the graph from epoch {}", head.epoch());
+                let check = check_graph(
+                    &db,
+                    head,
+                    stateroot_limit,
+                    (*repair).then_some(&fetch as &RepairFn),
+                )
+                .await?;
+
+                println!("Head: {} (epoch {})", check.head, check.head_epoch);
+                println!("Blocks checked: {}", check.blocks_checked);
+                println!("Missing blocks: {}", check.missing.len());
+                println!("Corrupt blocks: {}", check.corrupt.len());
+                if *repair {
+                    println!("Repaired blocks: {}", check.repaired.len());
+                }
+                if let Some(report) = report {
+                    std::fs::write(report, serde_json::to_vec_pretty(&check)?)?;
+                    println!("Report written to {}", report.display());
+                }
+                anyhow::ensure!(
+                    check.is_consistent(),
+                    "the database is inconsistent: {} blocks are missing or corrupt",
+                    check.unrepaired().len()
+                );
+                Ok(())
+            }
+            Self::Usage {
+                config,
+                chain,
+                depth,
+                history,
+            } => {
+                let config = read_config(config, chain)?;
+                let db_root_dir = db_root(&chain_path(&config))?;
+                let db = Arc::new(ManyCar::new(Arc::new(open_proxy_db(
+                    db_root_dir.clone(),
+                    config.db_config(),
+                )?)));
+                if *history {
+                    print_usage_history(&usage_history(db.writer().as_ref())?);
+                    return Ok(());
+                }
+
+                load_all_forest_cars(&db, &db_root_dir.join("car_db"))?;
+                let head_key = db
+                    .writer()
+                    .read_obj::<TipsetKeys>(HEAD_KEY)?
+                    .context("the database has no chain head")?;
+                let head = Tipset::load_required(&db, &head_key)?;
+                info!("sampling the graph from epoch {}", head.epoch());
+                let sample = sample_db_usage(
+                    db.clone(),
+                    head,
+                    depth.unwrap_or(config.chain.recent_state_roots),
+                    db.writer().total_size_in_bytes()?,
+                    |cid| db.writer().has(cid),
+                )
+                .await?;
+                print_usage_sample(&sample).await?;
+                record_usage_sample(db.writer().as_ref(), sample)?;
+                Ok(())
+            }
+            Self::Convert { config, chain, to } => {
+                let config = read_config(config, chain)?;
+                let db_root_dir = db_root(&chain_path(&config))?;
+                // Generations are named without an extension, unlike the
+                // leftovers of interrupted conversions, which are cleaned up
+                let mut generations = BTreeSet::new();
+                for entry in std::fs::read_dir(&db_root_dir)? {
+                    let path = entry?.path();
+                    if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+                        generations
+                            .insert(db_root_dir.join(name.split('.').next().unwrap_or(name)));
+                    }
+                }
+                for path in generations {
+                    recover_conversion(&path)?;
+                    if DbBackendKind::detect(&path).is_none() {
+                        continue;
+                    }
+                    convert_db(&path, *to, &config.db_config())?;
+                }
+                println!(
+                    "Converted {} to {to}. Set `backend = \"{to}\"` in the `[db]` section of the configuration to use it.",
+                    db_root_dir.display()
+                );
+                Ok(())
+            }
+            Self::Restore {
+                backup,
+                force,
+                config,
+                chain,
+            } => {
+                let config = read_config(config, chain)?;
+                let chain_data = chain_path(&config);
+
+                let manifest = BackupManifest::load(backup)?;
+                println!(
+                    "Backup of database version {}, taken by forest {}",
+                    manifest.db_version, manifest.forest_version
+                );
+                let target = chain_data.join(&manifest.db_version);
+                if target.exists() && !force {
+                    println!("Replacing {}", target.display());
+                    if !prompt_confirm() {
+                        println!("Aborted.");
+                        return Ok(());
+                    }
+                }
+                let restored = restore(backup, &chain_data, true)?;
+                println!("Restored database to {}", restored.display());
+                if manifest.backend != config.db.backend {
+                    println!(
+                        "The backup is stored in {0}, set `backend = \"{0}\"` in the [db] \
+                        section of the configuration to use it",
+                        manifest.backend
+                    );
+                }
+                Ok(())
+            }
             Self::Destroy {
                 force,
                 config,
@@ -82,3 +371,166 @@ impl DBCommands {
         }
     }
 }
+
+async fn print_usage_sample(sample: &DbUsageSample) -> anyhow::Result<()> {
+    let names = builtin_actor_names().await?;
+    let row = |kind: String, count: String, bytes: u64| {
+        println!("{kind:<40} {count:>12} {:>12}", bytes.human_count_bytes());
+    };
+    println!("Epoch: {}", sample.epoch);
+    println!("{:<40} {:>12} {:>12}", "kind", "count", "size");
+    for (kind, usage) in &sample.reachable {
+        row(kind.describe(&names), usage.count.to_string(), usage.bytes);
+    }
+    let total = sample.reachable_total();
+    row(
+        "total reachable".into(),
+        total.count.to_string(),
+        total.bytes,
+    );
+    row(
+        "unreachable".into(),
+        String::new(),
+        sample.unreachable_bytes,
+    );
+    Ok(())
+}
+
+fn print_usage_history(history: &[DbUsageSample]) {
+    println!(
+        "{:<20} {:>10} {:>12} {:>12} {:>12} {:>12} {:>12}",
+        "date", "epoch", "headers", "messages", "receipts", "reachable", "unreachable"
+    );
+    for sample in history {
+        let bytes = |kind: ObjectKind| {
+            sample
+                .reachable
+                .iter()
+                .find(|(k, _)| *k == kind)
+                .map(|(_, usage)| usage.bytes)
+                .unwrap_or_default()
+                .human_count_bytes()
+                .to_string()
+        };
+        let date = chrono::DateTime::from_timestamp(sample.timestamp, 0)
+            .map(|date| date.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default();
+        println!(
+            "{date:<20} {:>10} {:>12} {:>12} {:>12} {:>12} {:>12}",
+            sample.epoch,
+            bytes(ObjectKind::BlockHeader),
+            bytes(ObjectKind::Message),
+            bytes(ObjectKind::Receipt),
+            sample
+                .reachable_total()
+                .bytes
+                .human_count_bytes()
+                .to_string(),
+            sample.unreachable_bytes.human_count_bytes().to_string(),
+        );
+    }
+}
+
+/// Connect to the network of `config` to fetch blocks with bitswap. Fetched
+/// blocks are written to `db`.
+async fn connect_to_peers(
+    config: &Config,
+    db: &Arc<ManyCar<Arc<RollingDB>>>,
+) -> anyhow::Result<(flume::Sender<NetworkMessage>, Arc<RepairStore>)> {
+    let store = Arc::new(RepairStore {
+        db: Arc::clone(db),
+        fetched: MemoryDB::default(),
+    });
+    let genesis_header = read_genesis_header(
+        config.client.genesis_file.as_ref(),
+        config.chain.genesis_bytes(),
+        &store,
+    )
+    .await?;
+    let chain_store = Arc::new(ChainStore::new(
+        Arc::clone(&store),
+        db.writer().clone(),
+        config.chain.clone(),
+        genesis_header.clone(),
+    )?);
+    let state_manager = StateManager::new(Arc::clone(&chain_store), Arc::clone(&config.chain))?;
+    let network_name = get_network_name_from_genesis(&genesis_header, &state_manager)?;
+
+    let mut network_config = config.network.clone();
+    if network_config.bootstrap_peers.is_empty() {
+        network_config.bootstrap_peers = config.chain.bootstrap_peers.clone();
+    }
+    let peer_manager = Arc::new(PeerManager::default());
+    tokio::spawn(peer_manager.clone().peer_operation_event_loop_task());
+    // A throwaway identity, the node's reputation isn't at stake
+    let service = Libp2pService::new(
+        network_config,
+        chain_store,
+        peer_manager,
+        Keypair::generate_ed25519(),
+        &network_name,
+        *genesis_header.cid(),
+    )?;
+    let network_send = service.network_sender();
+    let network_rx = service.network_receiver();
+    tokio::spawn(service.run());
+
+    info!("connecting to peers");
+    let mut peers = 0;
+    let timeout = tokio::time::sleep(PEER_CONNECTION_TIMEOUT);
+    tokio::pin!(timeout);
+    while peers < MIN_REPAIR_PEERS {
+        tokio::select! {
+            _ = &mut timeout => break,
+            event = network_rx.recv_async() => {
+                if let NetworkEvent::PeerConnected(_) = event? {
+                    peers += 1;
+                }
+            }
+        }
+    }
+    anyhow::ensure!(peers > 0, "couldn't connect to any peers");
+    info!("connected to {peers} peers");
+    // Keep the service from failing to emit events
+    tokio::spawn(async move { while network_rx.recv_async().await.is_ok() {} });
+    Ok((network_send, store))
+}
+
+/// Blockstore of the network service used for repairs. Blocks fetched with
+/// bitswap are kept apart from the database, whose corrupt copies would
+/// otherwise be found, and short-circuit the requests.
+struct RepairStore {
+    db: Arc<ManyCar<Arc<RollingDB>>>,
+    fetched: MemoryDB,
+}
+
+impl Blockstore for RepairStore {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        match Blockstore::get(&self.fetched, k)? {
+            Some(data) => Ok(Some(data)),
+            None => Blockstore::get(&self.db, k),
+        }
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        self.fetched.put_keyed(k, block)
+    }
+}
+
+impl BitswapStoreRead for RepairStore {
+    fn contains(&self, cid: &Cid) -> anyhow::Result<bool> {
+        self.fetched.has(cid)
+    }
+
+    fn get(&self, cid: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        Blockstore::get(self, cid)
+    }
+}
+
+impl BitswapStoreReadWrite for RepairStore {
+    type Params = libipld::DefaultParams;
+
+    fn insert(&self, block: &libipld::Block<Self::Params>) -> anyhow::Result<()> {
+        self.put_keyed(block.cid(), block.data())
+    }
+}
@@ -6,17 +6,18 @@ use crate::blocks::Tipset;
 use crate::chain::index::{ChainIndex, ResolveNullTipset};
 use crate::cid_collections::CidHashSet;
 use crate::cli_shared::snapshot;
+use crate::cli_shared::snapshot::manifest::ManifestVerifier;
 use crate::daemon::bundle::load_actor_bundles;
 use crate::db::car::forest::DEFAULT_FOREST_CAR_FRAME_SIZE;
 use crate::db::car::{AnyCar, ManyCar};
 use crate::interpreter::{MessageCallbackCtx, VMTrace};
 use crate::ipld::recurse_links_hash;
 use crate::networks::{calibnet, mainnet, ChainConfig, NetworkChain};
-use crate::shim::address::CurrentNetwork;
+use crate::shim::address::{Address, CurrentNetwork};
 use crate::shim::clock::ChainEpoch;
 use crate::shim::fvm_shared_latest::address::Network;
 use crate::shim::machine::MultiEngine;
-use crate::state_manager::apply_block_messages;
+use crate::state_manager::{apply_block_messages, StateMismatch};
 use crate::utils::db::car_stream::CarStream;
 use crate::utils::proofs_api::paramfetch::ensure_params_downloaded;
 use anyhow::{bail, Context as _};
@@ -26,10 +27,16 @@ use dialoguer::{theme::ColorfulTheme, Confirm};
 use futures::TryStreamExt;
 use fvm_ipld_blockstore::Blockstore;
 use indicatif::{ProgressBar, ProgressStyle};
-use std::path::PathBuf;
-use std::sync::Arc;
+use itertools::Itertools as _;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
 use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
+use url::Url;
 
 #[derive(Debug, Subcommand)]
 pub enum SnapshotCommands {
@@ -43,6 +50,10 @@ pub enum SnapshotCommands {
         /// Vendor to fetch the snapshot from
         #[arg(short, long, value_enum, default_value_t = snapshot::TrustedVendor::default())]
         vendor: snapshot::TrustedVendor,
+        /// Verify the snapshot against its manifest, which must be signed by
+        /// one of these addresses. May be repeated.
+        #[arg(long = "trusted-publisher")]
+        trusted_publishers: Vec<Address>,
     },
 
     /// Validates the snapshot.
@@ -57,6 +68,11 @@ pub enum SnapshotCommands {
         /// Number of recent epochs to scan for bad messages/transactions
         #[arg(long, default_value_t = 60)]
         check_stateroots: u32,
+        /// Validate the state-roots in windows of this many epochs, in
+        /// parallel, and report every mismatching epoch instead of stopping
+        /// at the first one
+        #[arg(long)]
+        window_size: Option<NonZeroUsize>,
         /// Path to a snapshot CAR, which may be zstd compressed
         #[arg(required = true)]
         snapshot_files: Vec<PathBuf>,
@@ -77,6 +93,10 @@ pub enum SnapshotCommands {
         /// End zstd frames after they exceed this length
         #[arg(long, default_value_t = DEFAULT_FOREST_CAR_FRAME_SIZE)]
         frame_size: usize,
+        /// Compress zstd frames with a dictionary trained on the first blocks.
+        /// This gives smaller archives, which can only be read by Forest.
+        #[arg(long, default_value_t = false)]
+        zstd_dictionary: bool,
         /// Overwrite output file without prompting.
         #[arg(long, default_value_t = false)]
         force: bool,
@@ -117,8 +137,18 @@ impl SnapshotCommands {
                 directory,
                 chain,
                 vendor,
+                trusted_publishers,
             } => match snapshot::fetch(&directory, &chain, vendor).await {
-                Ok(out) => {
+                Ok((out, url)) => {
+                    if !trusted_publishers.is_empty() {
+                        let verifier = ManifestVerifier {
+                            network: chain.to_string(),
+                            trusted_publishers,
+                        };
+                        if let Err(e) = verify_fetched_snapshot(&verifier, &url, &out).await {
+                            cli_error_and_die(format!("Failed verifying the snapshot: {e}"), 1)
+                        }
+                    }
                     println!("{}", out.display());
                     Ok(())
                 }
@@ -128,6 +158,7 @@ impl SnapshotCommands {
                 check_links,
                 check_network,
                 check_stateroots,
+                window_size,
                 snapshot_files,
             } => {
                 let store = ManyCar::try_from(snapshot_files)?;
@@ -137,6 +168,7 @@ impl SnapshotCommands {
                     check_links,
                     check_network,
                     check_stateroots,
+                    window_size,
                 )
                 .await
             }
@@ -145,6 +177,7 @@ impl SnapshotCommands {
                 output_path,
                 compression_level,
                 frame_size,
+                zstd_dictionary,
                 force,
             } => {
                 // If input is 'snapshot.car.zst' and output is '.', set the
@@ -193,12 +226,22 @@ impl SnapshotCommands {
 
                 let mut dest = tokio::io::BufWriter::new(File::create(&destination).await?);
 
-                let frames = crate::db::car::forest::Encoder::compress_stream(
-                    frame_size,
-                    compression_level,
-                    block_stream.map_err(anyhow::Error::from),
-                );
-                crate::db::car::forest::Encoder::write(&mut dest, roots, frames).await?;
+                let blocks = block_stream.map_err(anyhow::Error::from);
+                if zstd_dictionary {
+                    let frames = crate::db::car::forest::Encoder::compress_stream_with_dictionary(
+                        frame_size,
+                        compression_level,
+                        blocks,
+                    );
+                    crate::db::car::forest::Encoder::write(&mut dest, roots, frames).await?;
+                } else {
+                    let frames = crate::db::car::forest::Encoder::compress_stream(
+                        frame_size,
+                        compression_level,
+                        blocks,
+                    );
+                    crate::db::car::forest::Encoder::write(&mut dest, roots, frames).await?;
+                }
                 dest.flush().await?;
                 Ok(())
             }
@@ -211,6 +254,26 @@ impl SnapshotCommands {
     }
 }
 
+/// Check a snapshot downloaded from `url` against its signed manifest.
+async fn verify_fetched_snapshot(
+    verifier: &ManifestVerifier,
+    url: &Url,
+    snapshot: &Path,
+) -> anyhow::Result<()> {
+    let manifest = verifier
+        .verify_snapshot(Path::new(url.as_str()), snapshot)
+        .await?;
+    let head = AnyCar::try_from(snapshot)?.heaviest_tipset()?;
+    if manifest.head != *head.key() {
+        bail!(
+            "snapshot head {} doesn't match the manifest head {}",
+            head.key(),
+            manifest.head
+        );
+    }
+    Ok(())
+}
+
 // Check the validity of a snapshot by looking at IPLD links, the genesis block,
 // and message output. More checks may be added in the future.
 //
@@ -233,6 +296,7 @@ async fn validate_with_blockstore<BlockstoreT>(
     check_links: u32,
     check_network: Option<NetworkChain>,
     check_stateroots: u32,
+    window_size: Option<NonZeroUsize>,
 ) -> anyhow::Result<()>
 where
     BlockstoreT: Blockstore + Send + Sync + 'static,
@@ -257,7 +321,7 @@ where
         let network = check_network
             .map(anyhow::Ok)
             .unwrap_or_else(|| query_network(&root, &store))?;
-        validate_stateroots(root, &store, network, check_stateroots).await?;
+        validate_stateroots(root, &store, network, check_stateroots, window_size).await?;
     }
 
     println!("Snapshot is valid");
@@ -341,6 +405,7 @@ async fn validate_stateroots<DB>(
     db: &Arc<DB>,
     network: NetworkChain,
     epochs: u32,
+    window_size: Option<NonZeroUsize>,
 ) -> anyhow::Result<()>
 where
     DB: Blockstore + Send + Sync + 'static,
@@ -367,6 +432,44 @@ where
     ensure_params_downloaded().await?;
 
     let chain_index = Arc::new(ChainIndex::new(Arc::new(db.clone())));
+    let beacon = Arc::new(chain_config.get_beacon_schedule(genesis.timestamp()));
+
+    if let Some(window_size) = window_size {
+        let validated = AtomicUsize::new(0);
+        let mismatches = crate::state_manager::validate_tipset_windows(
+            genesis.timestamp(),
+            chain_index,
+            chain_config,
+            beacon,
+            &MultiEngine::default(),
+            Arc::new(ts),
+            last_epoch..=ChainEpoch::MAX,
+            window_size,
+            |_| {
+                let validated = validated.fetch_add(1, Ordering::Relaxed) + 1;
+                pb.set_message(format!("validated tipsets: {validated}"));
+            },
+        )?;
+        if !mismatches.is_empty() {
+            for StateMismatch {
+                epoch,
+                expected: (expected_state, expected_receipt),
+                actual: (actual_state, actual_receipt),
+            } in &mismatches
+            {
+                println!(
+                    "epoch {epoch}: expected state {expected_state} and receipts {expected_receipt}, computed state {actual_state} and receipts {actual_receipt}"
+                );
+            }
+            bail!(
+                "state mismatch at {} epochs: {}",
+                mismatches.len(),
+                mismatches.iter().map(|mismatch| mismatch.epoch).join(", ")
+            );
+        }
+        pb.finish_with_message("✅ verified!");
+        return Ok(());
+    }
 
     // Prepare tipsets for validation
     let tipsets = chain_index
@@ -376,8 +479,6 @@ where
             pb.set_message(format!("epoch queue: {}", tipset.epoch() - last_epoch));
         });
 
-    let beacon = Arc::new(chain_config.get_beacon_schedule(genesis.timestamp()));
-
     // ProgressBar::wrap_iter believes the progress has been abandoned once the
     // iterator is consumed.
     crate::state_manager::validate_tipsets(
@@ -1,33 +1,152 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use crate::networks::{ActorBundleInfo, ACTOR_BUNDLES};
+use crate::daemon::bundle::load_actor_bundles;
+use crate::db::{car::ManyCar, MemoryDB};
+use crate::networks::{ActorBundleInfo, ChainConfig, Height, NetworkChain, ACTOR_BUNDLES};
+use crate::shim::machine::BuiltinActorManifest;
+use crate::state_migration::run_state_migration;
 use crate::utils::db::car_stream::{CarStream, CarWriter};
 use crate::utils::net::global_http_client;
-use anyhow::ensure;
+use ahash::HashMap;
+use anyhow::{bail, ensure};
 use async_compression::tokio::write::ZstdEncoder;
+use cid::Cid;
 use futures::{stream, StreamExt as _, TryStreamExt as _};
+use human_repr::{HumanCount as _, HumanDuration as _};
 use itertools::Itertools as _;
 use std::io::{self, Cursor};
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
 use tokio::fs::File;
 use tracing::info;
 
 #[derive(Debug, clap::Subcommand)]
+#[allow(clippy::large_enum_variant)]
 pub enum StateMigrationCommands {
     /// Generate a merged actor bundle from the hard-coded sources in forest
     ActorBundle {
         #[arg(default_value = "actor_bundles.car.zst")]
         output: PathBuf,
     },
+    /// Run the state migration of a network upgrade on a pre-upgrade snapshot,
+    /// keeping the migrated state in memory. Reports the time spent per actor
+    /// type and the memory usage.
+    Run {
+        /// Snapshot files to load the state from
+        #[arg(long, required = true)]
+        snapshot: Vec<PathBuf>,
+        /// Network upgrade to migrate the state for
+        #[arg(long, value_enum)]
+        height: Height,
+        /// Network the snapshot belongs to
+        #[arg(long, default_value_t = NetworkChain::Mainnet)]
+        chain: NetworkChain,
+        /// State root to migrate. Defaults to the parent state of the
+        /// snapshot head.
+        #[arg(long)]
+        state_root: Option<Cid>,
+        /// Check the migration specification against the input state
+        #[arg(long)]
+        verify: bool,
+        /// Fail if the migrated state root differs, e.g., from the one
+        /// computed by Lotus
+        #[arg(long)]
+        expected_state_root: Option<Cid>,
+    },
 }
 
 impl StateMigrationCommands {
     pub async fn run(self) -> anyhow::Result<()> {
         match self {
             Self::ActorBundle { output } => generate_actor_bundle(output).await,
+            Self::Run {
+                snapshot,
+                height,
+                chain,
+                state_root,
+                verify,
+                expected_state_root,
+            } => {
+                rehearse_migration(
+                    snapshot,
+                    height,
+                    chain,
+                    state_root,
+                    verify,
+                    expected_state_root,
+                )
+                .await
+            }
+        }
+    }
+}
+
+async fn rehearse_migration(
+    snapshot: Vec<PathBuf>,
+    height: Height,
+    chain: NetworkChain,
+    state_root: Option<Cid>,
+    verify: bool,
+    expected_state_root: Option<Cid>,
+) -> anyhow::Result<()> {
+    let store =
+        Arc::new(ManyCar::new(MemoryDB::default()).with_read_only_files(snapshot.into_iter())?);
+    // Migrated blocks only end up in the in-memory writer of the store.
+    load_actor_bundles(store.writer()).await?;
+    let state_root = match state_root {
+        Some(state_root) => state_root,
+        None => *store.heaviest_tipset()?.parent_state(),
+    };
+    let chain_config = ChainConfig::from_chain(&chain);
+
+    let mut actor_names = HashMap::default();
+    for ActorBundleInfo { manifest, .. } in ACTOR_BUNDLES.iter() {
+        for (actor, code) in BuiltinActorManifest::load_manifest(&store, manifest)?.builtin_actors()
+        {
+            actor_names.insert(code, actor.name());
+        }
+    }
+
+    println!("Migrating state {state_root} for {height}");
+    let memory_before = memory_stats::memory_stats().map(|it| it.physical_mem);
+    let start_time = Instant::now();
+    let (new_state_root, stats) = tokio::task::spawn_blocking({
+        let store = Arc::clone(&store);
+        move || run_state_migration(height, &chain_config, &store, &state_root, verify)
+    })
+    .await??;
+    let elapsed = start_time.elapsed();
+    let memory_after = memory_stats::memory_stats().map(|it| it.physical_mem);
+
+    println!("{:<20} {:>10} {:>12}", "Actor", "Count", "Time");
+    for (code, count, time) in stats.by_code() {
+        let name = actor_names.get(&code).copied().unwrap_or("unknown");
+        println!(
+            "{name:<20} {count:>10} {:>12}",
+            time.human_duration().to_string()
+        );
+    }
+    println!(
+        "Migrated to {new_state_root} in {}",
+        elapsed.human_duration()
+    );
+    if let (Some(before), Some(after)) = (memory_before, memory_after) {
+        println!(
+            "Memory usage: {} (+{})",
+            after.human_count_bytes(),
+            after.saturating_sub(before).human_count_bytes()
+        );
+    }
+
+    if let Some(expected_state_root) = expected_state_root {
+        if new_state_root != expected_state_root {
+            bail!("expected state root {expected_state_root}, got {new_state_root}");
         }
+        println!("State root matches the expected one");
     }
+    Ok(())
 }
 
 async fn generate_actor_bundle(output: PathBuf) -> anyhow::Result<()> {
@@ -13,14 +13,19 @@ use integer_encoding::VarInt;
 use pin_project_lite::pin_project;
 use serde::{Deserialize, Serialize};
 use std::io;
+use std::io::SeekFrom;
 use std::pin::Pin;
 use std::task::{Context, Poll};
-use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncWrite};
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncSeek, AsyncWrite, Take,
+};
 use tokio_util::codec::Encoder;
 use tokio_util::codec::FramedRead;
 use tokio_util::either::Either;
 use unsigned_varint::codec::UviBytes;
 
+use crate::db::car::forest::{is_dictionary_frame, FOREST_CAR_FILE_EXTENSION};
+use crate::db::car::v2::{encode_index_sorted, CarV2Header, CARV2_PRAGMA};
 use crate::utils::encoding::from_slice_with_fallback;
 
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
@@ -71,10 +76,11 @@ impl CarBlock {
 
 pin_project! {
     /// Stream of CAR blocks. If the input data is compressed with zstd, it will
-    /// automatically be decompressed.
+    /// automatically be decompressed. Uncompressed CARv2 input is streamed
+    /// from its CARv1 data payload.
     pub struct CarStream<ReaderT> {
         #[pin]
-        reader: FramedRead<Either<ReaderT, ZstdDecoder<ReaderT>>, UviBytes>,
+        reader: FramedRead<Either<Take<ReaderT>, ZstdDecoder<ReaderT>>, UviBytes>,
         pub header: CarHeader,
         first_block: Option<CarBlock>,
     }
@@ -90,12 +96,25 @@ fn is_zstd(buf: &[u8]) -> bool {
 impl<ReaderT: AsyncBufRead + Unpin> CarStream<ReaderT> {
     pub async fn new(mut reader: ReaderT) -> io::Result<Self> {
         let is_compressed = is_zstd(reader.fill_buf().await?);
+        if is_compressed && has_dictionary_frame(reader.fill_buf().await?) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "`{FOREST_CAR_FILE_EXTENSION}` file is compressed with a dictionary, \
+                     which requires a newer version of Forest to be streamed"
+                ),
+            ));
+        }
         let mut reader = if is_compressed {
             let mut zstd = ZstdDecoder::new(reader);
             zstd.multiple_members(true);
             FramedRead::new(Either::Right(zstd), UviBytes::default())
         } else {
-            FramedRead::new(Either::Left(reader), UviBytes::default())
+            let data_size = skip_carv2_header(&mut reader).await?;
+            FramedRead::new(
+                Either::Left(reader.take(data_size.unwrap_or(u64::MAX))),
+                UviBytes::default(),
+            )
         };
         let header = read_header(&mut reader)
             .await
@@ -126,6 +145,34 @@ impl<ReaderT: AsyncBufRead + Unpin> CarStream<ReaderT> {
     }
 }
 
+/// Whether the zstd frame at the start of `buf` is followed by a dictionary,
+/// as in `.forest.car.zst` archives compressed with a dictionary. Streaming
+/// decoders skip the dictionary and fail on the frames that use it.
+fn has_dictionary_frame(buf: &[u8]) -> bool {
+    match zstd::zstd_safe::find_frame_compressed_size(buf) {
+        Ok(header_size) => is_dictionary_frame(&buf[header_size..]),
+        Err(_) => false,
+    }
+}
+
+/// If `reader` is at the start of a CARv2 archive, skip to its data payload and
+/// return the size of the payload.
+async fn skip_carv2_header(mut reader: impl AsyncBufRead + Unpin) -> io::Result<Option<u64>> {
+    if !reader.fill_buf().await?.starts_with(&CARV2_PRAGMA) {
+        return Ok(None);
+    }
+    let mut bytes = [0; CARV2_PRAGMA.len() + CarV2Header::SIZE];
+    reader.read_exact(&mut bytes).await?;
+    let header =
+        CarV2Header::from_le_bytes(bytes[CARV2_PRAGMA.len()..].try_into().expect("infallible"));
+    let padding = header
+        .data_offset
+        .checked_sub(CarV2Header::DEFAULT_DATA_OFFSET)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid CARv2 data offset"))?;
+    tokio::io::copy(&mut (&mut reader).take(padding), &mut tokio::io::sink()).await?;
+    Ok(Some(header.data_size))
+}
+
 impl<ReaderT: AsyncBufRead> Stream for CarStream<ReaderT> {
     type Item = io::Result<CarBlock>;
 
@@ -164,15 +211,9 @@ impl<W: AsyncWrite> CarWriter<W> {
 impl<W: AsyncWrite> Sink<CarBlock> for CarWriter<W> {
     type Error = io::Error;
 
-    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        let mut this = self.as_mut().project();
-
-        while !this.buffer.is_empty() {
-            this = self.as_mut().project();
-            let bytes_written = ready!(this.inner.poll_write(cx, this.buffer))?;
-            this.buffer.advance(bytes_written);
-        }
-        Poll::Ready(Ok(()))
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.project();
+        poll_write_buffer(this.inner, this.buffer, cx)
     }
     fn start_send(self: Pin<&mut Self>, item: CarBlock) -> Result<(), Self::Error> {
         item.write(&mut self.project().buffer.writer())
@@ -187,6 +228,119 @@ impl<W: AsyncWrite> Sink<CarBlock> for CarWriter<W> {
     }
 }
 
+pin_project! {
+    /// Like [`CarWriter`], but writes a CARv2 archive with an `IndexSorted`
+    /// index of the blocks. The CARv2 header holds the size of the data
+    /// payload, so it is written when the sink is closed and the writer has to
+    /// be seekable.
+    pub struct CarV2Writer<W> {
+        #[pin]
+        inner: W,
+        buffer: BytesMut,
+        // Size of the CARv1 data payload written to the buffer so far
+        data_size: u64,
+        // Multihash digests and frame offsets of the blocks
+        index: Vec<(Vec<u8>, u64)>,
+        state: CarV2WriterState,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CarV2WriterState {
+    Blocks,
+    Index,
+    Seek,
+    Header,
+}
+
+impl<W: AsyncWrite + AsyncSeek> CarV2Writer<W> {
+    pub fn new(roots: Vec<Cid>, writer: W) -> io::Result<Self> {
+        let car_header = CarHeader { roots, version: 1 };
+
+        let mut buffer = BytesMut::new();
+        buffer.extend_from_slice(&CARV2_PRAGMA);
+        buffer.extend_from_slice(&CarV2Header::default().to_le_bytes());
+        UviBytes::default().encode(Bytes::from(to_vec(&car_header)?), &mut buffer)?;
+
+        Ok(Self {
+            inner: writer,
+            data_size: (buffer.len() as u64) - CarV2Header::DEFAULT_DATA_OFFSET,
+            buffer,
+            index: vec![],
+            state: CarV2WriterState::Blocks,
+        })
+    }
+}
+
+impl<W: AsyncWrite + AsyncSeek> Sink<CarBlock> for CarV2Writer<W> {
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.project();
+        poll_write_buffer(this.inner, this.buffer, cx)
+    }
+    fn start_send(self: Pin<&mut Self>, item: CarBlock) -> Result<(), Self::Error> {
+        let this = self.project();
+        this.index
+            .push((item.cid.hash().digest().to_vec(), *this.data_size));
+        let buffered = this.buffer.len();
+        item.write(&mut this.buffer.writer())?;
+        *this.data_size += (this.buffer.len() - buffered) as u64;
+        Ok(())
+    }
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        ready!(self.as_mut().poll_ready(cx))?;
+        self.project().inner.poll_flush(cx)
+    }
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        loop {
+            ready!(self.as_mut().poll_ready(cx))?;
+            let mut this = self.as_mut().project();
+            match *this.state {
+                CarV2WriterState::Blocks => {
+                    let index = encode_index_sorted(std::mem::take(this.index));
+                    this.buffer.extend_from_slice(&index);
+                    *this.state = CarV2WriterState::Index;
+                }
+                CarV2WriterState::Index => {
+                    // Pending writes have to complete before seeking
+                    ready!(this.inner.as_mut().poll_flush(cx))?;
+                    this.inner
+                        .start_seek(SeekFrom::Start(CARV2_PRAGMA.len() as u64))?;
+                    *this.state = CarV2WriterState::Seek;
+                }
+                CarV2WriterState::Seek => {
+                    ready!(this.inner.poll_complete(cx))?;
+                    let header = CarV2Header {
+                        characteristics: [0; 16],
+                        data_offset: CarV2Header::DEFAULT_DATA_OFFSET,
+                        data_size: *this.data_size,
+                        index_offset: CarV2Header::DEFAULT_DATA_OFFSET + *this.data_size,
+                    };
+                    this.buffer.extend_from_slice(&header.to_le_bytes());
+                    *this.state = CarV2WriterState::Header;
+                }
+                CarV2WriterState::Header => {
+                    ready!(this.inner.as_mut().poll_flush(cx))?;
+                    return this.inner.poll_shutdown(cx);
+                }
+            }
+        }
+    }
+}
+
+fn poll_write_buffer(
+    mut inner: Pin<&mut impl AsyncWrite>,
+    buffer: &mut BytesMut,
+    cx: &mut Context<'_>,
+) -> Poll<io::Result<()>> {
+    while !buffer.is_empty() {
+        let bytes_written = ready!(inner.as_mut().poll_write(cx, buffer))?;
+        buffer.advance(bytes_written);
+    }
+    Poll::Ready(Ok(()))
+}
+
 async fn read_header<ReaderT: AsyncRead + Unpin>(
     framed_reader: &mut FramedRead<ReaderT, UviBytes>,
 ) -> Option<CarHeader> {
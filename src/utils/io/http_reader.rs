@@ -0,0 +1,326 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::utils::net::global_http_client;
+use crate::utils::reqwest_resume::ClientExt as _;
+use ahash::HashMap;
+use bytes::{Bytes, BytesMut};
+use futures::TryStreamExt as _;
+use lru::LruCache;
+use nonzero_ext::nonzero;
+use parking_lot::Mutex;
+use positioned_io::{ReadAt, Size};
+use reqwest::{header, StatusCode};
+use std::io::{self, ErrorKind};
+use std::num::NonZeroUsize;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::{mpsc as std_mpsc, Arc};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::trace;
+use url::Url;
+
+/// Reads are aligned to blocks of this size.
+const BLOCK_SIZE: u64 = 1024 * 1024;
+/// Number of blocks kept in the cache of a reader.
+const CACHED_BLOCKS: NonZeroUsize = nonzero!(64usize);
+/// Timeout of every attempt to fetch a block.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+/// Number of times a failed request is retried before the read fails.
+const MAX_RETRIES: usize = 5;
+
+/// Parse `location` as an `http` or `https` URL.
+pub fn parse_http_url(location: &Path) -> Option<Url> {
+    Url::parse(location.to_str()?)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+}
+
+/// A [`ReadAt`] implementation for a remote file, served by a server that
+/// supports HTTP range requests.
+///
+/// Reads are aligned to blocks of [`BLOCK_SIZE`] bytes and the most recently
+/// read blocks are cached, so that the small reads of CAR block stores only
+/// issue a request per block. Concurrent reads of the same block share a
+/// request. Interrupted requests are retried and resumed with
+/// [`reqwest_resume`](crate::utils::reqwest_resume), up to [`MAX_RETRIES`]
+/// times, after which the read fails with an [`io::Error`].
+///
+/// Requests are sent by a background thread with its own runtime. Reads block
+/// until the response arrives, like reads of local files do. Reads issued from
+/// the workers of a multi-threaded runtime wait with
+/// [`tokio::task::block_in_place`], so they don't stall other tasks.
+pub struct HttpReader {
+    size: u64,
+    cache: Mutex<LruCache<u64, Bytes>>,
+    /// Blocks being fetched, locked while their request is in flight
+    pending: Mutex<HashMap<u64, Arc<Mutex<()>>>>,
+    requests: mpsc::UnboundedSender<BlockRequest>,
+}
+
+struct BlockRequest {
+    range: Range<u64>,
+    response: std_mpsc::SyncSender<io::Result<Bytes>>,
+}
+
+impl HttpReader {
+    /// Open the remote file at `url`, failing if the server doesn't support
+    /// range requests.
+    pub fn open(url: Url) -> io::Result<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let (requests, mut receiver) = mpsc::unbounded_channel::<BlockRequest>();
+        let (size_sender, size_receiver) = std_mpsc::sync_channel(1);
+        std::thread::Builder::new()
+            .name("http-reader".into())
+            .spawn(move || {
+                runtime.block_on(async move {
+                    let size = fetch_size(&url).await;
+                    let failed = size.is_err();
+                    let _ = size_sender.send(size);
+                    if failed {
+                        return;
+                    }
+                    // Exits once the reader and its sender are dropped
+                    while let Some(BlockRequest { range, response }) = receiver.recv().await {
+                        let url = url.clone();
+                        tokio::spawn(async move {
+                            let _ = response.send(fetch_range(&url, range).await);
+                        });
+                    }
+                })
+            })?;
+        let size = size_receiver
+            .recv()
+            .map_err(|_| io::Error::new(ErrorKind::Other, "HTTP reader thread exited"))??;
+
+        Ok(HttpReader {
+            size,
+            cache: Mutex::new(LruCache::new(CACHED_BLOCKS)),
+            pending: Mutex::new(HashMap::default()),
+            requests,
+        })
+    }
+
+    fn block(&self, index: u64) -> io::Result<Bytes> {
+        if let Some(block) = self.cache.lock().get(&index) {
+            return Ok(block.clone());
+        }
+        let pending = self.pending.lock().entry(index).or_default().clone();
+        let block = block_in_place(|| {
+            let _guard = pending.lock();
+            // Another read may have fetched the block in the meantime
+            if let Some(block) = self.cache.lock().get(&index) {
+                return Ok(block.clone());
+            }
+            self.fetch_block(index)
+        });
+        self.pending.lock().remove(&index);
+        block
+    }
+
+    fn fetch_block(&self, index: u64) -> io::Result<Bytes> {
+        let range = index * BLOCK_SIZE..self.size.min((index + 1) * BLOCK_SIZE);
+        trace!(?range, "fetching block");
+        let (response, receiver) = std_mpsc::sync_channel(1);
+        self.requests
+            .send(BlockRequest { range, response })
+            .map_err(|_| io::Error::new(ErrorKind::Other, "HTTP reader thread exited"))?;
+        let block = receiver
+            .recv()
+            .map_err(|_| io::Error::new(ErrorKind::Other, "HTTP reader thread exited"))??;
+        self.cache.lock().put(index, block.clone());
+        Ok(block)
+    }
+}
+
+/// Runs the blocking `f` with [`tokio::task::block_in_place`] on the workers of
+/// a multi-threaded runtime, which panics elsewhere.
+fn block_in_place<T>(f: impl FnOnce() -> T) -> T {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(f)
+        }
+        _ => f(),
+    }
+}
+
+impl ReadAt for HttpReader {
+    fn read_at(&self, pos: u64, buf: &mut [u8]) -> io::Result<usize> {
+        if pos >= self.size || buf.is_empty() {
+            // This matches the behaviour for reading past the end of a file
+            return Ok(0);
+        }
+        let block = self.block(pos / BLOCK_SIZE)?;
+        let offset = (pos % BLOCK_SIZE) as usize;
+        let read = buf.len().min(block.len() - offset);
+        buf[..read].copy_from_slice(&block[offset..offset + read]);
+        Ok(read)
+    }
+}
+
+impl Size for HttpReader {
+    fn size(&self) -> io::Result<Option<u64>> {
+        Ok(Some(self.size))
+    }
+}
+
+/// Fetch the size of the remote file from the `Content-Range` header of a
+/// range request for its first byte.
+async fn fetch_size(url: &Url) -> io::Result<u64> {
+    let response = send_range_request(url, 0..1).await?;
+    response
+        .response()
+        .headers()
+        .get(header::CONTENT_RANGE)
+        .and_then(|value| value.to_str().ok())
+        // e.g. `bytes 0-0/1234`
+        .and_then(|value| value.rsplit_once('/'))
+        .and_then(|(_, size)| size.parse().ok())
+        .ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!("couldn't get the size of {url}"),
+            )
+        })
+}
+
+async fn fetch_range(url: &Url, range: Range<u64>) -> io::Result<Bytes> {
+    let expected = (range.end - range.start) as usize;
+    let response = send_range_request(url, range).await?;
+    let mut block = BytesMut::with_capacity(expected);
+    let mut stream = response.bytes_stream();
+    while let Some(bytes) = stream.try_next().await.map_err(to_io_error)? {
+        block.extend_from_slice(&bytes);
+    }
+    if block.len() != expected {
+        return Err(io::Error::new(
+            ErrorKind::UnexpectedEof,
+            format!("expected {expected} bytes from {url}, got {}", block.len()),
+        ));
+    }
+    Ok(block.freeze())
+}
+
+async fn send_range_request(
+    url: &Url,
+    range: Range<u64>,
+) -> io::Result<crate::utils::reqwest_resume::Response> {
+    let response = global_http_client()
+        .resumable()
+        .get(url.clone())
+        .range(range)
+        .timeout(REQUEST_TIMEOUT)
+        .max_retries(MAX_RETRIES)
+        .send()
+        .await
+        .map_err(to_io_error)?;
+    response
+        .response()
+        .error_for_status_ref()
+        .map_err(to_io_error)?;
+    if response.response().status() != StatusCode::PARTIAL_CONTENT {
+        return Err(io::Error::new(
+            ErrorKind::Unsupported,
+            format!("{url} doesn't support range requests"),
+        ));
+    }
+    Ok(response)
+}
+
+fn to_io_error(error: reqwest::Error) -> io::Error {
+    io::Error::new(ErrorKind::Other, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::car::AnyCar;
+    use crate::networks::calibnet;
+    use fvm_ipld_blockstore::Blockstore as _;
+    use http_range_header::parse_range_header;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server};
+    use std::convert::Infallible;
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    /// A static file server of `content` that supports range requests.
+    async fn serve(content: &'static [u8]) -> Url {
+        let make_service = make_service_fn(move |_| async move {
+            Ok::<_, Infallible>(service_fn(move |request: Request<Body>| async move {
+                let range = request
+                    .headers()
+                    .get(header::RANGE)
+                    .and_then(|value| parse_range_header(value.to_str().ok()?).ok())
+                    .and_then(|ranges| ranges.validate(content.len() as u64).ok())
+                    .map(|ranges| ranges[0].clone());
+                let response = match range {
+                    Some(range) => Response::builder()
+                        .status(StatusCode::PARTIAL_CONTENT)
+                        .header(
+                            header::CONTENT_RANGE,
+                            format!("bytes {}-{}/{}", range.start(), range.end(), content.len()),
+                        )
+                        .body(Body::from(
+                            &content[*range.start() as usize..=*range.end() as usize],
+                        )),
+                    None => Response::builder().body(Body::from(content)),
+                };
+                Ok::<_, Infallible>(response.unwrap())
+            }))
+        });
+        let server =
+            Server::bind(&SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0)).serve(make_service);
+        let url = Url::parse(&format!("http://{}/genesis.car", server.local_addr())).unwrap();
+        tokio::spawn(server);
+        url
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn http_reader_reads_ranges() {
+        let url = serve(calibnet::DEFAULT_GENESIS).await;
+        let reader = HttpReader::open(url).unwrap();
+        assert_eq!(
+            reader.size().unwrap(),
+            Some(calibnet::DEFAULT_GENESIS.len() as u64)
+        );
+        let mut buf = vec![0; 100];
+        reader.read_exact_at(1000, &mut buf).unwrap();
+        assert_eq!(buf, calibnet::DEFAULT_GENESIS[1000..1100]);
+        assert_eq!(
+            reader
+                .read_at(calibnet::DEFAULT_GENESIS.len() as u64, &mut buf)
+                .unwrap(),
+            0
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn http_reader_car() {
+        let url = serve(calibnet::DEFAULT_GENESIS).await;
+        let car = AnyCar::new(HttpReader::open(url).unwrap()).unwrap();
+        assert!(car.has(&calibnet::GENESIS_CID).unwrap());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn http_reader_gives_up() {
+        // Nothing listens on the port once the listener is dropped
+        let listener = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let url = Url::parse(&format!(
+            "http://{}/genesis.car",
+            listener.local_addr().unwrap()
+        ))
+        .unwrap();
+        drop(listener);
+        assert!(HttpReader::open(url).is_err());
+    }
+
+    #[test]
+    fn http_url_detection() {
+        assert!(parse_http_url(Path::new("https://example.com/a.forest.car.zst")).is_some());
+        assert!(parse_http_url(Path::new("/tmp/a.forest.car.zst")).is_none());
+        assert!(parse_http_url(Path::new("c:/a.forest.car.zst")).is_none());
+    }
+}
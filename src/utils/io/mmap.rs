@@ -57,6 +57,15 @@ impl EitherMmapOrRandomAccessFile {
             Self::Mmap(Mmap::map_path(path)?)
         })
     }
+
+    /// Read from an already opened file, which may have been unlinked.
+    pub fn from_file(file: fs::File) -> io::Result<Self> {
+        Ok(if should_use_file_io() {
+            Self::RandomAccessFile(RandomAccessFile::try_new(file)?)
+        } else {
+            Self::Mmap(Mmap::map(&file)?)
+        })
+    }
 }
 
 impl ReadAt for EitherMmapOrRandomAccessFile {
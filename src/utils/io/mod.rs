@@ -1,6 +1,7 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+mod http_reader;
 mod mmap;
 pub mod progress_bar;
 pub mod progress_log;
@@ -13,6 +14,7 @@ use std::{
     path::Path,
 };
 
+pub use http_reader::{parse_http_url, HttpReader};
 pub use mmap::{EitherMmapOrRandomAccessFile, Mmap};
 pub use progress_bar::{ProgressBar, ProgressBarVisibility};
 pub use progress_log::{WithProgress, WithProgressRaw};
@@ -12,7 +12,7 @@
 //! The main goal of [`WithProgressRaw`] is to maintain a similar API to the previous one from progress bar so we could remove the [`indicatif`](https://crates.io/crates/indicatif) dependency,
 //! but, gradually, we would like to move to something better and use the [`WithProgress`] type.
 //! The [`WithProgress`] type will provide a way to wrap user code while handling logging presentation details.
-//! [`WithProgress`] is a wrapper that should extend to Iterators, Streams, Read/Write types. Right now it only wraps async reads.
+//! [`WithProgress`] is a wrapper that should extend to Iterators, Streams, Read/Write types. Right now it only wraps reads.
 //!
 //! # Example
 //! ```
@@ -75,6 +75,14 @@ impl<R: tokio::io::AsyncRead> tokio::io::AsyncRead for WithProgress<R> {
     }
 }
 
+impl<R: io::Read> io::Read for WithProgress<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.progress.inc(read as u64);
+        Ok(read)
+    }
+}
+
 impl<S> WithProgress<S> {
     pub fn wrap_async_read(message: &str, read: S, total_items: u64) -> WithProgress<S> {
         WithProgress {
@@ -82,6 +90,10 @@ impl<S> WithProgress<S> {
             progress: Progress::new(message, total_items),
         }
     }
+
+    pub fn wrap_read(message: &str, read: S, total_items: u64) -> WithProgress<S> {
+        Self::wrap_async_read(message, read, total_items)
+    }
 }
 
 #[derive(Debug, Clone)]
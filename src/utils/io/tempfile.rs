@@ -1,22 +1,78 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::{fs, path::PathBuf};
+use std::{fs, io, path::Path};
 
-use tracing::warn;
+use positioned_io::{ReadAt, Size};
 
-/// Wrapper of temporary file that deletes file on drop
-#[derive(Debug, Clone)]
+use super::EitherMmapOrRandomAccessFile;
+
+/// Temporary file that is unlinked as soon as it is created. Its space is
+/// reclaimed once the file is closed, even if the process is killed.
+#[derive(Debug)]
 pub struct TempFile {
-    path: PathBuf,
+    file: fs::File,
+}
+
+impl TempFile {
+    /// Create an empty temporary file in `dir` and open it for writing.
+    pub fn new_in(dir: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(TempFile {
+            file: ::tempfile::tempfile_in(dir)?,
+        })
+    }
+
+    /// Open the file for random access reads.
+    pub fn into_reader(self) -> io::Result<TempFileReader> {
+        Ok(TempFileReader {
+            reader: EitherMmapOrRandomAccessFile::from_file(self.file)?,
+        })
+    }
+}
+
+impl io::Write for TempFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
 }
 
-impl Drop for TempFile {
-    fn drop(&mut self) {
-        if self.path.exists() {
-            if let Err(err) = fs::remove_file(&self.path) {
-                warn!("Failed to delete {}: {err}", self.path.display());
-            }
-        }
+/// Random access reader of a [`TempFile`]
+pub struct TempFileReader {
+    reader: EitherMmapOrRandomAccessFile,
+}
+
+impl ReadAt for TempFileReader {
+    fn read_at(&self, pos: u64, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.read_at(pos, buf)
+    }
+}
+
+impl Size for TempFileReader {
+    fn size(&self) -> io::Result<Option<u64>> {
+        self.reader.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write as _;
+
+    #[test]
+    fn temp_file_unlinked() {
+        let dir = ::tempfile::tempdir().unwrap();
+        let mut temp_file = TempFile::new_in(dir.path()).unwrap();
+        assert!(fs::read_dir(dir.path()).unwrap().next().is_none());
+        temp_file.write_all(b"forest").unwrap();
+
+        let reader = temp_file.into_reader().unwrap();
+        let mut buf = [0; 6];
+        reader.read_exact_at(0, &mut buf).unwrap();
+        assert_eq!(&buf, b"forest");
+        assert_eq!(reader.size().unwrap(), Some(6));
     }
 }
@@ -22,6 +22,18 @@ pub fn global_http_client() -> reqwest::Client {
     CLIENT.clone()
 }
 
+/// Returns the URL that `url` redirects to, so that the same resource can be
+/// fetched again even if the redirect changes in the meantime. The body isn't
+/// read.
+pub async fn resolve_redirects(url: &Url) -> anyhow::Result<Url> {
+    let response = global_http_client()
+        .get(url.clone())
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(response.url().clone())
+}
+
 /// Download a file via IPFS HTTP gateway in trustless mode.
 /// See <https://github.com/ipfs/specs/blob/main/http-gateways/TRUSTLESS_GATEWAY.md>
 pub async fn download_ipfs_file_trustlessly(
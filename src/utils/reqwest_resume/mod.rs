@@ -14,6 +14,7 @@ use bytes::Bytes;
 use futures::{ready, FutureExt as _, Stream, TryFutureExt as _};
 use hyper::header::{self, HeaderMap, HeaderValue};
 use std::{
+    ops::Range,
     pin::Pin,
     task::{Context, Poll},
     time::Duration,
@@ -47,7 +48,14 @@ impl Client {
     ///
     /// See [`reqwest::Client::get()`].
     pub fn get(&self, url: reqwest::Url) -> RequestBuilder {
-        RequestBuilder(self.0.clone(), reqwest::Method::GET, url)
+        RequestBuilder {
+            client: self.0.clone(),
+            method: reqwest::Method::GET,
+            url,
+            range: None,
+            timeout: None,
+            max_retries: None,
+        }
     }
 }
 
@@ -55,18 +63,73 @@ impl Client {
 ///
 /// See [`reqwest::RequestBuilder`].
 #[derive(Debug)]
-pub struct RequestBuilder(reqwest::Client, reqwest::Method, reqwest::Url);
+pub struct RequestBuilder {
+    client: reqwest::Client,
+    method: reqwest::Method,
+    url: reqwest::Url,
+    range: Option<Range<u64>>,
+    timeout: Option<Duration>,
+    max_retries: Option<usize>,
+}
 impl RequestBuilder {
+    /// Only request the bytes of the body in `range`.
+    pub fn range(self, range: Range<u64>) -> Self {
+        Self {
+            range: Some(range),
+            ..self
+        }
+    }
+
+    /// Fail every attempt that doesn't complete within `timeout`, including
+    /// reading the body.
+    ///
+    /// See [`reqwest::RequestBuilder::timeout()`].
+    pub fn timeout(self, timeout: Duration) -> Self {
+        Self {
+            timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    /// Give up after `max_retries` consecutive failed attempts, rather than
+    /// retrying forever.
+    pub fn max_retries(self, max_retries: usize) -> Self {
+        Self {
+            max_retries: Some(max_retries),
+            ..self
+        }
+    }
+
     /// Constructs the Request and sends it the target URL, returning a Response.
     ///
     /// See [`reqwest::RequestBuilder::send()`].
     pub async fn send(self) -> reqwest::Result<Response> {
-        let RequestBuilder(client, method, url) = self;
+        let RequestBuilder {
+            client,
+            method,
+            url,
+            range,
+            timeout,
+            max_retries,
+        } = self;
 
+        let mut retries = 0;
         let response = loop {
-            let builder = client.request(method.clone(), url.clone());
+            let mut builder = client.request(method.clone(), url.clone());
+            if let Some(range) = &range {
+                builder = builder.header(header::RANGE, range_header(range.start, Some(range.end)));
+            }
+            if let Some(timeout) = timeout {
+                builder = builder.timeout(timeout);
+            }
             match builder.send().await {
-                Err(err) if !err.is_builder() && !err.is_redirect() && !err.is_status() => {
+                Err(err)
+                    if !err.is_builder()
+                        && !err.is_redirect()
+                        && !err.is_status()
+                        && max_retries.map_or(true, |max_retries| retries < max_retries) =>
+                {
+                    retries += 1;
                     sleep(Duration::from_secs(1)).await
                 }
                 x => break x?,
@@ -76,14 +139,18 @@ impl RequestBuilder {
             .headers()
             .get(header::ACCEPT_RANGES)
             .map(HeaderValue::as_bytes)
-            == Some(b"bytes");
+            == Some(b"bytes")
+            || response.status() == reqwest::StatusCode::PARTIAL_CONTENT;
         let resp = Response {
             client,
             method,
             url,
             response,
             accept_byte_ranges,
-            pos: 0,
+            pos: range.as_ref().map_or(0, |range| range.start),
+            end: range.map(|range| range.end),
+            timeout,
+            max_retries,
         };
         Ok(resp)
     }
@@ -100,6 +167,9 @@ pub struct Response {
     response: reqwest::Response,
     accept_byte_ranges: bool,
     pos: u64,
+    end: Option<u64>,
+    timeout: Option<Duration>,
+    max_retries: Option<usize>,
 }
 impl Response {
     /// Convert the response into a `Stream` of `Bytes` from the body.
@@ -113,6 +183,10 @@ impl Response {
             decoder: Box::pin(self.response.bytes_stream()),
             accept_byte_ranges: self.accept_byte_ranges,
             pos: self.pos,
+            end: self.end,
+            timeout: self.timeout,
+            max_retries: self.max_retries,
+            retries: 0,
         }
     }
 
@@ -128,6 +202,11 @@ struct Decoder {
     decoder: Pin<Box<dyn Stream<Item = reqwest::Result<Bytes>> + Send>>,
     accept_byte_ranges: bool,
     pos: u64,
+    end: Option<u64>,
+    timeout: Option<Duration>,
+    max_retries: Option<usize>,
+    /// Consecutive failed attempts to resume the body
+    retries: usize,
 }
 impl Stream for Decoder {
     type Item = reqwest::Result<Bytes>;
@@ -136,15 +215,20 @@ impl Stream for Decoder {
         loop {
             match ready!(self.decoder.as_mut().poll_next(cx)) {
                 Some(Err(err)) => {
-                    if !self.accept_byte_ranges {
+                    let exhausted = self
+                        .max_retries
+                        .is_some_and(|max_retries| self.retries >= max_retries);
+                    if !self.accept_byte_ranges || exhausted {
                         break Poll::Ready(Some(Err(err)));
                     }
+                    self.retries += 1;
                     let builder = self.client.request(self.method.clone(), self.url.clone());
                     let mut headers = HeaderMap::new();
-                    let value = HeaderValue::from_str(&std::format!("bytes={}-", self.pos))
-                        .expect("unreachable");
-                    headers.insert(header::RANGE, value);
-                    let builder = builder.headers(headers);
+                    headers.insert(header::RANGE, range_header(self.pos, self.end));
+                    let mut builder = builder.headers(headers);
+                    if let Some(timeout) = self.timeout {
+                        builder = builder.timeout(timeout);
+                    }
                     // https://developer.mozilla.org/en-US/docs/Web/HTTP/Range_requests
                     self.decoder = Box::pin(
                         sleep(Duration::from_secs(1))
@@ -154,6 +238,7 @@ impl Stream for Decoder {
                     );
                 }
                 Some(Ok(n)) => {
+                    self.retries = 0;
                     self.pos += n.len() as u64;
                     break Poll::Ready(Some(Ok(n)));
                 }
@@ -163,6 +248,16 @@ impl Stream for Decoder {
     }
 }
 
+/// The value of a `Range` header requesting the bytes from `start` up to the
+/// exclusive `end`, or up to the end of the body.
+fn range_header(start: u64, end: Option<u64>) -> HeaderValue {
+    let value = match end {
+        Some(end) => format!("bytes={start}-{}", end.saturating_sub(1)),
+        None => format!("bytes={start}-"),
+    };
+    HeaderValue::from_str(&value).expect("unreachable")
+}
+
 /// Shortcut method to quickly make a GET request.
 ///
 /// See [`reqwest::get`].